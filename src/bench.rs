@@ -0,0 +1,197 @@
+//! Serial vs parallel generation comparison (`--bench-compare`)
+//!
+//! The same generation is timed on a dedicated thread pool of each size,
+//! so the rayon `par_iter` over exporter groups runs with exactly that many
+//! threads and one thread is the serial baseline. The UDP send paths are
+//! timed as well, over loopback to a local sink.
+
+use crate::error::{NetflowError, Result};
+use std::fmt::Write;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+/// Generation rounds timed per thread count, after one warm-up round
+const ROUNDS: u32 = 20;
+
+/// Datagrams timed per UDP send path
+const SEND_ROUNDS: u32 = 20_000;
+
+/// Throughput measured with one thread count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    pub threads: usize,
+    pub packets_per_sec: f64,
+}
+
+/// Thread counts to compare: 1, the powers of two below `max`, and `max`
+pub fn thread_counts(max: usize) -> Vec<usize> {
+    let mut counts: Vec<usize> = std::iter::successors(Some(1usize), |n| n.checked_mul(2))
+        .take_while(|n| *n < max)
+        .collect();
+    counts.push(max.max(1));
+    counts.dedup();
+    counts
+}
+
+/// Time `generate`, which returns the number of packets built, at each thread count
+pub fn compare<F>(thread_counts: &[usize], generate: F) -> Result<Vec<BenchResult>>
+where
+    F: Fn() -> Result<usize> + Sync,
+{
+    thread_counts
+        .iter()
+        .map(|&threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|e| {
+                    NetflowError::Configuration(format!(
+                        "Failed to build a {}-thread pool: {}",
+                        threads, e
+                    ))
+                })?;
+
+            let (packets, elapsed) = pool.install(|| -> Result<(usize, Duration)> {
+                generate()?;
+                let start = Instant::now();
+                let mut packets = 0usize;
+                for _ in 0..ROUNDS {
+                    packets = packets.saturating_add(generate()?);
+                }
+                Ok((packets, start.elapsed()))
+            })?;
+
+            let packets = f64::from(u32::try_from(packets).unwrap_or(u32::MAX));
+            Ok(BenchResult {
+                threads,
+                packets_per_sec: packets / elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+            })
+        })
+        .collect()
+}
+
+/// Send throughput of `send_to` on an unconnected socket and `send` on a connected one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SendPathResult {
+    pub send_to_per_sec: f64,
+    pub connected_per_sec: f64,
+}
+
+/// Time both UDP send paths sending `packet` over loopback
+///
+/// The sink socket is never read, so datagrams beyond its receive buffer
+/// are dropped by the kernel and only the sending side is timed.
+pub fn compare_send_paths(packet: &[u8]) -> Result<SendPathResult> {
+    let network = |e: std::io::Error| NetflowError::Network(format!("UDP send benchmark: {}", e));
+    let sink = UdpSocket::bind("127.0.0.1:0").map_err(network)?;
+    let destination = sink.local_addr().map_err(network)?;
+
+    let rate = |send: &dyn Fn() -> std::io::Result<usize>| -> Result<f64> {
+        send().map_err(network)?;
+        let start = Instant::now();
+        for _ in 0..SEND_ROUNDS {
+            send().map_err(network)?;
+        }
+        Ok(f64::from(SEND_ROUNDS) / start.elapsed().as_secs_f64().max(f64::MIN_POSITIVE))
+    };
+
+    let unconnected = UdpSocket::bind("127.0.0.1:0").map_err(network)?;
+    let send_to_per_sec = rate(&|| unconnected.send_to(packet, destination))?;
+    let connected = UdpSocket::bind("127.0.0.1:0").map_err(network)?;
+    connected.connect(destination).map_err(network)?;
+    let connected_per_sec = rate(&|| connected.send(packet))?;
+
+    Ok(SendPathResult {
+        send_to_per_sec,
+        connected_per_sec,
+    })
+}
+
+/// Table of packets per second for each UDP send path, with the connected speedup
+pub fn format_send_paths(result: &SendPathResult) -> String {
+    let speedup = if result.send_to_per_sec > 0.0 {
+        result.connected_per_sec / result.send_to_per_sec
+    } else {
+        0.0
+    };
+    format!(
+        "{:>9}  {:>12}  {:>7}\n{:>9}  {:>12.0}  {:>6.2}x\n{:>9}  {:>12.0}  {:>6.2}x\n",
+        "UDP send",
+        "Packets/sec",
+        "Speedup",
+        "send_to",
+        result.send_to_per_sec,
+        1.0,
+        "connected",
+        result.connected_per_sec,
+        speedup
+    )
+}
+
+/// Table of throughput per thread count, with the speedup over the first row
+pub fn format_table(results: &[BenchResult]) -> String {
+    let baseline = results.first().map_or(0.0, |result| result.packets_per_sec);
+
+    let mut table = format!(
+        "{:>7}  {:>12}  {:>7}\n",
+        "Threads", "Packets/sec", "Speedup"
+    );
+    for result in results {
+        let speedup = if baseline > 0.0 {
+            result.packets_per_sec / baseline
+        } else {
+            0.0
+        };
+        // Writing to a String cannot fail
+        let _ = writeln!(
+            table,
+            "{:>7}  {:>12.0}  {:>6.2}x",
+            result.threads, result.packets_per_sec, speedup
+        );
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_compare() {
+        assert_eq!(thread_counts(1), vec![1]);
+        assert_eq!(thread_counts(4), vec![1, 2, 4]);
+        assert_eq!(thread_counts(6), vec![1, 2, 4, 6]);
+
+        let results = compare(&[1, 2], || Ok(rayon::current_num_threads())).unwrap();
+        assert_eq!(
+            results.iter().map(|r| r.threads).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(results.iter().all(|r| r.packets_per_sec > 0.0));
+
+        let table = format_table(&[
+            BenchResult {
+                threads: 1,
+                packets_per_sec: 1000.0,
+            },
+            BenchResult {
+                threads: 4,
+                packets_per_sec: 3500.0,
+            },
+        ]);
+        assert_eq!(
+            table,
+            "Threads   Packets/sec  Speedup\n      1          1000    1.00x\n      4          3500    3.50x\n"
+        );
+
+        let send = compare_send_paths(&[0u8; 64]).unwrap();
+        assert!(send.send_to_per_sec > 0.0 && send.connected_per_sec > 0.0);
+        assert_eq!(
+            format_send_paths(&SendPathResult {
+                send_to_per_sec: 1000.0,
+                connected_per_sec: 1250.0,
+            }),
+            " UDP send   Packets/sec  Speedup\n  send_to          1000    1.00x\nconnected          1250    1.25x\n"
+        );
+    }
+}
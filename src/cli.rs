@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::path::PathBuf;
 
 /// NetFlow packet generator supporting V5, V7, V9, and IPFIX formats
@@ -14,6 +14,70 @@ pub struct Cli {
     #[arg(short, long, value_name = "FILE")]
     pub config: Option<PathBuf>,
 
+    /// Directory of YAML configuration files to load and merge
+    ///
+    /// Every `.yaml`/`.yml` file in the directory is parsed and their flows
+    /// are combined into one configuration, in file name order.
+    #[arg(long, value_name = "DIR", conflicts_with = "config")]
+    pub config_dir: Option<PathBuf>,
+
+    /// Template-only YAML file to send inline --record data against
+    ///
+    /// The file uses the normal configuration format with V9 or IPFIX
+    /// template flowsets; the records from --record become its data.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["config", "config_dir"])]
+    pub template: Option<PathBuf>,
+
+    /// Send the NetFlow payloads captured in a pcap file once, then exit
+    ///
+    /// The UDP payload of every IPv4/UDP packet is replayed unchanged to
+    /// --dest (or written to --output). Captures from big-endian and
+    /// little-endian hosts are both read, based on the pcap magic number.
+    #[arg(
+        long,
+        value_name = "PCAP",
+        conflicts_with_all = ["config", "config_dir", "template"]
+    )]
+    pub replay: Option<PathBuf>,
+
+    /// Data record as comma-separated key=value pairs (repeatable)
+    ///
+    /// Example: --record "src_addr=1.2.3.4,dst_port=443". Records use the
+    /// first template in --template unless they set template_id=N.
+    #[arg(long, value_name = "KEY=VALUE,...", requires = "template")]
+    pub record: Vec<String>,
+
+    /// Newline-delimited JSON file of data records for --template
+    ///
+    /// Each non-empty line is one JSON object, e.g.
+    /// {"src_addr": "1.2.3.4", "dst_port": 443}, added after any --record
+    /// records. The file is read line by line; malformed lines are reported
+    /// with their line number.
+    #[arg(long, value_name = "FILE", requires = "template")]
+    pub records_ndjson: Option<PathBuf>,
+
+    /// V5 flow given as a one-line spec instead of YAML (repeatable)
+    ///
+    /// Example: --flow "tcp 192.168.1.10:54321 -> 172.217.14.206:443
+    /// pkts=150 bytes=95000". The protocol is tcp, udp, icmp or a number;
+    /// ports may be omitted. Optional keys are pkts, bytes, tcp_flags, tos,
+    /// input, output, next_hop, src_as, dst_as, src_mask and dst_mask.
+    /// Each --flow becomes one record of a single V5 flow.
+    #[arg(
+        long = "flow",
+        value_name = "SPEC",
+        conflicts_with_all = ["config", "config_dir", "template", "replay"]
+    )]
+    pub flows: Vec<String>,
+
+    /// YAML file of named V9/IPFIX templates that configs can reference
+    ///
+    /// A template flowset with `template: <name>` instead of `fields` gets
+    /// the named template's fields, and its template_id unless it sets one.
+    /// Applies to --config, --config-dir and --template files.
+    #[arg(long, value_name = "FILE")]
+    pub template_schema: Option<PathBuf>,
+
     /// Destination IP:PORT (overrides config file destination)
     ///
     /// Format: IP:PORT (e.g., "192.168.1.100:2055")
@@ -32,6 +96,84 @@ pub struct Cli {
     #[arg(short, long, value_name = "FILE")]
     pub output: Option<PathBuf>,
 
+    /// File format used with --output (default: pcap)
+    ///
+    /// `pcap` wraps each NetFlow payload in Ethernet/IP/UDP headers.
+    /// `pcapng` does the same and annotates each packet with a comment
+    /// naming its exporter (source_id / observation_domain_id).
+    /// `hex` writes one hex-encoded NetFlow payload per line.
+    /// `raw` writes the bare NetFlow payloads back to back with no
+    /// L2/L3/L4 wrapping, for tools that consume raw NetFlow bytes.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value = "pcap")]
+    pub output_format: OutputFormat,
+
+    /// Limit pcap/pcapng writes to this many megabits per second
+    ///
+    /// Protects long continuous captures from saturating disk I/O. Every
+    /// written copy of a NetFlow payload (one per --ttl-sweep TTL) counts
+    /// against the limit; the link, IP and UDP headers do not. What happens
+    /// to packets above the limit is set by --write-limit-policy, and the
+    /// number dropped is reported at shutdown.
+    #[arg(
+        long,
+        value_name = "MBPS",
+        requires = "output",
+        conflicts_with = "once",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub max_write_mbps: Option<u64>,
+
+    /// What to do with packets over --max-write-mbps
+    ///
+    /// `drop` leaves them out of the file so the send loop never waits;
+    /// `block` delays the loop until the write budget allows them.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "POLICY",
+        default_value = "drop",
+        requires = "max_write_mbps"
+    )]
+    pub write_limit_policy: WriteLimitPolicy,
+
+    /// Send packets via UDP and also write them to --output
+    ///
+    /// Every batch is transmitted first and then written, so the output
+    /// file records exactly the bytes the collector was sent.
+    #[arg(long, requires = "output")]
+    pub tee: bool,
+
+    /// Link-layer type for pcap output (default: ethernet)
+    ///
+    /// `ethernet` prefixes each packet with an Ethernet header, `raw`
+    /// writes bare IPv4 packets (DLT_RAW) and `linux-sll` uses the
+    /// Linux cooked capture header (DLT_LINUX_SLL).
+    #[arg(long, value_enum, value_name = "TYPE", default_value = "ethernet")]
+    pub datalink: Datalink,
+
+    /// Write each packet once per IP TTL in the list (pcap/pcapng output)
+    ///
+    /// Comma-separated TTLs, e.g. "64,63,62,61". Each NetFlow packet is
+    /// recorded once for every TTL, with the IP header checksum recomputed,
+    /// as if the same export were observed at different hop distances.
+    /// Without this option packets use TTL 64.
+    #[arg(
+        long,
+        value_name = "TTL,...",
+        value_delimiter = ',',
+        requires = "output",
+        value_parser = clap::value_parser!(u8).range(1..)
+    )]
+    pub ttl_sweep: Vec<u8>,
+
+    /// IP identification of the first packet in pcap/pcapng output
+    ///
+    /// Each following NetFlow packet gets the next value (wrapping at
+    /// 65535), as a real IP stack would. --ttl-sweep copies of a packet
+    /// share its identification.
+    #[arg(long, value_name = "N", default_value = "0")]
+    pub pcap_ip_id: u16,
+
     /// Enable verbose output
     ///
     /// Displays detailed information about packet generation
@@ -39,6 +181,14 @@ pub struct Cli {
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Suppress all non-error output
+    ///
+    /// Warnings are silenced as well; only errors are printed to stderr.
+    /// The exit code reports the outcome: 0 success, 1 configuration or
+    /// validation error, 2 transmission error, 3 I/O error.
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
     /// Continuously generate and send flows every N seconds (default: 2)
     ///
     /// By default, the generator runs continuously, sending flows
@@ -54,6 +204,41 @@ pub struct Cli {
     #[arg(long, conflicts_with = "interval")]
     pub once: bool,
 
+    /// Generate once and fail unless exactly N packets are produced
+    ///
+    /// Nothing is sent or written. Intended for CI, to lock down the output
+    /// of a configuration; a mismatch exits with code 1. Can be combined
+    /// with --assert-bytes.
+    #[arg(long, value_name = "N", conflicts_with = "output")]
+    pub assert_packets: Option<u64>,
+
+    /// Generate once and fail unless the payloads total exactly N bytes
+    ///
+    /// Counts NetFlow/IPFIX payload bytes, excluding IP/UDP headers.
+    /// Nothing is sent or written.
+    #[arg(long, value_name = "N", conflicts_with = "output")]
+    pub assert_bytes: Option<u64>,
+
+    /// Print the effective configuration as YAML and exit
+    ///
+    /// Shows the flows after directory merging, --template records, ASN
+    /// lookups and observation domain expansion, with the destination that
+    /// would be used. Without a configuration the built-in samples are printed.
+    #[arg(long, conflicts_with = "replay")]
+    pub print_config: bool,
+
+    /// Compare generation throughput at 1 thread up to --threads and exit
+    ///
+    /// Generates the configuration (or the built-in samples) repeatedly on
+    /// thread pools of 1, 2, 4, ... and --threads threads and prints
+    /// packets/sec with the speedup over one thread. Exporters are
+    /// generated in parallel, so configs with a single exporter do not
+    /// speed up. It then times sending the first packet over loopback with
+    /// `send_to` and with a connected socket; nothing is sent to the
+    /// collector or written.
+    #[arg(long, conflicts_with_all = ["replay", "print_config"])]
+    pub bench_compare: bool,
+
     /// Number of threads to use for parallel packet generation
     ///
     /// When processing multiple flows from a configuration file,
@@ -71,4 +256,531 @@ pub struct Cli {
     /// Must be different from the destination port when testing locally.
     #[arg(short = 's', long, value_name = "PORT", default_value = "2056")]
     pub source_port: u16,
+
+    /// Source port for one exporter, as EXPORTER_ID=PORT (repeatable)
+    ///
+    /// The exporter ID is the V9 source_id, IPFIX observation_domain_id or
+    /// V5 engine_id. Packets from a listed exporter are sent from (and
+    /// recorded in pcap output with) its port, so each simulated exporter
+    /// keeps a stable, distinct source port. Other exporters use
+    /// --source-port. Not applied with --transport dtls or tcp.
+    #[arg(long, value_name = "ID=PORT")]
+    pub exporter_port: Vec<crate::transmitter::ExporterPort>,
+
+    /// Send from a spoofed exporter IPv4 address over a raw socket
+    ///
+    /// Each packet is built with its full IPv4/UDP header, as in pcap
+    /// output, so collectors see it come from IP instead of this host.
+    /// Raw sockets require root or CAP_NET_RAW; without them the run
+    /// fails to start. Source ports follow --source-port and
+    /// --exporter-port. Unix only; cannot be combined with --transport.
+    #[arg(long, value_name = "IP", conflicts_with = "transport")]
+    pub spoof_source: Option<std::net::Ipv4Addr>,
+
+    /// Transport used to reach the collector (default: udp)
+    ///
+    /// `dtls` wraps each exported packet in a DTLS record (RFC 7011
+    /// section 10.4.2) over a single session kept open for the run. It
+    /// requires building with `--features dtls`. `tcp` streams IPFIX
+    /// messages over one connection (RFC 7011 section 10.4) and fails with
+    /// the last packet sent if the collector resets or closes it; V5, V7
+    /// and V9 cannot be sent over TCP, so it needs a configuration of
+    /// IPFIX flows.
+    #[arg(long, value_enum, default_value_t = Transport::Udp)]
+    pub transport: Transport,
+
+    /// PEM CA certificate used to verify the collector with --transport dtls
+    ///
+    /// Without it the system trust store is used.
+    #[arg(long, value_name = "FILE")]
+    pub dtls_ca: Option<PathBuf>,
+
+    /// Name the collector's DTLS certificate must match
+    ///
+    /// Also sent as SNI. By default the certificate must be issued for
+    /// the destination IP address.
+    #[arg(long, value_name = "NAME")]
+    pub dtls_server_name: Option<String>,
+
+    /// Skip DTLS certificate verification (testing only)
+    #[arg(long, conflicts_with = "dtls_ca")]
+    pub dtls_insecure: bool,
+
+    /// Allow a template ID to be redefined with different fields
+    ///
+    /// By default, reusing a V9/IPFIX template_id with a different field
+    /// list for the same exporter is a configuration error. With this flag
+    /// the collision is reported as a warning and the later definition is
+    /// sent, which is useful for testing collector template redefinition.
+    #[arg(long)]
+    pub allow_template_conflicts: bool,
+
+    /// MaxMind-format ASN database used to fill unset AS numbers
+    ///
+    /// Flow addresses are looked up in the database (e.g. GeoLite2-ASN)
+    /// and the source/destination AS fields filled wherever the
+    /// configuration leaves them unset. Requires building with
+    /// `--features asn-db`.
+    #[arg(long, value_name = "FILE")]
+    pub asn_db: Option<PathBuf>,
+
+    /// Derive the V9 source_id and IPFIX observation_domain_id from the hostname
+    ///
+    /// The hostname is hashed into a 32-bit exporter ID, given to every
+    /// V9/IPFIX flow whose header does not set one. Instances on different
+    /// hosts then report as distinct exporters to a shared collector
+    /// without per-instance configuration. Explicit IDs always win.
+    #[arg(long)]
+    pub exporter_id_from_host: bool,
+
+    /// Derive the exporter ID from ID instead of the hostname
+    ///
+    /// Works like --exporter-id-from-host, for instances that share a
+    /// hostname (e.g. several per host) or need a stable identity across
+    /// hosts.
+    #[arg(long, value_name = "ID")]
+    pub instance_id: Option<String>,
+
+    /// Let data flowsets use templates defined by other flows
+    ///
+    /// By default a V9/IPFIX data flowset must reference a template defined
+    /// in its own flow. With this flag it may also reference one defined by
+    /// any other flow of the same exporter (source_id or
+    /// observation_domain_id), so templates can live in one flow and data
+    /// in data-only flows. Each template is still sent only by its own flow.
+    #[arg(long)]
+    pub shared_templates: bool,
+
+    /// Reject likely configuration mistakes that are otherwise allowed
+    ///
+    /// Currently flags V5/V7 flowsets and V9/IPFIX data records that repeat
+    /// another's 5-tuple and timestamps, reporting the duplicated flow,
+    /// flowset and record indices.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Order of records within each V9/IPFIX data flowset (default: config)
+    ///
+    /// `config` keeps the order from the configuration file, `sorted`
+    /// orders records by their encoded field values (first template field
+    /// as the primary key) and `reverse` emits them last-to-first.
+    #[arg(long, value_enum, value_name = "ORDER", default_value = "config")]
+    pub record_order: RecordOrder,
+
+    /// Interleave packets from different flows round-robin
+    ///
+    /// By default all packets of one flow are emitted before the next
+    /// flow. With this flag the generator cycles through flows, so a
+    /// mixed V9/IPFIX config produces an interleaved stream. Each flow's
+    /// own packet order (templates before data) is preserved.
+    #[arg(long)]
+    pub interleave: bool,
+
+    /// Fill missing V9 FIRST_SWITCHED/LAST_SWITCHED values from sys_up_time
+    ///
+    /// When a V9 data record omits these fields, LAST_SWITCHED is set to
+    /// the header sys_up_time and FIRST_SWITCHED to 10 seconds earlier, so
+    /// flows show up as "just ended" on the collector. Explicit values in
+    /// the record are always used as-is.
+    #[arg(long)]
+    pub first_switched_now: bool,
+
+    /// Advance V9 sys_up_time with real elapsed time (continuous mode)
+    ///
+    /// sys_up_time starts at the configured header value (default 360000)
+    /// and grows by the milliseconds elapsed since the run started, so the
+    /// device does not appear frozen. Explicit FIRST_SWITCHED/LAST_SWITCHED
+    /// values advance by the same amount to stay relative to the uptime.
+    #[arg(long, conflicts_with = "once")]
+    pub advance_uptime: bool,
+
+    /// Timestamp flows as having ended SECS seconds before their export
+    ///
+    /// Flow start and end times move SECS seconds into the past while the
+    /// header export time stays current, as when an exporter holds expired
+    /// flows in its cache before sending them. Applies to V5/V7 first/last,
+    /// V9 FIRST_SWITCHED/LAST_SWITCHED and the IPFIX flowStart*/flowEnd*
+    /// fields. Each flow keeps its duration.
+    #[arg(long, value_name = "SECS")]
+    pub export_lag: Option<u64>,
+
+    /// Stamp each IPFIX data packet with the time it is built
+    ///
+    /// By default every message of a batch shares one exportTime. With
+    /// this flag the clock is read again for each data packet, so large
+    /// batches built over several seconds carry advancing export times.
+    /// A header export_time set in the config is always used as-is.
+    #[arg(long)]
+    pub per_packet_export_time: bool,
+
+    /// Maximum projected packet rate allowed in continuous mode (default: 10000)
+    ///
+    /// The generator estimates packets per second from the configuration
+    /// and the interval, and refuses to start if it exceeds this ceiling.
+    /// With --ramp the rate is projected at the ramp's CAP. This guards
+    /// against accidentally flooding a collector.
+    #[arg(long, value_name = "PPS", default_value = "10000")]
+    pub max_pps: u64,
+
+    /// Start even if the projected packet rate exceeds --max-pps
+    #[arg(long)]
+    pub force: bool,
+
+    /// Spacing in microseconds between successive packets' timestamps
+    ///
+    /// Within each batch, packet N gets N times this spacing added to its
+    /// header export time (and sys_up_time for V5/V7/V9) and to its pcap
+    /// record timestamp, so flows don't share one identical timestamp.
+    /// Default 0 keeps all packets of a batch at the same header time.
+    #[arg(long, value_name = "MICROS", default_value = "0")]
+    pub packet_spacing_us: u64,
+
+    /// Wait up to SECS for the collector to become reachable before sending
+    ///
+    /// Probes the destination with an empty UDP datagram, retrying with
+    /// exponential backoff. UDP is connectionless, so this is best-effort:
+    /// only a collector port that actively refuses (ICMP port unreachable)
+    /// is detected, and silence is treated as ready. Defaults to 30 seconds
+    /// when given without a value.
+    #[arg(
+        long,
+        value_name = "SECS",
+        num_args = 0..=1,
+        default_missing_value = "30"
+    )]
+    pub wait_for_collector: Option<u64>,
+
+    /// [Testing only] Force the header count of every V5/V7/V9 packet to N
+    ///
+    /// Produces deliberately non-conformant packets whose count disagrees
+    /// with the records or flowsets actually present, to test collector
+    /// robustness. No validation is applied. IPFIX has no count field and
+    /// is unaffected.
+    #[arg(long, value_name = "N")]
+    pub override_count: Option<u16>,
+
+    /// [Testing only] Trim the last N bytes off every NetFlow payload
+    ///
+    /// Simulates payloads cut short upstream (e.g. by a too-small
+    /// snaplen): header length fields still describe the full packet and
+    /// disagree with the bytes actually sent. No validation is applied;
+    /// payloads of N bytes or fewer are sent empty.
+    #[arg(long, value_name = "N")]
+    pub truncate_bytes: Option<usize>,
+
+    /// [Testing only] Send each batch's data packets in reverse order
+    ///
+    /// Sequence numbers are still assigned in ascending order, so the
+    /// collector receives them descending within each export and has to
+    /// reorder by sequence number. Unlike a shuffle the order is
+    /// deterministic. Template packets stay at the front of the batch.
+    #[arg(long)]
+    pub reverse_order: bool,
+
+    /// Send or write each flow's packets as soon as they are built (single-shot)
+    ///
+    /// By default a single export builds every packet of the config before
+    /// sending any. With this flag flows are built in config order, a few at
+    /// a time, while the packets of earlier flows are sent or written, so
+    /// only those flows' packets are held at once. The packets are the same
+    /// as without the flag. The config is still loaded whole. Options that
+    /// reorder the whole batch are not available.
+    #[arg(
+        long,
+        requires = "once",
+        conflicts_with_all = ["interleave", "reverse_order", "replay"]
+    )]
+    pub stream: bool,
+
+    /// [Testing only] Skip N sequence numbers periodically to simulate loss
+    ///
+    /// After every --sequence-gap-every exports, each exporter's sequence
+    /// counter jumps ahead by N, as if the packets carrying those numbers
+    /// were lost: N flows for V5, N packets for V9 and N data records for
+    /// IPFIX. A collector should report exactly that many missing.
+    #[arg(long, value_name = "N", conflicts_with = "once", value_parser = clap::value_parser!(u32).range(1..))]
+    pub sequence_gap: Option<u32>,
+
+    /// Exports between --sequence-gap skips (default: 10)
+    #[arg(
+        long,
+        value_name = "EXPORTS",
+        default_value = "10",
+        requires = "sequence_gap",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub sequence_gap_every: u64,
+
+    /// [Testing only] Simulate an exporter reboot every SECS seconds
+    ///
+    /// At the first export after each interval, every exporter's sequence
+    /// counter restarts at 0 and all V9/IPFIX templates are resent, as a
+    /// device does after booting. The collector should detect the reboot
+    /// rather than report a huge sequence gap.
+    #[arg(
+        long,
+        value_name = "SECS",
+        conflicts_with = "once",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub reboot_interval: Option<u64>,
+
+    /// Restart the advancing V9 sys_up_time at each --reboot-interval reboot
+    ///
+    /// sys_up_time drops back to the configured header value, so the reboot
+    /// also shows as an uptime drop.
+    #[arg(long, requires_all = ["reboot_interval", "advance_uptime"])]
+    pub reboot_resets_uptime: bool,
+
+    /// [Testing only] Never send V9/IPFIX templates, only data packets
+    ///
+    /// Data packets are still well-formed but reference templates the
+    /// collector has never seen, to test how it buffers or drops orphan
+    /// data records.
+    #[arg(long)]
+    pub suppress_templates: bool,
+
+    /// [Testing only] Write ID as the FlowSet ID of every V9 data flowset
+    ///
+    /// The data no longer matches the template it was encoded with, to
+    /// test that a collector ignores flowsets for unknown templates or
+    /// rejects reserved IDs (2-255). No validation is applied.
+    #[arg(long, value_name = "ID")]
+    pub override_data_flowset_id: Option<u16>,
+
+    /// Emit IPFIX sets without 4-byte alignment padding
+    ///
+    /// RFC 7011 makes set padding optional; this tests whether a collector
+    /// tolerates sets whose length is not a multiple of 4. Set and message
+    /// lengths still match the bytes sent.
+    #[arg(long)]
+    pub no_padding: bool,
+
+    /// Pad every generated packet to at least BYTES (at most 65500)
+    ///
+    /// V9 and IPFIX packets get empty data flowsets of a template they
+    /// define or use, holding less padding than one record, so they still
+    /// parse with the same records; V9 sizes round up to a multiple of 4.
+    /// V5 and V7 flows repeat their last record until the packet is long
+    /// enough, and sequence numbers count the repeats.
+    #[arg(
+        long,
+        value_name = "BYTES",
+        value_parser = clap::value_parser!(u16).range(1..=65500)
+    )]
+    pub min_packet_size: Option<u16>,
+
+    /// Emit V9/IPFIX options templates before regular templates
+    ///
+    /// Template packets normally follow the configured order. Some
+    /// collectors need scope metadata (options templates) registered before
+    /// the data templates that reference it; this puts options templates
+    /// first in every template packet, followed by templates, then data.
+    #[arg(long)]
+    pub options_first: bool,
+
+    /// Put all V9/IPFIX templates of a kind in one template set per packet
+    ///
+    /// Template packets normally carry one template flowset (V9) or set
+    /// (IPFIX) per template. With this flag, consecutive templates share
+    /// one set, and options templates one options template set, as some
+    /// exporters send them. Combine with --options-first to get exactly
+    /// one set of each kind.
+    #[arg(long)]
+    pub group_templates: bool,
+
+    /// Send N distinct flows, split over the V9/IPFIX data flowsets, every export
+    ///
+    /// The N flows are shared evenly by the data flowsets of all flows. The
+    /// first record of each data flowset becomes the prototype for its
+    /// share, with distinct source address/port pairs taken in order from
+    /// the flow's address_pools or counting up from the prototype's source
+    /// address. Configurations without enough source addresses for N flows
+    /// are rejected. The same flows are re-sent each export, pinning a
+    /// collector's active flow count for capacity testing. Records are
+    /// split over data packets of as many as fit in a 1500-byte MTU, going
+    /// by the template's record length. V5/V7 flows are sent as configured.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub unique_flows: Option<u32>,
+
+    /// Fraction of --unique-flows replaced by new flows on each export (default: 0)
+    ///
+    /// Each replaced flow gets a 5-tuple never sent before, so the active
+    /// count stays at N while old entries age out of the collector's cache.
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        default_value = "0",
+        requires = "unique_flows",
+        value_parser = parse_fraction
+    )]
+    pub unique_flows_churn: f64,
+
+    /// Send only templates for the first SECS seconds (continuous mode)
+    ///
+    /// Every iteration of the warmup sends all V9/IPFIX templates from the
+    /// template cache and no data, giving a cold collector time to register
+    /// them before any data arrives. Flows then export normally, with
+    /// templates on their periodic refresh; start delays still count from
+    /// the start of the run. Requires --config or --config-dir.
+    #[arg(
+        long,
+        value_name = "SECS",
+        conflicts_with_all = ["once", "suppress_templates"],
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub warmup: Option<u64>,
+
+    /// Ramp the flow export volume up exponentially (continuous mode)
+    ///
+    /// Format: START:FACTOR:CAP (e.g. "10:2:10000"). Each iteration
+    /// exports START flows, repeating the configured flows round-robin,
+    /// and the volume is multiplied by FACTOR every --ramp-step seconds
+    /// until it reaches CAP. Requires --config or --config-dir.
+    #[arg(long, value_name = "START:FACTOR:CAP", conflicts_with = "once")]
+    pub ramp: Option<crate::generator::Ramp>,
+
+    /// Export in bursts separated by idle gaps (continuous mode)
+    ///
+    /// Format: ACTIVE:IDLE in seconds (e.g. "60:300"). Flows are exported
+    /// at their configured rate for ACTIVE seconds, then nothing (not even
+    /// template refreshes) is sent for IDLE seconds, repeating from the
+    /// start of the run. Simulates on/off workloads and exercises collector
+    /// flow timeout and expiry logic.
+    #[arg(long, value_name = "ACTIVE:IDLE", conflicts_with = "once")]
+    pub burst: Option<crate::generator::Burst>,
+
+    /// Serve an HTTP health check at IP:PORT/healthz (continuous mode)
+    ///
+    /// Returns 200 while the generator loop is running and 503 once
+    /// shutdown has begun, for Kubernetes liveness/readiness probes.
+    #[arg(long, value_name = "IP:PORT", conflicts_with = "once")]
+    pub health_addr: Option<std::net::SocketAddr>,
+
+    /// Print a progress line to stderr every N seconds (continuous mode)
+    ///
+    /// Each line shows the elapsed time, total packets and bytes sent, and
+    /// the packet and byte rates since the previous line. Printed even with
+    /// --quiet, and far less output than --verbose on long runs.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        conflicts_with = "once",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub summary_interval: Option<u64>,
+
+    /// Seconds between --ramp volume increases
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value = "60",
+        requires = "ramp",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub ramp_step: u64,
+}
+
+impl Cli {
+    /// Whether packets go to the collector: always, unless only written to --output
+    pub fn sends_to_collector(&self) -> bool {
+        self.output.is_none() || self.tee
+    }
+}
+
+/// File format for packets written with --output
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Pcap capture with synthetic Ethernet/IP/UDP headers
+    Pcap,
+    /// Pcapng capture with per-packet exporter comments
+    Pcapng,
+    /// Newline-separated hex strings, one NetFlow payload per line
+    Hex,
+    /// Concatenated binary NetFlow payloads
+    Raw,
+}
+
+/// Transport used to send packets to the collector
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transport {
+    /// Plain UDP datagrams
+    #[default]
+    Udp,
+    /// UDP datagrams encrypted with DTLS
+    Dtls,
+    /// IPFIX messages over one TCP connection
+    Tcp,
+}
+
+/// Link-layer header type for pcap output
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Datalink {
+    /// Ethernet II header (DLT_EN10MB)
+    #[default]
+    Ethernet,
+    /// Raw IPv4 with no link-layer header (DLT_RAW)
+    Raw,
+    /// Linux cooked capture header (DLT_LINUX_SLL)
+    LinuxSll,
+}
+
+/// Handling of packets written over --max-write-mbps
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteLimitPolicy {
+    /// Leave packets over the limit out of the output file
+    #[default]
+    Drop,
+    /// Wait until the limit allows the write
+    Block,
+}
+
+/// Ordering of records within a V9/IPFIX data flowset
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordOrder {
+    /// Records in configuration order
+    #[default]
+    Config,
+    /// Records sorted by their encoded field values
+    Sorted,
+    /// Records in reverse configuration order
+    Reverse,
+}
+
+/// A fraction between 0.0 and 1.0
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|fraction| (0.0..=1.0).contains(fraction))
+        .ok_or_else(|| format!("'{}' is not a fraction between 0.0 and 1.0", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sends_to_collector() {
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(std::iter::once("netflow_generator").chain(args.iter().copied()))
+        };
+        assert!(parse(&[]).unwrap().sends_to_collector());
+        assert!(
+            !parse(&["--output", "out.pcap"])
+                .unwrap()
+                .sends_to_collector()
+        );
+        // --tee sends and writes the same packets
+        assert!(
+            parse(&["--output", "out.pcap", "--tee"])
+                .unwrap()
+                .sends_to_collector()
+        );
+        assert!(parse(&["--tee"]).is_err());
+    }
 }
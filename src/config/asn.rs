@@ -0,0 +1,218 @@
+//! AS numbers looked up from a MaxMind-style ASN database (`--asn-db`)
+//!
+//! Reading the database requires the `asn-db` Cargo feature.
+
+use crate::config::schema::{Config, FlowConfig, IPFixFlowSet, V9FlowSet};
+use std::net::Ipv4Addr;
+
+/// 2-byte placeholder for 4-byte AS numbers (RFC 6793), used in V5/V7 records
+const AS_TRANS: u16 = 23456;
+
+/// (address key, AS key) pairs filled in V9 data records
+const V9_AS_KEYS: [(&str, &str); 2] = [("src_addr", "src_as"), ("dst_addr", "dst_as")];
+
+/// (address key, AS key) pairs filled in IPFIX data records
+const IPFIX_AS_KEYS: [(&str, &str); 2] = [
+    ("source_ipv4_address", "bgp_source_as_number"),
+    ("destination_ipv4_address", "bgp_destination_as_number"),
+];
+
+#[cfg(feature = "asn-db")]
+pub use reader::AsnDatabase;
+#[cfg(not(feature = "asn-db"))]
+pub use stub::AsnDatabase;
+
+#[cfg(feature = "asn-db")]
+mod reader {
+    use crate::error::{NetflowError, Result};
+    use maxminddb::{Reader, geoip2};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::path::Path;
+
+    /// ASN database (GeoLite2-ASN or compatible) loaded into memory
+    pub struct AsnDatabase(Reader<Vec<u8>>);
+
+    impl AsnDatabase {
+        pub fn open(path: &Path) -> Result<Self> {
+            Reader::open_readfile(path).map(Self).map_err(|e| {
+                NetflowError::Configuration(format!(
+                    "Failed to open ASN database {:?}: {}",
+                    path, e
+                ))
+            })
+        }
+
+        /// AS number announcing `address`, if the database covers it
+        pub fn lookup(&self, address: Ipv4Addr) -> Option<u32> {
+            self.0
+                .lookup::<geoip2::Asn>(IpAddr::V4(address))
+                .ok()?
+                .autonomous_system_number
+        }
+    }
+}
+
+#[cfg(not(feature = "asn-db"))]
+mod stub {
+    use crate::error::{NetflowError, Result};
+    use std::net::Ipv4Addr;
+    use std::path::Path;
+
+    /// Placeholder when built without the `asn-db` feature; opening always fails
+    pub struct AsnDatabase(std::convert::Infallible);
+
+    impl AsnDatabase {
+        pub fn open(_path: &Path) -> Result<Self> {
+            Err(NetflowError::Configuration(
+                "--asn-db requires building with the `asn-db` feature \
+                 (cargo build --features asn-db)"
+                    .to_string(),
+            ))
+        }
+
+        pub fn lookup(&self, _address: Ipv4Addr) -> Option<u32> {
+            match self.0 {}
+        }
+    }
+}
+
+/// Fill AS numbers left unset in the configuration from `lookup`
+///
+/// V5/V7 flowsets get `src_as`/`dst_as` from their addresses, with AS
+/// numbers above 65535 written as AS_TRANS. V9 (`src_as`/`dst_as`) and IPFIX
+/// (`bgp_source_as_number`/`bgp_destination_as_number`) data records are
+/// filled when they hold a literal IPv4 address. Explicit AS values and
+/// records with an `as_path` are left alone.
+pub fn fill_as_numbers<F>(config: &mut Config, lookup: F)
+where
+    F: Fn(Ipv4Addr) -> Option<u32>,
+{
+    let lookup_u16 = |address| lookup(address).map(|asn| u16::try_from(asn).unwrap_or(AS_TRANS));
+
+    for flow in &mut config.flows {
+        match flow {
+            FlowConfig::V5(v5) => {
+                for fs in &mut v5.flowsets {
+                    fs.src_as = fs.src_as.or_else(|| lookup_u16(fs.src_addr));
+                    fs.dst_as = fs.dst_as.or_else(|| lookup_u16(fs.dst_addr));
+                }
+            }
+            FlowConfig::V7(v7) => {
+                for fs in &mut v7.flowsets {
+                    fs.src_as = fs.src_as.or_else(|| lookup_u16(fs.src_addr));
+                    fs.dst_as = fs.dst_as.or_else(|| lookup_u16(fs.dst_addr));
+                }
+            }
+            FlowConfig::V9(v9) => {
+                for flowset in &mut v9.flowsets {
+                    if let V9FlowSet::Data { records, .. } = flowset {
+                        fill_records(records, &V9_AS_KEYS, &lookup);
+                    }
+                }
+            }
+            FlowConfig::IPFix(ipfix) => {
+                for flowset in &mut ipfix.flowsets {
+                    if let IPFixFlowSet::Data { records, .. } = flowset {
+                        fill_records(records, &IPFIX_AS_KEYS, &lookup);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn fill_records<F>(records: &mut [serde_yaml::Value], keys: &[(&str, &str)], lookup: &F)
+where
+    F: Fn(Ipv4Addr) -> Option<u32>,
+{
+    for record in records {
+        let Some(map) = record.as_mapping_mut() else {
+            continue;
+        };
+        if map.contains_key("as_path") {
+            continue;
+        }
+
+        for &(address_key, as_key) in keys {
+            if map.contains_key(as_key) {
+                continue;
+            }
+            let asn = map
+                .get(address_key)
+                .and_then(|value| value.as_str())
+                .and_then(|address| address.parse().ok())
+                .and_then(lookup);
+            if let Some(asn) = asn {
+                map.insert(as_key.into(), asn.into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_as_numbers() {
+        let yaml = r#"
+flows:
+  - version: v5
+    flowsets:
+      - src_addr: "10.0.0.1"
+        dst_addr: "8.8.8.8"
+        next_hop: "0.0.0.0"
+        input: 1
+        output: 2
+        d_pkts: 1
+        first: 0
+        last: 0
+        src_port: 1
+        dst_port: 53
+        tcp_flags: 0
+        protocol: 17
+        tos: 0
+        dst_as: 1
+        src_mask: 8
+        dst_mask: 24
+  - version: ipfix
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - source_ipv4_address: "10.0.0.1"
+            destination_ipv4_address: "8.8.8.8"
+          - source_ipv4_address: "10.0.0.1"
+            as_path: [64500, 64501]
+"#;
+        let mut config = crate::config::parse_yaml_str(yaml).unwrap();
+        fill_as_numbers(&mut config, |address| match address.octets() {
+            [10, ..] => Some(4_200_000_000),
+            [8, ..] => Some(15169),
+            _ => None,
+        });
+
+        // Explicit values win and 4-byte AS numbers become AS_TRANS
+        let FlowConfig::V5(ref v5) = config.flows[0] else {
+            panic!("expected a V5 flow");
+        };
+        assert_eq!(v5.flowsets[0].src_as, Some(AS_TRANS));
+        assert_eq!(v5.flowsets[0].dst_as, Some(1));
+
+        let FlowConfig::IPFix(ref ipfix) = config.flows[1] else {
+            panic!("expected an IPFIX flow");
+        };
+        let IPFixFlowSet::Data { ref records, .. } = ipfix.flowsets[0] else {
+            panic!("expected a data flowset");
+        };
+        assert_eq!(
+            records[0]["bgp_source_as_number"].as_u64(),
+            Some(4_200_000_000)
+        );
+        assert_eq!(
+            records[0]["bgp_destination_as_number"].as_u64(),
+            Some(15169)
+        );
+        assert!(records[1].get("bgp_source_as_number").is_none());
+    }
+}
@@ -0,0 +1,166 @@
+//! Compact one-line V5 flow definitions (`--flow`)
+//!
+//! A spec reads `<protocol> <src>[:<port>] -> <dst>[:<port>] [key=value ...]`,
+//! e.g. `tcp 192.168.1.10:54321 -> 172.217.14.206:443 pkts=150 bytes=95000`.
+//! Fields the spec leaves out take defaults, and `bytes` is derived from
+//! `pkts` as for any V5 record without `d_octets`.
+
+use crate::config::schema::{
+    Config, ConfigDefaults, CounterMode, Destination, FlowConfig, RelativeUptime, UptimeValue,
+    V5Config, V5FlowSet, protocol_from_name,
+};
+use crate::error::{NetflowError, Result};
+use std::net::Ipv4Addr;
+
+/// Build a configuration with one V5 flow holding a record per spec
+pub fn parse_flow_specs(specs: &[String]) -> Result<Config> {
+    let flowsets = specs
+        .iter()
+        .map(|spec| {
+            parse_flow_spec(spec).map_err(|reason| {
+                NetflowError::Configuration(format!("Invalid --flow '{}': {}", spec, reason))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Config {
+        flows: vec![FlowConfig::V5(V5Config {
+            header: None,
+            start_delay: None,
+            export_interval: None,
+            tcp_flag_sequence: None,
+            packet_sizes: None,
+            average_payload_size: None,
+            address_pools: None,
+            counter_mode: CounterMode::Delta,
+            dscp_distribution: None,
+            protocol_distribution: None,
+            flow_size_distribution: None,
+            flowsets,
+        })],
+        destination: Destination::default(),
+        scenario: Vec::new(),
+        defaults: ConfigDefaults::default(),
+    })
+}
+
+/// Parse one spec into a V5 record
+fn parse_flow_spec(spec: &str) -> std::result::Result<V5FlowSet, String> {
+    let mut words = spec.split_whitespace();
+    let protocol = parse_protocol(words.next().ok_or("empty spec")?)?;
+    let (src_addr, src_port) = parse_endpoint(words.next().ok_or("missing source")?)?;
+    if words.next() != Some("->") {
+        return Err("expected '->' after the source".to_string());
+    }
+    let (dst_addr, dst_port) = parse_endpoint(words.next().ok_or("missing destination")?)?;
+
+    let mut record = V5FlowSet {
+        src_addr,
+        dst_addr,
+        next_hop: Ipv4Addr::UNSPECIFIED,
+        input: 0,
+        output: 0,
+        d_pkts: 1,
+        d_octets: None,
+        // Ended just now after a minute, like the V5 sample
+        first: UptimeValue::Relative(RelativeUptime(60_000)),
+        last: UptimeValue::Relative(RelativeUptime(0)),
+        src_port,
+        dst_port,
+        tcp_flags: 0,
+        protocol: Some(protocol),
+        tos: None,
+        src_as: None,
+        dst_as: None,
+        src_mask: 0,
+        dst_mask: 0,
+        pad1: None,
+        pad2: None,
+    };
+
+    for pair in words {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got '{}'", pair))?;
+        let invalid = || format!("invalid value for {}: '{}'", key, value);
+        match key {
+            "pkts" => record.d_pkts = value.parse().map_err(|_| invalid())?,
+            "bytes" => record.d_octets = Some(value.parse().map_err(|_| invalid())?),
+            "tcp_flags" | "flags" => record.tcp_flags = value.parse().map_err(|_| invalid())?,
+            "tos" => record.tos = Some(value.parse().map_err(|_| invalid())?),
+            "input" => record.input = value.parse().map_err(|_| invalid())?,
+            "output" => record.output = value.parse().map_err(|_| invalid())?,
+            "next_hop" => record.next_hop = value.parse().map_err(|_| invalid())?,
+            "src_as" => record.src_as = Some(value.parse().map_err(|_| invalid())?),
+            "dst_as" => record.dst_as = Some(value.parse().map_err(|_| invalid())?),
+            "src_mask" => record.src_mask = value.parse().map_err(|_| invalid())?,
+            "dst_mask" => record.dst_mask = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+    }
+
+    Ok(record)
+}
+
+/// Protocol by name (`tcp`, `udp`, `icmp`, ...) or number
+fn parse_protocol(word: &str) -> std::result::Result<u8, String> {
+    protocol_from_name(word).map(Ok).unwrap_or_else(|| {
+        word.parse()
+            .map_err(|_| format!("unknown protocol '{}'", word))
+    })
+}
+
+/// `addr:port`, or a bare address with port 0
+fn parse_endpoint(word: &str) -> std::result::Result<(Ipv4Addr, u16), String> {
+    let (addr, port) = match word.split_once(':') {
+        Some((addr, port)) => (
+            addr,
+            port.parse()
+                .map_err(|_| format!("invalid port in '{}'", word))?,
+        ),
+        None => (word, 0),
+    };
+    let addr = addr
+        .parse()
+        .map_err(|_| format!("invalid IPv4 address in '{}'", word))?;
+    Ok((addr, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_flow_specs() {
+        let config = parse_flow_specs(&[
+            "tcp 192.168.1.10:54321 -> 172.217.14.206:443 pkts=150 bytes=95000".to_string(),
+            "icmp 10.0.0.1 -> 10.0.0.2 tos=184".to_string(),
+        ])
+        .unwrap();
+        let FlowConfig::V5(v5) = &config.flows[0] else {
+            panic!("expected a V5 flow");
+        };
+        assert_eq!(v5.flowsets.len(), 2);
+
+        let tcp = &v5.flowsets[0];
+        assert_eq!(tcp.protocol, Some(6));
+        assert_eq!(tcp.src_addr, Ipv4Addr::new(192, 168, 1, 10));
+        assert_eq!((tcp.src_port, tcp.dst_port), (54321, 443));
+        assert_eq!((tcp.d_pkts, tcp.d_octets), (150, Some(95000)));
+
+        let icmp = &v5.flowsets[1];
+        assert_eq!(
+            (icmp.protocol, icmp.dst_port, icmp.tos),
+            (Some(1), 0, Some(184))
+        );
+        assert_eq!((icmp.d_pkts, icmp.d_octets), (1, None));
+
+        for spec in [
+            "tcp 10.0.0.1:1 10.0.0.2:2",
+            "tcp 10.0.0.1:x -> 10.0.0.2:2",
+            "tcp 10.0.0.1:1 -> 10.0.0.2:2 colour=red",
+        ] {
+            assert!(parse_flow_specs(&[spec.to_string()]).is_err(), "{}", spec);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Minimum/maximum TTL derived from a simulated hop distance (`hop_count`)
+
+use crate::config::schema::{Config, FlowConfig, HopCountConfig, IPFixFlowSet, V9FlowSet};
+use serde_yaml::Value;
+
+/// Minimum and maximum TTL keys of V9 data records
+const V9_TTL_KEYS: (&str, &str) = ("min_ttl", "max_ttl");
+
+/// Minimum and maximum TTL keys of IPFIX data records
+const IPFIX_TTL_KEYS: (&str, &str) = ("minimum_ttl", "maximum_ttl");
+
+/// Fill the TTL fields of V9/IPFIX flows with a `hop_count`
+///
+/// Each data record without them gets `min_ttl`/`max_ttl` (V9) or
+/// `minimum_ttl`/`maximum_ttl` (IPFIX) from the flow's hop distance.
+/// Explicit values are left alone, each key on its own.
+pub fn fill_hop_ttls(config: &mut Config) {
+    for flow in &mut config.flows {
+        let Some(hop_count) = flow.hop_count() else {
+            continue;
+        };
+        match flow {
+            FlowConfig::V5(_) | FlowConfig::V7(_) => {}
+            FlowConfig::V9(v9) => {
+                for flowset in &mut v9.flowsets {
+                    if let V9FlowSet::Data { records, .. } = flowset {
+                        fill_records(records, V9_TTL_KEYS, hop_count);
+                    }
+                }
+            }
+            FlowConfig::IPFix(ipfix) => {
+                for flowset in &mut ipfix.flowsets {
+                    if let IPFixFlowSet::Data { records, .. } = flowset {
+                        fill_records(records, IPFIX_TTL_KEYS, hop_count);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn fill_records(
+    records: &mut [Value],
+    (min_key, max_key): (&str, &str),
+    hop_count: HopCountConfig,
+) {
+    let max_ttl = hop_count.initial_ttl.saturating_sub(hop_count.hops);
+    let min_ttl = max_ttl.saturating_sub(hop_count.variation);
+
+    for record in records {
+        let Some(map) = record.as_mapping_mut() else {
+            continue;
+        };
+        if !map.contains_key(min_key) {
+            map.insert(min_key.into(), min_ttl.into());
+        }
+        if !map.contains_key(max_key) {
+            map.insert(max_key.into(), max_ttl.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_hop_ttls() {
+        let yaml = r#"
+flows:
+  - version: v9
+    hop_count:
+      hops: 12
+      variation: 3
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - src_addr: "10.0.0.1"
+          - src_addr: "10.0.0.2"
+            max_ttl: 250
+  - version: ipfix
+    hop_count:
+      hops: 5
+      initial_ttl: 128
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - source_ipv4_address: "10.0.0.1"
+"#;
+        let mut config = crate::config::parse_yaml_str(yaml).unwrap();
+        fill_hop_ttls(&mut config);
+
+        let FlowConfig::V9(v9) = &config.flows[0] else {
+            panic!("expected a V9 flow");
+        };
+        let V9FlowSet::Data { records, .. } = &v9.flowsets[0] else {
+            panic!("expected a data flowset");
+        };
+        assert_eq!(records[0]["min_ttl"].as_u64(), Some(49));
+        assert_eq!(records[0]["max_ttl"].as_u64(), Some(52));
+        // An explicit TTL overrides the hop count
+        assert_eq!(records[1]["min_ttl"].as_u64(), Some(49));
+        assert_eq!(records[1]["max_ttl"].as_u64(), Some(250));
+
+        let FlowConfig::IPFix(ipfix) = &config.flows[1] else {
+            panic!("expected an IPFIX flow");
+        };
+        let IPFixFlowSet::Data { records, .. } = &ipfix.flowsets[0] else {
+            panic!("expected a data flowset");
+        };
+        assert_eq!(records[0]["minimum_ttl"].as_u64(), Some(123));
+        assert_eq!(records[0]["maximum_ttl"].as_u64(), Some(123));
+    }
+}
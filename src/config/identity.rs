@@ -0,0 +1,112 @@
+//! Per-instance exporter IDs derived from the hostname (`--exporter-id-from-host`)
+
+use crate::config::schema::{Config, FlowConfig, IPFixHeader, V9Header};
+use crate::error::{NetflowError, Result};
+
+/// Files holding the hostname, tried in order before the environment
+const HOSTNAME_FILES: [&str; 2] = ["/proc/sys/kernel/hostname", "/etc/hostname"];
+
+/// Environment variables holding the hostname on shells and Windows
+const HOSTNAME_VARS: [&str; 2] = ["HOSTNAME", "COMPUTERNAME"];
+
+/// Name of the machine this instance runs on
+pub fn hostname() -> Result<String> {
+    HOSTNAME_FILES
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .chain(
+            HOSTNAME_VARS
+                .iter()
+                .filter_map(|var| std::env::var(var).ok()),
+        )
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+        .ok_or_else(|| {
+            NetflowError::Configuration(
+                "Could not determine the hostname; pass --instance-id instead".to_string(),
+            )
+        })
+}
+
+/// Exporter ID for an instance name: its 32-bit FNV-1a hash
+///
+/// Observation domain 0 is reserved (RFC 7011), so a hash of 0 becomes 1.
+pub fn derive_exporter_id(instance: &str) -> u32 {
+    let hash = instance.bytes().fold(0x811c_9dc5u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+    hash.max(1)
+}
+
+/// Give every V9 flow without a `source_id`, and every IPFIX flow without an
+/// `observation_domain_id`, the exporter ID `id`
+///
+/// Explicit header values, including those from the `defaults` section,
+/// are kept. V5 and V7 flows are left alone.
+pub fn fill_exporter_ids(config: &mut Config, id: u32) {
+    for flow in &mut config.flows {
+        match flow {
+            FlowConfig::V5(_) | FlowConfig::V7(_) => {}
+            FlowConfig::V9(v9) => {
+                let header = v9.header.get_or_insert(V9Header {
+                    sys_up_time: None,
+                    unix_secs: None,
+                    sequence_number: None,
+                    source_id: None,
+                });
+                header.source_id.get_or_insert(id);
+            }
+            FlowConfig::IPFix(ipfix) => {
+                let header = ipfix.header.get_or_insert(IPFixHeader {
+                    export_time: None,
+                    sequence_number: None,
+                    observation_domain_id: None,
+                });
+                header.observation_domain_id.get_or_insert(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_exporter_ids() {
+        let mut config = crate::config::parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    flowsets: []
+  - version: ipfix
+    header:
+      export_time: 1735141200
+    flowsets: []
+  - version: ipfix
+    header:
+      observation_domain_id: 7
+    flowsets: []
+"#,
+        )
+        .unwrap();
+        let id = derive_exporter_id("collector-test-03");
+        assert_eq!(id, derive_exporter_id("collector-test-03"));
+        assert_ne!(id, derive_exporter_id("collector-test-04"));
+
+        fill_exporter_ids(&mut config, id);
+        let ids: Vec<Option<u32>> = config
+            .flows
+            .iter()
+            .map(|flow| match flow {
+                FlowConfig::V9(v9) => v9.header.as_ref().and_then(|h| h.source_id),
+                FlowConfig::IPFix(ipfix) => {
+                    ipfix.header.as_ref().and_then(|h| h.observation_domain_id)
+                }
+                _ => None,
+            })
+            .collect();
+        // Explicit IDs override the derived one
+        assert_eq!(ids, vec![Some(id), Some(id), Some(7)]);
+    }
+}
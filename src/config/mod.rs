@@ -1,7 +1,15 @@
+pub mod asn;
+pub mod flow_spec;
+pub mod hops;
+pub mod identity;
 pub mod parser;
 pub mod schema;
 pub mod validator;
 
+pub use asn::{AsnDatabase, fill_as_numbers};
+pub use flow_spec::parse_flow_specs;
+pub use hops::fill_hop_ttls;
+pub use identity::{derive_exporter_id, fill_exporter_ids, hostname};
 pub use parser::*;
 pub use schema::*;
 pub use validator::*;
@@ -1,20 +1,604 @@
-use crate::config::schema::Config;
-use crate::error::Result;
+use crate::config::schema::{
+    Config, ConfigDefaults, Destination, FlowConfig, IPFixFlowSet, IPFixHeader, ScenarioStep,
+    SchemaTemplate, TemplateSchema, V5Header, V7Header, V9FlowSet, V9Header,
+};
+use crate::error::{NetflowError, Result};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 
-/// Parse a YAML configuration file
-pub fn parse_yaml_file<P: AsRef<Path>>(path: P) -> Result<Config> {
+/// Parse a YAML configuration file, resolving template references against `schema`
+pub fn parse_yaml_file<P: AsRef<Path>>(path: P, schema: Option<&TemplateSchema>) -> Result<Config> {
     let contents = fs::read_to_string(path)?;
-    parse_yaml_str(&contents)
+    match schema {
+        Some(schema) => parse_yaml_str_with_schema(&contents, schema),
+        None => parse_yaml_str(&contents),
+    }
 }
 
 /// Parse a YAML configuration string
 pub fn parse_yaml_str(contents: &str) -> Result<Config> {
-    let config: Config = serde_yaml::from_str(contents)?;
+    parse_yaml(contents, None)
+}
+
+/// Parse a YAML configuration string, resolving template references against `schema`
+pub fn parse_yaml_str_with_schema(contents: &str, schema: &TemplateSchema) -> Result<Config> {
+    parse_yaml(contents, Some(schema))
+}
+
+/// Parse a configuration, resolving template references when a schema is given
+///
+/// Only a schema brings template references to resolve, so other configs
+/// are deserialized straight from the text, without an intermediate
+/// `serde_yaml::Value`.
+fn parse_yaml(contents: &str, schema: Option<&TemplateSchema>) -> Result<Config> {
+    let mut config: Config = match schema {
+        Some(schema) => {
+            let mut value: serde_yaml::Value = serde_yaml::from_str(contents)?;
+            resolve_template_refs(&mut value, schema)?;
+            serde_yaml::from_value(value)?
+        }
+        None => serde_yaml::from_str(contents)?,
+    };
+    apply_defaults(&mut config);
     Ok(config)
 }
 
+/// Merge the config's per-version `defaults` into the headers of its flows
+///
+/// The defaults are consumed, so the parsed config (and `--print-config`)
+/// shows each flow's effective header.
+fn apply_defaults(config: &mut Config) {
+    let defaults = std::mem::take(&mut config.defaults);
+    for flow in &mut config.flows {
+        match flow {
+            FlowConfig::V5(v5) => {
+                if let Some(ref defaults) = defaults.v5 {
+                    let header = v5.header.take();
+                    v5.header = Some(merge_v5_header(header, &defaults.header));
+                }
+            }
+            FlowConfig::V7(v7) => {
+                if let Some(ref defaults) = defaults.v7 {
+                    let header = v7.header.take();
+                    v7.header = Some(merge_v7_header(header, &defaults.header));
+                }
+            }
+            FlowConfig::V9(v9) => {
+                if let Some(ref defaults) = defaults.v9 {
+                    let header = v9.header.take();
+                    v9.header = Some(merge_v9_header(header, &defaults.header));
+                }
+            }
+            FlowConfig::IPFix(ipfix) => {
+                if let Some(ref defaults) = defaults.ipfix {
+                    let header = ipfix.header.take();
+                    ipfix.header = Some(merge_ipfix_header(header, &defaults.header));
+                }
+            }
+        }
+    }
+}
+
+fn merge_v5_header(header: Option<V5Header>, defaults: &V5Header) -> V5Header {
+    let Some(header) = header else {
+        return defaults.clone();
+    };
+    V5Header {
+        unix_secs: header.unix_secs.or(defaults.unix_secs),
+        unix_nsecs: header.unix_nsecs.or(defaults.unix_nsecs),
+        sys_up_time: header.sys_up_time.or(defaults.sys_up_time),
+        flow_sequence: header.flow_sequence.or(defaults.flow_sequence),
+        engine_type: header.engine_type.or(defaults.engine_type),
+        engine_id: header.engine_id.or(defaults.engine_id),
+        sampling_interval: header.sampling_interval.or(defaults.sampling_interval),
+        sampling_interval_sweep: header
+            .sampling_interval_sweep
+            .or_else(|| defaults.sampling_interval_sweep.clone()),
+    }
+}
+
+fn merge_v7_header(header: Option<V7Header>, defaults: &V7Header) -> V7Header {
+    let Some(header) = header else {
+        return defaults.clone();
+    };
+    V7Header {
+        unix_secs: header.unix_secs.or(defaults.unix_secs),
+        unix_nsecs: header.unix_nsecs.or(defaults.unix_nsecs),
+        sys_up_time: header.sys_up_time.or(defaults.sys_up_time),
+        flow_sequence: header.flow_sequence.or(defaults.flow_sequence),
+        reserved: header.reserved.or(defaults.reserved),
+    }
+}
+
+fn merge_v9_header(header: Option<V9Header>, defaults: &V9Header) -> V9Header {
+    let Some(header) = header else {
+        return defaults.clone();
+    };
+    V9Header {
+        sys_up_time: header.sys_up_time.or(defaults.sys_up_time),
+        unix_secs: header.unix_secs.or(defaults.unix_secs),
+        sequence_number: header.sequence_number.or(defaults.sequence_number),
+        source_id: header.source_id.or(defaults.source_id),
+    }
+}
+
+fn merge_ipfix_header(header: Option<IPFixHeader>, defaults: &IPFixHeader) -> IPFixHeader {
+    let Some(header) = header else {
+        return defaults.clone();
+    };
+    IPFixHeader {
+        export_time: header.export_time.or(defaults.export_time),
+        sequence_number: header.sequence_number.or(defaults.sequence_number),
+        observation_domain_id: header
+            .observation_domain_id
+            .or(defaults.observation_domain_id),
+    }
+}
+
+/// Parse a file of named templates (`--template-schema`)
+pub fn parse_template_schema<P: AsRef<Path>>(path: P) -> Result<TemplateSchema> {
+    let contents = fs::read_to_string(path)?;
+    let schema: TemplateSchema = serde_yaml::from_str(&contents)?;
+    Ok(schema)
+}
+
+/// Replace `template: <name>` references in V9/IPFIX flowsets with the schema's definition
+///
+/// A template flowset gets the named template's fields (and scope fields,
+/// making it an options template) and its template ID unless the flowset
+/// sets `template_id` itself. A data flowset naming a template gets the ID
+/// that template resolved to in the same flow, or the schema's ID.
+fn resolve_template_refs(config: &mut serde_yaml::Value, schema: &TemplateSchema) -> Result<()> {
+    let Some(flows) = config
+        .get_mut("flows")
+        .and_then(serde_yaml::Value::as_sequence_mut)
+    else {
+        return Ok(());
+    };
+
+    for (flow_index, flow) in flows.iter_mut().enumerate() {
+        let Some(flowsets) = flow
+            .get_mut("flowsets")
+            .and_then(serde_yaml::Value::as_sequence_mut)
+        else {
+            continue;
+        };
+        let lookup = |name: &serde_yaml::Value| -> Result<(String, &SchemaTemplate)> {
+            let name = name.as_str().ok_or_else(|| {
+                NetflowError::Configuration(format!(
+                    "Flow {} has a template reference that is not a name",
+                    flow_index
+                ))
+            })?;
+            let template = schema.templates.get(name).ok_or_else(|| {
+                NetflowError::Configuration(format!(
+                    "Flow {} references template '{}', which is not in the template schema",
+                    flow_index, name
+                ))
+            })?;
+            Ok((name.to_string(), template))
+        };
+        let missing_id = |name: &str| {
+            NetflowError::Configuration(format!(
+                "Flow {} references template '{}' without a template_id, and the schema gives none",
+                flow_index, name
+            ))
+        };
+        let is_data = |flowset: &serde_yaml::Mapping| {
+            flowset.get("type").and_then(serde_yaml::Value::as_str) == Some("data")
+        };
+
+        // Templates first, so data flowsets see IDs overridden in this flow
+        let mut resolved_ids: HashMap<String, u16> = HashMap::new();
+        for flowset in flowsets.iter_mut() {
+            let Some(flowset) = flowset.as_mapping_mut().filter(|f| !is_data(f)) else {
+                continue;
+            };
+            let Some(reference) = flowset.remove("template") else {
+                continue;
+            };
+            let (name, template) = lookup(&reference)?;
+            let template_id = match flowset.get("template_id") {
+                Some(id) => id.as_u64().and_then(|id| u16::try_from(id).ok()),
+                None => template.template_id,
+            }
+            .ok_or_else(|| missing_id(&name))?;
+
+            let kind = match template.scope_fields {
+                Some(ref scope_fields) => {
+                    flowset.insert("scope_fields".into(), scope_fields.clone().into());
+                    "options_template"
+                }
+                None => "template",
+            };
+            flowset.insert("type".into(), kind.into());
+            flowset.insert("template_id".into(), template_id.into());
+            flowset.insert("fields".into(), template.fields.clone().into());
+            resolved_ids.insert(name, template_id);
+        }
+
+        for flowset in flowsets.iter_mut() {
+            let Some(flowset) = flowset.as_mapping_mut().filter(|f| is_data(f)) else {
+                continue;
+            };
+            let Some(reference) = flowset.remove("template") else {
+                continue;
+            };
+            if flowset.contains_key("template_id") {
+                continue;
+            }
+            let (name, template) = lookup(&reference)?;
+            let template_id = resolved_ids
+                .get(&name)
+                .copied()
+                .or(template.template_id)
+                .ok_or_else(|| missing_id(&name))?;
+            flowset.insert("template_id".into(), template_id.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse every `.yaml`/`.yml` file in a directory and merge their flows
+///
+/// Files are read in name order so the merged flow list is stable. Files that
+/// set a non-default destination must all agree on it.
+pub fn parse_yaml_dir<P: AsRef<Path>>(dir: P, schema: Option<&TemplateSchema>) -> Result<Config> {
+    let dir = dir.as_ref();
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    paths.retain(|path| {
+        path.is_file()
+            && path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ext == "yaml" || ext == "yml")
+    });
+    paths.sort();
+
+    if paths.is_empty() {
+        return Err(NetflowError::Configuration(format!(
+            "No .yaml or .yml files found in {:?}",
+            dir
+        )));
+    }
+
+    let mut merged = Config {
+        flows: Vec::new(),
+        destination: Destination::default(),
+        scenario: Vec::new(),
+        defaults: ConfigDefaults::default(),
+    };
+    let mut destination_source: Option<PathBuf> = None;
+
+    for path in paths {
+        let config = parse_yaml_file(&path, schema)?;
+
+        if config.destination != Destination::default() {
+            match destination_source {
+                Some(ref source) if config.destination != merged.destination => {
+                    return Err(NetflowError::Configuration(format!(
+                        "Conflicting destinations: {:?} sets {}:{} but {:?} sets {}:{}",
+                        source,
+                        merged.destination.ip,
+                        merged.destination.port,
+                        path,
+                        config.destination.ip,
+                        config.destination.port
+                    )));
+                }
+                Some(_) => {}
+                None => {
+                    merged.destination = config.destination;
+                    destination_source = Some(path.clone());
+                }
+            }
+        }
+
+        // Scenario steps name flows by their index within their own file
+        let offset = merged.flows.len();
+        merged
+            .scenario
+            .extend(config.scenario.into_iter().map(|step| ScenarioStep {
+                action: step.action.flow().map_or(step.action, |flow| {
+                    step.action.with_flow(offset.saturating_add(flow))
+                }),
+                ..step
+            }));
+        merged.flows.extend(config.flows);
+    }
+
+    Ok(merged)
+}
+
+/// Expand IPFIX flows with `observation_domains: N` into N single-domain flows
+///
+/// Copies use consecutive observation domain IDs starting at the flow's own
+/// (default 1), so each is tracked as a separate exporter. Scenario steps
+/// naming an expanded flow apply to every copy.
+pub fn expand_observation_domains(config: &mut Config) {
+    let flows = std::mem::take(&mut config.flows);
+    // Original index of each flow after expansion
+    let mut origins = Vec::with_capacity(flows.len());
+    for (origin, flow) in flows.into_iter().enumerate() {
+        let FlowConfig::IPFix(mut ipfix) = flow else {
+            config.flows.push(flow);
+            origins.push(origin);
+            continue;
+        };
+        let Some(domains) = ipfix.observation_domains.take() else {
+            config.flows.push(FlowConfig::IPFix(ipfix));
+            origins.push(origin);
+            continue;
+        };
+
+        let header = ipfix.header.take().unwrap_or(IPFixHeader {
+            export_time: None,
+            sequence_number: None,
+            observation_domain_id: None,
+        });
+        let base = header.observation_domain_id.unwrap_or(1);
+        for offset in 0..domains {
+            let mut copy = ipfix.clone();
+            copy.header = Some(IPFixHeader {
+                observation_domain_id: Some(base.saturating_add(offset)),
+                ..header.clone()
+            });
+            config.flows.push(FlowConfig::IPFix(copy));
+            origins.push(origin);
+        }
+    }
+
+    let steps = std::mem::take(&mut config.scenario);
+    for step in steps {
+        let Some(flow) = step.action.flow() else {
+            config.scenario.push(step);
+            continue;
+        };
+        for (index, _) in origins.iter().enumerate().filter(|(_, o)| **o == flow) {
+            config.scenario.push(ScenarioStep {
+                action: step.action.with_flow(index),
+                ..step
+            });
+        }
+    }
+}
+
+/// Parse a template-only YAML file and attach data records given on the command line
+///
+/// Each record is a `--record` argument of comma-separated `key=value` pairs,
+/// or a line of the `ndjson` file holding one JSON object per line. Records go
+/// to the first V9 or IPFIX flow that defines a template, against its first
+/// template unless the record sets `template_id`.
+pub fn parse_template_with_records<P: AsRef<Path>>(
+    path: P,
+    records: &[String],
+    ndjson: Option<&Path>,
+    schema: Option<&TemplateSchema>,
+) -> Result<Config> {
+    let mut config = parse_yaml_file(path, schema)?;
+
+    let (index, default_template_id) = records_target(&config)?;
+    let flow = &mut config.flows[index];
+
+    for arg in records {
+        let mut record = parse_record_arg(arg)?;
+        let template_id = record_template_id(
+            &mut record,
+            default_template_id,
+            &format!("record '{}'", arg),
+        )?;
+        attach_record(flow, template_id, serde_yaml::Value::Mapping(record));
+    }
+
+    if let Some(ndjson) = ndjson {
+        for record in NdjsonRecords::open(ndjson, default_template_id)? {
+            let (template_id, record) = record?;
+            attach_record(flow, template_id, record);
+        }
+    }
+
+    Ok(config)
+}
+
+/// Index of the flow that takes command-line records, and its first template ID
+///
+/// This is the first V9 or IPFIX flow that defines a template.
+pub fn records_target(config: &Config) -> Result<(usize, u16)> {
+    config
+        .flows
+        .iter()
+        .enumerate()
+        .find_map(|(index, flow)| first_template_id(flow).map(|id| (index, id)))
+        .ok_or_else(|| {
+            NetflowError::Configuration(
+                "Template file must define a V9 or IPFIX template".to_string(),
+            )
+        })
+}
+
+/// Records of an NDJSON file, read one line at a time
+///
+/// Yields each record with the template it belongs to: its `template_id`
+/// key, or the default template. Large record files are never held in memory.
+pub struct NdjsonRecords {
+    path: PathBuf,
+    lines: std::io::Lines<BufReader<fs::File>>,
+    line_number: usize,
+    default_template_id: u16,
+}
+
+impl NdjsonRecords {
+    /// Open `path`, assigning records without a `template_id` to `default_template_id`
+    pub fn open(path: &Path, default_template_id: u16) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            lines: BufReader::new(fs::File::open(path)?).lines(),
+            line_number: 0,
+            default_template_id,
+        })
+    }
+
+    /// Parse one non-empty line into its template ID and record
+    fn parse_line(&self, line: &str) -> Result<(u16, serde_yaml::Value)> {
+        // JSON is valid YAML, so each line parses with the YAML parser
+        let mut record = match serde_yaml::from_str::<serde_yaml::Value>(line) {
+            Ok(serde_yaml::Value::Mapping(record)) => record,
+            Ok(_) => {
+                return Err(NetflowError::Configuration(format!(
+                    "{:?} line {}: expected a JSON object",
+                    self.path, self.line_number
+                )));
+            }
+            Err(e) => {
+                return Err(NetflowError::Configuration(format!(
+                    "{:?} line {}: {}",
+                    self.path, self.line_number, e
+                )));
+            }
+        };
+        let template_id = record_template_id(
+            &mut record,
+            self.default_template_id,
+            &format!("{:?} line {}", self.path, self.line_number),
+        )?;
+        Ok((template_id, serde_yaml::Value::Mapping(record)))
+    }
+}
+
+impl Iterator for NdjsonRecords {
+    type Item = Result<(u16, serde_yaml::Value)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            self.line_number = self.line_number.saturating_add(1);
+            if !line.trim().is_empty() {
+                return Some(self.parse_line(&line));
+            }
+        }
+    }
+}
+
+/// Remove a record's `template_id` key, falling back to `default_template_id`
+///
+/// `source` names where the record came from, for error messages.
+fn record_template_id(
+    record: &mut serde_yaml::Mapping,
+    default_template_id: u16,
+    source: &str,
+) -> Result<u16> {
+    match record.remove("template_id") {
+        Some(id) => id
+            .as_u64()
+            .and_then(|id| u16::try_from(id).ok())
+            .ok_or_else(|| {
+                NetflowError::Configuration(format!("Invalid template_id in {}", source))
+            }),
+        None => Ok(default_template_id),
+    }
+}
+
+/// Add a record to the data flowset for its template, creating the flowset if needed
+fn attach_record(flow: &mut FlowConfig, template_id: u16, record: serde_yaml::Value) {
+    match flow {
+        FlowConfig::V9(config) => {
+            match config
+                .flowsets
+                .iter_mut()
+                .find_map(|flowset| match flowset {
+                    V9FlowSet::Data {
+                        template_id: id,
+                        records,
+                    } if *id == template_id => Some(records),
+                    _ => None,
+                }) {
+                Some(records) => records.push(record),
+                None => config.flowsets.push(V9FlowSet::Data {
+                    template_id,
+                    records: vec![record],
+                }),
+            }
+        }
+        FlowConfig::IPFix(config) => {
+            match config
+                .flowsets
+                .iter_mut()
+                .find_map(|flowset| match flowset {
+                    IPFixFlowSet::Data {
+                        template_id: id,
+                        records,
+                    } if *id == template_id => Some(records),
+                    _ => None,
+                }) {
+                Some(records) => records.push(record),
+                None => config.flowsets.push(IPFixFlowSet::Data {
+                    template_id,
+                    records: vec![record],
+                }),
+            }
+        }
+        FlowConfig::V5(_) | FlowConfig::V7(_) => {}
+    }
+}
+
+/// ID of the first template defined by a V9 or IPFIX flow
+fn first_template_id(flow: &FlowConfig) -> Option<u16> {
+    match flow {
+        FlowConfig::V9(config) => config.flowsets.iter().find_map(|flowset| match flowset {
+            V9FlowSet::Template { template_id, .. }
+            | V9FlowSet::OptionsTemplate { template_id, .. } => Some(*template_id),
+            V9FlowSet::Data { .. } => None,
+        }),
+        FlowConfig::IPFix(config) => config.flowsets.iter().find_map(|flowset| match flowset {
+            IPFixFlowSet::Template { template_id, .. }
+            | IPFixFlowSet::OptionsTemplate { template_id, .. } => Some(*template_id),
+            IPFixFlowSet::Data { .. } => None,
+        }),
+        FlowConfig::V5(_) | FlowConfig::V7(_) => None,
+    }
+}
+
+/// Parse `key=value,key=value` into a record mapping
+///
+/// Values are read as YAML scalars, so `443` is a number and `1.2.3.4` a string.
+pub fn parse_record_arg(arg: &str) -> Result<serde_yaml::Mapping> {
+    let mut record = serde_yaml::Mapping::new();
+    for pair in arg.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (key, value) = pair.split_once('=').ok_or_else(|| {
+            NetflowError::Configuration(format!(
+                "Invalid record field '{}': expected key=value",
+                pair
+            ))
+        })?;
+        let value = value.trim();
+        let value = match serde_yaml::from_str::<serde_yaml::Value>(value) {
+            Ok(
+                scalar @ (serde_yaml::Value::Number(_)
+                | serde_yaml::Value::Bool(_)
+                | serde_yaml::Value::String(_)),
+            ) => scalar,
+            _ => serde_yaml::Value::String(value.to_string()),
+        };
+        record.insert(key.trim().into(), value);
+    }
+
+    if record.is_empty() {
+        return Err(NetflowError::Configuration(format!(
+            "Record '{}' has no fields",
+            arg
+        )));
+    }
+    Ok(record)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,4 +632,240 @@ flows:
         let config = parse_yaml_str(yaml).unwrap();
         assert_eq!(config.flows.len(), 1);
     }
+
+    #[test]
+    fn test_version_defaults() {
+        let yaml = r#"
+defaults:
+  v9:
+    header:
+      sys_up_time: 500000
+      source_id: 7
+flows:
+  - version: v9
+    flowsets: []
+  - version: v9
+    header:
+      source_id: 8
+    flowsets: []
+  - version: ipfix
+    flowsets: []
+"#;
+        let config = parse_yaml_str(yaml).unwrap();
+        let header = |index: usize| match &config.flows[index] {
+            FlowConfig::V9(v9) => v9.header.clone().unwrap(),
+            _ => panic!("expected a V9 flow"),
+        };
+        assert_eq!(header(0).sys_up_time, Some(500000));
+        assert_eq!(header(0).source_id, Some(7));
+        // A flow's own values win over the defaults
+        assert_eq!(header(1).sys_up_time, Some(500000));
+        assert_eq!(header(1).source_id, Some(8));
+        // Other versions are untouched
+        assert!(matches!(&config.flows[2], FlowConfig::IPFix(ipfix) if ipfix.header.is_none()));
+        assert!(config.defaults.v9.is_none());
+    }
+
+    #[test]
+    fn test_parse_yaml_dir_merges_flows() {
+        let dir = std::env::temp_dir().join(format!("netflow_config_dir_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let flow = |id: u16| {
+            format!(
+                "flows:\n  - version: ipfix\n    flowsets:\n      - type: template\n        template_id: {}\n        fields:\n          - field_type: octetDeltaCount\n            field_length: 4\n",
+                id
+            )
+        };
+        fs::write(dir.join("b.yml"), flow(301)).unwrap();
+        fs::write(
+            dir.join("a.yaml"),
+            flow(300) + "destination:\n  ip: \"10.0.0.9\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("notes.txt"), "not a config").unwrap();
+
+        let config = parse_yaml_dir(&dir, None).unwrap();
+        assert_eq!(config.flows.len(), 2);
+        assert_eq!(config.destination.ip, "10.0.0.9");
+
+        fs::write(
+            dir.join("c.yaml"),
+            flow(302) + "destination:\n  ip: \"10.0.0.10\"\n",
+        )
+        .unwrap();
+        assert!(parse_yaml_dir(&dir, None).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_template_with_records() {
+        let path =
+            std::env::temp_dir().join(format!("netflow_template_{}.yaml", std::process::id()));
+        fs::write(
+            &path,
+            "flows:\n  - version: v9\n    flowsets:\n      - type: template\n        template_id: 260\n        fields:\n          - field_type: IPV4_SRC_ADDR\n            field_length: 4\n          - field_type: L4_DST_PORT\n            field_length: 2\n",
+        )
+        .unwrap();
+
+        let records = [
+            "src_addr=1.2.3.4,dst_port=443".to_string(),
+            "src_addr=5.6.7.8, dst_port=53".to_string(),
+        ];
+        let ndjson_path =
+            std::env::temp_dir().join(format!("netflow_records_{}.ndjson", std::process::id()));
+        fs::write(
+            &ndjson_path,
+            "{\"src_addr\": \"9.9.9.9\", \"dst_port\": 853}\n\n",
+        )
+        .unwrap();
+        let config =
+            parse_template_with_records(&path, &records, Some(&ndjson_path), None).unwrap();
+
+        // Malformed lines are reported with their line number
+        fs::write(&ndjson_path, "{\"dst_port\": 1}\n{\"dst_port\": \n").unwrap();
+        let err = parse_template_with_records(&path, &[], Some(&ndjson_path), None).unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+
+        // Records are read one at a time, each with its template
+        fs::write(
+            &ndjson_path,
+            "{\"dst_port\": 1, \"template_id\": 301}\n{\"dst_port\": \n",
+        )
+        .unwrap();
+        let mut lazy = NdjsonRecords::open(&ndjson_path, 260).unwrap();
+        assert!(matches!(lazy.next(), Some(Ok((301, _)))));
+        assert!(matches!(lazy.next(), Some(Err(_))));
+        fs::remove_file(&ndjson_path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let FlowConfig::V9(v9) = &config.flows[0] else {
+            panic!("expected a V9 flow");
+        };
+        match &v9.flowsets[1] {
+            V9FlowSet::Data {
+                template_id,
+                records,
+            } => {
+                assert_eq!(*template_id, 260);
+                assert_eq!(records.len(), 3);
+                assert_eq!(records[0]["src_addr"].as_str(), Some("1.2.3.4"));
+                assert_eq!(records[1]["dst_port"].as_u64(), Some(53));
+                assert_eq!(records[2]["dst_port"].as_u64(), Some(853));
+            }
+            other => panic!("expected a data flowset, got {:?}", other),
+        }
+
+        assert!(parse_record_arg("src_addr").is_err());
+    }
+
+    #[test]
+    fn test_expand_observation_domains() {
+        let mut config = parse_yaml_str(
+            r#"
+flows:
+  - version: ipfix
+    observation_domains: 3
+    header:
+      observation_domain_id: 10
+      sequence_number: 5
+    flowsets:
+      - type: template
+        template_id: 300
+        fields:
+          - field_type: octetDeltaCount
+            field_length: 4
+"#,
+        )
+        .unwrap();
+        expand_observation_domains(&mut config);
+
+        let domains: Vec<_> = config
+            .flows
+            .iter()
+            .map(|flow| match flow {
+                FlowConfig::IPFix(ipfix) => {
+                    let header = ipfix.header.as_ref().unwrap();
+                    assert_eq!(header.sequence_number, Some(5));
+                    header.observation_domain_id
+                }
+                _ => panic!("expected an IPFIX flow"),
+            })
+            .collect();
+        assert_eq!(domains, [Some(10), Some(11), Some(12)]);
+    }
+
+    #[test]
+    fn test_template_schema_references() {
+        let schema: TemplateSchema = serde_yaml::from_str(
+            r#"
+templates:
+  web:
+    template_id: 300
+    fields:
+      - field_type: sourceIPv4Address
+        field_length: 4
+      - field_type: octetDeltaCount
+        field_length: 8
+  unnumbered:
+    fields:
+      - field_type: packetDeltaCount
+        field_length: 8
+"#,
+        )
+        .unwrap();
+        let config = parse_yaml_str_with_schema(
+            r#"
+flows:
+  - version: ipfix
+    flowsets:
+      - template: web
+      - template: unnumbered
+        template_id: 400
+      - type: data
+        template: unnumbered
+        records:
+          - packet_delta_count: 7
+      - type: data
+        template: web
+        records: []
+"#,
+            &schema,
+        )
+        .unwrap();
+
+        let FlowConfig::IPFix(ipfix) = &config.flows[0] else {
+            panic!("expected an IPFIX flow");
+        };
+        let IPFixFlowSet::Template {
+            template_id,
+            fields,
+        } = &ipfix.flowsets[0]
+        else {
+            panic!("expected a template flowset");
+        };
+        assert_eq!(*template_id, 300);
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[1].field_type, "octetDeltaCount");
+        let data_ids: Vec<u16> = ipfix.flowsets[2..]
+            .iter()
+            .map(|flowset| match flowset {
+                IPFixFlowSet::Data { template_id, .. } => *template_id,
+                _ => panic!("expected a data flowset"),
+            })
+            .collect();
+        assert_eq!(data_ids, [400, 300]);
+
+        let unresolved = [
+            "flows:\n  - version: ipfix\n    flowsets:\n      - template: missing\n",
+            "flows:\n  - version: ipfix\n    flowsets:\n      - template: unnumbered\n",
+        ];
+        for yaml in unresolved {
+            assert!(parse_yaml_str_with_schema(yaml, &schema).is_err());
+        }
+        assert!(
+            parse_yaml_str("flows:\n  - version: ipfix\n    flowsets:\n      - template: web\n")
+                .is_err()
+        );
+    }
 }
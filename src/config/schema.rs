@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::net::Ipv4Addr;
 
 /// Root configuration structure
@@ -11,6 +13,36 @@ pub struct Config {
     /// Destination for UDP transmission
     #[serde(default)]
     pub destination: Destination,
+
+    /// Timed steps played by the continuous loop
+    #[serde(default)]
+    pub scenario: Vec<ScenarioStep>,
+
+    /// Per-version settings shared by every flow of that version
+    #[serde(default)]
+    pub defaults: ConfigDefaults,
+}
+
+/// Per-version defaults merged into each flow when the config is parsed (`defaults`)
+///
+/// A flow's own header values win; any it leaves unset are taken from the
+/// defaults for its version.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ConfigDefaults {
+    #[serde(default)]
+    pub v5: Option<VersionDefaults<V5Header>>,
+    #[serde(default)]
+    pub v7: Option<VersionDefaults<V7Header>>,
+    #[serde(default)]
+    pub v9: Option<VersionDefaults<V9Header>>,
+    #[serde(default)]
+    pub ipfix: Option<VersionDefaults<IPFixHeader>>,
+}
+
+/// Defaults for the flows of one version
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct VersionDefaults<H> {
+    pub header: H,
 }
 
 /// Flow configuration (version-specific)
@@ -27,6 +59,123 @@ pub enum FlowConfig {
     IPFix(IPFixConfig),
 }
 
+impl FlowConfig {
+    /// Seconds into a continuous run before this flow starts emitting
+    pub fn start_delay(&self) -> Option<u64> {
+        match self {
+            FlowConfig::V5(config) => config.start_delay,
+            FlowConfig::V7(config) => config.start_delay,
+            FlowConfig::V9(config) => config.start_delay,
+            FlowConfig::IPFix(config) => config.start_delay,
+        }
+    }
+
+    /// Seconds between exports of this flow in continuous mode, if set per flow
+    pub fn export_interval(&self) -> Option<u64> {
+        match self {
+            FlowConfig::V5(config) => config.export_interval,
+            FlowConfig::V7(config) => config.export_interval,
+            FlowConfig::V9(config) => config.export_interval,
+            FlowConfig::IPFix(config) => config.export_interval,
+        }
+    }
+
+    /// TCP flags cycled through on successive exports of this flow, if set
+    pub fn tcp_flag_sequence(&self) -> Option<&[u8]> {
+        match self {
+            FlowConfig::V5(config) => config.tcp_flag_sequence.as_deref(),
+            FlowConfig::V7(config) => config.tcp_flag_sequence.as_deref(),
+            FlowConfig::V9(config) => config.tcp_flag_sequence.as_deref(),
+            FlowConfig::IPFix(config) => config.tcp_flag_sequence.as_deref(),
+        }
+    }
+
+    /// Address pools of this flow, if set
+    pub fn address_pools(&self) -> Option<&AddressPoolsConfig> {
+        match self {
+            FlowConfig::V5(config) => config.address_pools.as_ref(),
+            FlowConfig::V7(config) => config.address_pools.as_ref(),
+            FlowConfig::V9(config) => config.address_pools.as_ref(),
+            FlowConfig::IPFix(config) => config.address_pools.as_ref(),
+        }
+    }
+
+    /// Observation domain rotation of this flow, if set (IPFIX only)
+    pub fn domain_rotation(&self) -> Option<&DomainRotationConfig> {
+        match self {
+            FlowConfig::IPFix(config) => config.domain_rotation.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Counter mode of this flow
+    pub fn counter_mode(&self) -> CounterMode {
+        match self {
+            FlowConfig::V5(config) => config.counter_mode,
+            FlowConfig::V7(config) => config.counter_mode,
+            FlowConfig::V9(config) => config.counter_mode,
+            FlowConfig::IPFix(config) => config.counter_mode,
+        }
+    }
+
+    /// DSCP distribution of this flow, if set
+    pub fn dscp_distribution(&self) -> Option<&DscpDistributionConfig> {
+        match self {
+            FlowConfig::V5(config) => config.dscp_distribution.as_ref(),
+            FlowConfig::V7(config) => config.dscp_distribution.as_ref(),
+            FlowConfig::V9(config) => config.dscp_distribution.as_ref(),
+            FlowConfig::IPFix(config) => config.dscp_distribution.as_ref(),
+        }
+    }
+
+    /// Protocol distribution of this flow, if set
+    pub fn protocol_distribution(&self) -> Option<&ProtocolDistributionConfig> {
+        match self {
+            FlowConfig::V5(config) => config.protocol_distribution.as_ref(),
+            FlowConfig::V7(config) => config.protocol_distribution.as_ref(),
+            FlowConfig::V9(config) => config.protocol_distribution.as_ref(),
+            FlowConfig::IPFix(config) => config.protocol_distribution.as_ref(),
+        }
+    }
+
+    /// Flow size distribution of this flow, if set
+    pub fn flow_size_distribution(&self) -> Option<&FlowSizeDistributionConfig> {
+        match self {
+            FlowConfig::V5(config) => config.flow_size_distribution.as_ref(),
+            FlowConfig::V7(config) => config.flow_size_distribution.as_ref(),
+            FlowConfig::V9(config) => config.flow_size_distribution.as_ref(),
+            FlowConfig::IPFix(config) => config.flow_size_distribution.as_ref(),
+        }
+    }
+
+    /// Hop count simulation of this flow, if set (V9 and IPFIX only)
+    pub fn hop_count(&self) -> Option<HopCountConfig> {
+        match self {
+            FlowConfig::V5(_) | FlowConfig::V7(_) => None,
+            FlowConfig::V9(config) => config.hop_count,
+            FlowConfig::IPFix(config) => config.hop_count,
+        }
+    }
+
+    /// Seed for this flow's field providers (0 for V5 and V7, which have none)
+    pub fn provider_seed(&self) -> u64 {
+        match self {
+            FlowConfig::V5(_) | FlowConfig::V7(_) => 0,
+            FlowConfig::V9(config) => config.provider_seed,
+            FlowConfig::IPFix(config) => config.provider_seed,
+        }
+    }
+
+    /// Flow table simulation of this flow, if set (V9 and IPFIX only)
+    pub fn flow_table(&self) -> Option<&FlowTableConfig> {
+        match self {
+            FlowConfig::V5(_) | FlowConfig::V7(_) => None,
+            FlowConfig::V9(config) => config.flow_table.as_ref(),
+            FlowConfig::IPFix(config) => config.flow_table.as_ref(),
+        }
+    }
+}
+
 // ============================================================================
 // NetFlow V5 Configuration
 // ============================================================================
@@ -37,6 +186,46 @@ pub struct V5Config {
     #[serde(default)]
     pub header: Option<V5Header>,
 
+    /// Seconds into a continuous run before this flow starts emitting
+    #[serde(default)]
+    pub start_delay: Option<u64>,
+
+    /// Seconds between exports of this flow in continuous mode (overrides --interval)
+    #[serde(default)]
+    pub export_interval: Option<u64>,
+
+    /// TCP flags applied to every record on successive exports, one phase per export
+    #[serde(default)]
+    pub tcp_flag_sequence: Option<Vec<u8>>,
+
+    /// Packet size distribution used to derive omitted d_octets (default IMIX)
+    #[serde(default)]
+    pub packet_sizes: Option<Vec<PacketSize>>,
+
+    /// Mean payload bytes per packet, deriving omitted d_octets with headers added instead
+    #[serde(default)]
+    pub average_payload_size: Option<u16>,
+
+    /// Weighted pools the source and destination addresses are drawn from on each export
+    #[serde(default)]
+    pub address_pools: Option<AddressPoolsConfig>,
+
+    /// Whether counters report each export's increment or a running total
+    #[serde(default)]
+    pub counter_mode: CounterMode,
+
+    /// Weighted DSCP classes the TOS of records without one is drawn from
+    #[serde(default)]
+    pub dscp_distribution: Option<DscpDistributionConfig>,
+
+    /// Weighted protocols the protocol of records without one is drawn from
+    #[serde(default)]
+    pub protocol_distribution: Option<ProtocolDistributionConfig>,
+
+    /// Heavy-tailed distribution every record's packet and byte counts are drawn from
+    #[serde(default)]
+    pub flow_size_distribution: Option<FlowSizeDistributionConfig>,
+
     /// Flow records
     pub flowsets: Vec<V5FlowSet>,
 }
@@ -50,6 +239,511 @@ pub struct V5Header {
     pub engine_type: Option<u8>,
     pub engine_id: Option<u8>,
     pub sampling_interval: Option<u16>,
+    /// Emit one packet per sampling interval in this list (overrides `sampling_interval`)
+    pub sampling_interval_sweep: Option<Vec<u16>>,
+}
+
+impl V5Config {
+    /// Number of packets generated per iteration (one per swept sampling interval)
+    pub fn packet_count(&self) -> usize {
+        self.header
+            .as_ref()
+            .and_then(|h| h.sampling_interval_sweep.as_ref())
+            .map_or(1, |sweep| sweep.len().max(1))
+    }
+}
+
+/// A V5/V7 `first`/`last` value in the router's sysUpTime domain
+///
+/// Either absolute milliseconds since boot, or a time before export such as
+/// `"-60s"` or `"-0s"` that resolves against the packet's `sys_up_time`.
+/// Relative values accept `ms`, `s`, `m` and `h` units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged, try_from = "RawUptimeValue")]
+pub enum UptimeValue {
+    Absolute(u32),
+    Relative(RelativeUptime),
+}
+
+/// Untyped form of [`UptimeValue`], so invalid strings report why they failed
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawUptimeValue {
+    Absolute(u32),
+    Relative(String),
+}
+
+impl TryFrom<RawUptimeValue> for UptimeValue {
+    type Error = String;
+
+    fn try_from(value: RawUptimeValue) -> Result<Self, Self::Error> {
+        match value {
+            RawUptimeValue::Absolute(value) => Ok(UptimeValue::Absolute(value)),
+            RawUptimeValue::Relative(value) => RelativeUptime::try_from(value).map(Self::Relative),
+        }
+    }
+}
+
+impl UptimeValue {
+    /// Milliseconds since boot for a packet whose header has `sys_up_time`
+    ///
+    /// Relative values older than the boot time clamp to 0.
+    pub fn resolve(self, sys_up_time: u32) -> u32 {
+        match self {
+            UptimeValue::Absolute(value) => value,
+            UptimeValue::Relative(ago) => sys_up_time.saturating_sub(ago.0),
+        }
+    }
+
+    /// The same time moved `millis` further into the past, clamping at boot
+    pub fn earlier(self, millis: u32) -> Self {
+        match self {
+            UptimeValue::Absolute(value) => UptimeValue::Absolute(value.saturating_sub(millis)),
+            UptimeValue::Relative(ago) => {
+                UptimeValue::Relative(RelativeUptime(ago.0.saturating_add(millis)))
+            }
+        }
+    }
+}
+
+impl From<u32> for UptimeValue {
+    fn from(value: u32) -> Self {
+        UptimeValue::Absolute(value)
+    }
+}
+
+impl fmt::Display for UptimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UptimeValue::Absolute(value) => write!(f, "{}", value),
+            UptimeValue::Relative(ago) => write!(f, "-{}ms", ago.0),
+        }
+    }
+}
+
+/// Milliseconds before export, parsed from strings like `"-60s"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RelativeUptime(pub u32);
+
+impl TryFrom<String> for RelativeUptime {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || {
+            format!(
+                "invalid relative time '{}': expected e.g. \"-60s\" or \"-500ms\"",
+                value
+            )
+        };
+        let ago = value.trim().strip_prefix('-').ok_or_else(invalid)?;
+        let split = ago
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (amount, unit) = ago.split_at(split);
+        let amount: u32 = amount.parse().map_err(|_| invalid())?;
+        let scale: u32 = match unit {
+            "ms" => 1,
+            "s" => 1_000,
+            "m" => 60_000,
+            "h" => 3_600_000,
+            _ => return Err(invalid()),
+        };
+        amount
+            .checked_mul(scale)
+            .map(Self)
+            .ok_or_else(|| format!("relative time '{}' exceeds u32 milliseconds", value))
+    }
+}
+
+impl From<RelativeUptime> for String {
+    fn from(value: RelativeUptime) -> Self {
+        format!("-{}ms", value.0)
+    }
+}
+
+/// One bucket of a packet size distribution
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PacketSize {
+    /// Packet size in bytes
+    pub size: u32,
+    /// Relative frequency of this size
+    pub weight: u32,
+}
+
+/// Flow table simulation for a V9 or IPFIX flow
+///
+/// The first record of each data flowset is the prototype for `size`
+/// active flows with stable keys. Each export reports every active flow with
+/// the prototype's counters as that export's increment (running totals since
+/// the flow started with `counter_mode: cumulative`), and replaces a `churn`
+/// fraction of them with new flows.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct FlowTableConfig {
+    /// Number of active flows
+    pub size: usize,
+    /// Fraction of active flows (0.0-1.0) replaced by new flows on each export
+    #[serde(default)]
+    pub churn: f64,
+    /// Seed for flow keys and churn, so runs are reproducible
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// Weighted IPv4 address pools for source/destination selection
+///
+/// On each export every record's source and destination address is drawn
+/// from a pool chosen by weight, at a random address within its prefix.
+/// An empty list leaves that address unchanged.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AddressPoolsConfig {
+    #[serde(default)]
+    pub source: Vec<AddressPool>,
+    #[serde(default)]
+    pub destination: Vec<AddressPool>,
+    /// Seed for address selection, so runs are reproducible
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// How a flow's byte and packet counters evolve in continuous mode (`counter_mode`)
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CounterMode {
+    /// Every export reports the configured counters, as one period's increment
+    #[default]
+    Delta,
+    /// Counters grow by the configured values on every export, as running totals
+    Cumulative,
+}
+
+/// Padding flowsets appended by V9 `pad_to` (`pad_flowset`)
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PadFlowset {
+    /// Data flowsets of a template in the packet, holding less than one record
+    #[default]
+    Data,
+    /// One template flowset (FlowSet ID 0) of zeros, as some exporters send
+    Zero,
+}
+
+/// Weighted DSCP classes for the TOS of generated records (`dscp_distribution`)
+///
+/// On each export every record without a configured TOS gets a class drawn
+/// by weight, written as `dscp << 2` in `tos`, `src_tos` or
+/// `ip_class_of_service`. Records that set the field keep their value.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DscpDistributionConfig {
+    pub classes: Vec<DscpClass>,
+    /// Seed for class selection, so runs are reproducible
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// One class of a DSCP distribution
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct DscpClass {
+    pub dscp: Dscp,
+    /// Relative frequency of this class
+    pub weight: u32,
+}
+
+/// DSCP code point, given as a number or a class name like `EF`, `AF41` or `CS1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "RawDscp", into = "u8")]
+pub struct Dscp(pub u8);
+
+/// Untyped form of [`Dscp`], so unknown class names report why they failed
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDscp {
+    Value(u8),
+    Name(String),
+}
+
+impl TryFrom<RawDscp> for Dscp {
+    type Error = String;
+
+    fn try_from(value: RawDscp) -> Result<Self, Self::Error> {
+        match value {
+            RawDscp::Value(value) if value <= 63 => Ok(Dscp(value)),
+            RawDscp::Value(value) => Err(format!("DSCP {} is out of range (0-63)", value)),
+            RawDscp::Name(name) => dscp_from_name(&name)
+                .map(Dscp)
+                .ok_or_else(|| format!("Unknown DSCP class '{}'", name)),
+        }
+    }
+}
+
+/// Code point of a named class: `BE`/`default`, `EF`, `CS0`-`CS7` or `AF11`-`AF43`
+fn dscp_from_name(name: &str) -> Option<u8> {
+    let name = name.to_ascii_uppercase();
+    let digit = |c: Option<char>| {
+        c.and_then(|c| c.to_digit(10))
+            .and_then(|d| u8::try_from(d).ok())
+    };
+    match name.as_str() {
+        "BE" | "DEFAULT" => Some(0),
+        "EF" => Some(46),
+        _ => {
+            if let Some(class) = name.strip_prefix("CS") {
+                let mut chars = class.chars();
+                let n = digit(chars.next()).filter(|n| *n <= 7)?;
+                chars.next().is_none().then(|| n.saturating_mul(8))
+            } else {
+                let class = name.strip_prefix("AF")?;
+                let mut chars = class.chars();
+                let x = digit(chars.next()).filter(|x| (1..=4).contains(x))?;
+                let y = digit(chars.next()).filter(|y| (1..=3).contains(y))?;
+                chars
+                    .next()
+                    .is_none()
+                    .then(|| x.saturating_mul(8).saturating_add(y.saturating_mul(2)))
+            }
+        }
+    }
+}
+
+impl From<Dscp> for u8 {
+    fn from(value: Dscp) -> Self {
+        value.0
+    }
+}
+
+/// Weighted IP protocols for generated records (`protocol_distribution`)
+///
+/// On each export every record without a configured protocol gets one
+/// drawn by weight, and its ports and TCP flags are made to match: no
+/// ports for ICMP and other portless protocols, and no TCP flags outside
+/// TCP. Records that set a protocol keep it and their other fields.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ProtocolDistributionConfig {
+    pub protocols: Vec<ProtocolWeight>,
+    /// Seed for protocol and port selection, so runs are reproducible
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// One protocol of a protocol distribution
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct ProtocolWeight {
+    pub protocol: Protocol,
+    /// Relative frequency of this protocol
+    pub weight: u32,
+}
+
+/// IP protocol number, given as a number or a name like `tcp` or `icmp`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "RawProtocol", into = "u8")]
+pub struct Protocol(pub u8);
+
+/// Untyped form of [`Protocol`], so unknown protocol names report why they failed
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawProtocol {
+    Value(u8),
+    Name(String),
+}
+
+impl TryFrom<RawProtocol> for Protocol {
+    type Error = String;
+
+    fn try_from(value: RawProtocol) -> Result<Self, Self::Error> {
+        match value {
+            RawProtocol::Value(value) => Ok(Protocol(value)),
+            RawProtocol::Name(name) => protocol_from_name(&name)
+                .map(Protocol)
+                .ok_or_else(|| format!("Unknown protocol '{}'", name)),
+        }
+    }
+}
+
+impl From<Protocol> for u8 {
+    fn from(value: Protocol) -> Self {
+        value.0
+    }
+}
+
+/// Protocol number of a common protocol name, ignoring case
+pub fn protocol_from_name(name: &str) -> Option<u8> {
+    match name.to_ascii_lowercase().as_str() {
+        "icmp" => Some(1),
+        "tcp" => Some(6),
+        "udp" => Some(17),
+        "gre" => Some(47),
+        "esp" => Some(50),
+        "icmpv6" => Some(58),
+        "sctp" => Some(132),
+        _ => None,
+    }
+}
+
+/// Heavy-tailed packet counts for generated records (`flow_size_distribution`)
+///
+/// On each export every record's packet count is drawn from the model, so
+/// a few elephant flows carry most of the traffic among many mice. Byte
+/// counts are derived from the drawn packet counts.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct FlowSizeDistributionConfig {
+    #[serde(flatten)]
+    pub model: FlowSizeModel,
+    /// Largest packet count drawn; larger draws are capped
+    #[serde(default)]
+    pub max_packets: Option<u32>,
+    /// Seed for the drawn counts, so runs are reproducible
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// Distribution of per-record packet counts
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(tag = "model", rename_all = "lowercase")]
+pub enum FlowSizeModel {
+    /// Pareto with the given minimum and shape (alpha); smaller shapes give heavier tails
+    Pareto {
+        #[serde(default = "default_min_packets")]
+        min_packets: f64,
+        shape: f64,
+    },
+    /// Log-normal whose logarithm has mean `mu` and standard deviation `sigma`
+    Lognormal { mu: f64, sigma: f64 },
+}
+
+fn default_min_packets() -> f64 {
+    1.0
+}
+
+/// Hop distance of a flow's traffic from its source (`hop_count`)
+///
+/// Records without minimum/maximum TTL values get `initial_ttl - hops -
+/// variation` and `initial_ttl - hops`, as a collector would see from a
+/// source that many routers away.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct HopCountConfig {
+    /// Routers between the traffic's source and the exporter on the shortest path
+    pub hops: u8,
+    /// Extra hops on the longest path seen, lowering the minimum TTL (default 0)
+    #[serde(default)]
+    pub variation: u8,
+    /// TTL the source sends with (default 64)
+    #[serde(default = "default_initial_ttl")]
+    pub initial_ttl: u8,
+}
+
+fn default_initial_ttl() -> u8 {
+    64
+}
+
+/// One pool of an address pool list
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AddressPool {
+    pub prefix: Ipv4Prefix,
+    /// Relative frequency of this pool
+    pub weight: u32,
+}
+
+/// IPv4 network in CIDR notation, parsed from strings like `"10.1.0.0/16"`
+///
+/// Host bits below the prefix length are cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Ipv4Prefix {
+    pub network: Ipv4Addr,
+    pub len: u8,
+}
+
+impl Ipv4Prefix {
+    /// Number of addresses in the prefix
+    pub fn size(&self) -> u64 {
+        1u64.checked_shl(u32::from(32u8.saturating_sub(self.len)))
+            .unwrap_or(0)
+    }
+
+    /// Address `offset` into the prefix, wrapping within it
+    pub fn nth(&self, offset: u64) -> Ipv4Addr {
+        let host = offset
+            .checked_rem(self.size())
+            .and_then(|host| u32::try_from(host).ok())
+            .unwrap_or(0);
+        Ipv4Addr::from(u32::from(self.network) | host)
+    }
+}
+
+impl TryFrom<String> for Ipv4Prefix {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid prefix '{}': expected e.g. \"10.1.0.0/16\"", value);
+        let (network, len) = value.trim().split_once('/').ok_or_else(invalid)?;
+        let network: Ipv4Addr = network.parse().map_err(|_| invalid())?;
+        let len: u8 = len
+            .parse()
+            .ok()
+            .filter(|len| *len <= 32)
+            .ok_or_else(invalid)?;
+        let mask = u32::MAX
+            .checked_shl(u32::from(32u8.saturating_sub(len)))
+            .unwrap_or(0);
+        Ok(Self {
+            network: Ipv4Addr::from(u32::from(network) & mask),
+            len,
+        })
+    }
+}
+
+impl From<Ipv4Prefix> for String {
+    fn from(value: Ipv4Prefix) -> Self {
+        format!("{}/{}", value.network, value.len)
+    }
+}
+
+/// One timed step of a `scenario`
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct ScenarioStep {
+    /// Seconds into the run at which the step fires
+    pub at: u64,
+    #[serde(flatten)]
+    pub action: ScenarioAction,
+}
+
+/// What a scenario step does
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ScenarioAction {
+    /// Send every V9/IPFIX template
+    SendTemplates,
+    /// Start exporting a flow (index into `flows`)
+    ///
+    /// Flows named by a `start_flow` step send nothing until it fires.
+    StartFlow { flow: usize },
+    /// Stop exporting a flow (index into `flows`)
+    StopFlow { flow: usize },
+    /// Skip `count` sequence numbers on every exporter
+    SequenceGap { count: u32 },
+    /// End the run
+    Stop,
+}
+
+impl ScenarioAction {
+    /// Index of the flow this action applies to, if any
+    pub fn flow(&self) -> Option<usize> {
+        match self {
+            ScenarioAction::StartFlow { flow } | ScenarioAction::StopFlow { flow } => Some(*flow),
+            ScenarioAction::SendTemplates
+            | ScenarioAction::SequenceGap { .. }
+            | ScenarioAction::Stop => None,
+        }
+    }
+
+    /// The same action applied to another flow index
+    pub fn with_flow(self, index: usize) -> Self {
+        match self {
+            ScenarioAction::StartFlow { .. } => ScenarioAction::StartFlow { flow: index },
+            ScenarioAction::StopFlow { .. } => ScenarioAction::StopFlow { flow: index },
+            other => other,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -60,18 +754,34 @@ pub struct V5FlowSet {
     pub input: u16,
     pub output: u16,
     pub d_pkts: u32,
-    pub d_octets: u32,
-    pub first: u32,
-    pub last: u32,
+    /// Derived from d_pkts and the flow's average_payload_size or packet_sizes when omitted
+    #[serde(default)]
+    pub d_octets: Option<u32>,
+    /// sysUpTime in milliseconds, or relative to the header's (e.g. "-60s")
+    pub first: UptimeValue,
+    pub last: UptimeValue,
     pub src_port: u16,
     pub dst_port: u16,
     pub tcp_flags: u8,
-    pub protocol: u8,
-    pub tos: u8,
-    pub src_as: u16,
-    pub dst_as: u16,
+    /// Drawn from the flow's protocol_distribution (else 0) when omitted
+    #[serde(default)]
+    pub protocol: Option<u8>,
+    /// Drawn from the flow's dscp_distribution (else 0) when omitted
+    #[serde(default)]
+    pub tos: Option<u8>,
+    /// Source/destination AS, looked up with --asn-db (else 0) when omitted
+    #[serde(default)]
+    pub src_as: Option<u16>,
+    #[serde(default)]
+    pub dst_as: Option<u16>,
     pub src_mask: u8,
     pub dst_mask: u8,
+    /// Override for the unused pad1 byte (defaults to 0)
+    #[serde(default)]
+    pub pad1: Option<u8>,
+    /// Override for the trailing pad2 bytes, which some vendors use for flags (defaults to 0)
+    #[serde(default)]
+    pub pad2: Option<u16>,
 }
 
 // ============================================================================
@@ -84,6 +794,46 @@ pub struct V7Config {
     #[serde(default)]
     pub header: Option<V7Header>,
 
+    /// Seconds into a continuous run before this flow starts emitting
+    #[serde(default)]
+    pub start_delay: Option<u64>,
+
+    /// Seconds between exports of this flow in continuous mode (overrides --interval)
+    #[serde(default)]
+    pub export_interval: Option<u64>,
+
+    /// TCP flags applied to every record on successive exports, one phase per export
+    #[serde(default)]
+    pub tcp_flag_sequence: Option<Vec<u8>>,
+
+    /// Packet size distribution used to derive omitted d_octets (default IMIX)
+    #[serde(default)]
+    pub packet_sizes: Option<Vec<PacketSize>>,
+
+    /// Mean payload bytes per packet, deriving omitted d_octets with headers added instead
+    #[serde(default)]
+    pub average_payload_size: Option<u16>,
+
+    /// Weighted pools the source and destination addresses are drawn from on each export
+    #[serde(default)]
+    pub address_pools: Option<AddressPoolsConfig>,
+
+    /// Whether counters report each export's increment or a running total
+    #[serde(default)]
+    pub counter_mode: CounterMode,
+
+    /// Weighted DSCP classes the TOS of records without one is drawn from
+    #[serde(default)]
+    pub dscp_distribution: Option<DscpDistributionConfig>,
+
+    /// Weighted protocols the protocol of records without one is drawn from
+    #[serde(default)]
+    pub protocol_distribution: Option<ProtocolDistributionConfig>,
+
+    /// Heavy-tailed distribution every record's packet and byte counts are drawn from
+    #[serde(default)]
+    pub flow_size_distribution: Option<FlowSizeDistributionConfig>,
+
     /// Flow records
     pub flowsets: Vec<V7FlowSet>,
 }
@@ -105,17 +855,27 @@ pub struct V7FlowSet {
     pub input: u16,
     pub output: u16,
     pub d_pkts: u32,
-    pub d_octets: u32,
-    pub first: u32,
-    pub last: u32,
+    /// Derived from d_pkts and the flow's average_payload_size or packet_sizes when omitted
+    #[serde(default)]
+    pub d_octets: Option<u32>,
+    /// sysUpTime in milliseconds, or relative to the header's (e.g. "-60s")
+    pub first: UptimeValue,
+    pub last: UptimeValue,
     pub src_port: u16,
     pub dst_port: u16,
     pub flags: u8,
     pub tcp_flags: u8,
-    pub protocol: u8,
-    pub tos: u8,
-    pub src_as: u16,
-    pub dst_as: u16,
+    /// Drawn from the flow's protocol_distribution (else 0) when omitted
+    #[serde(default)]
+    pub protocol: Option<u8>,
+    /// Drawn from the flow's dscp_distribution (else 0) when omitted
+    #[serde(default)]
+    pub tos: Option<u8>,
+    /// Source/destination AS, looked up with --asn-db (else 0) when omitted
+    #[serde(default)]
+    pub src_as: Option<u16>,
+    #[serde(default)]
+    pub dst_as: Option<u16>,
     pub src_mask: u8,
     pub dst_mask: u8,
     pub flags2: u16,
@@ -132,6 +892,75 @@ pub struct V9Config {
     #[serde(default)]
     pub header: Option<V9Header>,
 
+    /// Seconds into a continuous run before this flow starts emitting
+    #[serde(default)]
+    pub start_delay: Option<u64>,
+
+    /// Seconds between exports of this flow in continuous mode (overrides --interval)
+    #[serde(default)]
+    pub export_interval: Option<u64>,
+
+    /// TCP flags applied to every record on successive exports, one phase per export
+    #[serde(default)]
+    pub tcp_flag_sequence: Option<Vec<u8>>,
+
+    /// Simulated table of active flows replacing the data records on each export
+    #[serde(default)]
+    pub flow_table: Option<FlowTableConfig>,
+
+    /// Weighted pools the source and destination addresses are drawn from on each export
+    #[serde(default)]
+    pub address_pools: Option<AddressPoolsConfig>,
+
+    /// Whether counters report each export's increment or a running total
+    #[serde(default)]
+    pub counter_mode: CounterMode,
+
+    /// Weighted DSCP classes the TOS of records without one is drawn from
+    #[serde(default)]
+    pub dscp_distribution: Option<DscpDistributionConfig>,
+
+    /// Weighted protocols the protocol of records without one is drawn from
+    #[serde(default)]
+    pub protocol_distribution: Option<ProtocolDistributionConfig>,
+
+    /// Heavy-tailed distribution every record's packet and byte counts are drawn from
+    #[serde(default)]
+    pub flow_size_distribution: Option<FlowSizeDistributionConfig>,
+
+    /// Simulated hop distance that fills records' omitted minimum/maximum TTL
+    #[serde(default)]
+    pub hop_count: Option<HopCountConfig>,
+
+    /// Seed for field providers such as `"{{ ephemeral_port }}"` in records
+    #[serde(default)]
+    pub provider_seed: u64,
+
+    /// Emit each export as one self-contained packet
+    ///
+    /// The packet carries this flow's templates and options templates
+    /// followed by all of its data flowsets, in configuration order.
+    #[serde(default)]
+    pub combined_message: bool,
+
+    /// Pack consecutive data flowsets into shared packets of at most this many bytes
+    ///
+    /// Data for several templates then travels in one packet, with one
+    /// header counting every flowset. A data flowset larger than the limit
+    /// is still sent on its own.
+    #[serde(default)]
+    pub pack_data_flowsets: Option<u16>,
+
+    /// Pad every packet of this flow to this many bytes with padding flowsets
+    ///
+    /// Must be a multiple of 4; packets already this large are unchanged.
+    #[serde(default)]
+    pub pad_to: Option<u16>,
+
+    /// Kind of padding flowset `pad_to` appends
+    #[serde(default)]
+    pub pad_flowset: PadFlowset,
+
     /// Flowsets (templates and data)
     pub flowsets: Vec<V9FlowSet>,
 }
@@ -152,6 +981,13 @@ pub enum V9FlowSet {
         template_id: u16,
         fields: Vec<V9TemplateField>,
     },
+    #[serde(rename = "options_template")]
+    OptionsTemplate {
+        template_id: u16,
+        /// Scope fields: SYSTEM, INTERFACE, LINE_CARD, CACHE or TEMPLATE
+        scope_fields: Vec<V9TemplateField>,
+        fields: Vec<V9TemplateField>,
+    },
     #[serde(rename = "data")]
     Data {
         template_id: u16,
@@ -175,10 +1011,92 @@ pub struct IPFixConfig {
     #[serde(default)]
     pub header: Option<IPFixHeader>,
 
+    /// Export bidirectional flows per RFC 5103
+    ///
+    /// Appends reverse counterparts (enterprise 29305) of the counter fields
+    /// in each template, populated from `reverse_*` record values.
+    #[serde(default)]
+    pub biflow: bool,
+
+    /// Simulate this many exporters, one per consecutive observation domain
+    ///
+    /// Starting at the header's observation_domain_id, each domain is exported
+    /// with its own sequence numbers and template refresh timing.
+    #[serde(default)]
+    pub observation_domains: Option<u32>,
+
+    /// Move this exporter to the next observation domain on a schedule in continuous mode
+    #[serde(default)]
+    pub domain_rotation: Option<DomainRotationConfig>,
+
+    /// Seconds into a continuous run before this flow starts emitting
+    #[serde(default)]
+    pub start_delay: Option<u64>,
+
+    /// Seconds between exports of this flow in continuous mode (overrides --interval)
+    #[serde(default)]
+    pub export_interval: Option<u64>,
+
+    /// TCP flags applied to every record on successive exports, one phase per export
+    #[serde(default)]
+    pub tcp_flag_sequence: Option<Vec<u8>>,
+
+    /// Simulated table of active flows replacing the data records on each export
+    #[serde(default)]
+    pub flow_table: Option<FlowTableConfig>,
+
+    /// Weighted pools the source and destination addresses are drawn from on each export
+    #[serde(default)]
+    pub address_pools: Option<AddressPoolsConfig>,
+
+    /// Whether counters report each export's increment or a running total
+    #[serde(default)]
+    pub counter_mode: CounterMode,
+
+    /// Weighted DSCP classes the TOS of records without one is drawn from
+    #[serde(default)]
+    pub dscp_distribution: Option<DscpDistributionConfig>,
+
+    /// Weighted protocols the protocol of records without one is drawn from
+    #[serde(default)]
+    pub protocol_distribution: Option<ProtocolDistributionConfig>,
+
+    /// Heavy-tailed distribution every record's packet and byte counts are drawn from
+    #[serde(default)]
+    pub flow_size_distribution: Option<FlowSizeDistributionConfig>,
+
+    /// Simulated hop distance that fills records' omitted minimum/maximum TTL
+    #[serde(default)]
+    pub hop_count: Option<HopCountConfig>,
+
+    /// Seed for field providers such as `"{{ ephemeral_port }}"` in records
+    #[serde(default)]
+    pub provider_seed: u64,
+
+    /// Emit each export as one self-contained packet
+    ///
+    /// The packet carries this flow's templates and options templates
+    /// followed by all of its data sets, in configuration order.
+    #[serde(default)]
+    pub combined_message: bool,
+
     /// Flowsets (templates and data)
     pub flowsets: Vec<IPFixFlowSet>,
 }
 
+/// Observation domain rotation of one IPFIX exporter (`domain_rotation`)
+///
+/// The exporter uses each domain for `interval` seconds in turn, wrapping
+/// around, as if it had been reconfigured. Each new domain starts its own
+/// sequence numbers and has its templates sent before any data.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DomainRotationConfig {
+    /// Observation domain IDs in rotation order
+    pub domains: Vec<u32>,
+    /// Seconds spent in each domain
+    pub interval: u64,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct IPFixHeader {
     pub export_time: Option<u32>,
@@ -194,6 +1112,12 @@ pub enum IPFixFlowSet {
         template_id: u16,
         fields: Vec<IPFixTemplateField>,
     },
+    #[serde(rename = "options_template")]
+    OptionsTemplate {
+        template_id: u16,
+        scope_fields: Vec<IPFixTemplateField>,
+        fields: Vec<IPFixTemplateField>,
+    },
     #[serde(rename = "data")]
     Data {
         template_id: u16,
@@ -208,10 +1132,35 @@ pub struct IPFixTemplateField {
 }
 
 // ============================================================================
-// Destination Configuration
+// Template Schema
 // ============================================================================
 
+/// Named template definitions shared between configs (`--template-schema`)
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct TemplateSchema {
+    #[serde(default)]
+    pub templates: BTreeMap<String, SchemaTemplate>,
+}
+
+/// A named V9/IPFIX template
+///
+/// Fields have the same form as in a config's template flowset. Giving
+/// `scope_fields` makes it an options template.
 #[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SchemaTemplate {
+    /// Template ID used unless the referencing flowset sets its own
+    #[serde(default)]
+    pub template_id: Option<u16>,
+    #[serde(default)]
+    pub scope_fields: Option<Vec<serde_yaml::Value>>,
+    pub fields: Vec<serde_yaml::Value>,
+}
+
+// ============================================================================
+// Destination Configuration
+// ============================================================================
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct Destination {
     #[serde(default = "default_ip")]
     pub ip: String,
@@ -1,5 +1,49 @@
-use crate::config::schema::Config;
+use crate::config::schema::{
+    Config, CounterMode, FlowConfig, FlowSizeModel, IPFixFlowSet, V9FlowSet,
+};
 use crate::error::{NetflowError, Result};
+use crate::generator::lifecycle::{IPFIX_COUNTERS, V9_COUNTERS};
+use crate::generator::providers::is_provider;
+use std::collections::HashMap;
+
+/// V9 record keys identifying a flow: 5-tuple, then timestamps
+const V9_FLOW_KEYS: [&str; 7] = [
+    "src_addr",
+    "dst_addr",
+    "src_port",
+    "dst_port",
+    "protocol",
+    "first_switched",
+    "last_switched",
+];
+
+/// IPFIX record keys identifying a flow: 5-tuple, then timestamps
+const IPFIX_FLOW_KEYS: [&str; 10] = [
+    "source_ipv4_address",
+    "destination_ipv4_address",
+    "source_transport_port",
+    "destination_transport_port",
+    "protocol_identifier",
+    "flow_start_sys_up_time",
+    "flow_end_sys_up_time",
+    "flow_start_milliseconds",
+    "flow_end_milliseconds",
+    "observation_time_milliseconds",
+];
+
+/// (start, end) timestamp keys a V9 data record may carry
+const V9_TIMESTAMP_KEYS: [(&str, &str); 1] = [("first_switched", "last_switched")];
+
+/// (start, end) timestamp keys an IPFIX data record may carry
+const IPFIX_TIMESTAMP_KEYS: [(&str, &str); 2] = [
+    ("flow_start_sys_up_time", "flow_end_sys_up_time"),
+    ("flow_start_milliseconds", "flow_end_milliseconds"),
+];
+
+/// Plausible IPFIX flowStart/EndMilliseconds: 2000-01-01 to 2100-01-01 UTC
+///
+/// Values outside are almost always seconds or microseconds in the wrong field.
+const PLAUSIBLE_EPOCH_MILLIS: std::ops::Range<u64> = 946_684_800_000..4_102_444_800_000;
 
 /// Validate a configuration
 pub fn validate_config(config: &Config) -> Result<()> {
@@ -10,12 +54,508 @@ pub fn validate_config(config: &Config) -> Result<()> {
         ));
     }
 
+    // Per-flow export intervals must be positive
+    if let Some(index) = config
+        .flows
+        .iter()
+        .position(|flow| flow.export_interval() == Some(0))
+    {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has export_interval 0; it must be at least 1 second",
+            index
+        )));
+    }
+
+    // TCP flag sequences need at least one phase
+    if let Some(index) = config
+        .flows
+        .iter()
+        .position(|flow| flow.tcp_flag_sequence().is_some_and(<[u8]>::is_empty))
+    {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has an empty tcp_flag_sequence",
+            index
+        )));
+    }
+
+    // Packet size distributions need a positive total weight
+    if let Some(index) = config.flows.iter().position(|flow| {
+        let sizes = match flow {
+            FlowConfig::V5(v5) => v5.packet_sizes.as_deref(),
+            FlowConfig::V7(v7) => v7.packet_sizes.as_deref(),
+            _ => None,
+        };
+        sizes.is_some_and(|sizes| sizes.iter().all(|s| s.weight == 0))
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has a packet_sizes distribution with no positive weight",
+            index
+        )));
+    }
+
+    // A flow derives omitted d_octets from one packet size model
+    if let Some(index) = config.flows.iter().position(|flow| match flow {
+        FlowConfig::V5(v5) => v5.packet_sizes.is_some() && v5.average_payload_size.is_some(),
+        FlowConfig::V7(v7) => v7.packet_sizes.is_some() && v7.average_payload_size.is_some(),
+        _ => false,
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} sets both packet_sizes and average_payload_size",
+            index
+        )));
+    }
+
+    // Flow tables need at least one flow and a churn fraction
+    if let Some(index) = config.flows.iter().position(|flow| {
+        flow.flow_table()
+            .is_some_and(|table| table.size == 0 || !(0.0..=1.0).contains(&table.churn))
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has a flow_table with size 0 or churn outside 0.0-1.0",
+            index
+        )));
+    }
+
+    // Address pool lists need a positive total weight
+    if let Some(index) = config.flows.iter().position(|flow| {
+        flow.address_pools().is_some_and(|pools| {
+            [&pools.source, &pools.destination]
+                .iter()
+                .any(|list| !list.is_empty() && list.iter().all(|pool| pool.weight == 0))
+        })
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has an address pool list with no positive weight",
+            index
+        )));
+    }
+
+    // DSCP distributions need a positive total weight
+    if let Some(index) = config.flows.iter().position(|flow| {
+        flow.dscp_distribution()
+            .is_some_and(|distribution| distribution.classes.iter().all(|class| class.weight == 0))
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has a dscp_distribution with no positive weight",
+            index
+        )));
+    }
+
+    // Protocol distributions need a positive total weight
+    if let Some(index) = config.flows.iter().position(|flow| {
+        flow.protocol_distribution().is_some_and(|distribution| {
+            distribution
+                .protocols
+                .iter()
+                .all(|protocol| protocol.weight == 0)
+        })
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has a protocol_distribution with no positive weight",
+            index
+        )));
+    }
+
+    // Flow size models need positive parameters to produce counts
+    if let Some(index) = config.flows.iter().position(|flow| {
+        flow.flow_size_distribution().is_some_and(|distribution| {
+            let valid = match distribution.model {
+                FlowSizeModel::Pareto { min_packets, shape } => min_packets >= 1.0 && shape > 0.0,
+                FlowSizeModel::Lognormal { mu, sigma } => mu.is_finite() && sigma >= 0.0,
+            };
+            !valid || distribution.max_packets == Some(0)
+        })
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has a flow_size_distribution with min_packets below 1, a shape or sigma out of range, or max_packets 0",
+            index
+        )));
+    }
+
+    // Drawn counts replace each export's counters, which cumulative counters
+    // carry over instead
+    if let Some(index) = config.flows.iter().position(|flow| {
+        flow.flow_size_distribution().is_some() && flow.counter_mode() == CounterMode::Cumulative
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} combines flow_size_distribution with counter_mode: cumulative",
+            index
+        )));
+    }
+
+    // A provider draws a new count every export, which cumulative counters
+    // would scale by the export count rather than add to a running total.
+    // Flow table entries draw their values once, so they accumulate.
+    if let Some((index, field)) = config.flows.iter().enumerate().find_map(|(index, flow)| {
+        if flow.counter_mode() != CounterMode::Cumulative || flow.flow_table().is_some() {
+            return None;
+        }
+        provider_counter(flow).map(|field| (index, field))
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} combines counter_mode: cumulative with a provider for {}",
+            index, field
+        )));
+    }
+
+    // Simulated TTLs must stay above 0, or the traffic could not have arrived
+    if let Some(index) = config.flows.iter().position(|flow| {
+        flow.hop_count().is_some_and(|hop_count| {
+            u16::from(hop_count.hops).saturating_add(u16::from(hop_count.variation))
+                >= u16::from(hop_count.initial_ttl)
+        })
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has a hop_count whose hops and variation reach its initial_ttl",
+            index
+        )));
+    }
+
+    // Flow tables keep stable addresses, which address pools would redraw
+    if let Some(index) = config
+        .flows
+        .iter()
+        .position(|flow| flow.flow_table().is_some() && flow.address_pools().is_some())
+    {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} sets both flow_table and address_pools",
+            index
+        )));
+    }
+
+    // A combined message already holds every data flowset
+    if let Some(index) = config.flows.iter().position(|flow| {
+        matches!(flow, FlowConfig::V9(v9) if v9.combined_message && v9.pack_data_flowsets.is_some())
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} sets both combined_message and pack_data_flowsets",
+            index
+        )));
+    }
+
+    // Scenario steps can only start and stop configured flows
+    if let Some((index, flow)) = config
+        .scenario
+        .iter()
+        .enumerate()
+        .find_map(|(index, step)| {
+            let flow = step.action.flow()?;
+            (flow >= config.flows.len()).then_some((index, flow))
+        })
+    {
+        return Err(NetflowError::Validation(format!(
+            "Scenario step {} references flow {} but only {} flow(s) are configured",
+            index,
+            flow,
+            config.flows.len()
+        )));
+    }
+
+    // V9 flowsets are 4-byte aligned, so padded packets can only reach multiples of 4
+    if let Some(index) = config.flows.iter().position(
+        |flow| matches!(flow, FlowConfig::V9(v9) if v9.pad_to.is_some_and(|size| size % 4 != 0)),
+    ) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has a pad_to size that is not a multiple of 4",
+            index
+        )));
+    }
+
+    // A multi-domain IPFIX flow needs at least one domain
+    if let Some(index) = config.flows.iter().position(
+        |flow| matches!(flow, FlowConfig::IPFix(ipfix) if ipfix.observation_domains == Some(0)),
+    ) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has observation_domains 0; it must be at least 1",
+            index
+        )));
+    }
+
+    // A rotating exporter needs domains to rotate through, and is a single exporter
+    if let Some(index) = config.flows.iter().position(|flow| {
+        flow.domain_rotation()
+            .is_some_and(|rotation| rotation.domains.is_empty() || rotation.interval == 0)
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} has a domain_rotation with no domains or an interval of 0",
+            index
+        )));
+    }
+    if let Some(index) = config.flows.iter().position(|flow| {
+        matches!(flow, FlowConfig::IPFix(ipfix) if ipfix.domain_rotation.is_some() && ipfix.observation_domains.is_some())
+    }) {
+        return Err(NetflowError::Validation(format!(
+            "Flow {} sets both domain_rotation and observation_domains",
+            index
+        )));
+    }
+
+    // Per-record timestamps must describe a flow that ended before export
+    validate_record_timestamps(config)?;
+
     // Validate destination
     validate_destination(&config.destination)?;
 
     Ok(())
 }
 
+/// Lint checks enabled by --strict
+///
+/// Rejects V5/V7 flowsets, or V9/IPFIX data records, that repeat another's
+/// 5-tuple and timestamps anywhere in the config, which usually means a
+/// copy-paste mistake. V9/IPFIX records without any 5-tuple field (e.g.
+/// options data) are not checked.
+pub fn validate_strict(config: &Config) -> Result<()> {
+    let mut seen: HashMap<String, Vec<String>> = HashMap::new();
+    let mut add = |key: String, location: String| seen.entry(key).or_default().push(location);
+
+    for (flow_index, flow) in config.flows.iter().enumerate() {
+        match flow {
+            FlowConfig::V5(v5) => {
+                for (index, fs) in v5.flowsets.iter().enumerate() {
+                    let key = format!(
+                        "v5 {} {} {} {} {} {} {}",
+                        fs.src_addr,
+                        fs.dst_addr,
+                        fs.src_port,
+                        fs.dst_port,
+                        fs.protocol.unwrap_or(0),
+                        fs.first,
+                        fs.last
+                    );
+                    add(key, format!("flow {} flowset {}", flow_index, index));
+                }
+            }
+            FlowConfig::V7(v7) => {
+                for (index, fs) in v7.flowsets.iter().enumerate() {
+                    let key = format!(
+                        "v7 {} {} {} {} {} {} {}",
+                        fs.src_addr,
+                        fs.dst_addr,
+                        fs.src_port,
+                        fs.dst_port,
+                        fs.protocol.unwrap_or(0),
+                        fs.first,
+                        fs.last
+                    );
+                    add(key, format!("flow {} flowset {}", flow_index, index));
+                }
+            }
+            FlowConfig::V9(v9) => {
+                for (flowset_index, flowset) in v9.flowsets.iter().enumerate() {
+                    let V9FlowSet::Data { records, .. } = flowset else {
+                        continue;
+                    };
+                    for (index, record) in records.iter().enumerate() {
+                        if let Some(key) = record_flow_key("v9", &V9_FLOW_KEYS, record) {
+                            add(
+                                key,
+                                format!(
+                                    "flow {} flowset {} record {}",
+                                    flow_index, flowset_index, index
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+            FlowConfig::IPFix(ipfix) => {
+                for (flowset_index, flowset) in ipfix.flowsets.iter().enumerate() {
+                    let IPFixFlowSet::Data { records, .. } = flowset else {
+                        continue;
+                    };
+                    for (index, record) in records.iter().enumerate() {
+                        if let Some(key) = record_flow_key("ipfix", &IPFIX_FLOW_KEYS, record) {
+                            add(
+                                key,
+                                format!(
+                                    "flow {} flowset {} record {}",
+                                    flow_index, flowset_index, index
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut duplicates: Vec<String> = seen
+        .into_values()
+        .filter(|locations| locations.len() > 1)
+        .map(|locations| locations.join(", "))
+        .collect();
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+    duplicates.sort();
+    Err(NetflowError::Validation(format!(
+        "Duplicate flows with identical 5-tuple and timestamps: {}",
+        duplicates.join("; ")
+    )))
+}
+
+/// Identity of a V9/IPFIX record from its 5-tuple and timestamp fields
+///
+/// `None` when the record sets none of the 5-tuple fields (the first five keys).
+fn record_flow_key(version: &str, keys: &[&str], record: &serde_yaml::Value) -> Option<String> {
+    let values: Vec<Option<&serde_yaml::Value>> = keys.iter().map(|key| record.get(key)).collect();
+    if values.iter().take(5).all(Option::is_none) {
+        return None;
+    }
+    Some(format!("{} {:?}", version, values))
+}
+
+/// Check explicit V9/IPFIX record timestamps for replayed flows
+///
+/// Each record's start must not be after its end. V9 switched times must not
+/// be after the header `sys_up_time`, and IPFIX absolute times must fall in
+/// [`PLAUSIBLE_EPOCH_MILLIS`] and not after the header `export_time`, when
+/// those are set. Only literal integers are checked; expressions and omitted
+/// fields are left to the generator.
+/// The first byte or packet counter that a record of `flow` fills from a provider
+fn provider_counter(flow: &FlowConfig) -> Option<&'static str> {
+    let (records, counters): (Vec<&serde_yaml::Value>, &[&'static str]) = match flow {
+        FlowConfig::V9(v9) => (
+            v9.flowsets
+                .iter()
+                .flat_map(|flowset| match flowset {
+                    V9FlowSet::Data { records, .. } => records.as_slice(),
+                    _ => &[],
+                })
+                .collect(),
+            V9_COUNTERS,
+        ),
+        FlowConfig::IPFix(ipfix) => (
+            ipfix
+                .flowsets
+                .iter()
+                .flat_map(|flowset| match flowset {
+                    IPFixFlowSet::Data { records, .. } => records.as_slice(),
+                    _ => &[],
+                })
+                .collect(),
+            IPFIX_COUNTERS,
+        ),
+        FlowConfig::V5(_) | FlowConfig::V7(_) => return None,
+    };
+    counters.iter().copied().find(|&field| {
+        records
+            .iter()
+            .any(|record| record.get(field).is_some_and(is_provider))
+    })
+}
+
+fn validate_record_timestamps(config: &Config) -> Result<()> {
+    let u64_at = |record: &serde_yaml::Value, key: &str| record.get(key).and_then(|v| v.as_u64());
+
+    for (flow_index, flow) in config.flows.iter().enumerate() {
+        // (records by flowset, timestamp keys, latest plausible uptime, latest epoch ms)
+        let (flowsets, keys, max_uptime, max_epoch_ms): (Vec<_>, &[(&str, &str)], _, _) = match flow
+        {
+            FlowConfig::V9(v9) => (
+                v9.flowsets
+                    .iter()
+                    .map(|flowset| match flowset {
+                        V9FlowSet::Data { records, .. } => records.as_slice(),
+                        _ => &[],
+                    })
+                    .collect(),
+                &V9_TIMESTAMP_KEYS,
+                v9.header.as_ref().and_then(|h| h.sys_up_time),
+                None,
+            ),
+            FlowConfig::IPFix(ipfix) => (
+                ipfix
+                    .flowsets
+                    .iter()
+                    .map(|flowset| match flowset {
+                        IPFixFlowSet::Data { records, .. } => records.as_slice(),
+                        _ => &[],
+                    })
+                    .collect(),
+                &IPFIX_TIMESTAMP_KEYS,
+                None,
+                ipfix
+                    .header
+                    .as_ref()
+                    .and_then(|h| h.export_time)
+                    .map(|secs| u64::from(secs).saturating_mul(1000).saturating_add(999)),
+            ),
+            FlowConfig::V5(_) | FlowConfig::V7(_) => continue,
+        };
+
+        for (flowset_index, records) in flowsets.iter().enumerate() {
+            for (index, record) in records.iter().enumerate() {
+                let location = || {
+                    format!(
+                        "flow {} flowset {} record {}",
+                        flow_index, flowset_index, index
+                    )
+                };
+
+                for &(start_key, end_key) in keys {
+                    if let (Some(start), Some(end)) =
+                        (u64_at(record, start_key), u64_at(record, end_key))
+                        && start > end
+                    {
+                        return Err(NetflowError::Validation(format!(
+                            "{}: {} {} is after {} {}",
+                            location(),
+                            start_key,
+                            start,
+                            end_key,
+                            end
+                        )));
+                    }
+                }
+
+                if let Some(uptime) = max_uptime {
+                    for key in ["first_switched", "last_switched"] {
+                        if let Some(value) = u64_at(record, key)
+                            && value > u64::from(uptime)
+                        {
+                            return Err(NetflowError::Validation(format!(
+                                "{}: {} {} is after the header sys_up_time {}",
+                                location(),
+                                key,
+                                value,
+                                uptime
+                            )));
+                        }
+                    }
+                }
+
+                for key in ["flow_start_milliseconds", "flow_end_milliseconds"] {
+                    let Some(value) = u64_at(record, key) else {
+                        continue;
+                    };
+                    if !PLAUSIBLE_EPOCH_MILLIS.contains(&value) {
+                        return Err(NetflowError::Validation(format!(
+                            "{}: {} {} is not a plausible Unix time in milliseconds",
+                            location(),
+                            key,
+                            value
+                        )));
+                    }
+                    if let Some(max) = max_epoch_ms
+                        && value > max
+                    {
+                        return Err(NetflowError::Validation(format!(
+                            "{}: {} {} is after the header export_time",
+                            location(),
+                            key,
+                            value
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Validate destination configuration
 fn validate_destination(dest: &crate::config::schema::Destination) -> Result<()> {
     // Validate IP address format
@@ -34,7 +574,7 @@ fn validate_destination(dest: &crate::config::schema::Destination) -> Result<()>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::schema::{Destination, FlowConfig, V5Config, V5FlowSet};
+    use crate::config::schema::{ConfigDefaults, Destination, V5Config, V5FlowSet};
     use std::net::Ipv4Addr;
 
     #[test]
@@ -42,6 +582,8 @@ mod tests {
         let config = Config {
             flows: vec![],
             destination: Destination::default(),
+            scenario: Vec::new(),
+            defaults: ConfigDefaults::default(),
         };
 
         assert!(validate_config(&config).is_err());
@@ -52,6 +594,16 @@ mod tests {
         let mut config = Config {
             flows: vec![FlowConfig::V5(V5Config {
                 header: None,
+                start_delay: None,
+                export_interval: None,
+                tcp_flag_sequence: None,
+                packet_sizes: None,
+                average_payload_size: None,
+                address_pools: None,
+                counter_mode: CounterMode::Delta,
+                dscp_distribution: None,
+                protocol_distribution: None,
+                flow_size_distribution: None,
                 flowsets: vec![V5FlowSet {
                     src_addr: Ipv4Addr::new(192, 168, 1, 10),
                     dst_addr: Ipv4Addr::new(10, 0, 0, 50),
@@ -59,24 +611,107 @@ mod tests {
                     input: 1,
                     output: 2,
                     d_pkts: 100,
-                    d_octets: 65000,
-                    first: 350000,
-                    last: 360000,
+                    d_octets: Some(65000),
+                    first: 350000.into(),
+                    last: 360000.into(),
                     src_port: 54321,
                     dst_port: 443,
                     tcp_flags: 0x18,
-                    protocol: 6,
-                    tos: 0,
-                    src_as: 65001,
-                    dst_as: 65002,
+                    protocol: Some(6),
+                    tos: Some(0),
+                    src_as: Some(65001),
+                    dst_as: Some(65002),
                     src_mask: 24,
                     dst_mask: 24,
+                    pad1: None,
+                    pad2: None,
                 }],
             })],
             destination: Destination::default(),
+            scenario: Vec::new(),
+            defaults: ConfigDefaults::default(),
         };
 
         config.destination.ip = "invalid_ip".to_string();
         assert!(validate_config(&config).is_err());
     }
+
+    #[test]
+    fn test_validate_strict_duplicates() {
+        let config = crate::config::parse_yaml_str(
+            r#"
+flows:
+  - version: ipfix
+    flowsets:
+      - type: template
+        template_id: 300
+        fields:
+          - field_type: sourceIPv4Address
+            field_length: 4
+      - type: data
+        template_id: 300
+        records:
+          - source_ipv4_address: "10.0.0.1"
+            destination_transport_port: 443
+          - source_ipv4_address: "10.0.0.2"
+            destination_transport_port: 443
+          - source_ipv4_address: "10.0.0.1"
+            destination_transport_port: 443
+          - metering_process_id: 1
+          - metering_process_id: 1
+"#,
+        )
+        .unwrap();
+
+        let err = validate_strict(&config).unwrap_err().to_string();
+        assert!(err.contains("flow 0 flowset 1 record 0, flow 0 flowset 1 record 2"));
+        // Records without a 5-tuple are not flows and never duplicates
+        assert!(!err.contains("record 3"));
+    }
+
+    #[test]
+    fn test_validate_record_timestamps() {
+        let config = |record: &str| {
+            crate::config::parse_yaml_str(&format!(
+                "flows:\n  - version: ipfix\n    header:\n      export_time: 1735141260\n    flowsets:\n      - type: data\n        template_id: 256\n        records:\n          - {}\n",
+                record
+            ))
+            .unwrap()
+        };
+
+        assert!(
+            validate_config(&config(
+                "{flow_start_milliseconds: 1735141200000, flow_end_milliseconds: 1735141250000}"
+            ))
+            .is_ok()
+        );
+        // End before start, seconds in a milliseconds field, end after export
+        for record in [
+            "{flow_start_milliseconds: 1735141250000, flow_end_milliseconds: 1735141200000}",
+            "{flow_start_milliseconds: 1735141200}",
+            "{flow_end_milliseconds: 1735141300000}",
+        ] {
+            assert!(validate_config(&config(record)).is_err(), "{}", record);
+        }
+    }
+
+    #[test]
+    fn test_validate_cumulative_providers() {
+        let config = |counter_mode: &str, flow_table: &str| {
+            crate::config::parse_yaml_str(&format!(
+                "flows:\n  - version: ipfix\n    counter_mode: {}\n{}    flowsets:\n      - type: data\n        template_id: 256\n        records:\n          - {{octet_delta_count: \"{{{{ byte_count }}}}\", source_transport_port: \"{{{{ ephemeral_port }}}}\"}}\n",
+                counter_mode, flow_table
+            ))
+            .unwrap()
+        };
+
+        // A fresh draw each export cannot be scaled into a running total
+        let error = validate_config(&config("cumulative", ""))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("octet_delta_count"), "{}", error);
+        assert!(validate_config(&config("delta", "")).is_ok());
+        // Flow table entries keep their draws
+        assert!(validate_config(&config("cumulative", "    flow_table:\n      size: 4\n")).is_ok());
+    }
 }
@@ -41,5 +41,25 @@ pub enum NetflowError {
     ParserError(#[from] Box<dyn StdError + Send>),
 }
 
+impl NetflowError {
+    /// Process exit code for this error
+    ///
+    /// 1 for configuration/validation errors, 2 for transmission errors,
+    /// and 3 for I/O errors.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            NetflowError::Network(_) => 2,
+            NetflowError::Io(_) => 3,
+            NetflowError::YamlParse(_)
+            | NetflowError::Validation(_)
+            | NetflowError::Generation(_)
+            | NetflowError::InvalidDestination(_)
+            | NetflowError::InvalidPacket(_)
+            | NetflowError::Configuration(_)
+            | NetflowError::ParserError(_) => 1,
+        }
+    }
+}
+
 /// Result type alias for convenience
 pub type Result<T> = std::result::Result<T, NetflowError>;
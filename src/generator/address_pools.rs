@@ -0,0 +1,156 @@
+//! Weighted source/destination address selection (`address_pools`)
+
+use crate::config::schema::{AddressPool, AddressPoolsConfig, FlowConfig, IPFixFlowSet, V9FlowSet};
+use crate::generator::packet_sizes::SplitMix64;
+use serde_yaml::Value;
+use std::net::Ipv4Addr;
+
+/// Picks a prefix by weight, then a host within it, for the source and
+/// destination address of every record
+#[derive(Debug)]
+pub struct AddressPools {
+    source: Vec<AddressPool>,
+    destination: Vec<AddressPool>,
+    rng: SplitMix64,
+}
+
+impl AddressPools {
+    pub fn new(config: &AddressPoolsConfig) -> Self {
+        Self {
+            source: config.source.clone(),
+            destination: config.destination.clone(),
+            rng: SplitMix64(config.seed),
+        }
+    }
+
+    /// Draw new source and destination addresses for every record of `flow`
+    pub fn apply(&mut self, flow: &mut FlowConfig) {
+        match flow {
+            FlowConfig::V5(config) => {
+                for record in &mut config.flowsets {
+                    self.draw_into(&mut record.src_addr, &mut record.dst_addr);
+                }
+            }
+            FlowConfig::V7(config) => {
+                for record in &mut config.flowsets {
+                    self.draw_into(&mut record.src_addr, &mut record.dst_addr);
+                }
+            }
+            FlowConfig::V9(config) => {
+                for flowset in &mut config.flowsets {
+                    if let V9FlowSet::Data { records, .. } = flowset {
+                        self.draw_records(records, "src_addr", "dst_addr");
+                    }
+                }
+            }
+            FlowConfig::IPFix(config) => {
+                for flowset in &mut config.flowsets {
+                    if let IPFixFlowSet::Data { records, .. } = flowset {
+                        self.draw_records(
+                            records,
+                            "source_ipv4_address",
+                            "destination_ipv4_address",
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_into(&mut self, src_addr: &mut Ipv4Addr, dst_addr: &mut Ipv4Addr) {
+        if let Some(addr) = draw(&self.source, &mut self.rng) {
+            *src_addr = addr;
+        }
+        if let Some(addr) = draw(&self.destination, &mut self.rng) {
+            *dst_addr = addr;
+        }
+    }
+
+    fn draw_records(&mut self, records: &mut [Value], src_field: &str, dst_field: &str) {
+        for record in records {
+            let Value::Mapping(map) = record else {
+                continue;
+            };
+            if let Some(addr) = draw(&self.source, &mut self.rng) {
+                map.insert(src_field.into(), addr.to_string().into());
+            }
+            if let Some(addr) = draw(&self.destination, &mut self.rng) {
+                map.insert(dst_field.into(), addr.to_string().into());
+            }
+        }
+    }
+}
+
+/// Random address from a pool chosen by weight, or `None` without pools
+fn draw(pools: &[AddressPool], rng: &mut SplitMix64) -> Option<Ipv4Addr> {
+    let pool = rng.pick_weighted(pools, |pool| pool.weight)?;
+    Some(pool.prefix.nth(rng.next()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+    use crate::generator::exported_records;
+
+    #[test]
+    fn test_address_pools_weighted() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    address_pools:
+      seed: 3
+      source:
+        - prefix: "10.1.0.0/16"
+          weight: 60
+        - prefix: "192.168.7.0/24"
+          weight: 30
+        - prefix: "172.16.5.9/32"
+          weight: 10
+      destination:
+        - prefix: "203.0.113.0/24"
+          weight: 1
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - src_addr: "1.1.1.1"
+            dst_addr: "2.2.2.2"
+"#,
+        )
+        .unwrap();
+        let flow = &config.flows[0];
+        let mut pools = AddressPools::new(flow.address_pools().unwrap());
+
+        let draws: Vec<(Ipv4Addr, Ipv4Addr)> =
+            exported_records(flow, 1000, |flow| pools.apply(flow))
+                .iter()
+                .map(|records| {
+                    let addr = |field: &str| records[0][field].as_str().unwrap().parse().unwrap();
+                    (addr("src_addr"), addr("dst_addr"))
+                })
+                .collect();
+
+        let count = |octets: [u8; 2]| {
+            draws
+                .iter()
+                .filter(|(src, _)| src.octets()[..2] == octets)
+                .count()
+        };
+        assert!((500..700).contains(&count([10, 1])));
+        assert!((220..380).contains(&count([192, 168])));
+        assert!((50..150).contains(&count([172, 16])));
+        assert!(
+            draws
+                .iter()
+                .all(|(_, dst)| dst.octets()[..3] == [203, 0, 113])
+        );
+        assert!(
+            draws
+                .iter()
+                .filter(|(src, _)| src.octets()[0] == 172)
+                .all(|(src, _)| *src == Ipv4Addr::new(172, 16, 5, 9))
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Packet-sized batches of V9/IPFIX data records
+//!
+//! A data flowset is built into one packet, so a flowset with many records
+//! makes an oversized packet. Batches carry as many records as fit in one
+//! packet: the byte budget of an Ethernet MTU, less the packet and flowset
+//! headers, divided by the record length of the template the records use.
+
+use crate::config::schema::{FlowConfig, V9FlowSet};
+use crate::generator::field_serializer::VARIABLE_LENGTH;
+use crate::generator::ipfix::IPFixTemplate;
+
+/// Payload bytes of a 1500-byte Ethernet MTU after the IPv4 and UDP headers
+pub const PACKET_BYTE_BUDGET: usize = 1472;
+/// V9 packet header and data flowset header
+const V9_OVERHEAD: usize = 24;
+/// IPFIX message header and set header
+const IPFIX_OVERHEAD: usize = 20;
+/// Bytes assumed for a variable-length IPFIX field, length prefix included
+const VARIABLE_FIELD_ESTIMATE: usize = 16;
+/// Record length assumed when the flow does not define the template
+const UNKNOWN_RECORD_LEN: usize = 64;
+
+/// Records of `template_id` that fit in one packet of `flow`, at least one
+///
+/// V5/V7 flows have no templates and are not batched.
+pub fn records_per_packet(flow: &FlowConfig, template_id: u16) -> usize {
+    let (overhead, record_len) = match flow {
+        FlowConfig::V9(config) => (
+            V9_OVERHEAD,
+            config.flowsets.iter().find_map(|flowset| match flowset {
+                V9FlowSet::Template {
+                    template_id: id,
+                    fields,
+                } if *id == template_id => {
+                    Some(fields.iter().map(|f| usize::from(f.field_length)).sum())
+                }
+                V9FlowSet::OptionsTemplate {
+                    template_id: id,
+                    scope_fields,
+                    fields,
+                } if *id == template_id => Some(
+                    scope_fields
+                        .iter()
+                        .chain(fields)
+                        .map(|f| usize::from(f.field_length))
+                        .sum(),
+                ),
+                _ => None,
+            }),
+        ),
+        FlowConfig::IPFix(config) => (
+            IPFIX_OVERHEAD,
+            config
+                .flowsets
+                .iter()
+                .filter_map(|flowset| IPFixTemplate::from_flowset(config, flowset).ok().flatten())
+                .find(|template| template.template_id == template_id)
+                .map(|template| {
+                    template
+                        .fields
+                        .iter()
+                        .map(|field| match field.field_length {
+                            VARIABLE_LENGTH => VARIABLE_FIELD_ESTIMATE,
+                            length => usize::from(length),
+                        })
+                        .sum()
+                }),
+        ),
+        FlowConfig::V5(_) | FlowConfig::V7(_) => return usize::MAX,
+    };
+
+    let record_len = record_len.unwrap_or(UNKNOWN_RECORD_LEN).max(1);
+    PACKET_BYTE_BUDGET
+        .saturating_sub(overhead)
+        .checked_div(record_len)
+        .unwrap_or_default()
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parser::parse_yaml_str;
+
+    #[test]
+    fn test_records_per_packet() {
+        let yaml = r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IPV4_SRC_ADDR
+            field_length: 4
+          - field_type: IN_BYTES
+            field_length: 4
+      - type: data
+        template_id: 256
+        records:
+"#;
+        let records = "          - src_addr: 10.0.0.1\n            in_bytes: 1\n";
+        let flow = parse_yaml_str(&format!("{}{}", yaml, records))
+            .unwrap()
+            .flows
+            .remove(0);
+
+        // (1472 - 24) / 8 bytes per record
+        assert_eq!(records_per_packet(&flow, 256), 181);
+        assert_eq!(records_per_packet(&flow, 999), 22);
+    }
+}
@@ -0,0 +1,93 @@
+//! BGP AS fields derived from a record's `as_path`
+//!
+//! A data record may set `as_path: [65001, 65002, 15169]` instead of the
+//! individual AS fields. The path lists the source AS first, then the
+//! exporter's own AS, then the ASes toward the destination, ending with the
+//! destination AS.
+
+use crate::error::{NetflowError, Result};
+
+/// Record key holding the AS path
+const AS_PATH_KEY: &str = "as_path";
+
+/// Fill a record's AS fields from its `as_path`, if it has one
+///
+/// Sets the V9 `src_as`/`dst_as` and IPFIX `bgp_source_as_number`,
+/// `bgp_destination_as_number`, `bgp_prev_adjacent_as_number` and
+/// `bgp_next_adjacent_as_number` values. Fields already set in the record
+/// are kept. Returns `None` when the record has no `as_path`.
+pub fn expand_as_path(record: &serde_yaml::Value) -> Result<Option<serde_yaml::Value>> {
+    let Some(map) = record.as_mapping() else {
+        return Ok(None);
+    };
+    let Some(path) = map.get(AS_PATH_KEY) else {
+        return Ok(None);
+    };
+
+    let path: Vec<u32> = path
+        .as_sequence()
+        .map(|hops| {
+            hops.iter()
+                .map(|hop| hop.as_u64().and_then(|asn| u32::try_from(asn).ok()))
+                .collect::<Option<Vec<u32>>>()
+        })
+        .unwrap_or_default()
+        .filter(|path| path.len() >= 2)
+        .ok_or_else(|| {
+            NetflowError::Generation(format!(
+                "as_path must list at least the source and exporter AS numbers: {:?}",
+                path
+            ))
+        })?;
+
+    let source = path.first().copied().unwrap_or_default();
+    let destination = path.last().copied().unwrap_or_default();
+    // Traffic terminating in the exporter's own AS has no next adjacent AS
+    let next_adjacent = path.get(2).copied().unwrap_or_default();
+
+    let mut expanded = map.clone();
+    for (key, asn) in [
+        ("src_as", source),
+        ("dst_as", destination),
+        ("bgp_source_as_number", source),
+        ("bgp_destination_as_number", destination),
+        ("bgp_prev_adjacent_as_number", source),
+        ("bgp_next_adjacent_as_number", next_adjacent),
+    ] {
+        if !expanded.contains_key(key) {
+            expanded.insert(key.into(), asn.into());
+        }
+    }
+
+    Ok(Some(serde_yaml::Value::Mapping(expanded)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_as_path() {
+        let record: serde_yaml::Value =
+            serde_yaml::from_str("{as_path: [65001, 65002, 64700, 15169], dst_as: 1}").unwrap();
+        let expanded = expand_as_path(&record).unwrap().unwrap();
+
+        assert_eq!(expanded["src_as"].as_u64(), Some(65001));
+        assert_eq!(
+            expanded["bgp_prev_adjacent_as_number"].as_u64(),
+            Some(65001)
+        );
+        assert_eq!(
+            expanded["bgp_next_adjacent_as_number"].as_u64(),
+            Some(64700)
+        );
+        assert_eq!(expanded["bgp_destination_as_number"].as_u64(), Some(15169));
+        // Explicit fields win over the path
+        assert_eq!(expanded["dst_as"].as_u64(), Some(1));
+
+        let plain: serde_yaml::Value = serde_yaml::from_str("{src_as: 1}").unwrap();
+        assert!(expand_as_path(&plain).unwrap().is_none());
+        let short: serde_yaml::Value = serde_yaml::from_str("{as_path: [65001]}").unwrap();
+        assert!(expand_as_path(&short).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+//! On/off export schedule for simulating bursty traffic
+
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Alternating active and idle windows, starting with an active window
+///
+/// Flows are exported as usual during each active window and nothing is
+/// sent during the idle window that follows. Parsed from `ACTIVE:IDLE` in
+/// seconds (e.g. `60:300`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Burst {
+    pub active: Duration,
+    pub idle: Duration,
+}
+
+impl Burst {
+    /// Time left in the idle window at `elapsed` since the run started, if idle
+    pub fn idle_remaining(&self, elapsed: Duration) -> Option<Duration> {
+        let period = self.active.checked_add(self.idle)?;
+        let period_ms = period.as_millis();
+        let phase_ms = elapsed.as_millis().checked_rem(period_ms)?;
+        let phase = Duration::from_millis(u64::try_from(phase_ms).unwrap_or(u64::MAX));
+        (phase >= self.active).then(|| period.saturating_sub(phase))
+    }
+}
+
+impl FromStr for Burst {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((active, idle)) = s.split_once(':') else {
+            return Err(format!("expected ACTIVE:IDLE, got '{}'", s));
+        };
+        let parse = |name: &str, value: &str| -> Result<Duration, String> {
+            value
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|v| *v > 0)
+                .map(Duration::from_secs)
+                .ok_or_else(|| {
+                    format!(
+                        "{} must be a positive number of seconds, got '{}'",
+                        name, value
+                    )
+                })
+        };
+
+        Ok(Self {
+            active: parse("ACTIVE", active)?,
+            idle: parse("IDLE", idle)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_windows() {
+        let burst: Burst = "10:5".parse().unwrap();
+        let at = Duration::from_secs;
+
+        assert_eq!(burst.idle_remaining(at(0)), None);
+        assert_eq!(burst.idle_remaining(at(9)), None);
+        assert_eq!(burst.idle_remaining(at(10)), Some(at(5)));
+        assert_eq!(burst.idle_remaining(at(14)), Some(at(1)));
+        // The next active window starts after one full period
+        assert_eq!(burst.idle_remaining(at(15)), None);
+        assert_eq!(burst.idle_remaining(at(27)), Some(at(3)));
+
+        assert!("10".parse::<Burst>().is_err());
+        assert!("10:0".parse::<Burst>().is_err());
+    }
+}
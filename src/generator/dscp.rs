@@ -0,0 +1,129 @@
+//! Weighted TOS/DSCP selection for records without one (`dscp_distribution`)
+
+use crate::config::schema::{
+    DscpClass, DscpDistributionConfig, FlowConfig, IPFixFlowSet, V9FlowSet,
+};
+use crate::generator::packet_sizes::SplitMix64;
+use serde_yaml::Value;
+
+/// Weighted DSCP classes written as the TOS byte (`dscp << 2`) of records that
+/// leave it unset
+#[derive(Debug)]
+pub struct DscpDistribution {
+    classes: Vec<DscpClass>,
+    rng: SplitMix64,
+}
+
+impl DscpDistribution {
+    pub fn new(config: &DscpDistributionConfig) -> Self {
+        Self {
+            classes: config.classes.clone(),
+            rng: SplitMix64(config.seed),
+        }
+    }
+
+    /// Draw a TOS for every record of `flow` that does not set one
+    pub fn apply(&mut self, flow: &mut FlowConfig) {
+        match flow {
+            FlowConfig::V5(config) => {
+                for record in &mut config.flowsets {
+                    if record.tos.is_none() {
+                        record.tos = self.draw();
+                    }
+                }
+            }
+            FlowConfig::V7(config) => {
+                for record in &mut config.flowsets {
+                    if record.tos.is_none() {
+                        record.tos = self.draw();
+                    }
+                }
+            }
+            FlowConfig::V9(config) => {
+                for flowset in &mut config.flowsets {
+                    if let V9FlowSet::Data { records, .. } = flowset {
+                        self.draw_records(records, "src_tos");
+                    }
+                }
+            }
+            FlowConfig::IPFix(config) => {
+                for flowset in &mut config.flowsets {
+                    if let IPFixFlowSet::Data { records, .. } = flowset {
+                        self.draw_records(records, "ip_class_of_service");
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_records(&mut self, records: &mut [Value], field: &str) {
+        for record in records {
+            let Value::Mapping(map) = record else {
+                continue;
+            };
+            if !map.contains_key(field)
+                && let Some(tos) = self.draw()
+            {
+                map.insert(field.into(), u64::from(tos).into());
+            }
+        }
+    }
+
+    /// TOS byte of a class chosen by weight, or `None` without classes
+    fn draw(&mut self) -> Option<u8> {
+        let class = self
+            .rng
+            .pick_weighted(&self.classes, |class| class.weight)?;
+        // DSCP is the upper six bits of the TOS byte
+        Some(class.dscp.0.checked_shl(2).unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+    use crate::generator::exported_records;
+
+    #[test]
+    fn test_dscp_distribution() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: ipfix
+    dscp_distribution:
+      seed: 11
+      classes:
+        - dscp: BE
+          weight: 70
+        - dscp: EF
+          weight: 20
+        - dscp: af41
+          weight: 10
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - source_ipv4_address: "10.0.0.1"
+          - source_ipv4_address: "10.0.0.2"
+            ip_class_of_service: 32
+"#,
+        )
+        .unwrap();
+        let flow = &config.flows[0];
+        let mut distribution = DscpDistribution::new(flow.dscp_distribution().unwrap());
+
+        let mut counts = [0usize; 256];
+        for records in exported_records(flow, 1000, |flow| distribution.apply(flow)) {
+            let tos = records[0]["ip_class_of_service"].as_u64().unwrap();
+            counts[usize::try_from(tos).unwrap()] += 1;
+            // A configured TOS overrides the distribution
+            assert_eq!(records[1]["ip_class_of_service"].as_u64(), Some(32));
+        }
+
+        assert!((620..780).contains(&counts[0]));
+        assert!((140..260).contains(&counts[46 << 2]));
+        assert!((50..150).contains(&counts[34 << 2]));
+        assert_eq!(counts[0] + counts[46 << 2] + counts[34 << 2], 1000);
+    }
+}
@@ -0,0 +1,122 @@
+//! Flow timestamps held back from the export time (`--export-lag`)
+//!
+//! Real exporters send a flow some time after it expires, so its end time
+//! trails the header's export time. V5/V7 records have their `first`/`last`
+//! moved back before the packets are built. V9 and IPFIX records have their
+//! start and end timestamp fields moved back as they are encoded, including
+//! switched times derived with `--first-switched-now`.
+
+use crate::config::schema::FlowConfig;
+use std::time::Duration;
+
+/// Time unit of a V9/IPFIX start or end timestamp field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampUnit {
+    Seconds,
+    Milliseconds,
+}
+
+/// Move the `first`/`last` times of every V5/V7 record back by `lag`
+///
+/// Each flow keeps its duration. Times that would fall before boot clamp to 0.
+pub fn lag_record_times(flow: &mut FlowConfig, lag: Duration) {
+    let millis = u32::try_from(lag.as_millis()).unwrap_or(u32::MAX);
+    match flow {
+        FlowConfig::V5(config) => {
+            for record in &mut config.flowsets {
+                record.first = record.first.earlier(millis);
+                record.last = record.last.earlier(millis);
+            }
+        }
+        FlowConfig::V7(config) => {
+            for record in &mut config.flowsets {
+                record.first = record.first.earlier(millis);
+                record.last = record.last.earlier(millis);
+            }
+        }
+        FlowConfig::V9(_) | FlowConfig::IPFix(_) => {}
+    }
+}
+
+/// Unit of a V9 field if it is a flow start or end timestamp
+pub fn v9_timestamp_unit(field_type: u16) -> Option<TimestampUnit> {
+    match field_type {
+        // LAST_SWITCHED, FIRST_SWITCHED
+        21 | 22 => Some(TimestampUnit::Milliseconds),
+        _ => None,
+    }
+}
+
+/// Unit of an IANA IPFIX field if it is a flow start or end timestamp
+pub fn ipfix_timestamp_unit(field_type: u16) -> Option<TimestampUnit> {
+    match field_type {
+        // flowStartSeconds, flowEndSeconds
+        150 | 151 => Some(TimestampUnit::Seconds),
+        // flowEndSysUpTime, flowStartSysUpTime, flowStartMilliseconds, flowEndMilliseconds
+        21 | 22 | 152 | 153 => Some(TimestampUnit::Milliseconds),
+        _ => None,
+    }
+}
+
+/// A numeric timestamp moved back by `lag`, clamping at 0
+///
+/// Values that are not numbers are returned unchanged.
+pub fn lag_timestamp(
+    value: serde_yaml::Value,
+    unit: TimestampUnit,
+    lag: Duration,
+) -> serde_yaml::Value {
+    let Some(timestamp) = value.as_u64() else {
+        return value;
+    };
+    let lag = match unit {
+        TimestampUnit::Seconds => lag.as_secs(),
+        TimestampUnit::Milliseconds => u64::try_from(lag.as_millis()).unwrap_or(u64::MAX),
+    };
+    serde_yaml::Value::Number(timestamp.saturating_sub(lag).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::schema::UptimeValue;
+
+    #[test]
+    fn test_lag_record_times() {
+        let mut config = crate::config::parse_yaml_str(
+            r#"
+flows:
+  - version: v5
+    flowsets:
+      - src_addr: "10.0.0.1"
+        dst_addr: "10.0.0.2"
+        next_hop: "0.0.0.0"
+        input: 1
+        output: 2
+        d_pkts: 10
+        first: 350000
+        last: "-0s"
+        src_port: 1234
+        dst_port: 80
+        tcp_flags: 0
+        protocol: 6
+        src_mask: 24
+        dst_mask: 24
+"#,
+        )
+        .unwrap();
+        lag_record_times(&mut config.flows[0], Duration::from_secs(30));
+
+        let FlowConfig::V5(v5) = &config.flows[0] else {
+            panic!("expected a V5 flow");
+        };
+        assert_eq!(v5.flowsets[0].first, UptimeValue::Absolute(320_000));
+        assert_eq!(v5.flowsets[0].last.resolve(360_000), 330_000);
+
+        let lag = Duration::from_millis(2500);
+        let end = lag_timestamp(1_700_000_000.into(), TimestampUnit::Seconds, lag);
+        assert_eq!(end.as_u64(), Some(1_699_999_998));
+        let end = lag_timestamp(1000.into(), TimestampUnit::Milliseconds, lag);
+        assert_eq!(end.as_u64(), Some(0));
+    }
+}
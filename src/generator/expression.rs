@@ -0,0 +1,265 @@
+//! Arithmetic expressions in data record values
+//!
+//! A record value written as `"{{ in_pkts * 1500 }}"` is evaluated against the
+//! other fields of the same record before serialization. Expressions support
+//! integer literals, field references, `+ - * /` and parentheses.
+
+use crate::error::{NetflowError, Result};
+use crate::generator::field_serializer::get_field_value;
+
+/// Maximum depth of expressions referencing other expression fields
+const MAX_EXPRESSION_DEPTH: usize = 8;
+
+/// Resolve a record value, evaluating it if it is a `{{ ... }}` expression
+///
+/// Plain values are returned unchanged.
+pub fn resolve_value(
+    value: serde_yaml::Value,
+    record: &serde_yaml::Value,
+) -> Result<serde_yaml::Value> {
+    resolve_at_depth(value, record, 0)
+}
+
+fn resolve_at_depth(
+    value: serde_yaml::Value,
+    record: &serde_yaml::Value,
+    depth: usize,
+) -> Result<serde_yaml::Value> {
+    let Some(expression) = value.as_str().and_then(expression_body) else {
+        return Ok(value);
+    };
+
+    if depth >= MAX_EXPRESSION_DEPTH {
+        return Err(NetflowError::Generation(format!(
+            "Expression nesting too deep (max {}): {}",
+            MAX_EXPRESSION_DEPTH, expression
+        )));
+    }
+
+    let tokens = tokenize(expression)?;
+    let mut parser = ExpressionParser {
+        tokens: &tokens,
+        pos: 0,
+        record,
+        depth,
+    };
+    let result = parser.parse_sum()?;
+    if parser.pos != tokens.len() {
+        return Err(NetflowError::Generation(format!(
+            "Unexpected trailing input in expression: {}",
+            expression
+        )));
+    }
+
+    Ok(serde_yaml::Value::Number(result.into()))
+}
+
+/// Extract the expression between `{{` and `}}`, if the string is an expression
+pub(crate) fn expression_body(s: &str) -> Option<&str> {
+    s.trim()
+        .strip_prefix("{{")
+        .and_then(|rest| rest.strip_suffix("}}"))
+        .map(str::trim)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(u64),
+    Field(String),
+    Op(char),
+    LParen,
+    RParen,
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+            '0'..='9' => {
+                let mut literal = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    literal.push(d);
+                    chars.next();
+                }
+                let number = literal.parse().map_err(|_| {
+                    NetflowError::Generation(format!(
+                        "Number out of range in expression: {}",
+                        literal
+                    ))
+                })?;
+                tokens.push(Token::Number(number));
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let mut name = String::new();
+                while let Some(&d) = chars
+                    .peek()
+                    .filter(|d| d.is_ascii_alphanumeric() || **d == '_')
+                {
+                    name.push(d);
+                    chars.next();
+                }
+                tokens.push(Token::Field(name));
+            }
+            '+' | '-' | '*' | '/' => {
+                tokens.push(Token::Op(c));
+                chars.next();
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                chars.next();
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                chars.next();
+            }
+            _ => {
+                return Err(NetflowError::Generation(format!(
+                    "Unexpected character '{}' in expression: {}",
+                    c, expression
+                )));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser evaluating unsigned integer arithmetic
+struct ExpressionParser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    record: &'a serde_yaml::Value,
+    depth: usize,
+}
+
+impl ExpressionParser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        if token.is_some() {
+            self.pos = self.pos.saturating_add(1);
+        }
+        token
+    }
+
+    fn peek_op(&self, ops: &[char]) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<u64> {
+        let mut value = self.parse_product()?;
+        while let Some(op) = self.peek_op(&['+', '-']) {
+            self.next();
+            let rhs = self.parse_product()?;
+            value = match op {
+                '+' => value.checked_add(rhs),
+                _ => value.checked_sub(rhs),
+            }
+            .ok_or_else(|| overflow_error(op))?;
+        }
+        Ok(value)
+    }
+
+    fn parse_product(&mut self) -> Result<u64> {
+        let mut value = self.parse_atom()?;
+        while let Some(op) = self.peek_op(&['*', '/']) {
+            self.next();
+            let rhs = self.parse_atom()?;
+            value = match op {
+                '*' => value.checked_mul(rhs).ok_or_else(|| overflow_error(op))?,
+                _ => value.checked_div(rhs).ok_or_else(|| {
+                    NetflowError::Generation("Division by zero in expression".to_string())
+                })?,
+            };
+        }
+        Ok(value)
+    }
+
+    fn parse_atom(&mut self) -> Result<u64> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Field(name)) => self.field_value(&name),
+            Some(Token::LParen) => {
+                let value = self.parse_sum()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(value),
+                    _ => Err(NetflowError::Generation(
+                        "Missing ')' in expression".to_string(),
+                    )),
+                }
+            }
+            other => Err(NetflowError::Generation(format!(
+                "Unexpected token in expression: {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Look up a referenced field, evaluating it if it is itself an expression
+    fn field_value(&self, name: &str) -> Result<u64> {
+        let value = get_field_value(self.record, name).ok_or_else(|| {
+            NetflowError::Generation(format!("Expression references unknown field: {}", name))
+        })?;
+        let resolved = resolve_at_depth(value, self.record, self.depth.saturating_add(1))?;
+        resolved.as_u64().ok_or_else(|| {
+            NetflowError::Generation(format!("Expression references non-numeric field: {}", name))
+        })
+    }
+}
+
+fn overflow_error(op: char) -> NetflowError {
+    NetflowError::Generation(format!("Arithmetic overflow in expression ('{}')", op))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> serde_yaml::Value {
+        serde_yaml::from_str(
+            r#"
+in_pkts: 10
+in_bytes: "{{ in_pkts * 1500 }}"
+out_bytes: "{{ (in_bytes + 500) / 2 }}"
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_expressions() {
+        let record = record();
+        let resolve = |name: &str| {
+            resolve_value(get_field_value(&record, name).unwrap(), &record)
+                .unwrap()
+                .as_u64()
+        };
+
+        assert_eq!(resolve("in_pkts"), Some(10));
+        assert_eq!(resolve("in_bytes"), Some(15000));
+        assert_eq!(resolve("out_bytes"), Some(7750));
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        let record = record();
+        let eval = |expr: &str| resolve_value(serde_yaml::Value::String(expr.to_string()), &record);
+
+        assert!(eval("{{ missing * 2 }}").is_err());
+        assert!(eval("{{ in_pkts / 0 }}").is_err());
+        assert!(eval("{{ (in_pkts + 1 }}").is_err());
+        assert!(eval("{{ in_pkts - 11 }}").is_err());
+        // Non-expression strings pass through
+        assert_eq!(
+            eval("10.0.0.1").unwrap(),
+            serde_yaml::Value::String("10.0.0.1".to_string())
+        );
+    }
+}
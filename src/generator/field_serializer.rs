@@ -1,8 +1,16 @@
 /// Helper functions for serializing NetFlow field values
+use crate::cli::RecordOrder;
 use std::net::Ipv4Addr;
 
+/// Field length marking an IPFIX variable-length Information Element (RFC 7011 section 7)
+pub const VARIABLE_LENGTH: u16 = 65535;
+
 /// Serialize a field value based on its length
 pub fn serialize_field_value(value: &serde_yaml::Value, field_length: u16) -> Vec<u8> {
+    if field_length == VARIABLE_LENGTH {
+        return serialize_variable_length(value);
+    }
+
     // Convert field_length to usize safely
     let len = usize::from(field_length);
 
@@ -13,8 +21,10 @@ pub fn serialize_field_value(value: &serde_yaml::Value, field_length: u16) -> Ve
             if let Ok(ip) = s.parse::<Ipv4Addr>() {
                 ip.octets().to_vec()
             } else {
-                // Otherwise treat as hex string or raw bytes
-                vec![0; len]
+                // Otherwise treat as a UTF-8 string, zero-padded or truncated to the field length
+                let mut bytes = s.as_bytes().to_vec();
+                bytes.resize(len, 0);
+                bytes
             }
         }
         // Number values
@@ -44,6 +54,53 @@ pub fn serialize_field_value(value: &serde_yaml::Value, field_length: u16) -> Ve
     }
 }
 
+/// Serialize a float64 Information Element value
+///
+/// `field_length: 4` uses the float32 reduced-size encoding (RFC 7011
+/// section 6.2); other lengths are serialized as for any other field.
+pub fn serialize_float_value(value: &serde_yaml::Value, field_length: u16) -> Vec<u8> {
+    let float = value.as_f64().unwrap_or(0.0);
+    match field_length {
+        4 => (float as f32).to_be_bytes().to_vec(),
+        8 => float.to_be_bytes().to_vec(),
+        _ => serialize_field_value(value, field_length),
+    }
+}
+
+/// Serialize a variable-length field value with its length prefix
+///
+/// Lengths below 255 use a single length octet; longer values use 255
+/// followed by a 2-octet length, per RFC 7011 section 7.
+fn serialize_variable_length(value: &serde_yaml::Value) -> Vec<u8> {
+    let mut content = match value {
+        serde_yaml::Value::String(s) => s.as_bytes().to_vec(),
+        _ => Vec::new(),
+    };
+    // The 3-octet form can encode at most 65535 bytes
+    content.truncate(usize::from(u16::MAX));
+
+    let mut bytes = Vec::with_capacity(content.len().saturating_add(3));
+    match u8::try_from(content.len()) {
+        Ok(short_len) if short_len < 255 => bytes.push(short_len),
+        _ => {
+            bytes.push(255);
+            let long_len = u16::try_from(content.len()).unwrap_or(u16::MAX);
+            bytes.extend_from_slice(&long_len.to_be_bytes());
+        }
+    }
+    bytes.extend_from_slice(&content);
+    bytes
+}
+
+/// Reorder encoded data records according to the requested record order
+pub fn apply_record_order(records: &mut [Vec<u8>], order: RecordOrder) {
+    match order {
+        RecordOrder::Config => {}
+        RecordOrder::Sorted => records.sort(),
+        RecordOrder::Reverse => records.reverse(),
+    }
+}
+
 /// Get field value from a YAML mapping by field name
 pub fn get_field_value(record: &serde_yaml::Value, field_name: &str) -> Option<serde_yaml::Value> {
     if let serde_yaml::Value::Mapping(map) = record {
@@ -54,6 +111,77 @@ pub fn get_field_value(record: &serde_yaml::Value, field_name: &str) -> Option<s
     }
 }
 
+/// Resolve a named value (e.g. `egress`) for an enumerated IPFIX Information Element
+///
+/// Names are matched case-insensitively; numbers and unrecognized names pass through.
+/// `tcpControlBits` accepts flag names joined with `|` (e.g. `SYN|ACK`).
+pub fn resolve_ipfix_named_value(field_type: u16, value: serde_yaml::Value) -> serde_yaml::Value {
+    let Some(name) = value.as_str() else {
+        return value;
+    };
+
+    // tcpControlBits
+    if field_type == 6 {
+        return parse_tcp_control_bits(name)
+            .map(|bits| serde_yaml::Value::Number(bits.into()))
+            .unwrap_or(value);
+    }
+
+    let named: &[(&str, u64)] = match field_type {
+        // flowDirection
+        61 => &[("ingress", 0), ("egress", 1)],
+        // flowEndReason (RFC 7012)
+        136 => &[
+            ("idle timeout", 1),
+            ("active timeout", 2),
+            ("end of flow", 3),
+            ("forced end", 4),
+            ("lack of resources", 5),
+        ],
+        // firewallEvent (NSEL)
+        233 => &[
+            ("ignore", 0),
+            ("created", 1),
+            ("deleted", 2),
+            ("denied", 3),
+            ("alert", 4),
+            ("update", 5),
+        ],
+        _ => &[],
+    };
+
+    named
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| serde_yaml::Value::Number((*v).into()))
+        .unwrap_or(value)
+}
+
+/// TCP flag bits of the `tcpControlBits` IE (RFC 7125)
+///
+/// NS (0x100) is only carried by the 2-octet encoding.
+const TCP_CONTROL_BITS: [(&str, u16); 9] = [
+    ("fin", 0x001),
+    ("syn", 0x002),
+    ("rst", 0x004),
+    ("psh", 0x008),
+    ("ack", 0x010),
+    ("urg", 0x020),
+    ("ece", 0x040),
+    ("cwr", 0x080),
+    ("ns", 0x100),
+];
+
+/// Combine `|`-separated TCP flag names into their bit value, if all are known
+fn parse_tcp_control_bits(names: &str) -> Option<u16> {
+    names.split('|').try_fold(0u16, |bits, name| {
+        TCP_CONTROL_BITS
+            .iter()
+            .find(|(flag, _)| flag.eq_ignore_ascii_case(name.trim()))
+            .map(|(_, bit)| bits | bit)
+    })
+}
+
 /// Map V9 field type ID to common field names
 pub fn v9_field_id_to_name(field_type: u16) -> &'static str {
     match field_type {
@@ -81,6 +209,10 @@ pub fn v9_field_id_to_name(field_type: u16) -> &'static str {
         22 => "first_switched",
         23 => "out_bytes",
         24 => "out_pkts",
+        25 => "min_pkt_lngth",
+        26 => "max_pkt_lngth",
+        52 => "min_ttl",
+        53 => "max_ttl",
         _ => "unknown",
     }
 }
@@ -108,6 +240,105 @@ pub fn ipfix_field_id_to_name(field_type: u16) -> &'static str {
         18 => "bgp_next_hop_ipv4_address",
         21 => "flow_end_sys_up_time",
         22 => "flow_start_sys_up_time",
+        23 => "post_octet_delta_count",
+        24 => "post_packet_delta_count",
+        52 => "minimum_ttl",
+        53 => "maximum_ttl",
+        61 => "flow_direction",
+        82 => "interface_name",
+        83 => "interface_description",
+        84 => "sampler_name",
+        128 => "bgp_next_adjacent_as_number",
+        129 => "bgp_prev_adjacent_as_number",
+        130 => "exporter_ipv4_address",
+        136 => "flow_end_reason",
+        143 => "metering_process_id",
+        144 => "exporting_process_id",
+        149 => "observation_domain_id",
+        150 => "flow_start_seconds",
+        151 => "flow_end_seconds",
+        152 => "flow_start_milliseconds",
+        153 => "flow_end_milliseconds",
+        225 => "post_nat_source_ipv4_address",
+        226 => "post_nat_destination_ipv4_address",
+        227 => "post_napt_source_transport_port",
+        228 => "post_napt_destination_transport_port",
+        233 => "firewall_event",
+        302 => "selector_id",
+        304 => "selector_algorithm",
+        305 => "sampling_packet_interval",
+        306 => "sampling_packet_space",
+        311 => "sampling_probability",
+        323 => "observation_time_milliseconds",
         _ => "unknown",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_named_values() {
+        let resolve = |v: serde_yaml::Value| resolve_ipfix_named_value(61, v).as_u64();
+
+        assert_eq!(resolve("ingress".into()), Some(0));
+        assert_eq!(resolve("Egress".into()), Some(1));
+        assert_eq!(resolve(1.into()), Some(1));
+        assert_eq!(
+            resolve_ipfix_named_value(233, "deleted".into()).as_u64(),
+            Some(2)
+        );
+        assert_eq!(
+            resolve_ipfix_named_value(136, "Active Timeout".into()).as_u64(),
+            Some(2)
+        );
+        assert_eq!(resolve_ipfix_named_value(136, 7.into()).as_u64(), Some(7));
+        // Names are only resolved for enumerated IEs
+        assert_eq!(
+            resolve_ipfix_named_value(82, "egress".into()),
+            serde_yaml::Value::from("egress")
+        );
+    }
+
+    #[test]
+    fn test_resolve_tcp_control_bits() {
+        let resolve = |name: &str| resolve_ipfix_named_value(6, name.into()).as_u64();
+
+        assert_eq!(resolve("SYN|ACK"), Some(0x12));
+        assert_eq!(resolve("syn | ece | cwr"), Some(0xC2));
+        assert_eq!(resolve("NS|FIN"), Some(0x101));
+        assert_eq!(resolve("SYN|BOGUS"), None);
+
+        // Extended bits need the 2-octet encoding
+        let value = resolve_ipfix_named_value(6, "NS|ACK".into());
+        assert_eq!(serialize_field_value(&value, 2), vec![0x01, 0x10]);
+    }
+
+    #[test]
+    fn test_serialize_variable_length_string() {
+        let value = serde_yaml::Value::String("eth0".to_string());
+        assert_eq!(
+            serialize_field_value(&value, VARIABLE_LENGTH),
+            vec![4, b'e', b't', b'h', b'0']
+        );
+
+        let long = serde_yaml::Value::String("x".repeat(300));
+        let bytes = serialize_field_value(&long, VARIABLE_LENGTH);
+        assert_eq!(&bytes[..3], &[255, 0x01, 0x2c]);
+        assert_eq!(bytes.len(), 303);
+
+        // Missing values encode as an empty string
+        let zero = serde_yaml::Value::Number(0.into());
+        assert_eq!(serialize_field_value(&zero, VARIABLE_LENGTH), vec![0]);
+    }
+
+    #[test]
+    fn test_serialize_fixed_length_string() {
+        let value = serde_yaml::Value::String("eth0".to_string());
+        assert_eq!(
+            serialize_field_value(&value, 6),
+            vec![b'e', b't', b'h', b'0', 0, 0]
+        );
+    }
+}
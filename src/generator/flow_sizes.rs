@@ -0,0 +1,208 @@
+//! Heavy-tailed packet and byte counts (`flow_size_distribution`)
+
+use crate::config::schema::{
+    FlowConfig, FlowSizeDistributionConfig, FlowSizeModel, IPFixFlowSet, V9FlowSet,
+};
+use crate::generator::packet_sizes::{SplitMix64, derive_octets};
+use serde_yaml::{Mapping, Value};
+use std::net::Ipv4Addr;
+
+/// 5-tuple and counter keys of a V9/IPFIX data record
+struct RecordKeys {
+    src_addr: &'static str,
+    dst_addr: &'static str,
+    src_port: &'static str,
+    dst_port: &'static str,
+    protocol: &'static str,
+    packets: &'static str,
+    octets: &'static str,
+}
+
+const V9_KEYS: RecordKeys = RecordKeys {
+    src_addr: "src_addr",
+    dst_addr: "dst_addr",
+    src_port: "src_port",
+    dst_port: "dst_port",
+    protocol: "protocol",
+    packets: "in_pkts",
+    octets: "in_bytes",
+};
+
+const IPFIX_KEYS: RecordKeys = RecordKeys {
+    src_addr: "source_ipv4_address",
+    dst_addr: "destination_ipv4_address",
+    src_port: "source_transport_port",
+    dst_port: "destination_transport_port",
+    protocol: "protocol_identifier",
+    packets: "packet_delta_count",
+    octets: "octet_delta_count",
+};
+
+/// Replaces each record's packet count with a draw from the configured model,
+/// capped at `max_packets`, and derives its byte count from IMIX packet sizes
+#[derive(Debug)]
+pub struct FlowSizeDistribution {
+    model: FlowSizeModel,
+    max_packets: u32,
+    rng: SplitMix64,
+}
+
+impl FlowSizeDistribution {
+    pub fn new(config: &FlowSizeDistributionConfig) -> Self {
+        Self {
+            model: config.model,
+            max_packets: config.max_packets.unwrap_or(u32::MAX),
+            rng: SplitMix64(config.seed),
+        }
+    }
+
+    /// Draw the packet count of every record of `flow`, with byte counts to match
+    ///
+    /// V5/V7 byte counts are left to the usual derivation from the packet
+    /// count. V9/IPFIX byte counts are derived from IMIX packet sizes, seeded
+    /// by the record's 5-tuple.
+    pub fn apply(&mut self, flow: &mut FlowConfig) {
+        match flow {
+            FlowConfig::V5(config) => {
+                for record in &mut config.flowsets {
+                    record.d_pkts = self.draw();
+                    record.d_octets = None;
+                }
+            }
+            FlowConfig::V7(config) => {
+                for record in &mut config.flowsets {
+                    record.d_pkts = self.draw();
+                    record.d_octets = None;
+                }
+            }
+            FlowConfig::V9(config) => {
+                for flowset in &mut config.flowsets {
+                    if let V9FlowSet::Data { records, .. } = flowset {
+                        self.draw_records(records, &V9_KEYS);
+                    }
+                }
+            }
+            FlowConfig::IPFix(config) => {
+                for flowset in &mut config.flowsets {
+                    if let IPFixFlowSet::Data { records, .. } = flowset {
+                        self.draw_records(records, &IPFIX_KEYS);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_records(&mut self, records: &mut [Value], keys: &RecordKeys) {
+        for record in records {
+            let Value::Mapping(map) = record else {
+                continue;
+            };
+            let packets = self.draw();
+            let octets = derive_octets(packets, None, five_tuple(map, keys));
+            map.insert(keys.packets.into(), u64::from(packets).into());
+            map.insert(keys.octets.into(), u64::from(octets).into());
+        }
+    }
+
+    /// A packet count from the model, at least 1 and at most `max_packets`
+    fn draw(&mut self) -> u32 {
+        let packets = match self.model {
+            FlowSizeModel::Pareto { min_packets, shape } => {
+                // Inverse transform; 1 - U keeps the base in (0, 1]
+                min_packets / (1.0 - self.rng.uniform()).powf(1.0 / shape)
+            }
+            FlowSizeModel::Lognormal { mu, sigma } => {
+                // Box-Muller transform for a standard normal
+                let radius = (-2.0 * (1.0 - self.rng.uniform()).ln()).sqrt();
+                let normal = radius * (std::f64::consts::TAU * self.rng.uniform()).cos();
+                (mu + sigma * normal).exp()
+            }
+        };
+        // Float-to-int casts saturate, and NaN becomes 0
+        (packets.round() as u32).clamp(1, self.max_packets.max(1))
+    }
+}
+
+/// Record 5-tuple for seeding the byte count, with zeros for missing fields
+fn five_tuple(map: &Mapping, keys: &RecordKeys) -> (Ipv4Addr, Ipv4Addr, u16, u16, u8) {
+    let addr = |key: &str| {
+        map.get(key)
+            .and_then(Value::as_str)
+            .and_then(|addr| addr.parse().ok())
+            .unwrap_or(Ipv4Addr::UNSPECIFIED)
+    };
+    let number = |key: &str| map.get(key).and_then(Value::as_u64).unwrap_or(0);
+    (
+        addr(keys.src_addr),
+        addr(keys.dst_addr),
+        u16::try_from(number(keys.src_port)).unwrap_or(0),
+        u16::try_from(number(keys.dst_port)).unwrap_or(0),
+        u8::try_from(number(keys.protocol)).unwrap_or(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::exported_records;
+
+    #[test]
+    fn test_pareto_flow_sizes() {
+        let config = FlowSizeDistributionConfig {
+            model: FlowSizeModel::Pareto {
+                min_packets: 2.0,
+                shape: 1.1,
+            },
+            max_packets: Some(1_000_000),
+            seed: 42,
+        };
+        let mut distribution = FlowSizeDistribution::new(&config);
+        let mut sizes: Vec<u64> = (0..10_000)
+            .map(|_| u64::from(distribution.draw()))
+            .collect();
+
+        assert!(sizes.iter().all(|&size| (2..=1_000_000).contains(&size)));
+        // Elephants and mice: the largest 10% of flows carry most packets
+        sizes.sort_unstable();
+        let total: u64 = sizes.iter().sum();
+        let top: u64 = sizes[9_000..].iter().sum();
+        assert!(top * 2 > total, "top 10% carry {} of {}", top, total);
+
+        // The same seed draws the same sizes
+        let mut first = FlowSizeDistribution::new(&config);
+        let mut second = FlowSizeDistribution::new(&config);
+        assert!((0..100).all(|_| first.draw() == second.draw()));
+    }
+
+    #[test]
+    fn test_lognormal_records() {
+        let config = crate::config::parse_yaml_str(
+            r#"
+flows:
+  - version: ipfix
+    flow_size_distribution:
+      model: lognormal
+      mu: 3.0
+      sigma: 1.5
+      seed: 7
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - source_ipv4_address: "10.0.0.1"
+            packet_delta_count: 5
+            octet_delta_count: 500
+"#,
+        )
+        .unwrap();
+        let flow = &config.flows[0];
+        let mut distribution = FlowSizeDistribution::new(flow.flow_size_distribution().unwrap());
+        let records = &exported_records(flow, 1, |flow| distribution.apply(flow))[0];
+
+        let packets = records[0]["packet_delta_count"].as_u64().unwrap();
+        let octets = records[0]["octet_delta_count"].as_u64().unwrap();
+        assert_ne!((packets, octets), (5, 500));
+        // IMIX packets are 64 to 1500 bytes
+        assert!((packets * 64..=packets * 1500).contains(&octets));
+    }
+}
@@ -0,0 +1,291 @@
+//! Simulated table of active flows (`flow_table`)
+//!
+//! Each data flowset's first record is the prototype for every active flow.
+//! Flows keep their addresses and source port for as long as they stay in
+//! the table, so collectors see the same keys export after export, while
+//! churn retires some and starts others. Provider values are drawn when a
+//! flow starts and kept with its keys. Counters report each export's
+//! increment, or with `counter_mode: cumulative` each flow's running total.
+
+use crate::config::schema::{FlowConfig, FlowTableConfig, IPFixFlowSet, V9FlowSet};
+use crate::generator::lifecycle::{IPFIX_COUNTERS, V9_COUNTERS};
+use crate::generator::packet_sizes::SplitMix64;
+use crate::generator::providers::FieldProviders;
+use serde_yaml::{Mapping, Value};
+use std::net::Ipv4Addr;
+
+/// First source port given to simulated flows (IANA dynamic range)
+const DYNAMIC_PORT_START: u16 = 49152;
+
+/// Record fields a flow table rewrites for one NetFlow version
+struct TableFields {
+    src_addr: &'static str,
+    dst_addr: &'static str,
+    src_port: &'static str,
+    /// Counters turned into running totals with `counter_mode: cumulative`
+    counters: &'static [&'static str],
+}
+
+const V9_FIELDS: TableFields = TableFields {
+    src_addr: "src_addr",
+    dst_addr: "dst_addr",
+    src_port: "src_port",
+    counters: V9_COUNTERS,
+};
+
+const IPFIX_FIELDS: TableFields = TableFields {
+    src_addr: "source_ipv4_address",
+    dst_addr: "destination_ipv4_address",
+    src_port: "source_transport_port",
+    counters: IPFIX_COUNTERS,
+};
+
+/// One active flow
+#[derive(Debug, Clone, PartialEq)]
+struct TableEntry {
+    /// Low 16 bits of the source address
+    src_host: u16,
+    /// Low 16 bits of the destination address
+    dst_host: u16,
+    src_port: u16,
+    /// Exports this flow has been reported in
+    exports: u64,
+    /// Provider values drawn for each data flowset, in flowset order
+    provided: Vec<Mapping>,
+}
+
+impl TableEntry {
+    fn new(rng: &mut SplitMix64) -> Self {
+        let [a, b, c, d, ..] = rng.next().to_be_bytes();
+        Self {
+            src_host: u16::from_be_bytes([a, b]),
+            dst_host: u16::from_be_bytes([c, d]),
+            src_port: DYNAMIC_PORT_START | (u16::from_be_bytes([a ^ c, b ^ d]) >> 2),
+            exports: 0,
+            provided: Vec::new(),
+        }
+    }
+
+    /// The prototype record with this flow's keys, and its counters'
+    /// running totals if `cumulative`
+    ///
+    /// Delta counters keep the prototype's values, each export's increment,
+    /// so a collector summing them counts every export once.
+    fn record(&self, prototype: &Mapping, fields: &TableFields, cumulative: bool) -> Mapping {
+        let mut record = prototype.clone();
+
+        for (field, host) in [
+            (fields.src_addr, self.src_host),
+            (fields.dst_addr, self.dst_host),
+        ] {
+            if let Some(addr) = prototype
+                .get(field)
+                .and_then(Value::as_str)
+                .and_then(|addr| addr.parse::<Ipv4Addr>().ok())
+            {
+                let [a, b, ..] = addr.octets();
+                let [c, d] = host.to_be_bytes();
+                record.insert(field.into(), Ipv4Addr::new(a, b, c, d).to_string().into());
+            }
+        }
+        record.insert(fields.src_port.into(), self.src_port.into());
+
+        if !cumulative {
+            return record;
+        }
+        for &field in fields.counters {
+            if let Some(count) = prototype.get(field).and_then(Value::as_u64) {
+                record.insert(field.into(), count.saturating_mul(self.exports).into());
+            }
+        }
+
+        record
+    }
+}
+
+/// Table entries whose keys persist between exports until churn replaces them,
+/// each with its own counters
+#[derive(Debug)]
+pub struct FlowTable {
+    churn: f64,
+    /// Report each flow's running totals rather than per-export increments
+    cumulative: bool,
+    rng: SplitMix64,
+    entries: Vec<TableEntry>,
+}
+
+impl FlowTable {
+    pub fn new(config: &FlowTableConfig, cumulative: bool) -> Self {
+        let mut rng = SplitMix64(config.seed);
+        let entries = (0..config.size)
+            .map(|_| TableEntry::new(&mut rng))
+            .collect();
+        Self {
+            churn: config.churn,
+            cumulative,
+            rng,
+            entries,
+        }
+    }
+
+    /// Replace the data records of `flow` with the table's next export
+    ///
+    /// After the first export, each flow is replaced by a new one with
+    /// probability `churn` before the table is reported. New flows draw
+    /// their values from `providers`.
+    pub fn apply(&mut self, flow: &mut FlowConfig, mut providers: Option<&mut FieldProviders>) {
+        if self.entries.iter().any(|entry| entry.exports > 0) {
+            for entry in &mut self.entries {
+                if self.rng.uniform() < self.churn {
+                    *entry = TableEntry::new(&mut self.rng);
+                }
+            }
+        }
+        for entry in &mut self.entries {
+            entry.exports = entry.exports.saturating_add(1);
+        }
+
+        match flow {
+            FlowConfig::V5(_) | FlowConfig::V7(_) => {}
+            FlowConfig::V9(config) => {
+                let data = config
+                    .flowsets
+                    .iter_mut()
+                    .filter_map(|flowset| match flowset {
+                        V9FlowSet::Data { records, .. } => Some(records),
+                        _ => None,
+                    });
+                for (position, records) in data.enumerate() {
+                    self.fill(records, &V9_FIELDS, position, providers.as_deref_mut());
+                }
+            }
+            FlowConfig::IPFix(config) => {
+                let data = config
+                    .flowsets
+                    .iter_mut()
+                    .filter_map(|flowset| match flowset {
+                        IPFixFlowSet::Data { records, .. } => Some(records),
+                        _ => None,
+                    });
+                for (position, records) in data.enumerate() {
+                    self.fill(records, &IPFIX_FIELDS, position, providers.as_deref_mut());
+                }
+            }
+        }
+    }
+
+    /// Replace the records of the data flowset at `position` with one per flow
+    fn fill(
+        &mut self,
+        records: &mut Vec<Value>,
+        fields: &TableFields,
+        position: usize,
+        mut providers: Option<&mut FieldProviders>,
+    ) {
+        let Some(Value::Mapping(prototype)) = records.first().cloned() else {
+            return;
+        };
+        *records = self
+            .entries
+            .iter_mut()
+            .map(|entry| {
+                // Flowsets are filled in order, so a new flow draws each in turn
+                if entry.provided.len() <= position {
+                    entry.provided.resize(position, Mapping::new());
+                    let provided = providers
+                        .as_deref_mut()
+                        .map_or_else(Mapping::new, |providers| providers.draw(&prototype));
+                    entry.provided.push(provided);
+                }
+                let mut prototype = prototype.clone();
+                if let Some(provided) = entry.provided.get(position) {
+                    prototype.extend(provided.clone());
+                }
+                Value::Mapping(entry.record(&prototype, fields, self.cumulative))
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+    use crate::generator::exported_records;
+
+    fn sample_flow(churn: f64) -> FlowConfig {
+        let config = parse_yaml_str(&format!(
+            r#"
+flows:
+  - version: ipfix
+    flow_table:
+      size: 3
+      churn: {churn}
+      seed: 7
+    flowsets:
+      - type: data
+        template_id: 300
+        records:
+          - source_ipv4_address: "10.1.0.0"
+            destination_ipv4_address: "172.16.0.0"
+            source_transport_port: 0
+            octet_delta_count: 100
+"#
+        ))
+        .unwrap();
+        config.flows[0].clone()
+    }
+
+    /// (source address, source port, octets) of each record after one export
+    fn export(table: &mut FlowTable, flow: &FlowConfig) -> Vec<(String, u64, u64)> {
+        exported_records(flow, 1, |flow| table.apply(flow, None))
+            .remove(0)
+            .iter()
+            .map(|record| {
+                (
+                    record["source_ipv4_address"].as_str().unwrap().to_string(),
+                    record["source_transport_port"].as_u64().unwrap(),
+                    record["octet_delta_count"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_flow_table_keys_and_churn() {
+        // Without churn, keys are stable and delta counters repeat each increment
+        let flow = sample_flow(0.0);
+        let mut table = FlowTable::new(flow.flow_table().unwrap(), false);
+        let first = export(&mut table, &flow);
+        let second = export(&mut table, &flow);
+        assert_eq!(first.len(), 3);
+        for ((addr, port, octets), (next_addr, next_port, next_octets)) in first.iter().zip(&second)
+        {
+            assert!(addr.starts_with("10.1."));
+            assert!(*port >= u64::from(DYNAMIC_PORT_START));
+            assert_eq!((addr, port), (next_addr, next_port));
+            assert_eq!((*octets, *next_octets), (100, 100));
+        }
+
+        // The same seed gives the same table
+        let mut again = FlowTable::new(flow.flow_table().unwrap(), false);
+        assert_eq!(export(&mut again, &flow), first);
+
+        // Cumulative counters accumulate per flow
+        let mut table = FlowTable::new(flow.flow_table().unwrap(), true);
+        export(&mut table, &flow);
+        assert!(
+            export(&mut table, &flow)
+                .iter()
+                .all(|(_, _, octets)| *octets == 200)
+        );
+
+        // Full churn replaces every flow, which starts counting afresh
+        let flow = sample_flow(1.0);
+        let mut table = FlowTable::new(flow.flow_table().unwrap(), true);
+        let first = export(&mut table, &flow);
+        let second = export(&mut table, &flow);
+        assert_ne!(first, second);
+        assert!(second.iter().all(|(_, _, octets)| *octets == 100));
+    }
+}
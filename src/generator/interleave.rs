@@ -0,0 +1,48 @@
+//! Round-robin packet order across flows (`--interleave`)
+
+/// Interleave per-flow packet batches round-robin
+///
+/// Each flow keeps its own packet order, so templates still precede their
+/// data. Flows that run out of packets drop out of the rotation.
+pub fn interleave_flow_batches(flow_batches: Vec<Vec<Vec<u8>>>) -> Vec<Vec<u8>> {
+    let total = flow_batches.iter().map(Vec::len).sum();
+    let mut iters: Vec<_> = flow_batches.into_iter().map(Vec::into_iter).collect();
+    let mut interleaved = Vec::with_capacity(total);
+
+    while interleaved.len() < total {
+        for iter in iters.iter_mut() {
+            if let Some(packet) = iter.next() {
+                interleaved.push(packet);
+            }
+        }
+    }
+
+    interleaved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interleave_flow_batches() {
+        let batches = vec![
+            vec![vec![9, 0], vec![9, 1], vec![9, 2]],
+            vec![vec![10, 0]],
+            Vec::new(),
+            vec![vec![5, 0], vec![5, 1]],
+        ];
+        assert_eq!(
+            interleave_flow_batches(batches),
+            vec![
+                vec![9, 0],
+                vec![10, 0],
+                vec![5, 0],
+                vec![9, 1],
+                vec![5, 1],
+                vec![9, 2],
+            ]
+        );
+        assert!(interleave_flow_batches(Vec::new()).is_empty());
+    }
+}
@@ -1,10 +1,77 @@
-use crate::config::schema::{IPFixConfig, IPFixFlowSet as ConfigIPFixFlowSet};
+use crate::config::schema::{IPFixConfig, IPFixFlowSet as ConfigIPFixFlowSet, IPFixTemplateField};
 use crate::error::{NetflowError, Result};
+use crate::generator::GeneratorOptions;
+use crate::generator::bgp::expand_as_path;
+use crate::generator::export_lag::{ipfix_timestamp_unit, lag_timestamp};
+use crate::generator::expression::resolve_value;
 use crate::generator::field_serializer::{
-    get_field_value, ipfix_field_id_to_name, serialize_field_value,
+    VARIABLE_LENGTH, apply_record_order, get_field_value, ipfix_field_id_to_name,
+    resolve_ipfix_named_value, serialize_field_value, serialize_float_value,
 };
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// IANA Private Enterprise Number for reverse Information Elements (RFC 5103)
+pub const REVERSE_PEN: u32 = 29305;
+
+/// Forward Information Elements that get a reverse counterpart when `biflow` is enabled
+const BIFLOW_REVERSE_FIELDS: [&str; 3] = ["octetDeltaCount", "packetDeltaCount", "tcpControlBits"];
+
+/// Information Elements of type float64, sent as IEEE 754 floats (samplingProbability)
+const FLOAT_FIELDS: [u16; 1] = [311];
+
+/// An IPFIX template as sent on the wire
+///
+/// Options templates list their scope fields first and are emitted in an
+/// Options Template Set; regular templates have no scope fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IPFixTemplate {
+    pub template_id: u16,
+    /// Number of leading scope fields (0 for a regular template)
+    pub scope_field_count: u16,
+    pub fields: Vec<IPFixTemplateField>,
+}
+
+impl IPFixTemplate {
+    /// Build the template for a template or options template flowset, if it is one
+    pub fn from_flowset(
+        config: &IPFixConfig,
+        flowset: &ConfigIPFixFlowSet,
+    ) -> Result<Option<Self>> {
+        match flowset {
+            ConfigIPFixFlowSet::Template {
+                template_id,
+                fields,
+            } => Ok(Some(Self {
+                template_id: *template_id,
+                scope_field_count: 0,
+                fields: effective_template_fields(config, fields),
+            })),
+            ConfigIPFixFlowSet::OptionsTemplate {
+                template_id,
+                scope_fields,
+                fields,
+            } => {
+                // RFC 7011 section 3.4.2.2: the scope field count MUST NOT be zero
+                let scope_field_count = u16::try_from(scope_fields.len())
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or_else(|| {
+                        NetflowError::Generation(format!(
+                            "Options template {} needs between 1 and 65535 scope fields",
+                            template_id
+                        ))
+                    })?;
+                Ok(Some(Self {
+                    template_id: *template_id,
+                    scope_field_count,
+                    fields: scope_fields.iter().chain(fields).cloned().collect(),
+                }))
+            }
+            ConfigIPFixFlowSet::Data { .. } => Ok(None),
+        }
+    }
+}
+
 /// Build IPFIX packets from configuration
 /// Generates proper template and data flowsets
 ///
@@ -12,6 +79,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 /// * `config` - IPFIX configuration
 /// * `override_sequence_number` - Optional sequence number to use (overrides config value)
 /// * `send_templates` - Whether to include template packets (for periodic refresh)
+/// * `options` - Packet building options shared across flows
 ///
 /// # Returns
 /// * `(packets, next_sequence_number)` - Generated packets and the next sequence number to use
@@ -19,54 +87,112 @@ pub fn build_ipfix_packets(
     config: IPFixConfig,
     override_sequence_number: Option<u32>,
     send_templates: bool,
+    options: &GeneratorOptions,
+) -> Result<(Vec<Vec<u8>>, u32)> {
+    build_ipfix_packets_with_shared(
+        config,
+        override_sequence_number,
+        send_templates,
+        &[],
+        options,
+    )
+}
+
+/// Build IPFIX packets, resolving data sets against `shared` templates too
+///
+/// `shared` holds templates defined by other flows of the same observation
+/// domain (--shared-templates). They are only used to encode data sets whose
+/// template this flow doesn't define itself, and are never sent by this flow.
+pub fn build_ipfix_packets_with_shared(
+    config: IPFixConfig,
+    override_sequence_number: Option<u32>,
+    send_templates: bool,
+    shared: &[IPFixTemplate],
+    options: &GeneratorOptions,
+) -> Result<(Vec<Vec<u8>>, u32)> {
+    build_ipfix_packets_with_clock(
+        config,
+        override_sequence_number,
+        send_templates,
+        shared,
+        options,
+        &mut SystemTime::now,
+    )
+}
+
+/// Build IPFIX packets, taking the time of export from `clock`
+///
+/// With --per-packet-export-time the clock is read again for every data
+/// packet, so each message carries the time it was built (RFC 7011
+/// section 3.1); otherwise the whole batch shares the first reading.
+fn build_ipfix_packets_with_clock(
+    config: IPFixConfig,
+    override_sequence_number: Option<u32>,
+    send_templates: bool,
+    shared: &[IPFixTemplate],
+    options: &GeneratorOptions,
+    clock: &mut dyn FnMut() -> SystemTime,
 ) -> Result<(Vec<Vec<u8>>, u32)> {
     let mut packets = Vec::new();
 
     // Get header values
     let (export_time, mut sequence_number, observation_domain_id) =
-        get_header_values(&config, override_sequence_number)?;
+        get_header_values(&config, override_sequence_number, clock())?;
 
     // Separate templates and data flowsets
     let mut templates = Vec::new();
     let mut data_flowsets = Vec::new();
 
     for flowset in &config.flowsets {
-        match flowset {
-            ConfigIPFixFlowSet::Template {
-                template_id,
-                fields,
-            } => {
-                templates.push((*template_id, fields.clone()));
-            }
-            ConfigIPFixFlowSet::Data {
-                template_id,
-                records,
-            } => {
-                data_flowsets.push((*template_id, records.clone()));
-            }
+        if let ConfigIPFixFlowSet::Data {
+            template_id,
+            records,
+        } = flowset
+        {
+            data_flowsets.push((*template_id, records.clone()));
+        } else if let Some(template) = IPFixTemplate::from_flowset(&config, flowset)? {
+            templates.push(template);
         }
     }
 
+    // A template-only flow has nothing to send once its templates are cached,
+    // so only a flow without any flowsets is an error
+    if templates.is_empty() && data_flowsets.is_empty() {
+        return Err(NetflowError::Generation(
+            "IPFIX configuration must contain at least one template or data flowset".to_string(),
+        ));
+    }
+
     // Generate template packet if we have templates AND send_templates is true
+    // (--suppress-templates withholds them to produce orphan data);
+    // a combined message always carries its flow's templates
     // Per RFC 7011: Template packets (Template Sets) do NOT increment the sequence number
-    if !templates.is_empty() && send_templates {
+    // Template each packet defines or uses, for its padding sets
+    let mut packet_templates: Vec<&IPFixTemplate> = Vec::new();
+
+    let send_templates = send_templates || config.combined_message;
+    if !templates.is_empty() && send_templates && !options.suppress_templates {
         let template_packet = build_template_packet(
             export_time,
             sequence_number,
             observation_domain_id,
             &templates,
+            !options.no_padding,
+            options.options_first,
+            options.group_templates,
         )?;
         packets.push(template_packet);
+        packet_templates.extend(templates.first());
         // No sequence increment for template packets
     }
 
     // Generate data packets
     for (template_id, records) in data_flowsets {
         // Find the template definition
-        let template_fields = templates
+        let template = templates
             .iter()
-            .find(|(id, _)| *id == template_id)
-            .map(|(_, fields)| fields)
+            .chain(shared)
+            .find(|template| template.template_id == template_id)
             .ok_or_else(|| {
                 NetflowError::Generation(format!(
                     "Data flowset references undefined template ID: {}",
@@ -74,15 +200,24 @@ pub fn build_ipfix_packets(
                 ))
             })?;
 
+        let template_fields = &template.fields;
+        let record_bytes = encode_records(template_fields, &records, options)?;
+        let export_time = if options.per_packet_export_time {
+            header_export_time(&config, clock())?
+        } else {
+            export_time
+        };
         let data_packet = build_data_packet(
             export_time,
             sequence_number,
             observation_domain_id,
             template_id,
-            template_fields,
-            &records,
+            &record_bytes,
+            min_record_length(template_fields),
+            !options.no_padding,
         )?;
         packets.push(data_packet);
+        packet_templates.push(template);
 
         // Per RFC 7011: Sequence number increments by the number of data records
         let num_records = u32::try_from(records.len()).map_err(|_| {
@@ -93,10 +228,14 @@ pub fn build_ipfix_packets(
             .ok_or_else(|| NetflowError::Generation("Sequence number overflow".to_string()))?;
     }
 
-    if packets.is_empty() {
-        return Err(NetflowError::Generation(
-            "IPFIX configuration must contain at least one template or data flowset".to_string(),
-        ));
+    if config.combined_message && packets.len() > 1 {
+        packets = vec![combine_packets(&packets)?];
+        packet_templates.truncate(1);
+    }
+    if let Some(size) = options.min_packet_size {
+        for (packet, template) in packets.iter_mut().zip(packet_templates) {
+            pad_message(packet, usize::from(size), template)?;
+        }
     }
 
     Ok((packets, sequence_number))
@@ -105,17 +244,9 @@ pub fn build_ipfix_packets(
 fn get_header_values(
     config: &IPFixConfig,
     override_sequence_number: Option<u32>,
+    now: SystemTime,
 ) -> Result<(u32, u32, u32)> {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|e| NetflowError::Generation(format!("Failed to get system time: {}", e)))?;
-
-    let export_time = if let Some(ref h) = config.header {
-        h.export_time
-            .unwrap_or_else(|| u32::try_from(now.as_secs()).unwrap_or(u32::MAX))
-    } else {
-        u32::try_from(now.as_secs()).unwrap_or(u32::MAX)
-    };
+    let export_time = header_export_time(config, now)?;
 
     // Use override if provided, otherwise use config value, otherwise default to 0
     let sequence_number = if let Some(override_seq) = override_sequence_number {
@@ -135,27 +266,57 @@ fn get_header_values(
     Ok((export_time, sequence_number, observation_domain_id))
 }
 
+/// Export time from the config header, or `now` in seconds since the epoch
+fn header_export_time(config: &IPFixConfig, now: SystemTime) -> Result<u32> {
+    if let Some(export_time) = config.header.as_ref().and_then(|h| h.export_time) {
+        return Ok(export_time);
+    }
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| NetflowError::Generation(format!("Failed to get system time: {}", e)))?;
+    Ok(u32::try_from(now.as_secs()).unwrap_or(u32::MAX))
+}
+
 /// Public wrapper for building template packets (used by template cache)
 pub fn build_template_packet_for_cache(
     export_time: u32,
     sequence_number: u32,
     observation_domain_id: u32,
-    templates: &[(u16, Vec<crate::config::schema::IPFixTemplateField>)],
+    templates: &[IPFixTemplate],
+    pad_sets: bool,
+    options_first: bool,
+    grouped: bool,
 ) -> Result<Vec<u8>> {
     build_template_packet(
         export_time,
         sequence_number,
         observation_domain_id,
         templates,
+        pad_sets,
+        options_first,
+        grouped,
     )
 }
 
+/// Build a template packet; `pad_sets` aligns each set to 4 bytes (RFC 7011 SHOULD)
+///
+/// With `options_first`, options templates precede regular templates (each
+/// group keeping its order). Each template gets its own set, unless
+/// `grouped` puts consecutive templates of the same kind in one set.
 fn build_template_packet(
     export_time: u32,
     sequence_number: u32,
     observation_domain_id: u32,
-    templates: &[(u16, Vec<crate::config::schema::IPFixTemplateField>)],
+    templates: &[IPFixTemplate],
+    pad_sets: bool,
+    options_first: bool,
+    grouped: bool,
 ) -> Result<Vec<u8>> {
+    let mut templates: Vec<&IPFixTemplate> = templates.iter().collect();
+    if options_first {
+        templates.sort_by_key(|template| template.scope_field_count == 0);
+    }
+
     let mut packet = Vec::new();
 
     // IPFIX Header (16 bytes)
@@ -169,38 +330,67 @@ fn build_template_packet(
     packet.extend_from_slice(&sequence_number.to_be_bytes());
     packet.extend_from_slice(&observation_domain_id.to_be_bytes());
 
-    // Template Set
-    for (template_id, fields) in templates {
-        let set_id = 2u16; // 2 indicates template set
+    // Template Sets (2) and Options Template Sets (3)
+    let sets: Vec<&[&IPFixTemplate]> = if grouped {
+        templates
+            .chunk_by(|a, b| (a.scope_field_count > 0) == (b.scope_field_count > 0))
+            .collect()
+    } else {
+        templates.chunks(1).collect()
+    };
+    for set in sets {
+        let is_options = set
+            .first()
+            .is_some_and(|template| template.scope_field_count > 0);
+        let set_id: u16 = if is_options { 3 } else { 2 };
         packet.extend_from_slice(&set_id.to_be_bytes());
 
         // Set length placeholder
         let set_length_pos = packet.len();
         packet.extend_from_slice(&0u16.to_be_bytes());
 
-        // Template ID and field count
-        packet.extend_from_slice(&template_id.to_be_bytes());
-        let field_count = u16::try_from(fields.len()).map_err(|_| {
-            NetflowError::Generation("Too many fields in template (max 65535)".to_string())
-        })?;
-        packet.extend_from_slice(&field_count.to_be_bytes());
-
-        // Template fields
-        for field in fields {
-            let field_type = field_name_to_id(&field.field_type).ok_or_else(|| {
-                NetflowError::Generation(format!("Unknown field type: {}", field.field_type))
+        for template in set {
+            // Template ID and field count (plus scope field count for options templates)
+            packet.extend_from_slice(&template.template_id.to_be_bytes());
+            let field_count = u16::try_from(template.fields.len()).map_err(|_| {
+                NetflowError::Generation("Too many fields in template (max 65535)".to_string())
             })?;
-            packet.extend_from_slice(&field_type.to_be_bytes());
-            packet.extend_from_slice(&field.field_length.to_be_bytes());
+            packet.extend_from_slice(&field_count.to_be_bytes());
+            if template.scope_field_count > 0 {
+                packet.extend_from_slice(&template.scope_field_count.to_be_bytes());
+            }
+
+            // Template fields (enterprise-specific fields set the high bit and carry their PEN)
+            for field in &template.fields {
+                let (field_type, enterprise_number) = field_name_to_spec(&field.field_type)
+                    .ok_or_else(|| {
+                        NetflowError::Generation(format!(
+                            "Unknown field type: {}",
+                            field.field_type
+                        ))
+                    })?;
+                match enterprise_number {
+                    Some(pen) => {
+                        packet.extend_from_slice(&(field_type | 0x8000).to_be_bytes());
+                        packet.extend_from_slice(&field.field_length.to_be_bytes());
+                        packet.extend_from_slice(&pen.to_be_bytes());
+                    }
+                    None => {
+                        packet.extend_from_slice(&field_type.to_be_bytes());
+                        packet.extend_from_slice(&field.field_length.to_be_bytes());
+                    }
+                }
+            }
         }
 
         // Add padding if needed (set length must be multiple of 4)
-        while packet
-            .len()
-            .checked_sub(set_length_pos)
-            .and_then(|v| v.checked_add(2))
-            .map(|v| v % 4 != 0)
-            .unwrap_or(false)
+        while pad_sets
+            && packet
+                .len()
+                .checked_sub(set_length_pos)
+                .and_then(|v| v.checked_add(2))
+                .map(|v| v % 4 != 0)
+                .unwrap_or(false)
         {
             packet.push(0);
         }
@@ -229,13 +419,90 @@ fn build_template_packet(
     Ok(packet)
 }
 
+/// Smallest record a template allows: fixed-length fields at full size and
+/// variable-length fields at their 1-octet length prefix
+fn min_record_length(template_fields: &[IPFixTemplateField]) -> usize {
+    template_fields
+        .iter()
+        .map(|field| match field.field_length {
+            VARIABLE_LENGTH => 1,
+            length => usize::from(length),
+        })
+        .fold(0, usize::saturating_add)
+}
+
+/// Serialize data records against a template, in the configured record order
+fn encode_records(
+    template_fields: &[crate::config::schema::IPFixTemplateField],
+    records: &[serde_yaml::Value],
+    options: &GeneratorOptions,
+) -> Result<Vec<u8>> {
+    let mut encoded = Vec::with_capacity(records.len());
+
+    for record in records {
+        let expanded = expand_as_path(record)?;
+        let record = expanded.as_ref().unwrap_or(record);
+        let mut record_bytes = Vec::new();
+        for field in template_fields {
+            let (field_type, enterprise_number) = field_name_to_spec(&field.field_type)
+                .ok_or_else(|| {
+                    NetflowError::Generation(format!("Unknown field type: {}", field.field_type))
+                })?;
+            let field_name = match enterprise_number {
+                Some(REVERSE_PEN) => format!("reverse_{}", ipfix_field_id_to_name(field_type)),
+                _ => ipfix_field_id_to_name(field_type).to_string(),
+            };
+
+            // Get field value from record or use zero
+            let value =
+                get_field_value(record, &field_name).unwrap_or(serde_yaml::Value::Number(0.into()));
+            let value = resolve_value(value, record)?;
+            let value = match enterprise_number {
+                None | Some(REVERSE_PEN) => resolve_ipfix_named_value(field_type, value),
+                Some(_) => value,
+            };
+            let value = match (field_type, field.field_length, value.as_u64()) {
+                // 1-octet tcpControlBits carries only the low-order flags (RFC 7125)
+                (6, 1, Some(bits)) if matches!(enterprise_number, None | Some(REVERSE_PEN)) => {
+                    serde_yaml::Value::Number((bits & 0xFF).into())
+                }
+                _ => value,
+            };
+            let value = match (options.export_lag, ipfix_timestamp_unit(field_type)) {
+                (Some(lag), Some(unit))
+                    if matches!(enterprise_number, None | Some(REVERSE_PEN)) =>
+                {
+                    lag_timestamp(value, unit, lag)
+                }
+                _ => value,
+            };
+
+            // Serialize the field value
+            let bytes = if FLOAT_FIELDS.contains(&field_type)
+                && matches!(enterprise_number, None | Some(REVERSE_PEN))
+            {
+                serialize_float_value(&value, field.field_length)
+            } else {
+                serialize_field_value(&value, field.field_length)
+            };
+            record_bytes.extend_from_slice(&bytes);
+        }
+        encoded.push(record_bytes);
+    }
+
+    apply_record_order(&mut encoded, options.record_order);
+
+    Ok(encoded.concat())
+}
+
 fn build_data_packet(
     export_time: u32,
     sequence_number: u32,
     observation_domain_id: u32,
     template_id: u16,
-    template_fields: &[crate::config::schema::IPFixTemplateField],
-    records: &[serde_yaml::Value],
+    record_bytes: &[u8],
+    min_record_length: usize,
+    pad_sets: bool,
 ) -> Result<Vec<u8>> {
     let mut packet = Vec::new();
 
@@ -257,33 +524,21 @@ fn build_data_packet(
     let set_length_pos = packet.len();
     packet.extend_from_slice(&0u16.to_be_bytes());
 
-    // Serialize each record
-    for record in records {
-        for field in template_fields {
-            let field_type = field_name_to_id(&field.field_type).ok_or_else(|| {
-                NetflowError::Generation(format!("Unknown field type: {}", field.field_type))
-            })?;
-            let field_name = ipfix_field_id_to_name(field_type);
-
-            // Get field value from record or use zero
-            let value =
-                get_field_value(record, field_name).unwrap_or(serde_yaml::Value::Number(0.into()));
+    // Encoded records
+    packet.extend_from_slice(record_bytes);
 
-            // Serialize the field value
-            let bytes = serialize_field_value(&value, field.field_length);
-            packet.extend_from_slice(&bytes);
-        }
-    }
-
-    // Add padding if needed (set length must be multiple of 4)
-    while packet
+    // Pad to a multiple of 4 only when the padding is shorter than any allowable
+    // record, so collectors cannot mistake it for extra records (RFC 7011 section 3.3.1)
+    let unpadded_length = packet
         .len()
         .checked_sub(set_length_pos)
         .and_then(|v| v.checked_add(2))
-        .map(|v| v % 4 != 0)
-        .unwrap_or(false)
-    {
-        packet.push(0);
+        .ok_or_else(|| NetflowError::Generation("Set length overflow".to_string()))?;
+    let padding = unpadded_length
+        .next_multiple_of(4)
+        .saturating_sub(unpadded_length);
+    if pad_sets && padding < min_record_length {
+        packet.resize(packet.len().saturating_add(padding), 0);
     }
 
     // Update set length
@@ -309,6 +564,113 @@ fn build_data_packet(
     Ok(packet)
 }
 
+/// Append padding sets until `packet` is at least `target` bytes long (--min-packet-size)
+///
+/// As with V9 `pad_to`, each padding set is a data set of `template`, which
+/// the message defines or uses, holding only zero padding shorter than one
+/// record: it decodes as a set with no records. The message may end up to
+/// 3 bytes past `target`, since a set header takes 4.
+pub fn pad_message(packet: &mut Vec<u8>, target: usize, template: &IPFixTemplate) -> Result<()> {
+    const SET_HEADER_LEN: usize = 4;
+
+    // Largest set whose padding stays shorter than one record
+    let max_set_len = min_record_length(&template.fields)
+        .saturating_sub(1)
+        .saturating_add(SET_HEADER_LEN)
+        .min(usize::from(u16::MAX));
+    while packet.len() < target {
+        let set_len = target
+            .saturating_sub(packet.len())
+            .clamp(SET_HEADER_LEN, max_set_len);
+        let length = u16::try_from(set_len)
+            .map_err(|_| NetflowError::Generation("Set length overflow".to_string()))?;
+        packet.extend_from_slice(&template.template_id.to_be_bytes());
+        packet.extend_from_slice(&length.to_be_bytes());
+        packet.resize(
+            packet
+                .len()
+                .saturating_add(set_len.saturating_sub(SET_HEADER_LEN)),
+            0,
+        );
+    }
+
+    let length = u16::try_from(packet.len())
+        .map_err(|_| NetflowError::Generation("Packet length exceeds u16::MAX".to_string()))?;
+    packet
+        .get_mut(2..4)
+        .ok_or_else(|| NetflowError::Generation("IPFIX packet too short".to_string()))?
+        .copy_from_slice(&length.to_be_bytes());
+    Ok(())
+}
+
+/// Merge the packets of one export into a single message (`combined_message`)
+///
+/// The first packet's header is kept with its length updated, and every
+/// packet's sets follow in order.
+fn combine_packets(packets: &[Vec<u8>]) -> Result<Vec<u8>> {
+    const HEADER_LEN: usize = 16;
+
+    let mut combined = packets.first().cloned().unwrap_or_default();
+    for packet in packets.iter().skip(1) {
+        combined.extend_from_slice(packet.get(HEADER_LEN..).unwrap_or_default());
+    }
+
+    let length = u16::try_from(combined.len()).map_err(|_| {
+        NetflowError::Generation("Combined message exceeds 65535 bytes".to_string())
+    })?;
+    combined
+        .get_mut(2..4)
+        .ok_or_else(|| NetflowError::Generation("IPFIX packet too short".to_string()))?
+        .copy_from_slice(&length.to_be_bytes());
+    Ok(combined)
+}
+
+/// Template fields for a flow, with RFC 5103 reverse fields appended when `biflow` is set
+///
+/// Reverse fields follow all forward fields, in the order of their forward counterparts.
+pub fn effective_template_fields(
+    config: &IPFixConfig,
+    fields: &[IPFixTemplateField],
+) -> Vec<IPFixTemplateField> {
+    let mut expanded = fields.to_vec();
+
+    if config.biflow {
+        for field in fields {
+            if BIFLOW_REVERSE_FIELDS.contains(&field.field_type.as_str()) {
+                expanded.push(IPFixTemplateField {
+                    field_type: reverse_field_name(&field.field_type),
+                    field_length: field.field_length,
+                });
+            }
+        }
+    }
+
+    expanded
+}
+
+/// Reverse IE name for a forward IE name (e.g. octetDeltaCount -> reverseOctetDeltaCount)
+fn reverse_field_name(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => format!("reverse{}{}", first.to_ascii_uppercase(), chars.as_str()),
+        None => String::new(),
+    }
+}
+
+/// Map a field name to its IE ID and optional enterprise number
+///
+/// `reverse`-prefixed names resolve to the forward IE under the RFC 5103 reverse PEN.
+fn field_name_to_spec(name: &str) -> Option<(u16, Option<u32>)> {
+    if let Some(forward) = name.strip_prefix("reverse") {
+        let mut chars = forward.chars();
+        let first = chars.next()?;
+        let forward_name = format!("{}{}", first.to_ascii_lowercase(), chars.as_str());
+        return field_name_to_id(&forward_name).map(|id| (id, Some(REVERSE_PEN)));
+    }
+
+    field_name_to_id(name).map(|id| (id, None))
+}
+
 /// Map human-readable field names to IPFIX field type IDs (IANA Information Elements)
 fn field_name_to_id(name: &str) -> Option<u16> {
     match name {
@@ -332,6 +694,677 @@ fn field_name_to_id(name: &str) -> Option<u16> {
         "bgpNextHopIPv4Address" => Some(18),
         "flowEndSysUpTime" => Some(21),
         "flowStartSysUpTime" => Some(22),
+        "postOctetDeltaCount" => Some(23),
+        "postPacketDeltaCount" => Some(24),
+        "minimumTTL" => Some(52),
+        "maximumTTL" => Some(53),
+        "flowDirection" => Some(61),
+        "interfaceName" => Some(82),
+        "interfaceDescription" => Some(83),
+        "samplerName" => Some(84),
+        "bgpNextAdjacentAsNumber" => Some(128),
+        "bgpPrevAdjacentAsNumber" => Some(129),
+        "exporterIPv4Address" => Some(130),
+        "flowEndReason" => Some(136),
+        "meteringProcessId" => Some(143),
+        "exportingProcessId" => Some(144),
+        "observationDomainId" => Some(149),
+        "flowStartSeconds" => Some(150),
+        "flowEndSeconds" => Some(151),
+        "flowStartMilliseconds" => Some(152),
+        "flowEndMilliseconds" => Some(153),
+        "postNATSourceIPv4Address" => Some(225),
+        "postNATDestinationIPv4Address" => Some(226),
+        "postNAPTSourceTransportPort" => Some(227),
+        "postNAPTDestinationTransportPort" => Some(228),
+        "firewallEvent" => Some(233),
+        "selectorId" => Some(302),
+        "selectorAlgorithm" => Some(304),
+        "samplingPacketInterval" => Some(305),
+        "samplingPacketSpace" => Some(306),
+        "samplingProbability" => Some(311),
+        "observationTimeMilliseconds" => Some(323),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+    use crate::config::schema::FlowConfig;
+    use netflow_parser::variable_versions::ipfix::FlowSetBody;
+    use netflow_parser::{NetflowPacket, NetflowParser};
+
+    fn ipfix_config(yaml: &str) -> IPFixConfig {
+        match parse_yaml_str(yaml).unwrap().flows.remove(0) {
+            FlowConfig::IPFix(config) => config,
+            _ => panic!("expected an IPFIX flow"),
+        }
+    }
+
+    #[test]
+    fn test_variable_length_interface_name() {
+        let config = ipfix_config(
+            r#"
+flows:
+  - version: ipfix
+    flowsets:
+      - type: template
+        template_id: 310
+        fields:
+          - field_type: ingressInterface
+            field_length: 4
+          - field_type: interfaceName
+            field_length: 65535
+      - type: data
+        template_id: 310
+        records:
+          - ingress_interface: 3
+            interface_name: "eth0"
+"#,
+        );
+
+        let (packets, _) =
+            build_ipfix_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+        let data = &packets[1];
+        // Header (16) + set header (4) + ingressInterface (4), then the length-prefixed name
+        assert_eq!(&data[24..29], &[4, b'e', b't', b'h', b'0']);
+
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets.concat());
+        assert!(parsed.error.is_none());
+        assert_eq!(parsed.packets.len(), 2);
+    }
+
+    #[test]
+    fn test_biflow_reverse_fields() {
+        let config = ipfix_config(
+            r#"
+flows:
+  - version: ipfix
+    biflow: true
+    flowsets:
+      - type: template
+        template_id: 320
+        fields:
+          - field_type: sourceIPv4Address
+            field_length: 4
+          - field_type: octetDeltaCount
+            field_length: 4
+      - type: data
+        template_id: 320
+        records:
+          - source_ipv4_address: "10.0.0.1"
+            octet_delta_count: 1000
+            reverse_octet_delta_count: 250
+"#,
+        );
+
+        let (packets, _) =
+            build_ipfix_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+
+        // Template: header (16) + set header (4) + template header (4) + 2 forward specs (8)
+        let template = &packets[0];
+        assert_eq!(&template[22..24], &3u16.to_be_bytes());
+        assert_eq!(&template[32..34], &(1u16 | 0x8000).to_be_bytes());
+        assert_eq!(&template[36..40], &REVERSE_PEN.to_be_bytes());
+
+        let data = &packets[1];
+        assert_eq!(&data[24..28], &1000u32.to_be_bytes());
+        assert_eq!(&data[28..32], &250u32.to_be_bytes());
+
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets.concat());
+        assert!(parsed.error.is_none());
+        assert_eq!(parsed.packets.len(), 2);
+    }
+
+    #[test]
+    fn test_tcp_control_bits_width() {
+        let yaml = r#"
+flows:
+  - version: ipfix
+    flowsets:
+      - type: template
+        template_id: 330
+        fields:
+          - field_type: tcpControlBits
+            field_length: 2
+      - type: data
+        template_id: 330
+        records:
+          - tcp_control_bits: "NS|ECE|SYN"
+"#;
+        let (packets, _) =
+            build_ipfix_packets(ipfix_config(yaml), None, true, &GeneratorOptions::default())
+                .unwrap();
+        assert_eq!(&packets[1][20..22], &0x142u16.to_be_bytes());
+
+        // The 1-octet form drops the high-order flags
+        let yaml = yaml.replace("field_length: 2", "field_length: 1");
+        let (packets, _) = build_ipfix_packets(
+            ipfix_config(&yaml),
+            None,
+            true,
+            &GeneratorOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(packets[1][20], 0x42);
+    }
+
+    #[test]
+    fn test_post_nat_fields() {
+        let config = ipfix_config(
+            r#"
+flows:
+  - version: ipfix
+    flowsets:
+      - type: template
+        template_id: 340
+        fields:
+          - field_type: postNATSourceIPv4Address
+            field_length: 4
+          - field_type: postNATDestinationIPv4Address
+            field_length: 4
+          - field_type: postNAPTSourceTransportPort
+            field_length: 2
+          - field_type: postNAPTDestinationTransportPort
+            field_length: 2
+      - type: data
+        template_id: 340
+        records:
+          - post_nat_source_ipv4_address: "198.51.100.7"
+            post_nat_destination_ipv4_address: "203.0.113.80"
+            post_napt_source_transport_port: 40001
+            post_napt_destination_transport_port: 443
+"#,
+        );
+        let (packets, _) =
+            build_ipfix_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+
+        // Template set: IDs 225-228 follow the set and template record headers
+        let ids: Vec<u16> = packets[0][24..40]
+            .chunks(4)
+            .map(|spec| u16::from_be_bytes([spec[0], spec[1]]))
+            .collect();
+        assert_eq!(ids, vec![225, 226, 227, 228]);
+
+        assert_eq!(&packets[1][20..24], &[198, 51, 100, 7]);
+        assert_eq!(&packets[1][24..28], &[203, 0, 113, 80]);
+        assert_eq!(&packets[1][28..30], &40001u16.to_be_bytes());
+        assert_eq!(&packets[1][30..32], &443u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_per_packet_export_time() {
+        let yaml = r#"
+flows:
+  - version: ipfix
+    flowsets:
+      - type: template
+        template_id: 350
+        fields:
+          - field_type: octetDeltaCount
+            field_length: 4
+      - type: data
+        template_id: 350
+        records:
+          - octet_delta_count: 1
+      - type: data
+        template_id: 350
+        records:
+          - octet_delta_count: 2
+"#;
+        let export_times = |per_packet_export_time| {
+            // Each reading of the clock is one second after the last
+            let mut now = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+            let mut clock = || {
+                now += std::time::Duration::from_secs(1);
+                now
+            };
+            let options = GeneratorOptions {
+                per_packet_export_time,
+                ..GeneratorOptions::default()
+            };
+            let (packets, _) = build_ipfix_packets_with_clock(
+                ipfix_config(yaml),
+                None,
+                true,
+                &[],
+                &options,
+                &mut clock,
+            )
+            .unwrap();
+            packets
+                .iter()
+                .map(|packet| u32::from_be_bytes(packet[4..8].try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            export_times(false),
+            vec![1_700_000_001, 1_700_000_001, 1_700_000_001]
+        );
+        assert_eq!(
+            export_times(true),
+            vec![1_700_000_001, 1_700_000_002, 1_700_000_003]
+        );
+
+        // A configured export_time stays fixed
+        let pinned = yaml.replace(
+            "  - version: ipfix\n",
+            "  - version: ipfix\n    header:\n      export_time: 5\n",
+        );
+        let options = GeneratorOptions {
+            per_packet_export_time: true,
+            ..GeneratorOptions::default()
+        };
+        let (packets, _) =
+            build_ipfix_packets(ipfix_config(&pinned), None, true, &options).unwrap();
+        assert!(
+            packets
+                .iter()
+                .all(|packet| packet[4..8] == 5u32.to_be_bytes())
+        );
+    }
+
+    #[test]
+    fn test_combined_message() {
+        let config = ipfix_config(
+            r#"
+flows:
+  - version: ipfix
+    combined_message: true
+    flowsets:
+      - type: template
+        template_id: 340
+        fields:
+          - field_type: octetDeltaCount
+            field_length: 4
+      - type: options_template
+        template_id: 341
+        scope_fields:
+          - field_type: exportingProcessId
+            field_length: 4
+        fields:
+          - field_type: exporterIPv4Address
+            field_length: 4
+      - type: data
+        template_id: 340
+        records:
+          - octet_delta_count: 1000
+      - type: data
+        template_id: 341
+        records:
+          - exporting_process_id: 1
+            exporter_ipv4_address: "192.0.2.1"
+"#,
+        );
+
+        let (packets, next_seq) =
+            build_ipfix_packets(config, Some(3), false, &GeneratorOptions::default()).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(next_seq, 5);
+        assert_eq!(
+            &packets[0][2..4],
+            &u16::try_from(packets[0].len()).unwrap().to_be_bytes()
+        );
+        assert_eq!(&packets[0][8..12], &3u32.to_be_bytes());
+
+        // Template set, options template set, data set, options data set
+        let bodies = parse_single(&packets);
+        assert_eq!(bodies.len(), 4);
+        assert!(matches!(bodies[0], FlowSetBody::Template(_)));
+        assert!(matches!(bodies[1], FlowSetBody::OptionsTemplate(_)));
+        assert!(matches!(bodies[2], FlowSetBody::Data(_)));
+        assert!(matches!(bodies[3], FlowSetBody::OptionsData(_)));
+    }
+
+    #[test]
+    fn test_min_packet_size_padding() {
+        let yaml = r#"
+flows:
+  - version: ipfix
+    flowsets:
+      - type: template
+        template_id: 360
+        fields:
+          - field_type: sourceIPv4Address
+            field_length: 4
+          - field_type: octetDeltaCount
+            field_length: 8
+      - type: data
+        template_id: 360
+        records:
+          - source_ipv4_address: "10.0.0.1"
+            octet_delta_count: 100
+"#;
+        let options = GeneratorOptions {
+            min_packet_size: Some(101),
+            ..GeneratorOptions::default()
+        };
+        let (packets, next_seq) =
+            build_ipfix_packets(ipfix_config(yaml), None, true, &options).unwrap();
+        assert_eq!(next_seq, 1);
+        for packet in &packets {
+            assert!((101..=104).contains(&packet.len()));
+            assert_eq!(
+                usize::from(u16::from_be_bytes([packet[2], packet[3]])),
+                packet.len()
+            );
+        }
+
+        // The padding sets decode without records
+        let records: usize = parse_single(&packets)
+            .iter()
+            .map(|body| match body {
+                FlowSetBody::Data(data) => data.fields.len(),
+                _ => 0,
+            })
+            .sum();
+        assert_eq!(records, 1);
+    }
+
+    fn template_fields(names: &[&str]) -> Vec<IPFixTemplateField> {
+        names
+            .iter()
+            .map(|name| IPFixTemplateField {
+                field_type: name.to_string(),
+                field_length: 4,
+            })
+            .collect()
+    }
+
+    fn parse_single(packets: &[Vec<u8>]) -> Vec<FlowSetBody> {
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets.concat());
+        assert!(parsed.error.is_none());
+        match parsed.packets.last() {
+            Some(NetflowPacket::IPFix(ipfix)) => {
+                ipfix.flowsets.iter().map(|fs| fs.body.clone()).collect()
+            }
+            other => panic!("expected an IPFIX packet, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_template_set_lengths() {
+        let field_sets: [&[&str]; 4] = [
+            &["octetDeltaCount"],
+            &["octetDeltaCount", "packetDeltaCount"],
+            &["octetDeltaCount", "packetDeltaCount", "ingressInterface"],
+            &[
+                "octetDeltaCount",
+                "reverseOctetDeltaCount",
+                "egressInterface",
+            ],
+        ];
+
+        for names in field_sets {
+            let templates = vec![IPFixTemplate {
+                template_id: 400,
+                scope_field_count: 0,
+                fields: template_fields(names),
+            }];
+            let packet = build_template_packet(0, 0, 1, &templates, true, false, false).unwrap();
+
+            // Message and set lengths cover the packet exactly, with no trailing bytes
+            assert_eq!(
+                usize::from(u16::from_be_bytes([packet[2], packet[3]])),
+                packet.len()
+            );
+            let set_length = usize::from(u16::from_be_bytes([packet[18], packet[19]]));
+            assert_eq!(set_length, packet.len() - 16);
+            assert_eq!(set_length % 4, 0);
+
+            match parse_single(&[packet]).as_slice() {
+                [FlowSetBody::Template(template)] => {
+                    assert_eq!(template.fields.len(), names.len());
+                }
+                other => panic!("expected a single template set, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_grouped_template_sets() {
+        let template = |template_id, scope_field_count, names: &[&str]| IPFixTemplate {
+            template_id,
+            scope_field_count,
+            fields: template_fields(names),
+        };
+        let templates = vec![
+            template(400, 0, &["octetDeltaCount"]),
+            template(401, 0, &["packetDeltaCount", "ingressInterface"]),
+            template(402, 1, &["observationDomainId", "samplingPacketInterval"]),
+        ];
+        let packet = build_template_packet(0, 0, 1, &templates, true, false, true).unwrap();
+
+        // One template set for 400 and 401, then one options template set
+        let set_length = usize::from(u16::from_be_bytes([packet[18], packet[19]]));
+        assert_eq!(&packet[16..18], &2u16.to_be_bytes());
+        assert_eq!(set_length, 4 + (4 + 4) + (4 + 8));
+        assert_eq!(
+            &packet[16 + set_length..18 + set_length],
+            &3u16.to_be_bytes()
+        );
+
+        match parse_single(&[packet]).as_slice() {
+            [
+                FlowSetBody::Templates(templates),
+                FlowSetBody::OptionsTemplate(options),
+            ] => {
+                assert_eq!(templates.len(), 2);
+                assert_eq!(options.template_id, 402);
+            }
+            other => panic!(
+                "expected a template set and an options set, got {:?}",
+                other
+            ),
+        }
+    }
+
+    #[test]
+    fn test_data_set_padding_shorter_than_record() {
+        // (template fields, expected set length): 1-byte records can't be padded
+        // without the padding parsing as records; 3-byte records take 1 byte of padding
+        let cases: [(&[(&str, u16)], usize); 2] = [
+            (&[("protocolIdentifier", 1)], 5),
+            (&[("sourceTransportPort", 2), ("protocolIdentifier", 1)], 8),
+        ];
+
+        for (fields, expected_set_length) in cases {
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|(name, length)| IPFixTemplateField {
+                    field_type: name.to_string(),
+                    field_length: *length,
+                })
+                .collect();
+            let template = IPFixTemplate {
+                template_id: 401,
+                scope_field_count: 0,
+                fields: fields.clone(),
+            };
+            let template = build_template_packet(0, 0, 1, &[template], true, false, false).unwrap();
+            let record =
+                serde_yaml::from_str("{source_transport_port: 53, protocol_identifier: 17}")
+                    .unwrap();
+            let records = encode_records(&fields, &[record], &GeneratorOptions::default()).unwrap();
+            let data = build_data_packet(0, 0, 1, 401, &records, min_record_length(&fields), true)
+                .unwrap();
+
+            let set_length = usize::from(u16::from_be_bytes([data[18], data[19]]));
+            assert_eq!(set_length, expected_set_length);
+            assert_eq!(data.len(), 16 + expected_set_length);
+
+            match parse_single(&[template, data]).as_slice() {
+                [FlowSetBody::Data(data)] => assert_eq!(data.fields.len(), 1),
+                other => panic!("expected a single data set, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_padding() {
+        let fields = vec![
+            IPFixTemplateField {
+                field_type: "sourceTransportPort".to_string(),
+                field_length: 2,
+            },
+            IPFixTemplateField {
+                field_type: "protocolIdentifier".to_string(),
+                field_length: 1,
+            },
+        ];
+        let template = IPFixTemplate {
+            template_id: 402,
+            scope_field_count: 0,
+            fields: fields.clone(),
+        };
+        let template = build_template_packet(0, 0, 1, &[template], false, false, false).unwrap();
+        let record =
+            serde_yaml::from_str("{source_transport_port: 53, protocol_identifier: 17}").unwrap();
+        let records = encode_records(&fields, &[record], &GeneratorOptions::default()).unwrap();
+        let data =
+            build_data_packet(0, 0, 1, 402, &records, min_record_length(&fields), false).unwrap();
+
+        // Set header (4) + one 3-byte record, with accurate set and message lengths
+        assert_eq!(u16::from_be_bytes([data[18], data[19]]), 7);
+        assert_eq!(
+            usize::from(u16::from_be_bytes([data[2], data[3]])),
+            data.len()
+        );
+        assert_eq!(data.len(), 23);
+
+        match parse_single(&[template, data]).as_slice() {
+            [FlowSetBody::Data(data)] => assert_eq!(data.fields.len(), 1),
+            other => panic!("expected a single data set, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_options_template_process_ids() {
+        let config = ipfix_config(
+            r#"
+flows:
+  - version: ipfix
+    header:
+      observation_domain_id: 7
+    flowsets:
+      - type: options_template
+        template_id: 512
+        scope_fields:
+          - field_type: observationDomainId
+            field_length: 4
+        fields:
+          - field_type: meteringProcessId
+            field_length: 4
+          - field_type: exportingProcessId
+            field_length: 4
+      - type: data
+        template_id: 512
+        records:
+          - observation_domain_id: 7
+            metering_process_id: 1001
+            exporting_process_id: 2002
+"#,
+        );
+
+        let (packets, _) =
+            build_ipfix_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+        // Options Template Set ID 3, with a scope field count of 1
+        assert_eq!(&packets[0][16..18], &3u16.to_be_bytes());
+        assert_eq!(&packets[0][22..26], &[0, 3, 0, 1]);
+        // Each IE is a 4-byte integer
+        assert_eq!(
+            &packets[1][20..32],
+            &[0, 0, 0, 7, 0, 0, 3, 233, 0, 0, 7, 210]
+        );
+
+        match parse_single(&packets).as_slice() {
+            [FlowSetBody::OptionsData(data)] => assert_eq!(data.fields.len(), 1),
+            other => panic!("expected a single options data set, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exporter_ipv4_address_option() {
+        let config = ipfix_config(
+            r#"
+flows:
+  - version: ipfix
+    flowsets:
+      - type: options_template
+        template_id: 513
+        scope_fields:
+          - field_type: observationDomainId
+            field_length: 4
+        fields:
+          - field_type: exporterIPv4Address
+            field_length: 4
+      - type: data
+        template_id: 513
+        records:
+          - observation_domain_id: 1
+            exporter_ipv4_address: "192.0.2.10"
+"#,
+        );
+
+        let (packets, _) =
+            build_ipfix_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+        // IE 130 in the template, and the address string encoded as 4 octets
+        assert_eq!(&packets[0][30..34], &[0, 130, 0, 4]);
+        assert_eq!(&packets[1][24..28], &[192, 0, 2, 10]);
+        assert!(matches!(
+            parse_single(&packets).as_slice(),
+            [FlowSetBody::OptionsData(_)]
+        ));
+    }
+
+    #[test]
+    fn test_sampling_options_fields() {
+        let config = ipfix_config(
+            r#"
+flows:
+  - version: ipfix
+    flowsets:
+      - type: options_template
+        template_id: 514
+        scope_fields:
+          - field_type: selectorId
+            field_length: 8
+        fields:
+          - field_type: selectorAlgorithm
+            field_length: 2
+          - field_type: samplingProbability
+            field_length: 8
+          - field_type: samplerName
+            field_length: 16
+      - type: data
+        template_id: 514
+        records:
+          - selector_id: 9
+            selector_algorithm: 3
+            sampling_probability: 0.01
+            sampler_name: "edge-1in100"
+"#,
+        );
+
+        let (packets, _) =
+            build_ipfix_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+        // Scope IE 302, then IE 311 after selectorAlgorithm (304)
+        assert_eq!(&packets[0][26..30], &[1, 46, 0, 8]);
+        assert_eq!(&packets[0][34..38], &[1, 55, 0, 8]);
+        let data = &packets[1];
+        assert_eq!(&data[20..28], &9u64.to_be_bytes());
+        // samplingProbability is an IEEE 754 float64, not an integer
+        assert_eq!(&data[30..38], &0.01f64.to_be_bytes());
+        assert_eq!(&data[38..49], b"edge-1in100");
+        assert!(matches!(
+            parse_single(&packets).as_slice(),
+            [FlowSetBody::OptionsData(_)]
+        ));
+    }
+}
@@ -0,0 +1,431 @@
+//! Per-export evolution of flow records in continuous mode
+
+use crate::config::schema::{CounterMode, FlowConfig, IPFixFlowSet, IPFixHeader, V9FlowSet};
+use crate::error::Result;
+use crate::generator::address_pools::AddressPools;
+use crate::generator::dscp::DscpDistribution;
+use crate::generator::flow_sizes::FlowSizeDistribution;
+use crate::generator::flow_table::FlowTable;
+use crate::generator::options::GeneratorOptions;
+use crate::generator::protocols::ProtocolDistribution;
+use crate::generator::providers::FieldProviders;
+use crate::generator::unique_flows::{self, KeyRange, UniqueFlows};
+use serde_yaml::Value;
+use std::time::Duration;
+
+/// V9 record counters turned into running totals by `counter_mode: cumulative`
+pub(crate) const V9_COUNTERS: &[&str] = &["in_bytes", "in_pkts", "out_bytes", "out_pkts"];
+/// IPFIX record counters turned into running totals by `counter_mode: cumulative`
+pub(crate) const IPFIX_COUNTERS: &[&str] = &[
+    "octet_delta_count",
+    "packet_delta_count",
+    "post_octet_delta_count",
+    "post_packet_delta_count",
+];
+
+/// Per-flow generator state carried from one export to the next
+#[derive(Debug)]
+pub struct FlowState {
+    flow_table: Option<FlowTable>,
+    unique_flows: Option<UniqueFlows>,
+    field_providers: Option<FieldProviders>,
+    address_pools: Option<AddressPools>,
+    dscp_distribution: Option<DscpDistribution>,
+    protocol_distribution: Option<ProtocolDistribution>,
+    flow_size_distribution: Option<FlowSizeDistribution>,
+    /// Exports reported so far by a flow with cumulative counters and no
+    /// flow table, which keeps totals per simulated flow instead
+    cumulative_exports: Option<u64>,
+}
+
+impl FlowState {
+    /// State for the first export of each of `flows`
+    ///
+    /// The `--unique-flows` budget is split over the data flowsets of all
+    /// `flows`, failing when they cannot produce that many distinct flows.
+    pub fn for_flows(flows: &[FlowConfig], options: &GeneratorOptions) -> Result<Vec<Self>> {
+        let mut key_ranges = match options.unique_flows {
+            Some(total) => Some(unique_flows::key_ranges(flows, total)?.into_iter()),
+            None => None,
+        };
+        Ok(flows
+            .iter()
+            .map(|flow| Self::new(flow, options, key_ranges.as_mut().and_then(Iterator::next)))
+            .collect())
+    }
+
+    /// State for a flow's first export
+    ///
+    /// With `--unique-flows`, the flow's address pools supply the unique
+    /// flows' addresses instead of being drawn from on each export.
+    fn new(
+        flow: &FlowConfig,
+        options: &GeneratorOptions,
+        key_ranges: Option<Vec<KeyRange>>,
+    ) -> Self {
+        let cumulative = flow.counter_mode() == CounterMode::Cumulative;
+        let unique_flows = key_ranges.map(|ranges| {
+            UniqueFlows::new(ranges, options.unique_flows_churn, flow.address_pools())
+        });
+        Self {
+            flow_table: flow
+                .flow_table()
+                .map(|config| FlowTable::new(config, cumulative)),
+            address_pools: flow
+                .address_pools()
+                .filter(|_| unique_flows.is_none())
+                .map(AddressPools::new),
+            unique_flows,
+            field_providers: FieldProviders::new(flow),
+            dscp_distribution: flow.dscp_distribution().map(DscpDistribution::new),
+            protocol_distribution: flow.protocol_distribution().map(ProtocolDistribution::new),
+            flow_size_distribution: flow.flow_size_distribution().map(FlowSizeDistribution::new),
+            cumulative_exports: (cumulative && flow.flow_table().is_none()).then_some(0),
+        }
+    }
+
+    /// Evolve a copy of the configured flow for its next export
+    pub fn apply(&mut self, flow: &mut FlowConfig) {
+        // Simulated flows draw provider values once, when they start
+        if let Some(table) = &mut self.flow_table {
+            table.apply(flow, self.field_providers.as_mut());
+        }
+        if let Some(flows) = &mut self.unique_flows {
+            flows.apply(flow, self.field_providers.as_mut());
+        }
+        // Configured records draw new values every export
+        if let Some(providers) = &mut self.field_providers {
+            providers.apply(flow);
+        }
+        if let Some(pools) = &mut self.address_pools {
+            pools.apply(flow);
+        }
+        if let Some(distribution) = &mut self.dscp_distribution {
+            distribution.apply(flow);
+        }
+        if let Some(distribution) = &mut self.protocol_distribution {
+            distribution.apply(flow);
+        }
+        // After the protocol draw, which seeds derived byte counts
+        if let Some(distribution) = &mut self.flow_size_distribution {
+            distribution.apply(flow);
+        }
+        if let Some(exports) = &mut self.cumulative_exports {
+            *exports = exports.saturating_add(1);
+            accumulate_counters(flow, *exports);
+        }
+    }
+}
+
+/// Scale every record's byte and packet counters by `exports`, the running
+/// total after that many exports of the configured per-export increment
+fn accumulate_counters(flow: &mut FlowConfig, exports: u64) {
+    let factor = u32::try_from(exports).unwrap_or(u32::MAX);
+    match flow {
+        FlowConfig::V5(config) => {
+            for record in &mut config.flowsets {
+                record.d_pkts = record.d_pkts.saturating_mul(factor);
+                record.d_octets = record.d_octets.map(|octets| octets.saturating_mul(factor));
+            }
+        }
+        FlowConfig::V7(config) => {
+            for record in &mut config.flowsets {
+                record.d_pkts = record.d_pkts.saturating_mul(factor);
+                record.d_octets = record.d_octets.map(|octets| octets.saturating_mul(factor));
+            }
+        }
+        FlowConfig::V9(config) => {
+            for flowset in &mut config.flowsets {
+                if let V9FlowSet::Data { records, .. } = flowset {
+                    scale_record_fields(records, V9_COUNTERS, exports);
+                }
+            }
+        }
+        FlowConfig::IPFix(config) => {
+            for flowset in &mut config.flowsets {
+                if let IPFixFlowSet::Data { records, .. } = flowset {
+                    scale_record_fields(records, IPFIX_COUNTERS, exports);
+                }
+            }
+        }
+    }
+}
+
+fn scale_record_fields(records: &mut [Value], fields: &[&str], factor: u64) {
+    for record in records {
+        let Value::Mapping(map) = record else {
+            continue;
+        };
+        for &field in fields {
+            if let Some(count) = map.get(field).and_then(Value::as_u64) {
+                map.insert(field.into(), count.saturating_mul(factor).into());
+            }
+        }
+    }
+}
+
+/// Set the TCP flags of every record to the phase for the given export
+///
+/// Flows with a `tcp_flag_sequence` use entry `export_index % len`, so a
+/// sequence like `[0x02, 0x12, 0x18, 0x11]` tells a SYN, SYN-ACK, PSH-ACK,
+/// FIN-ACK story over successive exports. Flows without one are unchanged.
+pub fn apply_tcp_flag_phase(flow: &mut FlowConfig, export_index: usize) {
+    let Some(flags) = flow.tcp_flag_sequence().and_then(|sequence| {
+        export_index
+            .checked_rem(sequence.len())
+            .and_then(|phase| sequence.get(phase))
+            .copied()
+    }) else {
+        return;
+    };
+
+    match flow {
+        FlowConfig::V5(config) => {
+            for record in &mut config.flowsets {
+                record.tcp_flags = flags;
+            }
+        }
+        FlowConfig::V7(config) => {
+            for record in &mut config.flowsets {
+                record.tcp_flags = flags;
+            }
+        }
+        FlowConfig::V9(config) => {
+            for flowset in &mut config.flowsets {
+                if let V9FlowSet::Data { records, .. } = flowset {
+                    set_record_field(records, "tcp_flags", flags);
+                }
+            }
+        }
+        FlowConfig::IPFix(config) => {
+            for flowset in &mut config.flowsets {
+                if let IPFixFlowSet::Data { records, .. } = flowset {
+                    set_record_field(records, "tcp_control_bits", flags);
+                }
+            }
+        }
+    }
+}
+
+/// Move a `domain_rotation` flow to its observation domain at `elapsed` into the run
+///
+/// Each domain is used for the rotation's `interval` seconds in turn.
+/// Returns the new domain when the flow moved, `None` when it stayed put
+/// or has no rotation.
+pub fn rotate_observation_domain(flow: &mut FlowConfig, elapsed: Duration) -> Option<u32> {
+    let FlowConfig::IPFix(config) = flow else {
+        return None;
+    };
+    let rotation = config.domain_rotation.as_ref()?;
+    let slot = elapsed.as_secs().checked_div(rotation.interval)?;
+    let index = slot.checked_rem(u64::try_from(rotation.domains.len()).ok()?)?;
+    let domain = *rotation.domains.get(usize::try_from(index).ok()?)?;
+
+    let header = config.header.get_or_insert(IPFixHeader {
+        export_time: None,
+        sequence_number: None,
+        observation_domain_id: None,
+    });
+    if header.observation_domain_id == Some(domain) {
+        return None;
+    }
+    header.observation_domain_id = Some(domain);
+    Some(domain)
+}
+
+fn set_record_field(records: &mut [serde_yaml::Value], field_name: &str, value: u8) {
+    for record in records {
+        if let serde_yaml::Value::Mapping(map) = record {
+            map.insert(field_name.into(), value.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+    use crate::generator::exported_records;
+
+    #[test]
+    fn test_apply_tcp_flag_phase_cycles() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: ipfix
+    tcp_flag_sequence: [2, 18, 24, 17]
+    flowsets:
+      - type: data
+        template_id: 300
+        records:
+          - tcp_control_bits: 0
+"#,
+        )
+        .unwrap();
+
+        let flags_at = |export_index| {
+            let mut flow = config.flows[0].clone();
+            apply_tcp_flag_phase(&mut flow, export_index);
+            match flow {
+                FlowConfig::IPFix(ipfix) => match &ipfix.flowsets[0] {
+                    IPFixFlowSet::Data { records, .. } => records[0]["tcp_control_bits"].as_u64(),
+                    _ => None,
+                },
+                _ => None,
+            }
+        };
+
+        assert_eq!(flags_at(0), Some(2));
+        assert_eq!(flags_at(1), Some(18));
+        assert_eq!(flags_at(3), Some(17));
+        assert_eq!(flags_at(4), Some(2));
+    }
+
+    #[test]
+    fn test_rotate_observation_domain() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: ipfix
+    domain_rotation:
+      domains: [10, 20, 30]
+      interval: 60
+    flowsets: []
+  - version: v9
+    flowsets: []
+"#,
+        )
+        .unwrap();
+        let mut flow = config.flows[0].clone();
+        let at = Duration::from_secs;
+
+        assert_eq!(rotate_observation_domain(&mut flow, at(0)), Some(10));
+        assert_eq!(rotate_observation_domain(&mut flow, at(59)), None);
+        assert_eq!(rotate_observation_domain(&mut flow, at(60)), Some(20));
+        assert_eq!(rotate_observation_domain(&mut flow, at(150)), Some(30));
+        // Wraps around to the first domain
+        assert_eq!(rotate_observation_domain(&mut flow, at(185)), Some(10));
+        let FlowConfig::IPFix(ipfix) = &flow else {
+            panic!("expected an IPFIX flow");
+        };
+        assert_eq!(
+            ipfix.header.as_ref().unwrap().observation_domain_id,
+            Some(10)
+        );
+
+        let mut v9 = config.flows[1].clone();
+        assert_eq!(rotate_observation_domain(&mut v9, at(60)), None);
+    }
+
+    #[test]
+    fn test_cumulative_counters() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    counter_mode: cumulative
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - in_bytes: 1500
+            in_pkts: 3
+            protocol: 6
+  - version: v9
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - in_bytes: 1500
+            in_pkts: 3
+            protocol: 6
+"#,
+        )
+        .unwrap();
+
+        // (in_bytes, in_pkts, protocol) over three exports
+        let exports = |flow: &FlowConfig| {
+            let mut state = FlowState::new(flow, &GeneratorOptions::default(), None);
+            exported_records(flow, 3, |flow| state.apply(flow))
+                .iter()
+                .map(|records| {
+                    let field = |name: &str| records[0][name].as_u64().unwrap();
+                    (field("in_bytes"), field("in_pkts"), field("protocol"))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            exports(&config.flows[0]),
+            vec![(1500, 3, 6), (3000, 6, 6), (4500, 9, 6)]
+        );
+        assert_eq!(exports(&config.flows[1]), vec![(1500, 3, 6); 3]);
+    }
+
+    #[test]
+    fn test_simulated_flows_keep_provider_values() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    flow_table:
+      size: 4
+      seed: 3
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - src_addr: "10.0.0.1"
+            dst_addr: "10.1.0.1"
+            dst_port: "{{ ephemeral_port }}"
+  - version: v9
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - src_addr: "10.0.0.1"
+            src_port: "{{ ephemeral_port }}"
+            dst_port: "{{ ephemeral_port }}"
+"#,
+        )
+        .unwrap();
+        let options = GeneratorOptions {
+            unique_flows: Some(4),
+            ..GeneratorOptions::default()
+        };
+
+        // (src_port, dst_port) of each record over two exports
+        let exports = |flow: &FlowConfig, mut state: FlowState| {
+            exported_records(flow, 2, |flow| state.apply(flow))
+                .iter()
+                .map(|records| {
+                    records
+                        .iter()
+                        .map(|record| {
+                            let field = |name: &str| record[name].as_u64().unwrap();
+                            (field("src_port"), field("dst_port"))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Table entries draw their provider values once and keep them
+        let table = &config.flows[0];
+        let table_exports = exports(table, FlowState::new(table, &options, None));
+        assert_eq!(table_exports[0], table_exports[1]);
+        assert!(
+            table_exports[0]
+                .iter()
+                .any(|(_, port)| *port != table_exports[0][0].1)
+        );
+
+        // So do unique flows, whose key fields are never overwritten
+        let unique = &config.flows[1];
+        let mut states = FlowState::for_flows(std::slice::from_ref(unique), &options).unwrap();
+        let unique_exports = exports(unique, states.remove(0));
+        assert_eq!(unique_exports[0], unique_exports[1]);
+        let src_ports: Vec<u64> = unique_exports[0].iter().map(|(port, _)| *port).collect();
+        assert_eq!(src_ports, [49152, 49153, 49154, 49155]);
+    }
+}
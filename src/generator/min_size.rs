@@ -0,0 +1,126 @@
+//! Record padding of V5/V7 flows up to `--min-packet-size`
+//!
+//! V5 and V7 have no flowsets to carry padding, so a flow whose packet would
+//! be too short repeats its last record until the packet is long enough. This
+//! runs before sequence numbers are assigned, so the repeats are counted like
+//! any other record. A V5 packet holds at most 30 records and a V7 packet
+//! 28, so a larger minimum is rejected. V9 and IPFIX packets are padded with
+//! empty data flowsets as they are built.
+
+use crate::config::schema::FlowConfig;
+use crate::error::{NetflowError, Result};
+
+/// V5/V7 header length
+const HEADER_LEN: usize = 24;
+/// V5 flow record length
+const V5_RECORD_LEN: usize = 48;
+/// V7 flow record length
+const V7_RECORD_LEN: usize = 52;
+/// Most records a V5 packet may carry
+const V5_MAX_RECORDS: usize = 30;
+/// Most records a V7 packet may carry
+const V7_MAX_RECORDS: usize = 28;
+
+/// Repeat the last record of a V5/V7 flow until its packet is at least `min_packet_size` bytes
+///
+/// Fails if that would take more records than the version allows.
+pub fn pad_records(flow: &mut FlowConfig, min_packet_size: u16) -> Result<()> {
+    match flow {
+        FlowConfig::V5(config) => pad_with_last(
+            &mut config.flowsets,
+            ("V5", V5_RECORD_LEN, V5_MAX_RECORDS),
+            min_packet_size,
+        ),
+        FlowConfig::V7(config) => pad_with_last(
+            &mut config.flowsets,
+            ("V7", V7_RECORD_LEN, V7_MAX_RECORDS),
+            min_packet_size,
+        ),
+        FlowConfig::V9(_) | FlowConfig::IPFix(_) => Ok(()),
+    }
+}
+
+/// Pad `records` of a version given as (name, record length, most records)
+fn pad_with_last<T: Clone>(
+    records: &mut Vec<T>,
+    (version, record_len, max_records): (&str, usize, usize),
+    min_packet_size: u16,
+) -> Result<()> {
+    let Some(last) = records.last().cloned() else {
+        return Ok(());
+    };
+    let needed = usize::from(min_packet_size)
+        .saturating_sub(HEADER_LEN)
+        .div_ceil(record_len);
+    if needed > max_records {
+        return Err(NetflowError::Configuration(format!(
+            "--min-packet-size {} cannot be reached by {} packets, which hold at most {} records ({} bytes)",
+            min_packet_size,
+            version,
+            max_records,
+            HEADER_LEN.saturating_add(max_records.saturating_mul(record_len))
+        )));
+    }
+    if needed > records.len() {
+        records.resize(needed, last);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+    use crate::generator::build_v5_packet;
+
+    #[test]
+    fn test_pad_records() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v5
+    flowsets:
+      - src_addr: "10.0.0.1"
+        dst_addr: "10.0.0.2"
+        next_hop: "0.0.0.0"
+        input: 1
+        output: 2
+        d_pkts: 10
+        d_octets: 1000
+        first: 0
+        last: 0
+        src_port: 1234
+        dst_port: 80
+        tcp_flags: 0
+        protocol: 6
+        tos: 0
+        src_mask: 24
+        dst_mask: 24
+"#,
+        )
+        .unwrap();
+
+        let mut flow = config.flows[0].clone();
+        pad_records(&mut flow, 200).unwrap();
+        let FlowConfig::V5(v5) = flow else {
+            panic!("expected a V5 flow");
+        };
+        // 24 + 4 * 48 = 216 is the first size of at least 200 bytes
+        assert_eq!(v5.flowsets.len(), 4);
+        let packet = build_v5_packet(v5, None).unwrap();
+        assert_eq!(packet.len(), 216);
+        assert_eq!(&packet[2..4], &4u16.to_be_bytes());
+
+        // A packet already long enough is unchanged
+        let mut flow = config.flows[0].clone();
+        pad_records(&mut flow, 50).unwrap();
+        assert!(matches!(flow, FlowConfig::V5(v5) if v5.flowsets.len() == 1));
+
+        // 30 records (1464 bytes) is the most a V5 packet holds
+        let mut flow = config.flows[0].clone();
+        pad_records(&mut flow, 1464).unwrap();
+        assert!(matches!(&flow, FlowConfig::V5(v5) if v5.flowsets.len() == 30));
+        let mut flow = config.flows[0].clone();
+        assert!(pad_records(&mut flow, 1465).is_err());
+    }
+}
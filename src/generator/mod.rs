@@ -1,12 +1,82 @@
+pub mod address_pools;
+pub mod batching;
+pub mod bgp;
+pub mod burst;
+pub mod dscp;
+pub mod export_lag;
+pub mod expression;
 pub mod field_serializer;
+pub mod flow_sizes;
+pub mod flow_table;
+pub mod interleave;
 pub mod ipfix;
+pub mod lifecycle;
+pub mod min_size;
+pub mod options;
+pub mod overrides;
+pub mod packet_sizes;
+pub mod protocols;
+pub mod providers;
+pub mod ramp;
+pub mod rate;
 pub mod samples;
+pub mod scenario;
+pub mod schedule;
+pub mod timing;
+pub mod unique_flows;
 pub mod v5;
 pub mod v7;
 pub mod v9;
 
+pub use burst::*;
+pub use export_lag::*;
+pub use interleave::*;
 pub use ipfix::*;
+pub use lifecycle::*;
+pub use min_size::*;
+pub use options::*;
+pub use overrides::*;
+pub use ramp::*;
+pub use rate::*;
 pub use samples::*;
+pub use scenario::*;
+pub use schedule::*;
+pub use timing::*;
 pub use v5::*;
 pub use v7::*;
 pub use v9::*;
+
+/// Records of the first data flowset over `exports` exports of `flow`, each
+/// export a fresh copy of the flow passed through `apply`
+#[cfg(test)]
+pub(crate) fn exported_records(
+    flow: &crate::config::FlowConfig,
+    exports: usize,
+    mut apply: impl FnMut(&mut crate::config::FlowConfig),
+) -> Vec<Vec<serde_yaml::Value>> {
+    use crate::config::schema::{FlowConfig, IPFixFlowSet, V9FlowSet};
+
+    (0..exports)
+        .map(|_| {
+            let mut flow = flow.clone();
+            apply(&mut flow);
+            let records = match flow {
+                FlowConfig::V9(v9) => v9.flowsets.into_iter().find_map(|flowset| match flowset {
+                    V9FlowSet::Data { records, .. } => Some(records),
+                    _ => None,
+                }),
+                FlowConfig::IPFix(ipfix) => {
+                    ipfix
+                        .flowsets
+                        .into_iter()
+                        .find_map(|flowset| match flowset {
+                            IPFixFlowSet::Data { records, .. } => Some(records),
+                            _ => None,
+                        })
+                }
+                _ => None,
+            };
+            records.expect("expected a V9 or IPFIX data flowset")
+        })
+        .collect()
+}
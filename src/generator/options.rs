@@ -0,0 +1,71 @@
+use crate::cli::{Cli, RecordOrder};
+use std::time::Duration;
+
+/// Packet building options shared by the per-version generators
+///
+/// These are populated from CLI flags and apply to every flow in a run,
+/// as opposed to per-flow settings which live in the YAML configuration.
+#[derive(Debug, Clone, Default)]
+pub struct GeneratorOptions {
+    /// Order of records within a V9/IPFIX data flowset
+    pub record_order: RecordOrder,
+    /// Interleave packets from different flows round-robin instead of flow by flow
+    pub interleave: bool,
+    /// Derive missing V9 FIRST_SWITCHED/LAST_SWITCHED values from sys_up_time
+    pub first_switched_now: bool,
+    /// Time between successive packets' header timestamps within a batch
+    pub packet_spacing: Duration,
+    /// Header count forced onto V5/V7/V9 packets after building (testing only)
+    pub override_count: Option<u16>,
+    /// Bytes trimmed off the end of every packet after building (testing only)
+    pub truncate_bytes: Option<usize>,
+    /// Withhold V9/IPFIX template packets and send data only (testing only)
+    pub suppress_templates: bool,
+    /// FlowSet ID written on V9 data flowsets instead of the template ID (testing only)
+    pub data_flowset_id: Option<u16>,
+    /// Leave IPFIX sets unpadded instead of aligning them to 4 bytes
+    pub no_padding: bool,
+    /// Emit V9/IPFIX options templates ahead of regular templates
+    pub options_first: bool,
+    /// Put consecutive V9/IPFIX templates of the same kind in one template set
+    pub group_templates: bool,
+    /// Resolve data flowsets against templates from other flows of the same exporter
+    pub shared_templates: bool,
+    /// Run time added to V9 sys_up_time and explicit switched times (--advance-uptime)
+    pub uptime_elapsed: Option<Duration>,
+    /// Time flow start and end timestamps are moved back from the export time
+    pub export_lag: Option<Duration>,
+    /// Read the clock for each IPFIX data packet's export time instead of once per batch
+    pub per_packet_export_time: bool,
+    /// Smallest packet to emit, padding V9/IPFIX with empty flowsets and repeating V5/V7 records
+    pub min_packet_size: Option<u16>,
+    /// Distinct flows sent for each V9/IPFIX data flowset on every export
+    pub unique_flows: Option<usize>,
+    /// Fraction of unique flows replaced by new ones on each export
+    pub unique_flows_churn: f64,
+}
+
+impl From<&Cli> for GeneratorOptions {
+    fn from(args: &Cli) -> Self {
+        Self {
+            record_order: args.record_order,
+            interleave: args.interleave,
+            first_switched_now: args.first_switched_now,
+            packet_spacing: Duration::from_micros(args.packet_spacing_us),
+            override_count: args.override_count,
+            truncate_bytes: args.truncate_bytes,
+            suppress_templates: args.suppress_templates,
+            data_flowset_id: args.override_data_flowset_id,
+            no_padding: args.no_padding,
+            options_first: args.options_first,
+            group_templates: args.group_templates,
+            shared_templates: args.shared_templates,
+            uptime_elapsed: args.advance_uptime.then_some(Duration::ZERO),
+            export_lag: args.export_lag.map(Duration::from_secs),
+            per_packet_export_time: args.per_packet_export_time,
+            min_packet_size: args.min_packet_size,
+            unique_flows: args.unique_flows.and_then(|n| usize::try_from(n).ok()),
+            unique_flows_churn: args.unique_flows_churn,
+        }
+    }
+}
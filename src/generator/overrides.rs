@@ -0,0 +1,79 @@
+//! Deliberately non-conformant header patches for collector robustness testing
+
+use std::collections::HashMap;
+
+/// Overwrite the header record count of every V5, V7 and V9 packet
+///
+/// The count no longer matches the records (V5/V7) or flowsets (V9) actually
+/// present. IPFIX headers carry no count field and are left untouched.
+pub fn override_header_count(packets: &mut [Vec<u8>], count: u16) {
+    for packet in packets.iter_mut() {
+        if packet.len() < 4 {
+            continue;
+        }
+        if matches!(u16::from_be_bytes([packet[0], packet[1]]), 5 | 7 | 9) {
+            packet[2..4].copy_from_slice(&count.to_be_bytes());
+        }
+    }
+}
+
+/// Trim the last `bytes` bytes off every packet
+///
+/// Header length fields (IPFIX message length, V9 flowset lengths) still
+/// describe the full packet, as if it were cut short in transit. Packets no
+/// longer than `bytes` become empty.
+pub fn truncate_packets(packets: &mut [Vec<u8>], bytes: usize) {
+    for packet in packets.iter_mut() {
+        packet.truncate(packet.len().saturating_sub(bytes));
+    }
+}
+
+/// Advance every exporter's next sequence number by `gap`, wrapping at u32::MAX
+///
+/// The skipped numbers are never sent, so collectors see them as lost.
+pub fn skip_sequence_numbers<K>(sequence_numbers: &mut HashMap<K, u32>, gap: u32) {
+    for sequence in sequence_numbers.values_mut() {
+        *sequence = sequence.wrapping_add(gap);
+    }
+}
+
+/// Restart every exporter's next sequence number at 0, as after a reboot
+pub fn reset_sequence_numbers<K>(sequence_numbers: &mut HashMap<K, u32>) {
+    for sequence in sequence_numbers.values_mut() {
+        *sequence = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_header_count() {
+        let mut packets = vec![
+            vec![0x00, 0x09, 0x00, 0x01, 0xff],
+            vec![0x00, 0x0a, 0x00, 0x20, 0xff],
+        ];
+        override_header_count(&mut packets, 7);
+
+        assert_eq!(&packets[0][..4], &[0x00, 0x09, 0x00, 0x07]);
+        // IPFIX message length is not a count and stays intact
+        assert_eq!(&packets[1][..4], &[0x00, 0x0a, 0x00, 0x20]);
+
+        truncate_packets(&mut packets, 2);
+        assert_eq!(packets[0], [0x00, 0x09, 0x00]);
+        truncate_packets(&mut packets, 10);
+        assert!(packets[1].is_empty());
+    }
+
+    #[test]
+    fn test_skip_sequence_numbers() {
+        let mut sequence_numbers = HashMap::from([(1u32, 10u32), (2, u32::MAX)]);
+        skip_sequence_numbers(&mut sequence_numbers, 5);
+        assert_eq!(sequence_numbers[&1], 15);
+        assert_eq!(sequence_numbers[&2], 4);
+
+        reset_sequence_numbers(&mut sequence_numbers);
+        assert!(sequence_numbers.values().all(|&sequence| sequence == 0));
+    }
+}
@@ -0,0 +1,258 @@
+//! Weighted protocol selection for records without one (`protocol_distribution`)
+
+use crate::config::schema::{
+    FlowConfig, IPFixFlowSet, ProtocolDistributionConfig, ProtocolWeight, V9FlowSet,
+};
+use crate::generator::packet_sizes::SplitMix64;
+use serde_yaml::{Mapping, Value};
+
+const TCP: u8 = 6;
+const UDP: u8 = 17;
+const SCTP: u8 = 132;
+
+/// Destination ports of common TCP services, drawn for TCP records without ports
+const TCP_SERVICE_PORTS: &[u16] = &[80, 443, 22, 25, 3389, 8080];
+/// Destination ports of common UDP services, drawn for UDP records without ports
+const UDP_SERVICE_PORTS: &[u16] = &[53, 123, 161, 443, 514];
+/// First port of the IANA ephemeral range (RFC 6335), where source ports are drawn
+const EPHEMERAL_PORT_START: u16 = 49152;
+
+/// Protocol, port and TCP flag keys of a V9/IPFIX data record
+struct RecordKeys {
+    protocol: &'static str,
+    src_port: &'static str,
+    dst_port: &'static str,
+    tcp_flags: &'static str,
+}
+
+const V9_KEYS: RecordKeys = RecordKeys {
+    protocol: "protocol",
+    src_port: "src_port",
+    dst_port: "dst_port",
+    tcp_flags: "tcp_flags",
+};
+
+const IPFIX_KEYS: RecordKeys = RecordKeys {
+    protocol: "protocol_identifier",
+    src_port: "source_transport_port",
+    dst_port: "destination_transport_port",
+    tcp_flags: "tcp_control_bits",
+};
+
+/// Draws a protocol for each record that names none, then fits the record to
+/// it: only TCP keeps its flags, and protocols without ports lose their ports
+#[derive(Debug)]
+pub struct ProtocolDistribution {
+    protocols: Vec<ProtocolWeight>,
+    rng: SplitMix64,
+}
+
+impl ProtocolDistribution {
+    pub fn new(config: &ProtocolDistributionConfig) -> Self {
+        Self {
+            protocols: config.protocols.clone(),
+            rng: SplitMix64(config.seed),
+        }
+    }
+
+    /// Draw a protocol for every record of `flow` that does not set one
+    ///
+    /// Ports and TCP flags of those records are made to match the protocol.
+    pub fn apply(&mut self, flow: &mut FlowConfig) {
+        match flow {
+            FlowConfig::V5(config) => {
+                for record in &mut config.flowsets {
+                    if record.protocol.is_none()
+                        && let Some(protocol) = self.draw()
+                    {
+                        record.protocol = Some(protocol);
+                        (record.src_port, record.dst_port) =
+                            self.ports_for(protocol, (record.src_port, record.dst_port));
+                        if protocol != TCP {
+                            record.tcp_flags = 0;
+                        }
+                    }
+                }
+            }
+            FlowConfig::V7(config) => {
+                for record in &mut config.flowsets {
+                    if record.protocol.is_none()
+                        && let Some(protocol) = self.draw()
+                    {
+                        record.protocol = Some(protocol);
+                        (record.src_port, record.dst_port) =
+                            self.ports_for(protocol, (record.src_port, record.dst_port));
+                        if protocol != TCP {
+                            record.tcp_flags = 0;
+                        }
+                    }
+                }
+            }
+            FlowConfig::V9(config) => {
+                for flowset in &mut config.flowsets {
+                    if let V9FlowSet::Data { records, .. } = flowset {
+                        self.draw_records(records, &V9_KEYS);
+                    }
+                }
+            }
+            FlowConfig::IPFix(config) => {
+                for flowset in &mut config.flowsets {
+                    if let IPFixFlowSet::Data { records, .. } = flowset {
+                        self.draw_records(records, &IPFIX_KEYS);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_records(&mut self, records: &mut [Value], keys: &RecordKeys) {
+        for record in records {
+            let Value::Mapping(map) = record else {
+                continue;
+            };
+            if map.contains_key(keys.protocol) {
+                continue;
+            }
+            let Some(protocol) = self.draw() else {
+                return;
+            };
+            map.insert(keys.protocol.into(), u64::from(protocol).into());
+
+            let port = |map: &Mapping, key: &str| {
+                map.get(key)
+                    .and_then(Value::as_u64)
+                    .and_then(|port| u16::try_from(port).ok())
+                    .unwrap_or(0)
+            };
+            let (src_port, dst_port) = self.ports_for(
+                protocol,
+                (port(map, keys.src_port), port(map, keys.dst_port)),
+            );
+            map.insert(keys.src_port.into(), u64::from(src_port).into());
+            map.insert(keys.dst_port.into(), u64::from(dst_port).into());
+            if protocol != TCP {
+                map.insert(keys.tcp_flags.into(), 0u64.into());
+            }
+        }
+    }
+
+    /// Ports for a record given `protocol`
+    ///
+    /// Protocols without ports get none. TCP and UDP records without ports
+    /// get an ephemeral source port and a common service as destination;
+    /// configured ports are kept.
+    fn ports_for(&mut self, protocol: u8, ports: (u16, u16)) -> (u16, u16) {
+        let services = match protocol {
+            TCP => TCP_SERVICE_PORTS,
+            UDP => UDP_SERVICE_PORTS,
+            SCTP => return ports,
+            _ => return (0, 0),
+        };
+        if ports != (0, 0) {
+            return ports;
+        }
+        let ephemeral_ports = u64::from(u16::MAX)
+            .saturating_sub(u64::from(EPHEMERAL_PORT_START))
+            .saturating_add(1);
+        let src_port = self
+            .rng
+            .next()
+            .checked_rem(ephemeral_ports)
+            .and_then(|offset| u16::try_from(offset).ok())
+            .map_or(EPHEMERAL_PORT_START, |offset| {
+                EPHEMERAL_PORT_START.saturating_add(offset)
+            });
+        let dst_port = usize::try_from(self.rng.next())
+            .ok()
+            .and_then(|pick| services.get(pick.checked_rem(services.len())?))
+            .copied()
+            .unwrap_or(0);
+        (src_port, dst_port)
+    }
+
+    /// A protocol chosen by weight, or `None` without protocols
+    fn draw(&mut self) -> Option<u8> {
+        let protocol = self
+            .rng
+            .pick_weighted(&self.protocols, |protocol| protocol.weight)?;
+        Some(protocol.protocol.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+    use crate::generator::exported_records;
+
+    #[test]
+    fn test_protocol_distribution() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    protocol_distribution:
+      seed: 5
+      protocols:
+        - protocol: tcp
+          weight: 70
+        - protocol: udp
+          weight: 25
+        - protocol: icmp
+          weight: 5
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - src_addr: "10.0.0.1"
+            src_port: 40000
+            dst_port: 8443
+            tcp_flags: 27
+          - src_addr: "10.0.0.2"
+            protocol: 47
+"#,
+        )
+        .unwrap();
+        let flow = &config.flows[0];
+        let mut distribution = ProtocolDistribution::new(flow.protocol_distribution().unwrap());
+
+        let mut counts = [0usize; 256];
+        for records in exported_records(flow, 1000, |flow| distribution.apply(flow)) {
+            let record = &records[0];
+            let protocol = record["protocol"].as_u64().unwrap();
+            counts[usize::try_from(protocol).unwrap()] += 1;
+
+            // Ports and flags follow the drawn protocol
+            let ports = (record["src_port"].as_u64(), record["dst_port"].as_u64());
+            let flags = record["tcp_flags"].as_u64();
+            match protocol {
+                6 => assert_eq!((ports, flags), ((Some(40000), Some(8443)), Some(27))),
+                17 => assert_eq!((ports, flags), ((Some(40000), Some(8443)), Some(0))),
+                _ => assert_eq!((ports, flags), ((Some(0), Some(0)), Some(0))),
+            }
+            // A configured protocol overrides the distribution
+            assert_eq!(records[1]["protocol"].as_u64(), Some(47));
+            assert!(records[1].get("src_port").is_none());
+        }
+
+        assert!((630..770).contains(&counts[6]));
+        assert!((190..310).contains(&counts[17]));
+        assert!((20..90).contains(&counts[1]));
+        assert_eq!(counts[6] + counts[17] + counts[1], 1000);
+    }
+
+    #[test]
+    fn test_drawn_ports() {
+        let mut distribution = ProtocolDistribution::new(&ProtocolDistributionConfig {
+            protocols: Vec::new(),
+            seed: 1,
+        });
+        for _ in 0..100 {
+            let (src_port, dst_port) = distribution.ports_for(UDP, (0, 0));
+            assert!(src_port >= EPHEMERAL_PORT_START);
+            assert!(UDP_SERVICE_PORTS.contains(&dst_port));
+        }
+        assert_eq!(distribution.ports_for(1, (1234, 80)), (0, 0));
+        assert_eq!(distribution.draw(), None);
+    }
+}
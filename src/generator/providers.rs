@@ -0,0 +1,310 @@
+//! Faker-style field providers for V9/IPFIX record values
+//!
+//! A record value naming a provider, such as `"{{ ephemeral_port }}"`, is
+//! replaced with a freshly generated value on every export. Providers draw
+//! from the flow's `provider_seed`, so runs are reproducible. Any other
+//! `{{ ... }}` value is left for expression evaluation, and expressions
+//! may refer to fields filled by providers.
+
+use crate::config::schema::{FlowConfig, IPFixFlowSet, V9FlowSet};
+use crate::generator::expression::expression_body;
+use crate::generator::packet_sizes::SplitMix64;
+use serde_yaml::{Mapping, Value};
+use std::net::Ipv4Addr;
+
+/// A named generator of record values
+struct Provider {
+    name: &'static str,
+    generate: fn(&mut SplitMix64) -> Value,
+}
+
+/// Every provider a record value can name
+const PROVIDERS: &[Provider] = &[
+    Provider {
+        name: "ephemeral_port",
+        generate: ephemeral_port,
+    },
+    Provider {
+        name: "well_known_port",
+        generate: well_known_port,
+    },
+    Provider {
+        name: "private_ipv4",
+        generate: private_ipv4,
+    },
+    Provider {
+        name: "public_ipv4",
+        generate: public_ipv4,
+    },
+    Provider {
+        name: "ip_protocol",
+        generate: ip_protocol,
+    },
+    Provider {
+        name: "packet_count",
+        generate: packet_count,
+    },
+    Provider {
+        name: "byte_count",
+        generate: byte_count,
+    },
+];
+
+/// Service ports by how often they show up in typical traffic
+const SERVICE_PORTS: &[(u16, u32)] = &[
+    (443, 40),
+    (80, 25),
+    (53, 12),
+    (22, 4),
+    (123, 3),
+    (25, 3),
+    (3389, 3),
+    (8080, 3),
+    (993, 2),
+    (445, 2),
+    (161, 2),
+    (514, 1),
+];
+
+/// TCP, UDP and ICMP by share of typical traffic
+const PROTOCOLS: &[(u8, u32)] = &[(6, 80), (17, 17), (1, 3)];
+
+/// RFC 1918 private networks as (network, prefix length)
+const PRIVATE_NETWORKS: &[([u8; 4], u32)] = &[
+    ([10, 0, 0, 0], 8),
+    ([172, 16, 0, 0], 12),
+    ([192, 168, 0, 0], 16),
+];
+
+/// Largest flow drawn by `packet_count`
+const MAX_PACKETS: f64 = 10_000.0;
+
+/// Resolves `{{ provider }}` record values afresh on every export, from one
+/// `provider_seed` stream per flow so a run can be replayed
+#[derive(Debug)]
+pub struct FieldProviders {
+    rng: SplitMix64,
+}
+
+impl FieldProviders {
+    /// Providers for `flow`, or `None` if none of its records name one
+    pub fn new(flow: &FlowConfig) -> Option<Self> {
+        let names_provider = |records: &[Value]| {
+            records
+                .iter()
+                .filter_map(Value::as_mapping)
+                .any(|record| record.values().any(|value| provider_for(value).is_some()))
+        };
+        let uses_providers = match flow {
+            FlowConfig::V5(_) | FlowConfig::V7(_) => false,
+            FlowConfig::V9(config) => config.flowsets.iter().any(|flowset| {
+                matches!(flowset, V9FlowSet::Data { records, .. } if names_provider(records))
+            }),
+            FlowConfig::IPFix(config) => config.flowsets.iter().any(|flowset| {
+                matches!(flowset, IPFixFlowSet::Data { records, .. } if names_provider(records))
+            }),
+        };
+        uses_providers.then(|| Self {
+            rng: SplitMix64(flow.provider_seed()),
+        })
+    }
+
+    /// Generated values for the fields of `prototype` that name a provider
+    ///
+    /// Simulated flows draw these once, when they start, and keep them.
+    pub fn draw(&mut self, prototype: &Mapping) -> Mapping {
+        prototype
+            .iter()
+            .filter_map(|(field, value)| {
+                provider_for(value)
+                    .map(|provider| (field.clone(), (provider.generate)(&mut self.rng)))
+            })
+            .collect()
+    }
+
+    /// Replace every provider value in the records of `flow` with a generated one
+    pub fn apply(&mut self, flow: &mut FlowConfig) {
+        for_each_record(flow, |record| {
+            for value in record.values_mut() {
+                if let Some(provider) = provider_for(value) {
+                    *value = (provider.generate)(&mut self.rng);
+                }
+            }
+        });
+    }
+}
+
+/// Whether a record value names a provider
+pub(crate) fn is_provider(value: &Value) -> bool {
+    provider_for(value).is_some()
+}
+
+/// The provider a record value names, if any
+fn provider_for(value: &Value) -> Option<&'static Provider> {
+    let name = value.as_str().and_then(expression_body)?;
+    PROVIDERS.iter().find(|provider| provider.name == name)
+}
+
+/// Call `visit` on every V9/IPFIX data record of `flow`
+fn for_each_record(flow: &mut FlowConfig, mut visit: impl FnMut(&mut Mapping)) {
+    let records: Vec<&mut Vec<Value>> = match flow {
+        FlowConfig::V5(_) | FlowConfig::V7(_) => Vec::new(),
+        FlowConfig::V9(config) => config
+            .flowsets
+            .iter_mut()
+            .filter_map(|flowset| match flowset {
+                V9FlowSet::Data { records, .. } => Some(records),
+                _ => None,
+            })
+            .collect(),
+        FlowConfig::IPFix(config) => config
+            .flowsets
+            .iter_mut()
+            .filter_map(|flowset| match flowset {
+                IPFixFlowSet::Data { records, .. } => Some(records),
+                _ => None,
+            })
+            .collect(),
+    };
+    for record in records.into_iter().flatten() {
+        if let Value::Mapping(map) = record {
+            visit(map);
+        }
+    }
+}
+
+/// A random 32-bit value
+fn random_u32(rng: &mut SplitMix64) -> u32 {
+    u32::try_from(rng.next() >> 32).unwrap_or(u32::MAX)
+}
+
+/// Source port from the IANA dynamic range (49152-65535)
+fn ephemeral_port(rng: &mut SplitMix64) -> Value {
+    let offset = u16::try_from(rng.next() % 16384).unwrap_or(0);
+    Value::from(49152u16.saturating_add(offset))
+}
+
+/// Port of a common service, weighted towards web and DNS traffic
+fn well_known_port(rng: &mut SplitMix64) -> Value {
+    Value::from(
+        rng.pick_weighted(SERVICE_PORTS, |(_, weight)| *weight)
+            .map_or(443, |(port, _)| *port),
+    )
+}
+
+/// Address in one of the RFC 1918 private networks
+fn private_ipv4(rng: &mut SplitMix64) -> Value {
+    let (network, len) = PRIVATE_NETWORKS
+        .get(usize::try_from(rng.next() % 3).unwrap_or(0))
+        .copied()
+        .unwrap_or(([10, 0, 0, 0], 8));
+    let host = random_u32(rng).checked_shr(len).unwrap_or(0);
+    Value::from(Ipv4Addr::from(u32::from_be_bytes(network) | host).to_string())
+}
+
+/// Globally routable unicast address, avoiding private and reserved ranges
+fn public_ipv4(rng: &mut SplitMix64) -> Value {
+    let addr = std::iter::repeat_with(|| Ipv4Addr::from(random_u32(rng)))
+        .take(64)
+        .find(|addr| is_public(*addr))
+        .unwrap_or(Ipv4Addr::new(8, 8, 8, 8));
+    Value::from(addr.to_string())
+}
+
+fn is_public(addr: Ipv4Addr) -> bool {
+    let [first, second, ..] = addr.octets();
+    !(addr.is_private()
+        || addr.is_loopback()
+        || addr.is_link_local()
+        || addr.is_documentation()
+        || addr.is_broadcast()
+        || first == 0
+        || first >= 224
+        // Shared address space (RFC 6598)
+        || (first == 100 && (64..128).contains(&second)))
+}
+
+/// TCP, UDP or ICMP, weighted as in typical traffic
+fn ip_protocol(rng: &mut SplitMix64) -> Value {
+    Value::from(
+        rng.pick_weighted(PROTOCOLS, |(_, weight)| *weight)
+            .map_or(6, |(protocol, _)| *protocol),
+    )
+}
+
+/// Packets in a flow, log-uniform from 1 to 10000 so small flows dominate
+fn packet_count(rng: &mut SplitMix64) -> Value {
+    let packets = MAX_PACKETS.powf(rng.uniform()).round() as u64;
+    Value::from(packets.max(1))
+}
+
+/// Bytes in a flow: a `packet_count` of packets between 40 and 1500 bytes each
+fn byte_count(rng: &mut SplitMix64) -> Value {
+    let packets = packet_count(rng).as_u64().unwrap_or(1);
+    let size = 40u64.saturating_add(rng.next() % 1461);
+    Value::from(packets.saturating_mul(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+    use crate::generator::exported_records;
+
+    #[test]
+    fn test_field_providers() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: ipfix
+    provider_seed: 9
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - source_ipv4_address: "{{ private_ipv4 }}"
+            destination_ipv4_address: "{{ public_ipv4 }}"
+            source_transport_port: "{{ ephemeral_port }}"
+            destination_transport_port: "{{ well_known_port }}"
+            packet_delta_count: "{{ packet_count }}"
+            octet_delta_count: "{{ packet_delta_count * 500 }}"
+  - version: ipfix
+    flowsets: []
+"#,
+        )
+        .unwrap();
+        let flow = &config.flows[0];
+        assert!(FieldProviders::new(&config.flows[1]).is_none());
+
+        let export = |providers: &mut FieldProviders, exports| {
+            exported_records(flow, exports, |flow| providers.apply(flow))
+                .into_iter()
+                .map(|mut records| records.swap_remove(0))
+                .collect::<Vec<Value>>()
+        };
+        let records = export(&mut FieldProviders::new(flow).unwrap(), 200);
+
+        for record in &records {
+            let addr = |field: &str| record[field].as_str().unwrap().parse::<Ipv4Addr>().unwrap();
+            assert!(addr("source_ipv4_address").is_private());
+            assert!(is_public(addr("destination_ipv4_address")));
+            assert!(record["source_transport_port"].as_u64().unwrap() >= 49152);
+            let port = record["destination_transport_port"].as_u64().unwrap();
+            assert!(
+                SERVICE_PORTS
+                    .iter()
+                    .any(|(service, _)| u64::from(*service) == port)
+            );
+            assert!((1..=10_000).contains(&record["packet_delta_count"].as_u64().unwrap()));
+            // Expressions are left for encoding
+            assert_eq!(
+                record["octet_delta_count"].as_str(),
+                Some("{{ packet_delta_count * 500 }}")
+            );
+        }
+        // Values vary between exports, and the seed reproduces them
+        assert_ne!(records[0], records[1]);
+        let again = export(&mut FieldProviders::new(flow).unwrap(), 1);
+        assert_eq!(again[0], records[0]);
+    }
+}
@@ -0,0 +1,85 @@
+//! Exponential volume ramp for collector stress testing
+
+use crate::config::schema::FlowConfig;
+use std::str::FromStr;
+
+/// Flow export volume that grows geometrically up to a cap
+///
+/// At ramp step `n` the volume is `start * factor^n`, capped at `cap`.
+/// Parsed from `START:FACTOR:CAP` (e.g. `10:2:10000`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ramp {
+    pub start: usize,
+    pub factor: usize,
+    pub cap: usize,
+}
+
+impl Ramp {
+    /// Number of flow exports per iteration at the given ramp step
+    pub fn volume(&self, step: u64) -> usize {
+        let step = u32::try_from(step).unwrap_or(u32::MAX);
+        self.start
+            .saturating_mul(self.factor.saturating_pow(step))
+            .min(self.cap)
+    }
+}
+
+impl FromStr for Ramp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [start, factor, cap] = parts.as_slice() else {
+            return Err(format!("expected START:FACTOR:CAP, got '{}'", s));
+        };
+        let parse = |name: &str, value: &str| -> Result<usize, String> {
+            value
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|v| *v > 0)
+                .ok_or_else(|| format!("{} must be a positive integer, got '{}'", name, value))
+        };
+
+        let ramp = Self {
+            start: parse("START", start)?,
+            factor: parse("FACTOR", factor)?,
+            cap: parse("CAP", cap)?,
+        };
+        if ramp.cap < ramp.start {
+            return Err(format!(
+                "CAP ({}) must not be below START ({})",
+                ramp.cap, ramp.start
+            ));
+        }
+        Ok(ramp)
+    }
+}
+
+/// Repeat the due flows round-robin until `volume` flow exports are queued
+///
+/// Repeated flows are exported as separate packets, so no packet grows past
+/// what its configuration produces on its own.
+pub fn scale_flows(flows: Vec<FlowConfig>, volume: usize) -> Vec<FlowConfig> {
+    if flows.is_empty() {
+        return flows;
+    }
+    flows.iter().cycle().take(volume).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ramp_volume() {
+        let ramp: Ramp = "10:2:100".parse().unwrap();
+        let volumes: Vec<usize> = (0..6).map(|step| ramp.volume(step)).collect();
+        assert_eq!(volumes, [10, 20, 40, 80, 100, 100]);
+        assert_eq!(ramp.volume(u64::MAX), 100);
+
+        assert!("10:2".parse::<Ramp>().is_err());
+        assert!("0:2:100".parse::<Ramp>().is_err());
+        assert!("100:2:10".parse::<Ramp>().is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Projected packet rate of a continuous run (`--max-pps`)
+
+use crate::config::Config;
+use crate::config::schema::FlowConfig;
+use crate::generator::ramp::Ramp;
+use crate::generator::unique_flows::{KeyRange, data_packets, key_ranges};
+use std::collections::HashSet;
+
+/// Estimate how many packets one export of a flow produces (including its template)
+fn estimate_flow_packets(flow: &FlowConfig, ranges: &[KeyRange]) -> u64 {
+    let data_packets = match flow {
+        FlowConfig::V5(config) => return u64::try_from(config.packet_count()).unwrap_or(1),
+        FlowConfig::V7(_) => return 1,
+        // --unique-flows spreads each data flowset's records over several packets
+        FlowConfig::V9(_) | FlowConfig::IPFix(_) => data_packets(flow, ranges),
+    };
+    u64::try_from(data_packets)
+        .unwrap_or(u64::MAX)
+        .saturating_add(1)
+}
+
+/// Project the steady-state packet rate, honoring per-flow export intervals
+///
+/// Each flow's share is summed as a fraction and the total rounded up, so
+/// many flows with long intervals still add up. With --ramp the rate is
+/// projected at CAP instead: every iteration then exports CAP flows, taken
+/// as the largest flow, and iterations run once per distinct export
+/// interval. Returns `u64::MAX` when any flow is exported with a zero
+/// interval.
+pub fn projected_packets_per_sec(
+    config: Option<&Config>,
+    interval_secs: u64,
+    unique_flows: Option<usize>,
+    ramp: Option<Ramp>,
+) -> u64 {
+    let Some(cfg) = config else {
+        // Default samples: V5, V7, plus template and data packets for V9 and IPFIX
+        return match interval_secs {
+            0 => u64::MAX,
+            interval => 6u64.div_ceil(interval),
+        };
+    };
+
+    // The budget was checked when the config was loaded
+    let flow_ranges = unique_flows
+        .and_then(|total| key_ranges(&cfg.flows, total).ok())
+        .unwrap_or_default();
+    let mut rate = 0.0;
+    let mut intervals = HashSet::new();
+    let mut largest_flow = 0;
+    for (index, flow) in cfg.flows.iter().enumerate() {
+        let flow_interval = flow.export_interval().unwrap_or(interval_secs);
+        if flow_interval == 0 {
+            return u64::MAX;
+        }
+        let ranges = flow_ranges.get(index).map_or(&[][..], Vec::as_slice);
+        let packets = estimate_flow_packets(flow, ranges);
+        rate += packets as f64 / flow_interval as f64;
+        intervals.insert(flow_interval);
+        largest_flow = largest_flow.max(packets);
+    }
+    if let Some(ramp) = ramp {
+        let iterations: f64 = intervals
+            .iter()
+            .map(|&interval| 1.0 / interval as f64)
+            .sum();
+        rate = ramp.cap as f64 * largest_flow as f64 * iterations;
+    }
+    // Float-to-int casts saturate
+    rate.ceil() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+
+    #[test]
+    fn test_projected_packets_per_sec() {
+        // Default samples send six packets per interval
+        assert_eq!(projected_packets_per_sec(None, 2, None, None), 3);
+        assert_eq!(projected_packets_per_sec(None, 0, None, None), u64::MAX);
+
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v7
+    flowsets: []
+  - version: ipfix
+    export_interval: 10
+    flowsets:
+      - type: data
+        template_id: 256
+        records:
+          - source_ipv4_address: "10.0.0.1"
+  - version: v7
+    export_interval: 30
+    flowsets: []
+  - version: v7
+    export_interval: 30
+    flowsets: []
+"#,
+        )
+        .unwrap();
+        // 1/1 + 2/10 + 1/30 + 1/30 packets per second, rounded up once
+        assert_eq!(projected_packets_per_sec(Some(&config), 1, None, None), 2);
+        // With --ramp, CAP exports of the largest flow each time an interval comes round
+        let ramp = Ramp {
+            start: 1,
+            factor: 2,
+            cap: 100,
+        };
+        assert_eq!(
+            projected_packets_per_sec(Some(&config), 1, None, Some(ramp)),
+            227
+        );
+        // A flow exported with no interval is unbounded
+        assert_eq!(
+            projected_packets_per_sec(Some(&config), 0, None, None),
+            u64::MAX
+        );
+    }
+}
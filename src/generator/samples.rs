@@ -1,8 +1,9 @@
 use crate::config::schema::{
-    IPFixConfig, IPFixFlowSet, IPFixTemplateField, V5Config, V5FlowSet, V7Config, V7FlowSet,
-    V9Config, V9FlowSet, V9TemplateField,
+    Config, CounterMode, FlowConfig, IPFixConfig, IPFixFlowSet, IPFixTemplateField, PadFlowset,
+    V5Config, V5FlowSet, V7Config, V7FlowSet, V9Config, V9FlowSet, V9TemplateField,
 };
 use crate::error::Result;
+use crate::generator::GeneratorOptions;
 use std::net::Ipv4Addr;
 
 /// Generate sample V5 configuration
@@ -10,6 +11,16 @@ use std::net::Ipv4Addr;
 pub fn sample_v5_config() -> V5Config {
     V5Config {
         header: None, // Use defaults
+        start_delay: None,
+        export_interval: None,
+        tcp_flag_sequence: None,
+        packet_sizes: None,
+        average_payload_size: None,
+        address_pools: None,
+        counter_mode: CounterMode::Delta,
+        dscp_distribution: None,
+        protocol_distribution: None,
+        flow_size_distribution: None,
         flowsets: vec![V5FlowSet {
             src_addr: Ipv4Addr::new(192, 168, 1, 100),
             dst_addr: Ipv4Addr::new(172, 217, 14, 206), // Google IP
@@ -17,18 +28,20 @@ pub fn sample_v5_config() -> V5Config {
             input: 1,
             output: 2,
             d_pkts: 150,
-            d_octets: 95000,
-            first: 350000,
-            last: 360000,
+            d_octets: Some(95000),
+            first: 350000.into(),
+            last: 360000.into(),
             src_port: 52341,
-            dst_port: 443,   // HTTPS
-            tcp_flags: 0x18, // ACK + PSH
-            protocol: 6,     // TCP
-            tos: 0,
-            src_as: 65000,
-            dst_as: 15169, // Google ASN
+            dst_port: 443,     // HTTPS
+            tcp_flags: 0x18,   // ACK + PSH
+            protocol: Some(6), // TCP
+            tos: Some(0),
+            src_as: Some(65000),
+            dst_as: Some(15169), // Google ASN
             src_mask: 24,
             dst_mask: 24,
+            pad1: None,
+            pad2: None,
         }],
     }
 }
@@ -38,6 +51,16 @@ pub fn sample_v5_config() -> V5Config {
 pub fn sample_v7_config() -> V7Config {
     V7Config {
         header: None, // Use defaults
+        start_delay: None,
+        export_interval: None,
+        tcp_flag_sequence: None,
+        packet_sizes: None,
+        average_payload_size: None,
+        address_pools: None,
+        counter_mode: CounterMode::Delta,
+        dscp_distribution: None,
+        protocol_distribution: None,
+        flow_size_distribution: None,
         flowsets: vec![V7FlowSet {
             src_addr: Ipv4Addr::new(10, 0, 0, 50),
             dst_addr: Ipv4Addr::new(8, 8, 8, 8), // Google DNS
@@ -45,17 +68,17 @@ pub fn sample_v7_config() -> V7Config {
             input: 10,
             output: 20,
             d_pkts: 2,
-            d_octets: 128,
-            first: 355000,
-            last: 355100,
+            d_octets: Some(128),
+            first: 355000.into(),
+            last: 355100.into(),
             src_port: 54123,
             dst_port: 53, // DNS
             flags: 0,
             tcp_flags: 0,
-            protocol: 17, // UDP
-            tos: 0,
-            src_as: 64512,
-            dst_as: 15169, // Google ASN
+            protocol: Some(17), // UDP
+            tos: Some(0),
+            src_as: Some(64512),
+            dst_as: Some(15169), // Google ASN
             src_mask: 16,
             dst_mask: 8,
             flags2: 0,
@@ -77,6 +100,21 @@ pub fn sample_v9_config() -> V9Config {
             sequence_number: None,
             source_id: Some(1), // V9 uses source_id=1
         }),
+        start_delay: None,
+        export_interval: None,
+        tcp_flag_sequence: None,
+        flow_table: None,
+        address_pools: None,
+        counter_mode: CounterMode::Delta,
+        dscp_distribution: None,
+        protocol_distribution: None,
+        flow_size_distribution: None,
+        hop_count: None,
+        provider_seed: 0,
+        combined_message: false,
+        pack_data_flowsets: None,
+        pad_to: None,
+        pad_flowset: PadFlowset::Data,
         flowsets: vec![
             // Template definition
             V9FlowSet::Template {
@@ -164,6 +202,21 @@ pub fn sample_ipfix_config() -> IPFixConfig {
             sequence_number: None,
             observation_domain_id: Some(2), // IPFIX uses observation_domain_id=2 to avoid collision with V9
         }),
+        biflow: false,
+        observation_domains: None,
+        domain_rotation: None,
+        start_delay: None,
+        export_interval: None,
+        tcp_flag_sequence: None,
+        flow_table: None,
+        address_pools: None,
+        counter_mode: CounterMode::Delta,
+        dscp_distribution: None,
+        protocol_distribution: None,
+        flow_size_distribution: None,
+        hop_count: None,
+        provider_seed: 0,
+        combined_message: false,
         flowsets: vec![
             // Template definition
             IPFixFlowSet::Template {
@@ -245,6 +298,7 @@ pub fn sample_ipfix_config() -> IPFixConfig {
 /// * `v9_seq` - Current V9 sequence number (will be incremented)
 /// * `ipfix_seq` - Current IPFIX sequence number (will be incremented)
 /// * `send_templates` - Whether to include template packets (for periodic refresh)
+/// * `options` - Packet building options shared across flows
 ///
 /// # Returns
 /// * `(packets, next_v9_seq, next_ipfix_seq)` - Generated packets and updated sequence numbers
@@ -252,6 +306,7 @@ pub fn generate_all_samples_with_seq(
     v9_seq: u32,
     ipfix_seq: u32,
     send_templates: bool,
+    options: &GeneratorOptions,
 ) -> Result<(Vec<Vec<u8>>, u32, u32)> {
     let mut packets = Vec::new();
 
@@ -268,7 +323,7 @@ pub fn generate_all_samples_with_seq(
     // V9 sample (may return multiple packets)
     let v9_config = sample_v9_config();
     let (v9_packets, next_v9_seq) =
-        crate::generator::v9::build_v9_packets(v9_config, Some(v9_seq), send_templates)?;
+        crate::generator::v9::build_v9_packets(v9_config, Some(v9_seq), send_templates, options)?;
     packets.extend(v9_packets);
 
     // IPFIX sample (may return multiple packets)
@@ -277,14 +332,44 @@ pub fn generate_all_samples_with_seq(
         ipfix_config,
         Some(ipfix_seq),
         send_templates,
+        options,
     )?;
     packets.extend(ipfix_packets);
 
     Ok((packets, next_v9_seq, next_ipfix_seq))
 }
 
+/// Configuration equivalent to the built-in samples
+pub fn sample_config() -> Config {
+    Config {
+        flows: vec![
+            FlowConfig::V5(sample_v5_config()),
+            FlowConfig::V7(sample_v7_config()),
+            FlowConfig::V9(sample_v9_config()),
+            FlowConfig::IPFix(sample_ipfix_config()),
+        ],
+        destination: Default::default(),
+        scenario: Vec::new(),
+        defaults: Default::default(),
+    }
+}
+
 /// Generate all sample packets (legacy function for backwards compatibility)
-pub fn generate_all_samples() -> Result<Vec<Vec<u8>>> {
-    let (packets, _, _) = generate_all_samples_with_seq(0, 0, true)?;
+pub fn generate_all_samples(options: &GeneratorOptions) -> Result<Vec<Vec<u8>>> {
+    let (packets, _, _) = generate_all_samples_with_seq(0, 0, true, options)?;
     Ok(packets)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_config_round_trip() {
+        // --print-config output must load back as the same configuration
+        let yaml = serde_yaml::to_string(&sample_config()).unwrap();
+        let reparsed = crate::config::parse_yaml_str(&yaml).unwrap();
+        assert_eq!(serde_yaml::to_string(&reparsed).unwrap(), yaml);
+        assert_eq!(reparsed.flows.len(), 4);
+    }
+}
@@ -0,0 +1,133 @@
+//! Timed scenario steps played by the continuous loop (`scenario`)
+
+use crate::config::schema::{ScenarioAction, ScenarioStep};
+use std::time::Duration;
+
+/// Progress through a configured scenario
+#[derive(Debug)]
+pub struct ScenarioPlayer {
+    /// Steps in firing order
+    steps: Vec<ScenarioStep>,
+    /// Index of the next step to fire
+    next: usize,
+    /// Whether each flow may export, as set by `start_flow`/`stop_flow`
+    running: Vec<bool>,
+}
+
+impl ScenarioPlayer {
+    pub fn new(steps: &[ScenarioStep], flow_count: usize) -> Self {
+        let mut steps = steps.to_vec();
+        // Stable, so steps at the same time fire in configuration order
+        steps.sort_by_key(|step| step.at);
+
+        let mut running = vec![true; flow_count];
+        for step in &steps {
+            if let ScenarioAction::StartFlow { flow } = step.action
+                && let Some(running) = running.get_mut(flow)
+            {
+                *running = false;
+            }
+        }
+
+        Self {
+            steps,
+            next: 0,
+            running,
+        }
+    }
+
+    /// Fire every step due at `elapsed` since the run started
+    ///
+    /// Flow starts and stops are applied here. All fired actions are
+    /// returned so the caller can carry out the rest.
+    pub fn advance(&mut self, elapsed: Duration) -> Vec<ScenarioAction> {
+        let mut actions = Vec::new();
+        while let Some(&step) = self.steps.get(self.next)
+            && Duration::from_secs(step.at) <= elapsed
+        {
+            match step.action {
+                ScenarioAction::StartFlow { flow } => self.set_running(flow, true),
+                ScenarioAction::StopFlow { flow } => self.set_running(flow, false),
+                _ => {}
+            }
+            actions.push(step.action);
+            self.next = self.next.saturating_add(1);
+        }
+        actions
+    }
+
+    /// Whether a flow may export at this point of the scenario
+    pub fn is_running(&self, flow: usize) -> bool {
+        self.running.get(flow).copied().unwrap_or(true)
+    }
+
+    /// Time since the run started at which the next step fires
+    pub fn next_step(&self) -> Option<Duration> {
+        self.steps
+            .get(self.next)
+            .map(|step| Duration::from_secs(step.at))
+    }
+
+    fn set_running(&mut self, flow: usize, running: bool) {
+        if let Some(state) = self.running.get_mut(flow) {
+            *state = running;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{expand_observation_domains, parse_yaml_str};
+
+    #[test]
+    fn test_scenario_player() {
+        let mut config = parse_yaml_str(
+            r#"
+flows:
+  - version: ipfix
+    observation_domains: 2
+    flowsets: []
+  - version: v9
+    flowsets: []
+scenario:
+  - at: 60
+    action: stop
+  - at: 5
+    action: start_flow
+    flow: 0
+  - at: 30
+    action: sequence_gap
+    count: 10
+  - at: 30
+    action: stop_flow
+    flow: 1
+"#,
+        )
+        .unwrap();
+        // Steps follow the IPFIX flow to each of its observation domain copies
+        expand_observation_domains(&mut config);
+        assert_eq!(config.scenario.len(), 5);
+
+        let at = Duration::from_secs;
+        let mut player = ScenarioPlayer::new(&config.scenario, config.flows.len());
+        assert!(player.advance(at(0)).is_empty());
+        assert_eq!(player.next_step(), Some(at(5)));
+        assert!(!player.is_running(0) && !player.is_running(1) && player.is_running(2));
+
+        player.advance(at(7));
+        assert!(player.is_running(0) && player.is_running(1));
+
+        assert_eq!(
+            player.advance(at(30)),
+            vec![
+                ScenarioAction::SequenceGap { count: 10 },
+                ScenarioAction::StopFlow { flow: 2 },
+            ]
+        );
+        assert!(!player.is_running(2));
+
+        assert_eq!(player.advance(at(90)), vec![ScenarioAction::Stop]);
+        assert_eq!(player.next_step(), None);
+    }
+}
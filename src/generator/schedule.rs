@@ -0,0 +1,234 @@
+//! Per-flow export schedule of the continuous loop (`start_delay`, `export_interval`, `--warmup`)
+
+use crate::config::schema::FlowConfig;
+use std::time::Duration;
+
+/// When each configured flow starts and is next exported
+///
+/// Times are measured since the run started, like the scenario player's.
+#[derive(Debug)]
+pub struct FlowSchedule {
+    flows: Vec<ScheduledFlow>,
+    /// Time between iterations while no flow is scheduled
+    default_interval: Duration,
+    /// Templates-only period at the start of the run (`--warmup`)
+    warmup: Duration,
+}
+
+#[derive(Debug)]
+struct ScheduledFlow {
+    /// Time before the flow's first export (`start_delay`)
+    start_delay: Duration,
+    /// Time between exports (`export_interval`, or the global interval)
+    interval: Duration,
+    /// Time of the next export, once the flow has started
+    next_export: Option<Duration>,
+    /// Exports so far
+    exports: usize,
+}
+
+/// Flows exported by one iteration of the continuous loop
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DueExports {
+    /// Index and export count of each flow due, in configuration order
+    pub flows: Vec<(usize, usize)>,
+    /// Start delays of the flows exporting for the first time
+    pub started: Vec<Duration>,
+}
+
+impl FlowSchedule {
+    pub fn new(flows: &[FlowConfig], default_interval: Duration, warmup: Option<Duration>) -> Self {
+        Self {
+            flows: flows
+                .iter()
+                .map(|flow| ScheduledFlow {
+                    start_delay: Duration::from_secs(flow.start_delay().unwrap_or(0)),
+                    interval: flow
+                        .export_interval()
+                        .map_or(default_interval, Duration::from_secs),
+                    next_export: None,
+                    exports: 0,
+                })
+                .collect(),
+            default_interval,
+            warmup: warmup.unwrap_or_default(),
+        }
+    }
+
+    /// Time left in the warmup at `elapsed`, while it lasts
+    pub fn warmup_remaining(&self, elapsed: Duration) -> Option<Duration> {
+        self.warmup
+            .checked_sub(elapsed)
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Take the running flows due for export at `elapsed`
+    ///
+    /// No flow is due during the warmup. Afterwards a flow starts once its
+    /// start delay has elapsed and is exported right away. Each flow taken
+    /// is due again one interval later.
+    pub fn take_due(
+        &mut self,
+        elapsed: Duration,
+        is_running: impl Fn(usize) -> bool,
+    ) -> DueExports {
+        let mut due = DueExports::default();
+        if self.warmup_remaining(elapsed).is_some() {
+            return due;
+        }
+        for (index, flow) in self.flows.iter_mut().enumerate() {
+            if !is_running(index) {
+                continue;
+            }
+            match flow.next_export {
+                None if elapsed >= flow.start_delay => due.started.push(flow.start_delay),
+                Some(next_export) if elapsed >= next_export => {}
+                _ => continue,
+            }
+            flow.next_export = elapsed.checked_add(flow.interval);
+            due.flows.push((index, flow.exports));
+            flow.exports = flow.exports.saturating_add(1);
+        }
+        due
+    }
+
+    /// Time of the next iteration after one at `elapsed`
+    ///
+    /// During the warmup templates repeat every default interval until it
+    /// ends. After it, this is when a running flow next starts or is due, if
+    /// any.
+    pub fn next_wakeup(
+        &self,
+        elapsed: Duration,
+        is_running: impl Fn(usize) -> bool,
+    ) -> Option<Duration> {
+        if self.warmup_remaining(elapsed).is_some() {
+            return Some(
+                elapsed
+                    .saturating_add(self.default_interval)
+                    .min(self.warmup),
+            );
+        }
+        self.flows
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| is_running(*index))
+            .map(|(_, flow)| flow.next_export.unwrap_or(flow.start_delay))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+
+    #[test]
+    fn test_start_delay() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    flowsets: []
+  - version: ipfix
+    start_delay: 30
+    flowsets: []
+  - version: v5
+    start_delay: 10
+    flowsets: []
+"#,
+        )
+        .unwrap();
+        let at = Duration::from_secs;
+        let mut schedule = FlowSchedule::new(&config.flows, at(20), None);
+        let running = |_| true;
+
+        // Only the flow without a delay starts with the run
+        let due = schedule.take_due(at(0), running);
+        assert_eq!(due.flows, [(0, 0)]);
+        assert_eq!(due.started, [at(0)]);
+        assert_eq!(schedule.next_wakeup(at(0), running), Some(at(10)));
+
+        let due = schedule.take_due(at(10), running);
+        assert_eq!(due.flows, [(2, 0)]);
+        assert_eq!(due.started, [at(10)]);
+
+        // A flow stopped by the scenario waits, even once its delay is over
+        let stopped = |index| index != 1;
+        let due = schedule.take_due(at(30), stopped);
+        assert_eq!(due.flows, [(0, 1), (2, 1)]);
+        assert!(due.started.is_empty());
+        assert_eq!(schedule.next_wakeup(at(30), stopped), Some(at(50)));
+        assert_eq!(schedule.next_wakeup(at(30), running), Some(at(30)));
+
+        let due = schedule.take_due(at(31), running);
+        assert_eq!(due.flows, [(1, 0)]);
+        assert_eq!(due.started, [at(30)]);
+        assert_eq!(schedule.next_wakeup(at(31), running), Some(at(50)));
+    }
+
+    #[test]
+    fn test_export_intervals() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    export_interval: 1
+    flowsets: []
+  - version: v9
+    export_interval: 30
+    flowsets: []
+  - version: v9
+    flowsets: []
+"#,
+        )
+        .unwrap();
+        let at = Duration::from_secs;
+        let mut schedule = FlowSchedule::new(&config.flows, at(10), None);
+        let running = |_| true;
+
+        // Each flow is exported on its own cadence, the last on the global interval
+        let mut exports = [0usize; 3];
+        let mut elapsed = at(0);
+        while elapsed < at(60) {
+            for (index, export_count) in schedule.take_due(elapsed, running).flows {
+                assert_eq!(export_count, exports[index]);
+                exports[index] += 1;
+            }
+            elapsed = schedule.next_wakeup(elapsed, running).unwrap();
+        }
+        assert_eq!(exports, [60, 2, 6]);
+    }
+
+    #[test]
+    fn test_warmup() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    flowsets: []
+  - version: v9
+    start_delay: 5
+    flowsets: []
+"#,
+        )
+        .unwrap();
+        let at = Duration::from_secs;
+        let mut schedule = FlowSchedule::new(&config.flows, at(4), Some(at(10)));
+        let running = |_| true;
+
+        // Only templates go out until the warmup ends, once every interval
+        assert_eq!(schedule.warmup_remaining(at(0)), Some(at(10)));
+        assert_eq!(schedule.take_due(at(0), running), DueExports::default());
+        assert_eq!(schedule.next_wakeup(at(0), running), Some(at(4)));
+        assert!(schedule.take_due(at(8), running).flows.is_empty());
+        assert_eq!(schedule.next_wakeup(at(8), running), Some(at(10)));
+
+        // Then every flow whose start delay has passed exports at once
+        assert_eq!(schedule.warmup_remaining(at(10)), None);
+        let due = schedule.take_due(at(10), running);
+        assert_eq!(due.flows, [(0, 0), (1, 0)]);
+        assert_eq!(due.started, [at(0), at(5)]);
+        assert_eq!(schedule.next_wakeup(at(10), running), Some(at(14)));
+    }
+}
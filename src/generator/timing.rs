@@ -0,0 +1,127 @@
+//! Post-generation timestamp adjustments for NetFlow packet headers
+
+use std::time::Duration;
+
+/// Stagger header timestamps so successive packets in a batch are `spacing` apart
+///
+/// Packet `i` has `start + i * spacing` added to its header time fields:
+/// * V5/V7: `sys_up_time` (ms) and `unix_secs`/`unix_nsecs`
+/// * V9: `sys_up_time` (ms) and `unix_secs`
+/// * IPFIX: `export_time`
+///
+/// Returns the offset for the packet after the batch, so a stream of
+/// batches is spaced as one (--stream).
+pub fn stagger_packet_timestamps_from(
+    packets: &mut [Vec<u8>],
+    spacing: Duration,
+    start: Duration,
+) -> Duration {
+    if spacing.is_zero() {
+        return start;
+    }
+
+    let mut offset = start;
+    for packet in packets.iter_mut() {
+        apply_time_offset(packet, offset);
+        offset = offset.saturating_add(spacing);
+    }
+    offset
+}
+
+/// Add a time offset to the header time fields of a single packet
+fn apply_time_offset(packet: &mut [u8], offset: Duration) {
+    if packet.len() < 2 || offset.is_zero() {
+        return;
+    }
+
+    let offset_ms = u32::try_from(offset.as_millis()).unwrap_or(u32::MAX);
+    let offset_secs = u32::try_from(offset.as_secs()).unwrap_or(u32::MAX);
+
+    match u16::from_be_bytes([packet[0], packet[1]]) {
+        5 | 7 if packet.len() >= 16 => {
+            add_u32(packet, 4, offset_ms, u32::wrapping_add);
+
+            // Carry nanoseconds into seconds
+            let nsecs =
+                u64::from(read_u32(packet, 12)).saturating_add(u64::from(offset.subsec_nanos()));
+            let carry = u32::try_from(nsecs / 1_000_000_000).unwrap_or(0);
+            let nsecs = u32::try_from(nsecs % 1_000_000_000).unwrap_or(0);
+            add_u32(
+                packet,
+                8,
+                offset_secs.saturating_add(carry),
+                u32::saturating_add,
+            );
+            packet[12..16].copy_from_slice(&nsecs.to_be_bytes());
+        }
+        9 if packet.len() >= 12 => {
+            add_u32(packet, 4, offset_ms, u32::wrapping_add);
+            add_u32(packet, 8, offset_secs, u32::saturating_add);
+        }
+        10 if packet.len() >= 8 => {
+            add_u32(packet, 4, offset_secs, u32::saturating_add);
+        }
+        _ => {}
+    }
+}
+
+fn read_u32(packet: &[u8], pos: usize) -> u32 {
+    u32::from_be_bytes([
+        packet[pos],
+        packet[pos + 1],
+        packet[pos + 2],
+        packet[pos + 3],
+    ])
+}
+
+fn add_u32(packet: &mut [u8], pos: usize, value: u32, add: fn(u32, u32) -> u32) {
+    let updated = add(read_u32(packet, pos), value);
+    packet[pos..pos + 4].copy_from_slice(&updated.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stagger_packet_timestamps() {
+        // V5 header: sys_up_time=1000, unix_secs=100, unix_nsecs=900_000_000
+        let mut v5 = vec![0u8; 24];
+        v5[0..2].copy_from_slice(&5u16.to_be_bytes());
+        v5[4..8].copy_from_slice(&1000u32.to_be_bytes());
+        v5[8..12].copy_from_slice(&100u32.to_be_bytes());
+        v5[12..16].copy_from_slice(&900_000_000u32.to_be_bytes());
+
+        // IPFIX header: export_time=100
+        let mut ipfix = vec![0u8; 16];
+        ipfix[0..2].copy_from_slice(&10u16.to_be_bytes());
+        ipfix[4..8].copy_from_slice(&100u32.to_be_bytes());
+
+        let mut packets = vec![v5.clone(), v5, ipfix.clone()];
+        let next = stagger_packet_timestamps_from(
+            &mut packets,
+            Duration::from_millis(600),
+            Duration::ZERO,
+        );
+        assert_eq!(next, Duration::from_millis(1800));
+
+        // First packet is unchanged
+        assert_eq!(read_u32(&packets[0], 8), 100);
+        // Second packet is 600ms later, carrying into the next second
+        assert_eq!(read_u32(&packets[1], 4), 1600);
+        assert_eq!(read_u32(&packets[1], 8), 101);
+        assert_eq!(read_u32(&packets[1], 12), 500_000_000);
+        // Third packet is 1.2s later
+        assert_eq!(read_u32(&packets[2], 4), 101);
+
+        // A following batch continues from where this one left off
+        let mut next = vec![ipfix];
+        let offset = stagger_packet_timestamps_from(
+            &mut next,
+            Duration::from_millis(600),
+            Duration::from_millis(1800),
+        );
+        assert_eq!(read_u32(&next[0], 4), 101);
+        assert_eq!(offset, Duration::from_millis(2400));
+    }
+}
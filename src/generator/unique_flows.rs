@@ -0,0 +1,588 @@
+//! A fixed number of distinct flows re-sent every export (`--unique-flows`)
+//!
+//! The first record of each V9/IPFIX data flowset is the prototype for its
+//! share of N flows with distinct 5-tuples, so a collector's active flow
+//! count settles at a known value. N is split over the data flowsets of all
+//! flows, which take turns through the key indexes so no two ever share a
+//! key. A key picks the source port from the dynamic range and, once those
+//! run out, the next source address. Addresses come from the flow's
+//! `address_pools` when it has them, taken in order rather than drawn, and
+//! otherwise count up from the prototype's. A configuration with too few
+//! source addresses for its share is rejected. Provider values are drawn
+//! when a flow starts and kept for its life. Churn retires flows and gives
+//! their slots unused keys, never-used ones first.
+
+use crate::config::schema::{AddressPool, AddressPoolsConfig, FlowConfig, IPFixFlowSet, V9FlowSet};
+use crate::error::{NetflowError, Result};
+use crate::generator::batching::records_per_packet;
+use crate::generator::packet_sizes::SplitMix64;
+use crate::generator::providers::FieldProviders;
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv4Addr;
+
+/// First source port given to unique flows (IANA dynamic range)
+const DYNAMIC_PORT_START: u16 = 49152;
+/// Source ports per address, the size of the dynamic range
+const PORTS_PER_ADDRESS: u64 = 16384;
+/// Source addresses a prototype's own address can count up through
+const IPV4_ADDRESSES: u64 = 1 << 32;
+
+/// Key fields of a V9/IPFIX data record
+struct RecordKeys {
+    src_addr: &'static str,
+    dst_addr: &'static str,
+    src_port: &'static str,
+}
+
+const V9_KEYS: RecordKeys = RecordKeys {
+    src_addr: "src_addr",
+    dst_addr: "dst_addr",
+    src_port: "src_port",
+};
+
+const IPFIX_KEYS: RecordKeys = RecordKeys {
+    src_addr: "source_ipv4_address",
+    dst_addr: "destination_ipv4_address",
+    src_port: "source_transport_port",
+};
+
+/// One data flowset's share of the `--unique-flows` budget
+///
+/// Its keys are `offset`, `offset + stride`, ..., where `stride` is the
+/// number of data flowsets sharing the budget.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyRange {
+    offset: u64,
+    stride: u64,
+    /// Flows the flowset sends
+    size: usize,
+    /// Keys of the flowset that map to distinct source address/port pairs
+    limit: u64,
+}
+
+impl KeyRange {
+    /// Key of the flowset's `index`th flow
+    fn key(&self, index: u64) -> u64 {
+        index
+            .saturating_mul(self.stride)
+            .saturating_add(self.offset)
+    }
+}
+
+/// Split `total` unique flows over the V9/IPFIX data flowsets of `flows`
+///
+/// Returns the key ranges of each flow, one per data flowset with a
+/// prototype record, in flowset order. Fails when there are more data
+/// flowsets than flows to share, or when a flowset's share needs more
+/// source address/port pairs than its flow can produce.
+pub fn key_ranges(flows: &[FlowConfig], total: usize) -> Result<Vec<Vec<KeyRange>>> {
+    let flowsets = flows
+        .iter()
+        .map(|flow| prototypes(flow).len())
+        .fold(0, usize::saturating_add);
+    if flowsets == 0 {
+        return Ok(vec![Vec::new(); flows.len()]);
+    }
+    if total < flowsets {
+        return Err(NetflowError::Configuration(format!(
+            "--unique-flows {} is less than the {} V9/IPFIX data flowsets it is split over",
+            total, flowsets
+        )));
+    }
+    let stride = u64::try_from(flowsets).unwrap_or(u64::MAX);
+    let extra = total % flowsets;
+
+    let mut position = 0usize;
+    flows
+        .iter()
+        .enumerate()
+        .map(|(index, flow)| {
+            prototypes(flow)
+                .into_iter()
+                .map(|(prototype, keys)| {
+                    let size = (total / flowsets).saturating_add(usize::from(position < extra));
+                    let offset = u64::try_from(position).unwrap_or(u64::MAX);
+                    position = position.saturating_add(1);
+
+                    let addresses = source_addresses(flow, prototype, keys);
+                    let limit = addresses
+                        .saturating_mul(PORTS_PER_ADDRESS)
+                        .saturating_sub(offset)
+                        .div_ceil(stride);
+                    if limit < u64::try_from(size).unwrap_or(u64::MAX) {
+                        return Err(NetflowError::Configuration(format!(
+                            "--unique-flows {} needs {} distinct flows from flow {}, which has {} source address(es) of {} ports each; add address_pools or a source address",
+                            total, size, index, addresses, PORTS_PER_ADDRESS
+                        )));
+                    }
+                    Ok(KeyRange {
+                        offset,
+                        stride,
+                        size,
+                        limit,
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Source addresses unique flows of a prototype can use: the flow's source
+/// pools, else every address counting up from the prototype's, else only
+/// its ports vary
+fn source_addresses(flow: &FlowConfig, prototype: &Mapping, keys: &RecordKeys) -> u64 {
+    let pools = flow
+        .address_pools()
+        .map_or(0, |pools| pool_size(&pools.source));
+    if pools > 0 {
+        pools
+    } else if prototype
+        .get(keys.src_addr)
+        .and_then(Value::as_str)
+        .is_some_and(|addr| addr.parse::<Ipv4Addr>().is_ok())
+    {
+        IPV4_ADDRESSES
+    } else {
+        1
+    }
+}
+
+/// Active flows of one data flowset
+#[derive(Debug)]
+struct FlowsetKeys {
+    range: KeyRange,
+    /// Index within the range of each active flow
+    active: Vec<u64>,
+    /// The indexes in `active`
+    in_use: HashSet<u64>,
+    /// Next index a churned flow tries
+    next: u64,
+    /// Provider values drawn for each active flow, by index
+    provided: HashMap<u64, Mapping>,
+}
+
+impl FlowsetKeys {
+    fn new(range: KeyRange) -> Self {
+        let size = u64::try_from(range.size).unwrap_or(u64::MAX);
+        Self {
+            range,
+            active: (0..size).collect(),
+            in_use: (0..size).collect(),
+            next: size,
+            provided: HashMap::new(),
+        }
+    }
+
+    /// An index no active flow has, never-used ones first, or `None` when
+    /// every key of the range is active
+    fn unused(&mut self) -> Option<u64> {
+        if self.range.limit <= u64::try_from(self.in_use.len()).unwrap_or(u64::MAX) {
+            return None;
+        }
+        loop {
+            let index = self.next.checked_rem(self.range.limit)?;
+            self.next = self.next.wrapping_add(1);
+            if !self.in_use.contains(&index) {
+                return Some(index);
+            }
+        }
+    }
+}
+
+/// The distinct keys each data flowset cycles through, churning a share of them
+/// to never-used keys after every export
+#[derive(Debug)]
+pub struct UniqueFlows {
+    churn: f64,
+    source: Vec<AddressPool>,
+    destination: Vec<AddressPool>,
+    rng: SplitMix64,
+    /// Active flows of each data flowset with a prototype, in flowset order
+    flowsets: Vec<FlowsetKeys>,
+    exported: bool,
+}
+
+impl UniqueFlows {
+    pub fn new(ranges: Vec<KeyRange>, churn: f64, pools: Option<&AddressPoolsConfig>) -> Self {
+        Self {
+            churn,
+            source: pools.map_or_else(Vec::new, |pools| pools.source.clone()),
+            destination: pools.map_or_else(Vec::new, |pools| pools.destination.clone()),
+            rng: SplitMix64(pools.map_or(0, |pools| pools.seed)),
+            flowsets: ranges.into_iter().map(FlowsetKeys::new).collect(),
+            exported: false,
+        }
+    }
+
+    /// Replace the data records of `flow` with the active flows
+    ///
+    /// After the first export, each flow is replaced by a new one with
+    /// probability `churn`. The records are split over as many data
+    /// flowsets as needed for each to fit in one packet. V5 and V7 flows are
+    /// left alone. New flows draw their values from `providers`.
+    pub fn apply(&mut self, flow: &mut FlowConfig, providers: Option<&mut FieldProviders>) {
+        if self.exported {
+            for flowset in &mut self.flowsets {
+                for position in 0..flowset.active.len() {
+                    if self.rng.uniform() < self.churn
+                        && let Some(index) = flowset.unused()
+                    {
+                        flowset.in_use.remove(&flowset.active[position]);
+                        flowset.provided.remove(&flowset.active[position]);
+                        flowset.in_use.insert(index);
+                        flowset.active[position] = index;
+                    }
+                }
+            }
+        }
+        self.exported = true;
+
+        if let Some(providers) = providers {
+            for ((prototype, _), flowset) in prototypes(flow).into_iter().zip(&mut self.flowsets) {
+                for &index in &flowset.active {
+                    flowset
+                        .provided
+                        .entry(index)
+                        .or_insert_with(|| providers.draw(prototype));
+                }
+            }
+        }
+
+        // Records of each data flowset that fit in one packet, in flowset order
+        let mut per_packet = data_flowsets(flow)
+            .into_iter()
+            .map(|(template_id, _)| records_per_packet(flow, template_id))
+            .collect::<Vec<_>>()
+            .into_iter();
+        let mut flowsets = self.flowsets.iter();
+        match flow {
+            FlowConfig::V5(_) | FlowConfig::V7(_) => {}
+            FlowConfig::V9(config) => {
+                config.flowsets = std::mem::take(&mut config.flowsets)
+                    .into_iter()
+                    .flat_map(|flowset| match flowset {
+                        V9FlowSet::Data {
+                            template_id,
+                            records,
+                        } => self
+                            .expand(
+                                &records,
+                                &V9_KEYS,
+                                &mut flowsets,
+                                per_packet.next().unwrap_or(1),
+                            )
+                            .into_iter()
+                            .map(|records| V9FlowSet::Data {
+                                template_id,
+                                records,
+                            })
+                            .collect(),
+                        flowset => vec![flowset],
+                    })
+                    .collect();
+            }
+            FlowConfig::IPFix(config) => {
+                config.flowsets = std::mem::take(&mut config.flowsets)
+                    .into_iter()
+                    .flat_map(|flowset| match flowset {
+                        IPFixFlowSet::Data {
+                            template_id,
+                            records,
+                        } => self
+                            .expand(
+                                &records,
+                                &IPFIX_KEYS,
+                                &mut flowsets,
+                                per_packet.next().unwrap_or(1),
+                            )
+                            .into_iter()
+                            .map(|records| IPFixFlowSet::Data {
+                                template_id,
+                                records,
+                            })
+                            .collect(),
+                        flowset => vec![flowset],
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    /// Records of the active flows from the first of `records`, in chunks of `per_packet`
+    ///
+    /// A data flowset with a prototype takes the next of `flowsets`.
+    fn expand<'a>(
+        &self,
+        records: &[Value],
+        keys: &RecordKeys,
+        flowsets: &mut impl Iterator<Item = &'a FlowsetKeys>,
+        per_packet: usize,
+    ) -> Vec<Vec<Value>> {
+        let (Some(Value::Mapping(prototype)), Some(flowset)) = (records.first(), flowsets.next())
+        else {
+            return vec![records.to_vec()];
+        };
+        let records: Vec<Value> = flowset
+            .active
+            .iter()
+            .map(|&index| {
+                let mut record = self.record(prototype, flowset.range.key(index), keys);
+                // Key fields stay unique; the others get the flow's own values
+                for (field, value) in flowset.provided.get(&index).into_iter().flatten() {
+                    if record.get(field) == prototype.get(field) {
+                        record.insert(field.clone(), value.clone());
+                    }
+                }
+                Value::Mapping(record)
+            })
+            .collect();
+        records.chunks(per_packet).map(<[Value]>::to_vec).collect()
+    }
+
+    /// The prototype record with the keys of flow `key`
+    fn record(&self, prototype: &Mapping, key: u64, keys: &RecordKeys) -> Mapping {
+        let mut record = prototype.clone();
+        let port_offset = u16::try_from(key % PORTS_PER_ADDRESS).unwrap_or(0);
+        let host = key / PORTS_PER_ADDRESS;
+        let addr = |field: &str| {
+            prototype
+                .get(field)
+                .and_then(Value::as_str)
+                .and_then(|addr| addr.parse::<Ipv4Addr>().ok())
+        };
+
+        let src_addr = nth_address(&self.source, host).or_else(|| {
+            addr(keys.src_addr).map(|base| {
+                let host = u32::try_from(host % IPV4_ADDRESSES).unwrap_or(0);
+                Ipv4Addr::from(u32::from(base).wrapping_add(host))
+            })
+        });
+        if let Some(src_addr) = src_addr {
+            record.insert(keys.src_addr.into(), src_addr.to_string().into());
+        }
+        // Destinations spread over their pools without affecting uniqueness
+        if let Some(dst_addr) = nth_address(&self.destination, key) {
+            record.insert(keys.dst_addr.into(), dst_addr.to_string().into());
+        }
+        record.insert(
+            keys.src_port.into(),
+            DYNAMIC_PORT_START.saturating_add(port_offset).into(),
+        );
+        record
+    }
+}
+
+/// Addresses in a list of pools
+fn pool_size(pools: &[AddressPool]) -> u64 {
+    pools
+        .iter()
+        .map(|pool| pool.prefix.size())
+        .fold(0, u64::saturating_add)
+}
+
+/// Address `index` counting through the pools in order, wrapping after the
+/// last, or `None` without pools
+///
+/// Source keys never wrap, as `key_ranges` keeps them within the pools.
+fn nth_address(pools: &[AddressPool], index: u64) -> Option<Ipv4Addr> {
+    let mut offset = index.checked_rem(pool_size(pools))?;
+    pools.iter().find_map(|pool| {
+        if offset < pool.prefix.size() {
+            Some(pool.prefix.nth(offset))
+        } else {
+            offset = offset.saturating_sub(pool.prefix.size());
+            None
+        }
+    })
+}
+
+/// Template ID and prototype record of each of a V9/IPFIX flow's data flowsets
+fn data_flowsets(flow: &FlowConfig) -> Vec<(u16, Option<&Mapping>)> {
+    fn prototype(records: &[Value]) -> Option<&Mapping> {
+        match records.first() {
+            Some(Value::Mapping(prototype)) => Some(prototype),
+            _ => None,
+        }
+    }
+    match flow {
+        FlowConfig::V9(config) => config
+            .flowsets
+            .iter()
+            .filter_map(|flowset| match flowset {
+                V9FlowSet::Data {
+                    template_id,
+                    records,
+                } => Some((*template_id, prototype(records))),
+                _ => None,
+            })
+            .collect(),
+        FlowConfig::IPFix(config) => config
+            .flowsets
+            .iter()
+            .filter_map(|flowset| match flowset {
+                IPFixFlowSet::Data {
+                    template_id,
+                    records,
+                } => Some((*template_id, prototype(records))),
+                _ => None,
+            })
+            .collect(),
+        FlowConfig::V5(_) | FlowConfig::V7(_) => Vec::new(),
+    }
+}
+
+/// Prototype records of a flow's data flowsets, with the flow's key fields
+fn prototypes(flow: &FlowConfig) -> Vec<(&Mapping, &'static RecordKeys)> {
+    let keys = match flow {
+        FlowConfig::IPFix(_) => &IPFIX_KEYS,
+        _ => &V9_KEYS,
+    };
+    data_flowsets(flow)
+        .into_iter()
+        .filter_map(|(_, prototype)| prototype.map(|prototype| (prototype, keys)))
+        .collect()
+}
+
+/// Data packets one export of a V9/IPFIX flow sends with its unique flow
+/// `ranges`, or with one packet per data flowset without them
+pub fn data_packets(flow: &FlowConfig, ranges: &[KeyRange]) -> usize {
+    let mut ranges = ranges.iter();
+    data_flowsets(flow)
+        .into_iter()
+        .map(|(template_id, prototype)| {
+            let size = prototype
+                .and_then(|_| ranges.next())
+                .map_or(1, |range| range.size);
+            size.div_ceil(records_per_packet(flow, template_id)).max(1)
+        })
+        .fold(0, usize::saturating_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+    use std::collections::HashSet;
+
+    /// Source keys of every record after one export
+    fn export(flows: &mut UniqueFlows, flow: &FlowConfig) -> Vec<(String, u64)> {
+        let mut flow = flow.clone();
+        flows.apply(&mut flow, None);
+        let FlowConfig::V9(config) = flow else {
+            panic!("expected a V9 flow");
+        };
+        config
+            .flowsets
+            .iter()
+            .flat_map(|flowset| match flowset {
+                V9FlowSet::Data { records, .. } => {
+                    // (1472 - 24) / 6 bytes per record
+                    assert!(records.len() <= 241);
+                    records.clone()
+                }
+                _ => Vec::new(),
+            })
+            .map(|record| {
+                (
+                    record["src_addr"].as_str().unwrap_or_default().to_string(),
+                    record["src_port"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unique_flows_and_churn() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    address_pools:
+      source:
+        - prefix: "10.9.0.0/30"
+          weight: 1
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IPV4_SRC_ADDR
+            field_length: 4
+          - field_type: L4_SRC_PORT
+            field_length: 2
+      - type: data
+        template_id: 256
+        records:
+          - src_addr: "192.168.0.1"
+            src_port: 1234
+"#,
+        )
+        .unwrap();
+        let flow = &config.flows[0];
+
+        let ranges = |total| key_ranges(&config.flows, total).unwrap().remove(0);
+
+        // 40000 flows exhaust the ports of 10.9.0.0 and spill onto 10.9.0.1
+        let mut flows = UniqueFlows::new(ranges(40_000), 0.0, flow.address_pools());
+        let first = export(&mut flows, flow);
+        assert_eq!(first.len(), 40_000);
+        assert_eq!(first.iter().collect::<HashSet<_>>().len(), 40_000);
+        assert!(first.iter().all(|(addr, _)| addr.starts_with("10.9.0.")));
+        assert_eq!(first[0], ("10.9.0.0".to_string(), 49152));
+        // Without churn the same flows are re-sent
+        assert_eq!(export(&mut flows, flow), first);
+
+        // Churned flows get keys never used before, so flows stay distinct
+        let mut flows = UniqueFlows::new(ranges(100), 0.5, None);
+        let first = export(&mut flows, flow);
+        let second = export(&mut flows, flow);
+        let kept = first.iter().filter(|key| second.contains(key)).count();
+        assert!((20..80).contains(&kept), "kept {}", kept);
+        assert_eq!(second.iter().collect::<HashSet<_>>().len(), 100);
+        assert!(second.iter().all(|(addr, _)| addr == "192.168.0.1"));
+
+        // The pool's 4 addresses give at most 4 * 16384 distinct flows
+        assert!(key_ranges(&config.flows, 65_536).is_ok());
+        assert!(key_ranges(&config.flows, 65_537).is_err());
+    }
+
+    #[test]
+    fn test_unique_flows_budget() {
+        let config = parse_yaml_str(
+            r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: L4_SRC_PORT
+            field_length: 2
+      - type: data
+        template_id: 256
+        records:
+          - src_port: 1
+      - type: data
+        template_id: 256
+        records:
+          - src_port: 2
+"#,
+        )
+        .unwrap();
+        let flow = &config.flows[0];
+
+        // N is split over both data flowsets, which never share a key
+        let mut flows =
+            UniqueFlows::new(key_ranges(&config.flows, 5).unwrap().remove(0), 0.0, None);
+        let ports: Vec<u64> = export(&mut flows, flow)
+            .into_iter()
+            .map(|(_, port)| port)
+            .collect();
+        assert_eq!(ports, [49152, 49154, 49156, 49153, 49155]);
+
+        // Without a source address only the 16384 ports vary
+        assert!(key_ranges(&config.flows, 16_384).is_ok());
+        assert!(key_ranges(&config.flows, 16_385).is_err());
+        assert!(key_ranges(&config.flows, 1).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::config::schema::V5Config;
 use crate::error::{NetflowError, Result};
+use crate::generator::packet_sizes::{derive_octets, octets_from_payload};
 use netflow_parser::static_versions::v5::{FlowSet, Header, V5};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -9,6 +10,59 @@ use std::time::{SystemTime, UNIX_EPOCH};
 /// * `config` - V5 configuration
 /// * `override_sequence` - Optional sequence number to use (overrides config value)
 pub fn build_v5_packet(config: V5Config, override_sequence: Option<u32>) -> Result<Vec<u8>> {
+    build_v5_packet_with_interval(&config, override_sequence, None)
+}
+
+/// Build NetFlow V5 packets, one per entry in `sampling_interval_sweep`
+///
+/// Without a sweep this produces a single packet, as `build_v5_packet` does.
+/// Each swept packet carries the same flow records, so the sequence number
+/// advances by the record count per packet.
+///
+/// # Returns
+/// * `(packets, next_sequence)` - Generated packets and the next sequence number to use
+pub fn build_v5_packets(
+    config: V5Config,
+    override_sequence: Option<u32>,
+) -> Result<(Vec<Vec<u8>>, u32)> {
+    let sweep = config
+        .header
+        .as_ref()
+        .and_then(|h| h.sampling_interval_sweep.clone())
+        .filter(|sweep| !sweep.is_empty());
+
+    let mut sequence = override_sequence
+        .or_else(|| config.header.as_ref().and_then(|h| h.flow_sequence))
+        .unwrap_or(0);
+    let record_count = u32::try_from(config.flowsets.len())
+        .map_err(|_| NetflowError::Generation("Too many flowsets (max 65535)".to_string()))?;
+
+    let intervals: Vec<Option<u16>> = match sweep {
+        Some(sweep) => sweep.into_iter().map(Some).collect(),
+        None => vec![None],
+    };
+
+    let mut packets = Vec::with_capacity(intervals.len());
+    for interval in intervals {
+        packets.push(build_v5_packet_with_interval(
+            &config,
+            Some(sequence),
+            interval,
+        )?);
+        sequence = sequence
+            .checked_add(record_count)
+            .ok_or_else(|| NetflowError::Generation("Sequence number overflow".to_string()))?;
+    }
+
+    Ok((packets, sequence))
+}
+
+/// Build a single V5 packet, optionally overriding the header sampling interval
+fn build_v5_packet_with_interval(
+    config: &V5Config,
+    override_sequence: Option<u32>,
+    sampling_interval: Option<u16>,
+) -> Result<Vec<u8>> {
     if config.flowsets.is_empty() {
         return Err(NetflowError::Generation(
             "V5 configuration must contain at least one flowset".to_string(),
@@ -16,34 +70,51 @@ pub fn build_v5_packet(config: V5Config, override_sequence: Option<u32>) -> Resu
     }
 
     // Build header with defaults where needed
-    let header = build_header(&config, override_sequence)?;
+    let mut header = build_header(config, override_sequence)?;
+    if let Some(interval) = sampling_interval {
+        header.sampling_interval = interval;
+    }
 
     // Build flowsets
     let flowsets: Vec<FlowSet> = config
         .flowsets
         .iter()
-        .map(|fs| FlowSet {
-            src_addr: fs.src_addr,
-            dst_addr: fs.dst_addr,
-            next_hop: fs.next_hop,
-            input: fs.input,
-            output: fs.output,
-            d_pkts: fs.d_pkts,
-            d_octets: fs.d_octets,
-            first: fs.first,
-            last: fs.last,
-            src_port: fs.src_port,
-            dst_port: fs.dst_port,
-            pad1: 0,
-            tcp_flags: fs.tcp_flags,
-            protocol_number: fs.protocol,
-            protocol_type: netflow_parser::protocol::ProtocolTypes::from(fs.protocol),
-            tos: fs.tos,
-            src_as: fs.src_as,
-            dst_as: fs.dst_as,
-            src_mask: fs.src_mask,
-            dst_mask: fs.dst_mask,
-            pad2: 0,
+        .map(|fs| {
+            let protocol = fs.protocol.unwrap_or(0);
+            FlowSet {
+                src_addr: fs.src_addr,
+                dst_addr: fs.dst_addr,
+                next_hop: fs.next_hop,
+                input: fs.input,
+                output: fs.output,
+                d_pkts: fs.d_pkts,
+                d_octets: fs
+                    .d_octets
+                    .unwrap_or_else(|| match config.average_payload_size {
+                        Some(payload_size) => {
+                            octets_from_payload(fs.d_pkts, payload_size, protocol)
+                        }
+                        None => derive_octets(
+                            fs.d_pkts,
+                            config.packet_sizes.as_deref(),
+                            (fs.src_addr, fs.dst_addr, fs.src_port, fs.dst_port, protocol),
+                        ),
+                    }),
+                first: fs.first.resolve(header.sys_up_time),
+                last: fs.last.resolve(header.sys_up_time),
+                src_port: fs.src_port,
+                dst_port: fs.dst_port,
+                pad1: fs.pad1.unwrap_or(0),
+                tcp_flags: fs.tcp_flags,
+                protocol_number: protocol,
+                protocol_type: netflow_parser::protocol::ProtocolTypes::from(protocol),
+                tos: fs.tos.unwrap_or(0),
+                src_as: fs.src_as.unwrap_or(0),
+                dst_as: fs.dst_as.unwrap_or(0),
+                src_mask: fs.src_mask,
+                dst_mask: fs.dst_mask,
+                pad2: fs.pad2.unwrap_or(0),
+            }
         })
         .collect();
 
@@ -125,6 +196,7 @@ fn build_header(config: &V5Config, override_sequence: Option<u32>) -> Result<Hea
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::schema::CounterMode;
     use crate::config::schema::V5FlowSet as ConfigV5FlowSet;
     use netflow_parser::NetflowParser;
     use std::net::Ipv4Addr;
@@ -133,6 +205,16 @@ mod tests {
     fn test_build_v5_packet() {
         let config = V5Config {
             header: None,
+            start_delay: None,
+            export_interval: None,
+            tcp_flag_sequence: None,
+            packet_sizes: None,
+            average_payload_size: None,
+            address_pools: None,
+            counter_mode: CounterMode::Delta,
+            dscp_distribution: None,
+            protocol_distribution: None,
+            flow_size_distribution: None,
             flowsets: vec![ConfigV5FlowSet {
                 src_addr: Ipv4Addr::new(192, 168, 1, 10),
                 dst_addr: Ipv4Addr::new(10, 0, 0, 50),
@@ -140,26 +222,125 @@ mod tests {
                 input: 1,
                 output: 2,
                 d_pkts: 100,
-                d_octets: 65000,
-                first: 350000,
-                last: 360000,
+                d_octets: Some(65000),
+                first: 350000.into(),
+                last: 360000.into(),
                 src_port: 54321,
                 dst_port: 443,
                 tcp_flags: 0x18,
-                protocol: 6,
-                tos: 0,
-                src_as: 65001,
-                dst_as: 65002,
+                protocol: Some(6),
+                tos: Some(0),
+                src_as: Some(65001),
+                dst_as: Some(65002),
                 src_mask: 24,
                 dst_mask: 24,
+                pad1: None,
+                pad2: None,
             }],
         };
 
-        let packet = build_v5_packet(config, None).unwrap();
+        let packet = build_v5_packet(config.clone(), None).unwrap();
 
         // Verify packet can be parsed back
         let mut parser = NetflowParser::default();
         let parsed = parser.parse_bytes(&packet);
         assert_eq!(parsed.packets.len(), 1);
+
+        // Pad fields default to zero and can be overridden
+        // (record starts at byte 24; pad1 is at offset 36, pad2 at 46..48)
+        assert_eq!(
+            (packet[24 + 36], &packet[24 + 46..24 + 48]),
+            (0, &[0u8, 0][..])
+        );
+        let mut config = config;
+        config.flowsets[0].pad1 = Some(0xAB);
+        config.flowsets[0].pad2 = Some(0x0102);
+        let packet = build_v5_packet(config, None).unwrap();
+        assert_eq!(
+            (packet[24 + 36], &packet[24 + 46..24 + 48]),
+            (0xAB, &[1u8, 2][..])
+        );
+    }
+
+    #[test]
+    fn test_sampling_interval_sweep() {
+        let yaml = r#"
+flows:
+  - version: v5
+    header:
+      sampling_interval_sweep: [1, 10, 100]
+    flowsets:
+      - src_addr: "192.168.1.10"
+        dst_addr: "10.0.0.50"
+        next_hop: "192.168.1.1"
+        input: 1
+        output: 2
+        d_pkts: 100
+        d_octets: 65000
+        first: 350000
+        last: 360000
+        src_port: 54321
+        dst_port: 443
+        tcp_flags: 0x18
+        protocol: 6
+        tos: 0
+        src_as: 65001
+        dst_as: 65002
+        src_mask: 24
+        dst_mask: 24
+"#;
+        let config = match crate::config::parse_yaml_str(yaml).unwrap().flows.remove(0) {
+            crate::config::FlowConfig::V5(config) => config,
+            _ => panic!("expected a V5 flow"),
+        };
+
+        let (packets, next_seq) = build_v5_packets(config, Some(5)).unwrap();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(next_seq, 8);
+
+        // sampling_interval is the last header field (bytes 22..24), flow_sequence is 16..20
+        for (packet, (interval, seq)) in packets.iter().zip([(1u16, 5u32), (10, 6), (100, 7)]) {
+            assert_eq!(&packet[22..24], &interval.to_be_bytes());
+            assert_eq!(&packet[16..20], &seq.to_be_bytes());
+        }
+    }
+
+    #[test]
+    fn test_relative_first_last() {
+        let yaml = r#"
+flows:
+  - version: v5
+    header:
+      sys_up_time: 360000
+    flowsets:
+      - src_addr: "192.168.1.10"
+        dst_addr: "10.0.0.50"
+        next_hop: "192.168.1.1"
+        input: 1
+        output: 2
+        d_pkts: 100
+        first: "-60s"
+        last: "-0s"
+        src_port: 54321
+        dst_port: 443
+        tcp_flags: 0x18
+        protocol: 6
+        tos: 0
+        src_as: 65001
+        dst_as: 65002
+        src_mask: 24
+        dst_mask: 24
+"#;
+        let config = match crate::config::parse_yaml_str(yaml).unwrap().flows.remove(0) {
+            crate::config::FlowConfig::V5(config) => config,
+            _ => panic!("expected a V5 flow"),
+        };
+
+        // first/last follow the 24-byte header at record offsets 24..28 and 28..32
+        let packet = build_v5_packet(config, None).unwrap();
+        assert_eq!(&packet[48..52], &300000u32.to_be_bytes());
+        assert_eq!(&packet[52..56], &360000u32.to_be_bytes());
+
+        assert!(crate::config::parse_yaml_str(&yaml.replace("\"-60s\"", "\"60s\"")).is_err());
     }
 }
@@ -1,5 +1,6 @@
 use crate::config::schema::V7Config;
 use crate::error::{NetflowError, Result};
+use crate::generator::packet_sizes::{derive_octets, octets_from_payload};
 use netflow_parser::static_versions::v7::{FlowSet, Header, V7};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -18,29 +19,43 @@ pub fn build_v7_packet(config: V7Config) -> Result<Vec<u8>> {
     let flowsets: Vec<FlowSet> = config
         .flowsets
         .iter()
-        .map(|fs| FlowSet {
-            src_addr: fs.src_addr,
-            dst_addr: fs.dst_addr,
-            next_hop: fs.next_hop,
-            input: fs.input,
-            output: fs.output,
-            d_pkts: fs.d_pkts,
-            d_octets: fs.d_octets,
-            first: fs.first,
-            last: fs.last,
-            src_port: fs.src_port,
-            dst_port: fs.dst_port,
-            flags_fields_valid: fs.flags,
-            tcp_flags: fs.tcp_flags,
-            protocol_number: fs.protocol,
-            protocol_type: netflow_parser::protocol::ProtocolTypes::from(fs.protocol),
-            tos: fs.tos,
-            src_as: fs.src_as,
-            dst_as: fs.dst_as,
-            src_mask: fs.src_mask,
-            dst_mask: fs.dst_mask,
-            flags_fields_invalid: fs.flags2,
-            router_src: fs.router_src,
+        .map(|fs| {
+            let protocol = fs.protocol.unwrap_or(0);
+            FlowSet {
+                src_addr: fs.src_addr,
+                dst_addr: fs.dst_addr,
+                next_hop: fs.next_hop,
+                input: fs.input,
+                output: fs.output,
+                d_pkts: fs.d_pkts,
+                d_octets: fs
+                    .d_octets
+                    .unwrap_or_else(|| match config.average_payload_size {
+                        Some(payload_size) => {
+                            octets_from_payload(fs.d_pkts, payload_size, protocol)
+                        }
+                        None => derive_octets(
+                            fs.d_pkts,
+                            config.packet_sizes.as_deref(),
+                            (fs.src_addr, fs.dst_addr, fs.src_port, fs.dst_port, protocol),
+                        ),
+                    }),
+                first: fs.first.resolve(header.sys_up_time),
+                last: fs.last.resolve(header.sys_up_time),
+                src_port: fs.src_port,
+                dst_port: fs.dst_port,
+                flags_fields_valid: fs.flags,
+                tcp_flags: fs.tcp_flags,
+                protocol_number: protocol,
+                protocol_type: netflow_parser::protocol::ProtocolTypes::from(protocol),
+                tos: fs.tos.unwrap_or(0),
+                src_as: fs.src_as.unwrap_or(0),
+                dst_as: fs.dst_as.unwrap_or(0),
+                src_mask: fs.src_mask,
+                dst_mask: fs.dst_mask,
+                flags_fields_invalid: fs.flags2,
+                router_src: fs.router_src,
+            }
         })
         .collect();
 
@@ -105,6 +120,7 @@ fn build_header(config: &V7Config) -> Result<Header> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::schema::CounterMode;
     use crate::config::schema::V7FlowSet as ConfigV7FlowSet;
     use netflow_parser::NetflowParser;
     use std::net::Ipv4Addr;
@@ -113,6 +129,16 @@ mod tests {
     fn test_build_v7_packet() {
         let config = V7Config {
             header: None,
+            start_delay: None,
+            export_interval: None,
+            tcp_flag_sequence: None,
+            packet_sizes: None,
+            average_payload_size: None,
+            address_pools: None,
+            counter_mode: CounterMode::Delta,
+            dscp_distribution: None,
+            protocol_distribution: None,
+            flow_size_distribution: None,
             flowsets: vec![ConfigV7FlowSet {
                 src_addr: Ipv4Addr::new(10, 1, 1, 5),
                 dst_addr: Ipv4Addr::new(172, 16, 0, 100),
@@ -120,17 +146,17 @@ mod tests {
                 input: 10,
                 output: 20,
                 d_pkts: 250,
-                d_octets: 150000,
-                first: 350000,
-                last: 360000,
+                d_octets: Some(150000),
+                first: 350000.into(),
+                last: 360000.into(),
                 src_port: 12345,
                 dst_port: 80,
                 flags: 0,
                 tcp_flags: 0x02,
-                protocol: 6,
-                tos: 0,
-                src_as: 64512,
-                dst_as: 64513,
+                protocol: Some(6),
+                tos: Some(0),
+                src_as: Some(64512),
+                dst_as: Some(64513),
                 src_mask: 16,
                 dst_mask: 24,
                 flags2: 0,
@@ -1,10 +1,69 @@
-use crate::config::schema::{V9Config, V9FlowSet as ConfigV9FlowSet};
+use crate::config::schema::{PadFlowset, V9Config, V9FlowSet as ConfigV9FlowSet, V9TemplateField};
 use crate::error::{NetflowError, Result};
+use crate::generator::GeneratorOptions;
+use crate::generator::bgp::expand_as_path;
+use crate::generator::export_lag::{lag_timestamp, v9_timestamp_unit};
+use crate::generator::expression::resolve_value;
 use crate::generator::field_serializer::{
-    get_field_value, serialize_field_value, v9_field_id_to_name,
+    apply_record_order, get_field_value, serialize_field_value, v9_field_id_to_name,
 };
+use std::ops::Range;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Flow duration (ms) used when deriving FIRST_SWITCHED with --first-switched-now
+const DEFAULT_FLOW_DURATION_MS: u32 = 10_000;
+
+/// A V9 template or options template as sent on the wire
+///
+/// Regular templates have no scope fields. Options templates (RFC 3954
+/// section 6.1) describe scope fields, whose types come from a separate
+/// space (SYSTEM, INTERFACE, ...), followed by option fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct V9Template {
+    pub template_id: u16,
+    pub scope_fields: Vec<V9TemplateField>,
+    pub fields: Vec<V9TemplateField>,
+}
+
+impl V9Template {
+    /// Build the template for a template or options template flowset, if it is one
+    pub fn from_flowset(flowset: &ConfigV9FlowSet) -> Result<Option<Self>> {
+        match flowset {
+            ConfigV9FlowSet::Template {
+                template_id,
+                fields,
+            } => Ok(Some(Self {
+                template_id: *template_id,
+                scope_fields: Vec::new(),
+                fields: fields.clone(),
+            })),
+            ConfigV9FlowSet::OptionsTemplate {
+                template_id,
+                scope_fields,
+                fields,
+            } => {
+                // An options template without scope would be sent as a regular template
+                if scope_fields.is_empty() {
+                    return Err(NetflowError::Generation(format!(
+                        "Options template {} needs at least one scope field",
+                        template_id
+                    )));
+                }
+                Ok(Some(Self {
+                    template_id: *template_id,
+                    scope_fields: scope_fields.clone(),
+                    fields: fields.clone(),
+                }))
+            }
+            ConfigV9FlowSet::Data { .. } => Ok(None),
+        }
+    }
+
+    fn is_options(&self) -> bool {
+        !self.scope_fields.is_empty()
+    }
+}
+
 /// Build NetFlow V9 packets from configuration
 /// Generates proper template and data flowsets
 ///
@@ -12,6 +71,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 /// * `config` - V9 configuration
 /// * `override_sequence_number` - Optional sequence number to use (overrides config value)
 /// * `send_templates` - Whether to include template packets (for periodic refresh)
+/// * `options` - Packet building options shared across flows
 ///
 /// # Returns
 /// * `(packets, next_sequence_number)` - Generated packets and the next sequence number to use
@@ -19,55 +79,90 @@ pub fn build_v9_packets(
     config: V9Config,
     override_sequence_number: Option<u32>,
     send_templates: bool,
+    options: &GeneratorOptions,
+) -> Result<(Vec<Vec<u8>>, u32)> {
+    build_v9_packets_with_shared(
+        config,
+        override_sequence_number,
+        send_templates,
+        &[],
+        options,
+    )
+}
+
+/// Build NetFlow V9 packets, resolving data flowsets against `shared` templates too
+///
+/// `shared` holds templates defined by other flows of the same exporter
+/// (--shared-templates). They are only used to encode data flowsets whose
+/// template this flow doesn't define itself, and are never sent by this flow.
+pub fn build_v9_packets_with_shared(
+    config: V9Config,
+    override_sequence_number: Option<u32>,
+    send_templates: bool,
+    shared: &[V9Template],
+    options: &GeneratorOptions,
 ) -> Result<(Vec<Vec<u8>>, u32)> {
     let mut packets = Vec::new();
 
     // Get header values
     let (sys_up_time, unix_secs, mut sequence_number, source_id) =
-        get_header_values(&config, override_sequence_number)?;
+        get_header_values(&config, override_sequence_number, options.uptime_elapsed)?;
 
     // Separate templates and data flowsets
     let mut templates = Vec::new();
     let mut data_flowsets = Vec::new();
 
     for flowset in &config.flowsets {
-        match flowset {
-            ConfigV9FlowSet::Template {
-                template_id,
-                fields,
-            } => {
-                templates.push((*template_id, fields.clone()));
-            }
-            ConfigV9FlowSet::Data {
-                template_id,
-                records,
-            } => {
-                data_flowsets.push((*template_id, records.clone()));
-            }
+        if let ConfigV9FlowSet::Data {
+            template_id,
+            records,
+        } = flowset
+        {
+            data_flowsets.push((*template_id, records.clone()));
+        } else if let Some(template) = V9Template::from_flowset(flowset)? {
+            templates.push(template);
         }
     }
 
+    // A template-only flow has nothing to send once its templates are cached,
+    // so only a flow without any flowsets is an error
+    if templates.is_empty() && data_flowsets.is_empty() {
+        return Err(NetflowError::Generation(
+            "V9 configuration must contain at least one template or data flowset".to_string(),
+        ));
+    }
+
+    // Template each packet defines or uses, for its padding flowsets
+    let mut packet_templates: Vec<&V9Template> = Vec::new();
+
     // Generate template packet if we have templates AND send_templates is true
+    // (--suppress-templates withholds them to produce orphan data);
+    // a combined message always carries its flow's templates
     // Per RFC 3954: Template packets do NOT increment the sequence number
-    if !templates.is_empty() && send_templates {
+    let send_templates = send_templates || config.combined_message;
+    if !templates.is_empty() && send_templates && !options.suppress_templates {
         let template_packet = build_template_packet(
             sys_up_time,
             unix_secs,
             sequence_number,
             source_id,
             &templates,
+            options.options_first,
+            options.group_templates,
         )?;
         packets.push(template_packet);
+        packet_templates.extend(templates.first());
         // No sequence increment for template packets
     }
 
     // Generate data packets
+    let data_start = packets.len();
     for (template_id, records) in data_flowsets {
         // Find the template definition
-        let template_fields = templates
+        let template = templates
             .iter()
-            .find(|(id, _)| *id == template_id)
-            .map(|(_, fields)| fields)
+            .chain(shared)
+            .find(|template| template.template_id == template_id)
             .ok_or_else(|| {
                 NetflowError::Generation(format!(
                     "Data flowset references undefined template ID: {}",
@@ -75,16 +170,17 @@ pub fn build_v9_packets(
                 ))
             })?;
 
+        let record_bytes = encode_records(template, &records, sys_up_time, options)?;
         let data_packet = build_data_packet(
             sys_up_time,
             unix_secs,
             sequence_number,
             source_id,
-            template_id,
-            template_fields,
-            &records,
+            options.data_flowset_id.unwrap_or(template_id),
+            &record_bytes,
         )?;
         packets.push(data_packet);
+        packet_templates.push(template);
 
         // Per RFC 3954: Sequence number increments by the number of flow records
         let num_records = u32::try_from(records.len()).map_err(|_| {
@@ -95,18 +191,35 @@ pub fn build_v9_packets(
             .ok_or_else(|| NetflowError::Generation("Sequence number overflow".to_string()))?;
     }
 
-    if packets.is_empty() {
-        return Err(NetflowError::Generation(
-            "V9 configuration must contain at least one template or data flowset".to_string(),
-        ));
+    if config.combined_message && packets.len() > 1 {
+        packets = vec![combine_packets(&packets)?];
+        packet_templates.truncate(1);
+    } else if let Some(max_size) = config.pack_data_flowsets {
+        let data_packets = packets.split_off(data_start);
+        let data_templates = packet_templates.split_off(data_start);
+        for run in pack_runs(&data_packets, usize::from(max_size)) {
+            packet_templates.extend(data_templates.get(run.start).copied());
+            packets.push(combine_packets(data_packets.get(run).unwrap_or_default())?);
+        }
+    }
+    let min_target = options.min_packet_size.map(min_packet_target);
+    if let Some(target) = config.pad_to.map(usize::from).max(min_target) {
+        for (packet, template) in packets.iter_mut().zip(packet_templates) {
+            pad_packet(packet, target, template, config.pad_flowset)?;
+        }
     }
 
     Ok((packets, sequence_number))
 }
 
+/// Header values from the config, with defaults for anything not set
+///
+/// `uptime_elapsed` (from --advance-uptime) is added to the configured
+/// sys_up_time, wrapping at 2^32 ms like a real device's uptime counter.
 fn get_header_values(
     config: &V9Config,
     override_sequence_number: Option<u32>,
+    uptime_elapsed: Option<std::time::Duration>,
 ) -> Result<(u32, u32, u32, u32)> {
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -123,7 +236,8 @@ fn get_header_values(
         h.sys_up_time.unwrap_or(360000)
     } else {
         360000
-    };
+    }
+    .wrapping_add(uptime_elapsed.map_or(0, elapsed_millis));
 
     // Use override if provided, otherwise use config value, otherwise default to 0
     let sequence_number = if let Some(override_seq) = override_sequence_number {
@@ -149,7 +263,9 @@ pub fn build_template_packet_for_cache(
     unix_secs: u32,
     sequence_number: u32,
     source_id: u32,
-    templates: &[(u16, Vec<crate::config::schema::V9TemplateField>)],
+    templates: &[V9Template],
+    options_first: bool,
+    grouped: bool,
 ) -> Result<Vec<u8>> {
     build_template_packet(
         sys_up_time,
@@ -157,51 +273,116 @@ pub fn build_template_packet_for_cache(
         sequence_number,
         source_id,
         templates,
+        options_first,
+        grouped,
     )
 }
 
+/// Build a template packet, one flowset per template
+///
+/// With `options_first`, options templates are emitted before regular
+/// templates (each group keeping its order) for collectors that need the
+/// scope metadata registered first. With `grouped`, consecutive templates
+/// of the same kind share one flowset instead.
 fn build_template_packet(
     sys_up_time: u32,
     unix_secs: u32,
     sequence_number: u32,
     source_id: u32,
-    templates: &[(u16, Vec<crate::config::schema::V9TemplateField>)],
+    templates: &[V9Template],
+    options_first: bool,
+    grouped: bool,
 ) -> Result<Vec<u8>> {
+    let mut templates: Vec<&V9Template> = templates.iter().collect();
+    if options_first {
+        templates.sort_by_key(|template| !template.is_options());
+    }
+
     let mut packet = Vec::new();
 
     // V9 Header (20 bytes)
     packet.extend_from_slice(&9u16.to_be_bytes()); // Version
     let count = u16::try_from(templates.len())
         .map_err(|_| NetflowError::Generation("Too many templates (max 65535)".to_string()))?;
-    packet.extend_from_slice(&count.to_be_bytes()); // Count (number of flowsets)
+    packet.extend_from_slice(&count.to_be_bytes()); // Count (one record per template)
     packet.extend_from_slice(&sys_up_time.to_be_bytes());
     packet.extend_from_slice(&unix_secs.to_be_bytes());
     packet.extend_from_slice(&sequence_number.to_be_bytes());
     packet.extend_from_slice(&source_id.to_be_bytes());
 
-    // Template FlowSet
-    for (template_id, fields) in templates {
-        let flowset_id = 0u16; // 0 indicates template flowset
+    // Template FlowSets (0) and Options Template FlowSets (1)
+    let flowsets: Vec<&[&V9Template]> = if grouped {
+        templates
+            .chunk_by(|a, b| a.is_options() == b.is_options())
+            .collect()
+    } else {
+        templates.chunks(1).collect()
+    };
+    for flowset in flowsets {
+        let is_options = flowset
+            .first()
+            .is_some_and(|template| template.is_options());
+        let flowset_id: u16 = if is_options { 1 } else { 0 };
         packet.extend_from_slice(&flowset_id.to_be_bytes());
 
         // Calculate flowset length (will update later)
         let length_pos = packet.len();
         packet.extend_from_slice(&0u16.to_be_bytes()); // Placeholder for length
 
-        // Template ID and field count
-        packet.extend_from_slice(&template_id.to_be_bytes());
-        let field_count = u16::try_from(fields.len()).map_err(|_| {
-            NetflowError::Generation("Too many fields in template (max 65535)".to_string())
-        })?;
-        packet.extend_from_slice(&field_count.to_be_bytes());
+        for template in flowset {
+            packet.extend_from_slice(&template.template_id.to_be_bytes());
+            if template.is_options() {
+                // Options templates give the scope and option lengths in bytes (4 per field spec)
+                for fields in [&template.scope_fields, &template.fields] {
+                    let length = fields
+                        .len()
+                        .checked_mul(4)
+                        .and_then(|v| u16::try_from(v).ok())
+                        .ok_or_else(|| {
+                            NetflowError::Generation(
+                                "Too many fields in options template".to_string(),
+                            )
+                        })?;
+                    packet.extend_from_slice(&length.to_be_bytes());
+                }
+                for field in &template.scope_fields {
+                    let field_type =
+                        scope_field_name_to_id(&field.field_type).ok_or_else(|| {
+                            NetflowError::Generation(format!(
+                                "Unknown scope field type: {}",
+                                field.field_type
+                            ))
+                        })?;
+                    packet.extend_from_slice(&field_type.to_be_bytes());
+                    packet.extend_from_slice(&field.field_length.to_be_bytes());
+                }
+            } else {
+                // Field count
+                let field_count = u16::try_from(template.fields.len()).map_err(|_| {
+                    NetflowError::Generation("Too many fields in template (max 65535)".to_string())
+                })?;
+                packet.extend_from_slice(&field_count.to_be_bytes());
+            }
 
-        // Template fields
-        for field in fields {
-            let field_type = field_name_to_id(&field.field_type).ok_or_else(|| {
-                NetflowError::Generation(format!("Unknown field type: {}", field.field_type))
-            })?;
-            packet.extend_from_slice(&field_type.to_be_bytes());
-            packet.extend_from_slice(&field.field_length.to_be_bytes());
+            // Template fields
+            for field in &template.fields {
+                let field_type = field_name_to_id(&field.field_type).ok_or_else(|| {
+                    NetflowError::Generation(format!("Unknown field type: {}", field.field_type))
+                })?;
+                packet.extend_from_slice(&field_type.to_be_bytes());
+                packet.extend_from_slice(&field.field_length.to_be_bytes());
+            }
+        }
+
+        // Options template flowsets are padded to a 4-byte boundary
+        while is_options
+            && packet
+                .len()
+                .checked_sub(length_pos)
+                .and_then(|v| v.checked_add(2))
+                .is_some_and(|v| v % 4 != 0)
+        {
+            packet.push(0);
         }
 
         // Update flowset length (from flowset_id to end of this flowset)
@@ -220,49 +401,108 @@ fn build_template_packet(
     Ok(packet)
 }
 
+/// Serialize data records against a template, in the configured record order
+///
+/// Options data records carry their scope values first, read from the
+/// lowercase scope name (e.g. `system`).
+fn encode_records(
+    template: &V9Template,
+    records: &[serde_yaml::Value],
+    sys_up_time: u32,
+    options: &GeneratorOptions,
+) -> Result<Vec<u8>> {
+    let mut encoded = Vec::with_capacity(records.len());
+
+    for record in records {
+        let expanded = expand_as_path(record)?;
+        let record = expanded.as_ref().unwrap_or(record);
+        let mut record_bytes = Vec::new();
+        for field in &template.scope_fields {
+            let field_name = field.field_type.to_lowercase();
+            let value =
+                get_field_value(record, &field_name).unwrap_or(serde_yaml::Value::Number(0.into()));
+            let value = resolve_value(value, record)?;
+            record_bytes.extend_from_slice(&serialize_field_value(&value, field.field_length));
+        }
+        for field in &template.fields {
+            let field_type = field_name_to_id(&field.field_type).ok_or_else(|| {
+                NetflowError::Generation(format!("Unknown field type: {}", field.field_type))
+            })?;
+            let field_name = v9_field_id_to_name(field_type);
+
+            // Get field value from record, derive switched times if requested, or use zero
+            let explicit = get_field_value(record, field_name);
+            let advance_switched = explicit.is_some() && matches!(field_type, 21 | 22);
+            let value = explicit
+                .or_else(|| {
+                    if !options.first_switched_now {
+                        return None;
+                    }
+                    match field_type {
+                        21 => Some(serde_yaml::Value::Number(sys_up_time.into())),
+                        22 => Some(serde_yaml::Value::Number(
+                            sys_up_time.saturating_sub(DEFAULT_FLOW_DURATION_MS).into(),
+                        )),
+                        _ => None,
+                    }
+                })
+                .unwrap_or(serde_yaml::Value::Number(0.into()));
+            let mut value = resolve_value(value, record)?;
+
+            // Explicit switched times move with an advancing sys_up_time
+            if advance_switched
+                && let (Some(elapsed), Some(switched)) = (options.uptime_elapsed, value.as_u64())
+            {
+                let switched = u32::try_from(switched).unwrap_or(u32::MAX);
+                value = serde_yaml::Value::Number(
+                    switched.wrapping_add(elapsed_millis(elapsed)).into(),
+                );
+            }
+            if let (Some(lag), Some(unit)) = (options.export_lag, v9_timestamp_unit(field_type)) {
+                value = lag_timestamp(value, unit, lag);
+            }
+
+            // Serialize the field value
+            let bytes = serialize_field_value(&value, field.field_length);
+            record_bytes.extend_from_slice(&bytes);
+        }
+        encoded.push(record_bytes);
+    }
+
+    apply_record_order(&mut encoded, options.record_order);
+
+    Ok(encoded.concat())
+}
+
 fn build_data_packet(
     sys_up_time: u32,
     unix_secs: u32,
     sequence_number: u32,
     source_id: u32,
-    template_id: u16,
-    template_fields: &[crate::config::schema::V9TemplateField],
-    records: &[serde_yaml::Value],
+    flowset_id: u16,
+    record_bytes: &[u8],
 ) -> Result<Vec<u8>> {
     let mut packet = Vec::new();
 
     // V9 Header (20 bytes)
     packet.extend_from_slice(&9u16.to_be_bytes()); // Version
-    packet.extend_from_slice(&1u16.to_be_bytes()); // Count (1 data flowset)
+    // Count is the number of flowsets in the packet: a single data flowset,
+    // however many records it carries
+    packet.extend_from_slice(&1u16.to_be_bytes());
     packet.extend_from_slice(&sys_up_time.to_be_bytes());
     packet.extend_from_slice(&unix_secs.to_be_bytes());
     packet.extend_from_slice(&sequence_number.to_be_bytes());
     packet.extend_from_slice(&source_id.to_be_bytes());
 
     // Data FlowSet
-    packet.extend_from_slice(&template_id.to_be_bytes()); // FlowSet ID = Template ID
+    packet.extend_from_slice(&flowset_id.to_be_bytes()); // FlowSet ID = Template ID unless overridden
 
     // Calculate flowset length (will update later)
     let length_pos = packet.len();
     packet.extend_from_slice(&0u16.to_be_bytes()); // Placeholder for length
 
-    // Serialize each record
-    for record in records {
-        for field in template_fields {
-            let field_type = field_name_to_id(&field.field_type).ok_or_else(|| {
-                NetflowError::Generation(format!("Unknown field type: {}", field.field_type))
-            })?;
-            let field_name = v9_field_id_to_name(field_type);
-
-            // Get field value from record or use zero
-            let value =
-                get_field_value(record, field_name).unwrap_or(serde_yaml::Value::Number(0.into()));
-
-            // Serialize the field value
-            let bytes = serialize_field_value(&value, field.field_length);
-            packet.extend_from_slice(&bytes);
-        }
-    }
+    // Encoded records
+    packet.extend_from_slice(record_bytes);
 
     // Add padding if needed (flowset length must be multiple of 4)
     while packet
@@ -290,6 +530,175 @@ fn build_data_packet(
     Ok(packet)
 }
 
+/// Merge the packets of one export into a single packet (`combined_message`)
+///
+/// The first packet's header is kept with the count summed over all
+/// packets, and every packet's flowsets follow in order.
+fn combine_packets(packets: &[Vec<u8>]) -> Result<Vec<u8>> {
+    const HEADER_LEN: usize = 20;
+
+    let mut combined = packets.first().cloned().unwrap_or_default();
+    let mut count = 0u16;
+    for packet in packets {
+        let packet_count = packet
+            .get(2..4)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u16::from_be_bytes)
+            .ok_or_else(|| NetflowError::Generation("V9 packet too short".to_string()))?;
+        count = count.checked_add(packet_count).ok_or_else(|| {
+            NetflowError::Generation("Combined message count exceeds 65535".to_string())
+        })?;
+    }
+    for packet in packets.iter().skip(1) {
+        combined.extend_from_slice(packet.get(HEADER_LEN..).unwrap_or_default());
+    }
+
+    combined
+        .get_mut(2..4)
+        .ok_or_else(|| NetflowError::Generation("V9 packet too short".to_string()))?
+        .copy_from_slice(&count.to_be_bytes());
+    Ok(combined)
+}
+
+/// Runs of consecutive data packets that fit in `max_size` bytes once
+/// combined (`pack_data_flowsets`)
+///
+/// A packet larger than `max_size` forms a run of its own.
+fn pack_runs(packets: &[Vec<u8>], max_size: usize) -> Vec<Range<usize>> {
+    const HEADER_LEN: usize = 20;
+
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    let mut size = 0usize;
+    for (index, packet) in packets.iter().enumerate() {
+        let flowsets_len = packet.len().saturating_sub(HEADER_LEN);
+        match runs.last_mut() {
+            Some(run) if size.saturating_add(flowsets_len) <= max_size => {
+                run.end = index.saturating_add(1);
+                size = size.saturating_add(flowsets_len);
+            }
+            _ => {
+                runs.push(index..index.saturating_add(1));
+                size = packet.len();
+            }
+        }
+    }
+    runs
+}
+
+/// Pad `packet` to at least `min_packet_size` bytes (--min-packet-size)
+pub fn pad_to_min_size(
+    packet: &mut Vec<u8>,
+    min_packet_size: u16,
+    template: &V9Template,
+) -> Result<()> {
+    pad_packet(
+        packet,
+        min_packet_target(min_packet_size),
+        template,
+        PadFlowset::Data,
+    )
+}
+
+/// Padded length for --min-packet-size; V9 flowsets are 4-byte aligned, so
+/// the size is rounded up to a multiple of 4
+fn min_packet_target(min_packet_size: u16) -> usize {
+    usize::from(min_packet_size).next_multiple_of(4)
+}
+
+/// Append padding flowsets until `packet` is `target` bytes long (`pad_to`)
+///
+/// Reserved FlowSet IDs are rejected by some collectors, so by default each
+/// padding flowset is a data flowset of `template`, which the packet defines
+/// or uses, holding only zero padding shorter than one record: it decodes as
+/// a flowset with no records. `PadFlowset::Zero` instead appends one
+/// template flowset (ID 0) of zeros, as some exporters do. The header count
+/// includes the padding flowsets.
+fn pad_packet(
+    packet: &mut Vec<u8>,
+    target: usize,
+    template: &V9Template,
+    style: PadFlowset,
+) -> Result<()> {
+    const FLOWSET_HEADER_LEN: usize = 4;
+
+    // A template flowset needs at least one 4-byte (empty) template to parse
+    let (flowset_id, min_flowset_len, max_flowset_len) = match style {
+        PadFlowset::Data => {
+            let record_len = template
+                .scope_fields
+                .iter()
+                .chain(&template.fields)
+                .map(|field| usize::from(field.field_length))
+                .fold(0usize, usize::saturating_add);
+            if record_len == 0 {
+                return Err(NetflowError::Generation(format!(
+                    "Template {} has empty records and cannot carry padding",
+                    template.template_id
+                )));
+            }
+            // Largest 4-byte aligned flowset whose padding stays shorter than one record
+            let max_flowset_len = (record_len.saturating_sub(1) / 4 * 4)
+                .saturating_add(FLOWSET_HEADER_LEN)
+                .min(usize::from(u16::MAX) / 4 * 4);
+            (template.template_id, FLOWSET_HEADER_LEN, max_flowset_len)
+        }
+        PadFlowset::Zero => (0, FLOWSET_HEADER_LEN * 2, usize::from(u16::MAX) / 4 * 4),
+    };
+
+    let mut count = packet
+        .get(2..4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u16::from_be_bytes)
+        .ok_or_else(|| NetflowError::Generation("V9 packet too short".to_string()))?;
+    while packet.len() < target {
+        let flowset_len = target.saturating_sub(packet.len()).min(max_flowset_len);
+        if flowset_len < min_flowset_len {
+            return Err(NetflowError::Generation(format!(
+                "Cannot pad a {}-byte packet to {} bytes",
+                packet.len(),
+                target
+            )));
+        }
+        let length = u16::try_from(flowset_len)
+            .map_err(|_| NetflowError::Generation("Flowset length overflow".to_string()))?;
+        packet.extend_from_slice(&flowset_id.to_be_bytes());
+        packet.extend_from_slice(&length.to_be_bytes());
+        packet.resize(
+            packet
+                .len()
+                .saturating_add(flowset_len.saturating_sub(FLOWSET_HEADER_LEN)),
+            0,
+        );
+        count = count.checked_add(1).ok_or_else(|| {
+            NetflowError::Generation("Padded packet count exceeds 65535".to_string())
+        })?;
+    }
+
+    packet
+        .get_mut(2..4)
+        .ok_or_else(|| NetflowError::Generation("V9 packet too short".to_string()))?
+        .copy_from_slice(&count.to_be_bytes());
+    Ok(())
+}
+
+/// Milliseconds of `elapsed`, truncated to the 32-bit uptime counter
+pub(crate) fn elapsed_millis(elapsed: std::time::Duration) -> u32 {
+    // sys_up_time wraps every ~49.7 days, so keep only the low 32 bits
+    u32::try_from(elapsed.as_millis() & u128::from(u32::MAX)).unwrap_or(u32::MAX)
+}
+
+/// Map V9 options template scope names to scope field types (RFC 3954 section 6.1)
+fn scope_field_name_to_id(name: &str) -> Option<u16> {
+    match name {
+        "SYSTEM" => Some(1),
+        "INTERFACE" => Some(2),
+        "LINE_CARD" => Some(3),
+        "CACHE" => Some(4),
+        "TEMPLATE" => Some(5),
+        _ => None,
+    }
+}
+
 /// Map human-readable field names to NetFlow V9 field type IDs
 fn field_name_to_id(name: &str) -> Option<u16> {
     match name {
@@ -317,6 +726,660 @@ fn field_name_to_id(name: &str) -> Option<u16> {
         "FIRST_SWITCHED" => Some(22),
         "OUT_BYTES" => Some(23),
         "OUT_PKTS" => Some(24),
+        "MIN_PKT_LNGTH" => Some(25),
+        "MAX_PKT_LNGTH" => Some(26),
+        "MIN_TTL" => Some(52),
+        "MAX_TTL" => Some(53),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::RecordOrder;
+    use crate::config::parse_yaml_str;
+    use crate::config::schema::FlowConfig;
+    use netflow_parser::variable_versions::v9::FlowSetBody;
+    use netflow_parser::{NetflowPacket, NetflowParser};
+
+    fn v9_config(yaml: &str) -> V9Config {
+        match parse_yaml_str(yaml).unwrap().flows.remove(0) {
+            FlowConfig::V9(config) => config,
+            _ => panic!("expected a V9 flow"),
+        }
+    }
+
+    const TWO_RECORDS: &str = r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: L4_DST_PORT
+            field_length: 2
+          - field_type: PROTOCOL
+            field_length: 1
+          - field_type: SRC_TOS
+            field_length: 1
+      - type: data
+        template_id: 256
+        records:
+          - dst_port: 443
+            protocol: 6
+          - dst_port: 53
+            protocol: 17
+"#;
+
+    #[test]
+    fn test_build_v9_packets_parse_back() {
+        let (packets, next_seq) = build_v9_packets(
+            v9_config(TWO_RECORDS),
+            Some(10),
+            true,
+            &GeneratorOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(next_seq, 12);
+
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets.concat());
+        assert!(parsed.error.is_none());
+        assert_eq!(parsed.packets.len(), 2);
+    }
+
+    #[test]
+    fn test_shared_templates() {
+        let mut config = v9_config(TWO_RECORDS);
+        let shared: Vec<V9Template> = config
+            .flowsets
+            .iter()
+            .filter_map(|flowset| V9Template::from_flowset(flowset).unwrap())
+            .collect();
+        // A data-only flow whose template lives in another flow
+        config.flowsets.remove(0);
+        assert!(
+            build_v9_packets(config.clone(), None, true, &GeneratorOptions::default()).is_err()
+        );
+
+        let (packets, next_seq) = build_v9_packets_with_shared(
+            config,
+            Some(0),
+            true,
+            &shared,
+            &GeneratorOptions::default(),
+        )
+        .unwrap();
+        // No template packet of its own; the data uses template 256's 4-byte records
+        assert_eq!(packets.len(), 1);
+        assert_eq!(&packets[0][20..22], &256u16.to_be_bytes());
+        assert_eq!(packets[0].len(), 20 + 4 + 8);
+        assert_eq!(next_seq, 2);
+    }
+
+    #[test]
+    fn test_suppress_templates() {
+        let options = GeneratorOptions {
+            suppress_templates: true,
+            ..Default::default()
+        };
+        let (packets, _) = build_v9_packets(v9_config(TWO_RECORDS), None, true, &options).unwrap();
+
+        // Only the data packet is sent, and it still references template 256
+        assert_eq!(packets.len(), 1);
+        assert_eq!(&packets[0][20..22], &256u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_options_template_first() {
+        let config = v9_config(
+            r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IN_BYTES
+            field_length: 4
+      - type: options_template
+        template_id: 257
+        scope_fields:
+          - field_type: SYSTEM
+            field_length: 4
+        fields:
+          - field_type: IN_PKTS
+            field_length: 4
+      - type: data
+        template_id: 257
+        records:
+          - system: 1
+            in_pkts: 500
+"#,
+        );
+        let options = GeneratorOptions {
+            options_first: true,
+            ..Default::default()
+        };
+        let (packets, _) = build_v9_packets(config, None, true, &options).unwrap();
+
+        // Options template flowset (ID 1) leads the template packet
+        assert_eq!(&packets[0][20..22], &1u16.to_be_bytes());
+        assert_eq!(&packets[0][24..26], &257u16.to_be_bytes());
+
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets.concat());
+        assert!(parsed.error.is_none());
+        assert_eq!(parsed.packets.len(), 2);
+
+        // Options data carries the scope value before the option fields
+        let data = packets.last().unwrap();
+        assert_eq!(&data[20..22], &257u16.to_be_bytes());
+        assert_eq!(&data[24..32], &[0, 0, 0, 1, 0, 0, 1, 244]);
+    }
+
+    #[test]
+    fn test_header_count_matches_flowsets() {
+        let config = v9_config(
+            r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IN_BYTES
+            field_length: 4
+      - type: template
+        template_id: 257
+        fields:
+          - field_type: IN_PKTS
+            field_length: 4
+      - type: data
+        template_id: 256
+        records:
+          - in_bytes: 100
+          - in_bytes: 200
+          - in_bytes: 300
+"#,
+        );
+
+        let (packets, _) =
+            build_v9_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets.concat());
+        assert!(parsed.error.is_none());
+
+        let counts: Vec<_> = parsed
+            .packets
+            .iter()
+            .map(|packet| match packet {
+                NetflowPacket::V9(v9) => {
+                    assert_eq!(usize::from(v9.header.count), v9.flowsets.len());
+                    v9.header.count
+                }
+                other => panic!("expected a V9 packet, got {:?}", other),
+            })
+            .collect();
+        // Two template flowsets, then one data flowset carrying three records
+        assert_eq!(counts, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_grouped_template_flowsets() {
+        let config = v9_config(
+            r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IN_BYTES
+            field_length: 4
+      - type: template
+        template_id: 257
+        fields:
+          - field_type: IN_PKTS
+            field_length: 4
+      - type: options_template
+        template_id: 258
+        scope_fields:
+          - field_type: SYSTEM
+            field_length: 4
+        fields:
+          - field_type: IN_PKTS
+            field_length: 4
+"#,
+        );
+        let options = GeneratorOptions {
+            group_templates: true,
+            ..Default::default()
+        };
+        let (packets, _) = build_v9_packets(config, None, true, &options).unwrap();
+        assert_eq!(packets.len(), 1);
+
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets[0]);
+        assert!(parsed.error.is_none());
+        match parsed.packets.as_slice() {
+            [NetflowPacket::V9(v9)] => {
+                // The count is one per template, not one per flowset
+                assert_eq!(v9.header.count, 3);
+                match v9
+                    .flowsets
+                    .iter()
+                    .map(|flowset| &flowset.body)
+                    .collect::<Vec<_>>()
+                    .as_slice()
+                {
+                    [
+                        FlowSetBody::Template(templates),
+                        FlowSetBody::OptionsTemplate(options),
+                    ] => {
+                        assert_eq!(templates.templates.len(), 2);
+                        assert_eq!(options.templates.len(), 1);
+                    }
+                    other => panic!(
+                        "expected a template flowset and an options flowset, got {:?}",
+                        other
+                    ),
+                }
+            }
+            other => panic!("expected one V9 packet, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_combined_message() {
+        let config = v9_config(
+            r#"
+flows:
+  - version: v9
+    combined_message: true
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IN_BYTES
+            field_length: 4
+      - type: options_template
+        template_id: 257
+        scope_fields:
+          - field_type: SYSTEM
+            field_length: 4
+        fields:
+          - field_type: IN_PKTS
+            field_length: 4
+      - type: data
+        template_id: 256
+        records:
+          - in_bytes: 100
+      - type: data
+        template_id: 257
+        records:
+          - system: 1
+            in_pkts: 500
+"#,
+        );
+
+        // Templates travel with the data even when no refresh is due
+        let (packets, next_seq) =
+            build_v9_packets(config, Some(7), false, &GeneratorOptions::default()).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(next_seq, 9);
+
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets[0]);
+        assert!(parsed.error.is_none());
+        match parsed.packets.as_slice() {
+            [NetflowPacket::V9(v9)] => {
+                assert_eq!(v9.header.sequence_number, 7);
+                assert_eq!(usize::from(v9.header.count), v9.flowsets.len());
+                assert_eq!(v9.flowsets.len(), 4);
+            }
+            other => panic!("expected one V9 packet, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_pack_data_flowsets() {
+        let config = v9_config(
+            r#"
+flows:
+  - version: v9
+    pack_data_flowsets: 1400
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IN_BYTES
+            field_length: 4
+      - type: template
+        template_id: 257
+        fields:
+          - field_type: IN_PKTS
+            field_length: 4
+          - field_type: PROTOCOL
+            field_length: 1
+      - type: data
+        template_id: 256
+        records:
+          - in_bytes: 100
+          - in_bytes: 200
+      - type: data
+        template_id: 257
+        records:
+          - in_pkts: 5
+            protocol: 17
+"#,
+        );
+
+        let (packets, next_seq) =
+            build_v9_packets(config.clone(), Some(3), true, &GeneratorOptions::default()).unwrap();
+        // The template packet, then both data flowsets in one packet
+        assert_eq!(packets.len(), 2);
+        assert_eq!(next_seq, 6);
+
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets.concat());
+        assert!(parsed.error.is_none());
+        match parsed.packets.as_slice() {
+            [NetflowPacket::V9(_), NetflowPacket::V9(data)] => {
+                assert_eq!(data.header.sequence_number, 3);
+                assert_eq!(data.header.count, 2);
+                let records: Vec<usize> = data
+                    .flowsets
+                    .iter()
+                    .map(|flowset| match &flowset.body {
+                        FlowSetBody::Data(data) => data.fields.len(),
+                        other => panic!("expected a data flowset, got {:?}", other),
+                    })
+                    .collect();
+                assert_eq!(records, vec![2, 1]);
+            }
+            other => panic!("expected two V9 packets, got {:?}", other),
+        }
+
+        // Flowsets that would overflow the limit go into the next packet
+        let mut config = config;
+        config.pack_data_flowsets = Some(20);
+        let (packets, _) =
+            build_v9_packets(config, None, false, &GeneratorOptions::default()).unwrap();
+        assert_eq!(packets.len(), 2);
+    }
+
+    #[test]
+    fn test_pad_to() {
+        let yaml = r#"
+flows:
+  - version: v9
+    pad_to: 128
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IPV4_SRC_ADDR
+            field_length: 4
+          - field_type: IN_BYTES
+            field_length: 4
+          - field_type: IN_PKTS
+            field_length: 4
+      - type: data
+        template_id: 256
+        records:
+          - src_addr: "10.0.0.1"
+            in_bytes: 1000
+            in_pkts: 10
+"#;
+
+        let (packets, _) =
+            build_v9_packets(v9_config(yaml), None, true, &GeneratorOptions::default()).unwrap();
+        assert!(packets.iter().all(|packet| packet.len() == 128));
+
+        // Padding flowsets decode with no records and are included in the count
+        let mut parser = NetflowParser::default();
+        let parsed = parser.parse_bytes(&packets.concat());
+        assert!(parsed.error.is_none());
+        let mut records = 0;
+        for packet in &parsed.packets {
+            let NetflowPacket::V9(v9) = packet else {
+                panic!("expected a V9 packet, got {:?}", packet);
+            };
+            assert_eq!(usize::from(v9.header.count), v9.flowsets.len());
+            for flowset in &v9.flowsets {
+                if let FlowSetBody::Data(data) = &flowset.body {
+                    records += data.fields.len();
+                }
+            }
+        }
+        assert_eq!(records, 1);
+
+        // Or one FlowSet ID 0 flowset of zeros, as some exporters pad
+        let config = v9_config(&yaml.replace("pad_to: 128", "pad_to: 128\n    pad_flowset: zero"));
+        let (packets, _) =
+            build_v9_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+        assert!(packets.iter().all(|packet| packet.len() == 128));
+        let parsed = NetflowParser::default().parse_bytes(&packets.concat());
+        assert!(parsed.error.is_none());
+        for packet in &parsed.packets {
+            let NetflowPacket::V9(v9) = packet else {
+                panic!("expected a V9 packet, got {:?}", packet);
+            };
+            assert_eq!(usize::from(v9.header.count), v9.flowsets.len());
+            assert_eq!(v9.flowsets.last().unwrap().header.flowset_id, 0);
+        }
+    }
+
+    #[test]
+    fn test_override_data_flowset_id() {
+        let yaml = r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IN_BYTES
+            field_length: 4
+      - type: data
+        template_id: 256
+        records:
+          - in_bytes: 1000
+"#;
+        let options = GeneratorOptions {
+            data_flowset_id: Some(300),
+            ..GeneratorOptions::default()
+        };
+        let (packets, _) = build_v9_packets(v9_config(yaml), None, true, &options).unwrap();
+
+        // Only the data flowset is relabelled; the template still defines 256
+        assert_eq!(&packets[0][20..22], &0u16.to_be_bytes());
+        assert_eq!(&packets[0][24..26], &256u16.to_be_bytes());
+        assert_eq!(&packets[1][20..22], &300u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_in_out_counters() {
+        // Template names and record names agree in both directions
+        for name in ["IN_BYTES", "IN_PKTS", "OUT_BYTES", "OUT_PKTS"] {
+            let id = field_name_to_id(name).unwrap();
+            assert_eq!(v9_field_id_to_name(id), name.to_lowercase());
+        }
+
+        // An asymmetric download: few request bytes in, many response bytes out
+        let config = v9_config(
+            r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 258
+        fields:
+          - field_type: IN_BYTES
+            field_length: 4
+          - field_type: IN_PKTS
+            field_length: 4
+          - field_type: OUT_BYTES
+            field_length: 4
+          - field_type: OUT_PKTS
+            field_length: 4
+      - type: data
+        template_id: 258
+        records:
+          - in_bytes: 1200
+            in_pkts: 10
+            out_bytes: 850000
+            out_pkts: 600
+"#,
+        );
+
+        let (packets, _) =
+            build_v9_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+        // Header (20) + flowset header (4), then the four counters in template order
+        let expected: Vec<u8> = [1200u32, 10, 850000, 600]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        assert_eq!(&packets[1][24..40], expected.as_slice());
+
+        let mut parser = NetflowParser::default();
+        assert!(parser.parse_bytes(&packets.concat()).error.is_none());
+    }
+
+    #[test]
+    fn test_ttl_and_packet_length_fields() {
+        for name in ["MIN_PKT_LNGTH", "MAX_PKT_LNGTH", "MIN_TTL", "MAX_TTL"] {
+            let id = field_name_to_id(name).unwrap();
+            assert_eq!(v9_field_id_to_name(id), name.to_lowercase());
+        }
+
+        let config = v9_config(
+            r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 259
+        fields:
+          - field_type: MIN_TTL
+            field_length: 1
+          - field_type: MAX_TTL
+            field_length: 1
+          - field_type: MIN_PKT_LNGTH
+            field_length: 2
+          - field_type: MAX_PKT_LNGTH
+            field_length: 2
+      - type: data
+        template_id: 259
+        records:
+          - min_ttl: 52
+            max_ttl: 64
+            min_pkt_lngth: 40
+            max_pkt_lngth: 1500
+"#,
+        );
+
+        let (packets, _) =
+            build_v9_packets(config, None, true, &GeneratorOptions::default()).unwrap();
+        // Header (20) + flowset header (4), then the fields in template order
+        assert_eq!(&packets[1][24..30], &[52, 64, 0, 40, 0x05, 0xdc]);
+
+        let mut parser = NetflowParser::default();
+        assert!(parser.parse_bytes(&packets.concat()).error.is_none());
+    }
+
+    #[test]
+    fn test_record_order() {
+        let data_for = |record_order| {
+            let options = GeneratorOptions {
+                record_order,
+                ..Default::default()
+            };
+            let (packets, _) =
+                build_v9_packets(v9_config(TWO_RECORDS), None, false, &options).unwrap();
+            // Skip the 20-byte header and 4-byte flowset header
+            packets[0][24..32].to_vec()
+        };
+
+        let in_config_order = vec![0x01, 0xbb, 6, 0, 0x00, 0x35, 17, 0];
+        let reversed = vec![0x00, 0x35, 17, 0, 0x01, 0xbb, 6, 0];
+        assert_eq!(data_for(RecordOrder::Config), in_config_order);
+        assert_eq!(data_for(RecordOrder::Reverse), reversed);
+        assert_eq!(data_for(RecordOrder::Sorted), reversed);
+    }
+
+    #[test]
+    fn test_first_switched_now() {
+        let config = v9_config(
+            r#"
+flows:
+  - version: v9
+    header:
+      sys_up_time: 500000
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: FIRST_SWITCHED
+            field_length: 4
+          - field_type: LAST_SWITCHED
+            field_length: 4
+      - type: data
+        template_id: 256
+        records:
+          - in_bytes: 100
+          - first_switched: 1000
+            last_switched: 2000
+"#,
+        );
+        let options = GeneratorOptions {
+            first_switched_now: true,
+            ..Default::default()
+        };
+
+        let (packets, _) = build_v9_packets(config, None, false, &options).unwrap();
+        let data = &packets[0][24..40];
+        assert_eq!(&data[0..4], &490000u32.to_be_bytes());
+        assert_eq!(&data[4..8], &500000u32.to_be_bytes());
+        // Explicit values are kept
+        assert_eq!(&data[8..12], &1000u32.to_be_bytes());
+        assert_eq!(&data[12..16], &2000u32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_advance_uptime() {
+        let config = v9_config(
+            r#"
+flows:
+  - version: v9
+    header:
+      sys_up_time: 500000
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: FIRST_SWITCHED
+            field_length: 4
+          - field_type: IN_BYTES
+            field_length: 4
+      - type: data
+        template_id: 256
+        records:
+          - first_switched: 1000
+            in_bytes: 1000
+"#,
+        );
+        let options = GeneratorOptions {
+            uptime_elapsed: Some(std::time::Duration::from_millis(2500)),
+            ..Default::default()
+        };
+
+        let (packets, _) = build_v9_packets(config, None, false, &options).unwrap();
+        assert_eq!(&packets[0][4..8], &502500u32.to_be_bytes());
+        // Switched times shift with the uptime, other fields don't
+        assert_eq!(&packets[0][24..32], &[0, 0, 0x0d, 0xac, 0, 0, 0x03, 0xe8]);
+    }
+}
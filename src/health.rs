@@ -0,0 +1,128 @@
+use crate::error::{NetflowError, Result};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the responder thread checks for new connections and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Minimal HTTP responder for orchestrator liveness/readiness probes
+///
+/// `GET /healthz` returns 200 while the generator loop runs and 503 once
+/// shutdown has been requested; any other path returns 404. The responder
+/// thread stops when the server is dropped.
+pub struct HealthServer {
+    local_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl HealthServer {
+    /// Bind `addr` and serve health checks until dropped
+    ///
+    /// `shutdown` is the generator's shutdown flag; once set, probes get 503.
+    pub fn start(addr: SocketAddr, shutdown: Arc<AtomicBool>) -> Result<Self> {
+        let listener = TcpListener::bind(addr).map_err(|e| {
+            NetflowError::Network(format!("Failed to bind health endpoint {}: {}", addr, e))
+        })?;
+        listener.set_nonblocking(true).map_err(|e| {
+            NetflowError::Network(format!("Failed to configure health endpoint: {}", e))
+        })?;
+        let local_addr = listener.local_addr().map_err(|e| {
+            NetflowError::Network(format!("Failed to read health endpoint address: {}", e))
+        })?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        // A misbehaving client only affects its own probe
+                        let _ = respond(stream, shutdown.load(Ordering::Relaxed));
+                    }
+                    // No pending connection (WouldBlock) or a transient accept error
+                    Err(_) => thread::sleep(POLL_INTERVAL),
+                }
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// Address the endpoint is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for HealthServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Answer a single HTTP request and close the connection
+fn respond(mut stream: TcpStream, shutting_down: bool) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+
+    // Only the request line matters; probes send small requests
+    let mut buf = [0u8; 1024];
+    let len = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(buf.get(..len).unwrap_or_default());
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, body) = match path {
+        "/healthz" if shutting_down => ("503 Service Unavailable", "shutting down\n"),
+        "/healthz" => ("200 OK", "ok\n"),
+        _ => ("404 Not Found", "not found\n"),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_healthz_status() {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let server = HealthServer::start("127.0.0.1:0".parse().unwrap(), shutdown.clone()).unwrap();
+        let addr = server.local_addr();
+
+        assert!(get(addr, "/healthz").starts_with("HTTP/1.1 200 OK"));
+        assert!(get(addr, "/metrics").starts_with("HTTP/1.1 404"));
+
+        shutdown.store(true, Ordering::Relaxed);
+        assert!(get(addr, "/healthz").starts_with("HTTP/1.1 503"));
+
+        drop(server);
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
@@ -1,16 +1,23 @@
+mod bench;
 mod cli;
 mod config;
 mod error;
 mod generator;
+mod health;
+mod stats;
 mod template_cache;
 mod transmitter;
 
 use clap::Parser;
 use cli::Cli;
-use config::{FlowConfig, parse_yaml_file, validate_config};
+use config::{
+    FlowConfig, parse_template_with_records, parse_yaml_dir, parse_yaml_file, validate_config,
+};
 use error::Result;
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::net::SocketAddr;
+use std::process::ExitCode;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
@@ -30,10 +37,48 @@ enum ExporterId {
     IPFix(u32),
 }
 
-fn main() -> Result<()> {
+/// Packets generated for each flow, kept per flow so they can be reordered
+type FlowPackets = Vec<Vec<Vec<u8>>>;
+
+/// Next sequence number of each V5, V9 and IPFIX exporter, carried across exports
+struct SequenceNumbers<'a> {
+    /// V5 Key: (engine_type, engine_id)
+    v5: &'a mut HashMap<(u8, u8), u32>,
+    /// V9 Key: source_id
+    v9: &'a mut HashMap<u32, u32>,
+    /// IPFIX Key: observation_domain_id
+    ipfix: &'a mut HashMap<u32, u32>,
+}
+
+impl SequenceNumbers<'_> {
+    /// Sequence number of an exporter's next flow, or `None` for V7, which is not tracked
+    fn next_for(&mut self, exporter_id: ExporterId) -> Option<&mut u32> {
+        match exporter_id {
+            ExporterId::V5 {
+                engine_type,
+                engine_id,
+            } => Some(self.v5.entry((engine_type, engine_id)).or_insert(0)),
+            ExporterId::V7(_) => None,
+            ExporterId::V9(source_id) => Some(self.v9.entry(source_id).or_insert(0)),
+            ExporterId::IPFix(obs_domain_id) => Some(self.ipfix.entry(obs_domain_id).or_insert(0)),
+        }
+    }
+}
+
+fn main() -> ExitCode {
     // Parse CLI arguments
     let args = Cli::parse();
 
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run(args: Cli) -> Result<()> {
     // Configure rayon thread pool
     rayon::ThreadPoolBuilder::new()
         .num_threads(args.threads)
@@ -47,9 +92,63 @@ fn main() -> Result<()> {
         println!("Using {} threads for parallel processing", args.threads);
     }
 
+    if let Some(count) = args.override_count
+        && !args.quiet
+    {
+        eprintln!(
+            "Warning: --override-count {} emits non-conformant packets (testing only)",
+            count
+        );
+    }
+    if let Some(bytes) = args.truncate_bytes
+        && !args.quiet
+    {
+        eprintln!(
+            "Warning: --truncate-bytes {} emits truncated packets (testing only)",
+            bytes
+        );
+    }
+    if args.dtls_insecure && !args.quiet {
+        eprintln!(
+            "Warning: --dtls-insecure skips collector certificate verification (testing only)"
+        );
+    }
+    if let Some(gap) = args.sequence_gap
+        && !args.quiet
+    {
+        eprintln!(
+            "Warning: --sequence-gap {} skips sequence numbers every {} export(s) (testing only)",
+            gap, args.sequence_gap_every
+        );
+    }
+    if args.suppress_templates && !args.quiet {
+        eprintln!(
+            "Warning: --suppress-templates sends V9/IPFIX data without templates (testing only)"
+        );
+    }
+    if let Some(id) = args.override_data_flowset_id
+        && !args.quiet
+    {
+        eprintln!(
+            "Warning: --override-data-flowset-id {} sends V9 data that matches no template (testing only)",
+            id
+        );
+    }
+
+    if args.print_config {
+        return print_config(&args);
+    }
+    if args.bench_compare {
+        return bench_compare(&args);
+    }
+
     // Check if we're in single-shot mode or continuous mode
-    if args.once {
-        // Single-shot mode
+    if args.once
+        || args.assert_packets.is_some()
+        || args.assert_bytes.is_some()
+        || args.replay.is_some()
+    {
+        // Single-shot mode (assertions only ever check one generation, replays send once)
         run_once(&args)?;
     } else {
         // Continuous mode (default)
@@ -72,37 +171,66 @@ fn main() -> Result<()> {
             error::NetflowError::Configuration(format!("Failed to set Ctrl+C handler: {}", e))
         })?;
 
-        // Load config once if provided
-        let config = if let Some(ref config_path) = args.config {
-            if args.verbose {
-                println!("Loading configuration from {:?}", config_path);
-            }
-            let cfg = parse_yaml_file(config_path)?;
-            validate_config(&cfg)?;
-            if args.verbose {
-                println!("Configuration loaded: {} flow(s)", cfg.flows.len());
-            }
-            Some(cfg)
-        } else {
-            if args.verbose {
-                println!("No configuration provided, using default samples");
+        // Serve /healthz for orchestrators while the loop runs
+        let health_server = match args.health_addr {
+            Some(addr) => {
+                let server = health::HealthServer::start(addr, shutdown.clone())?;
+                if args.verbose {
+                    println!(
+                        "Health endpoint listening on http://{}/healthz",
+                        server.local_addr()
+                    );
+                }
+                Some(server)
             }
-            None
+            None => None,
         };
 
+        // Load config once if provided
+        let mut config = load_config(&args)?;
+        check_tcp_flows(&args, config.as_ref())?;
+        if args.ramp.is_some() && config.is_none() {
+            return Err(error::NetflowError::Configuration(
+                "--ramp requires --config or --config-dir".to_string(),
+            ));
+        }
+        if args.warmup.is_some() && config.is_none() {
+            return Err(error::NetflowError::Configuration(
+                "--warmup requires --config or --config-dir".to_string(),
+            ));
+        }
+
+        // Refuse to flood the collector unless explicitly forced
+        check_packet_rate(&args, config.as_ref(), interval_secs)?;
+
         // Get destination (needed for both UDP transmission and pcap file generation)
         let destination = parse_destination(&args)?;
+        let mut options = generator::GeneratorOptions::from(&args);
+        wait_for_collector(&args, destination)?;
 
-        // Create persistent pcap writer if output path is specified
-        let mut pcap_writer = if let Some(ref output_path) = args.output {
-            Some(transmitter::PersistentPcapWriter::new(
+        // A FIFO output needs a reader before it can be opened without blocking
+        let fifo_reader = wait_for_fifo_reader(&args, &shutdown)?;
+        if shutdown.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        // Create persistent output writer if output path is specified
+        let mut output_writer = if let Some(ref output_path) = args.output {
+            Some(transmitter::OutputWriter::new(
                 output_path,
+                args.output_format,
                 destination,
+                &transmitter::PcapOptions::from(&args),
                 args.verbose,
             )?)
         } else {
             None
         };
+        drop(fifo_reader);
+        let mut dtls_sender = connect_dtls(&args, destination)?;
+        let mut tcp_sender = connect_tcp(&args, destination)?;
+        let mut raw_sender = open_raw_sender(&args, destination)?;
+        let exporter_ports = transmitter::ExporterPorts::new(&args.exporter_port);
 
         // Track sequence numbers across iterations for V5/V9/IPFIX
         // V5 Key: (engine_type, engine_id)
@@ -113,9 +241,12 @@ fn main() -> Result<()> {
         let mut ipfix_sequence_numbers: HashMap<u32, u32> = HashMap::new();
 
         // Build template cache once (validates no template_id collisions)
-        let template_cache = if let Some(ref cfg) = config {
+        let mut template_cache = if let Some(ref cfg) = config {
             Some(template_cache::TemplateCache::from_config(
                 &cfg.flows,
+                args.allow_template_conflicts,
+                &options,
+                args.quiet,
                 args.verbose,
             )?)
         } else {
@@ -123,10 +254,38 @@ fn main() -> Result<()> {
         };
 
         // Track template refresh timing per RFC 7011/3954
-        // Templates should be sent periodically (e.g., every 30 seconds) not on every packet
+        // Templates should be sent periodically (e.g., every 30 seconds) not on every packet.
+        // Configured exporters refresh on their own timers in the template cache;
+        // this global timer paces the built-in samples.
         let mut last_template_send = std::time::Instant::now();
         const TEMPLATE_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
 
+        // Track per-flow activation (start_delay) and next export time (export_interval)
+        let run_start = std::time::Instant::now();
+        let mut last_reboot = run_start;
+        let default_interval = Duration::from_secs(interval_secs);
+        let flow_count = config.as_ref().map_or(0, |cfg| cfg.flows.len());
+        let mut schedule = generator::FlowSchedule::new(
+            config.as_ref().map_or(&[], |cfg| cfg.flows.as_slice()),
+            default_interval,
+            args.warmup.map(Duration::from_secs),
+        );
+        let mut flow_states: Vec<generator::FlowState> = match config {
+            Some(ref cfg) => generator::FlowState::for_flows(&cfg.flows, &options)?,
+            None => Vec::new(),
+        };
+
+        // Accumulated counters for --summary-interval progress lines
+        let mut run_stats = stats::RunStats::new(run_start);
+        let summary_interval = args.summary_interval.map(Duration::from_secs);
+
+        // Timed steps from the config's scenario section
+        let mut scenario = generator::ScenarioPlayer::new(
+            config.as_ref().map_or(&[], |cfg| cfg.scenario.as_slice()),
+            flow_count,
+        );
+        let mut scenario_templates = false;
+
         // Loop until shutdown signal received
         let mut iteration = 1;
         loop {
@@ -138,16 +297,155 @@ fn main() -> Result<()> {
                 break;
             }
 
+            // Fire scenario steps that are due
+            let mut scenario_stop = false;
+            for action in scenario.advance(run_start.elapsed()) {
+                if args.verbose {
+                    println!("Scenario: {:?}", action);
+                }
+                match action {
+                    config::schema::ScenarioAction::SendTemplates => scenario_templates = true,
+                    config::schema::ScenarioAction::SequenceGap { count } => {
+                        generator::skip_sequence_numbers(&mut v5_sequence_numbers, count);
+                        generator::skip_sequence_numbers(&mut v9_sequence_numbers, count);
+                        generator::skip_sequence_numbers(&mut ipfix_sequence_numbers, count);
+                    }
+                    config::schema::ScenarioAction::Stop => scenario_stop = true,
+                    config::schema::ScenarioAction::StartFlow { .. }
+                    | config::schema::ScenarioAction::StopFlow { .. } => {}
+                }
+            }
+            if scenario_stop {
+                if args.verbose {
+                    println!("Scenario complete, exiting...");
+                }
+                break;
+            }
+
+            // Send nothing during a --burst idle window
+            if let Some(idle) = args
+                .burst
+                .and_then(|burst| burst.idle_remaining(run_start.elapsed()))
+            {
+                if args.verbose {
+                    println!("Burst idle: resuming in {}s", idle.as_secs_f64().ceil());
+                }
+                sleep_unless_shutdown(idle, &shutdown);
+                continue;
+            }
+
             if args.verbose {
                 println!("\n--- Iteration {} ---", iteration);
             }
 
+            // Select flows whose start_delay has elapsed and whose export is due
+            let now = std::time::Instant::now();
+            let elapsed = now.duration_since(run_start);
+
+            // Simulate an exporter reboot: sequence numbers restart and templates are resent
+            let rebooted = args
+                .reboot_interval
+                .is_some_and(|secs| now.duration_since(last_reboot) >= Duration::from_secs(secs));
+            if rebooted {
+                if args.verbose {
+                    println!("Simulating exporter reboot: resetting sequence numbers");
+                }
+                last_reboot = now;
+                generator::reset_sequence_numbers(&mut v5_sequence_numbers);
+                generator::reset_sequence_numbers(&mut v9_sequence_numbers);
+                generator::reset_sequence_numbers(&mut ipfix_sequence_numbers);
+            }
+
+            if args.advance_uptime {
+                options.uptime_elapsed = Some(if args.reboot_resets_uptime {
+                    now.duration_since(last_reboot)
+                } else {
+                    elapsed
+                });
+            }
+
+            // Move rotating exporters to their scheduled observation domain; each
+            // new domain starts its own sequence numbers and registers its templates
+            let mut rotated = false;
+            if let Some(ref mut cfg) = config {
+                for flow in &mut cfg.flows {
+                    if let Some(domain) = generator::rotate_observation_domain(flow, elapsed) {
+                        if args.verbose {
+                            println!("Rotating to observation_domain_id={}", domain);
+                        }
+                        ipfix_sequence_numbers.remove(&domain);
+                        rotated = true;
+                    }
+                }
+                if rotated {
+                    template_cache = Some(template_cache::TemplateCache::from_config(
+                        &cfg.flows,
+                        args.allow_template_conflicts,
+                        &options,
+                        args.quiet,
+                        false,
+                    )?);
+                }
+            }
+
+            // During --warmup only templates are sent, so no flow exports yet
+            let warmup_remaining = schedule.warmup_remaining(elapsed);
+            if let Some(remaining) = warmup_remaining
+                && args.verbose
+            {
+                println!(
+                    "Warmup: sending templates only, data in {}s",
+                    remaining.as_secs_f64().ceil()
+                );
+            }
+
+            let mut newly_started = false;
+            let mut active_flows = Vec::new();
+            if let Some(ref cfg) = config {
+                let due = schedule.take_due(elapsed, |index| scenario.is_running(index));
+                newly_started = !due.started.is_empty();
+                for delay in due.started {
+                    if args.verbose && delay > Duration::ZERO {
+                        println!("Flow starting after {}s start delay", delay.as_secs());
+                    }
+                }
+                for (index, export_count) in due.flows {
+                    if let (Some(flow), Some(flow_state)) =
+                        (cfg.flows.get(index), flow_states.get_mut(index))
+                    {
+                        let mut flow = flow.clone();
+                        generator::apply_tcp_flag_phase(&mut flow, export_count);
+                        flow_state.apply(&mut flow);
+                        active_flows.push(flow);
+                    }
+                }
+            }
+
+            // Scale the export volume when ramping
+            if let Some(ramp) = args.ramp {
+                let step = elapsed.as_secs().checked_div(args.ramp_step).unwrap_or(0);
+                let volume = ramp.volume(step);
+                if args.verbose {
+                    println!("Ramp step {}: exporting {} flow(s)", step, volume);
+                }
+                active_flows = generator::scale_flows(active_flows, volume);
+            }
+
             // Determine if we should send templates this iteration
             // Send on first 3 iterations for reliability, then every 30+ seconds
+            // Flows starting late also trigger a send so their data is preceded by templates
+            // A scenario's send_templates step, a domain rotation or a reboot forces one as well,
+            // and every --warmup iteration sends them all
+            let force_templates = iteration <= 3
+                || warmup_remaining.is_some()
+                || newly_started
+                || rotated
+                || rebooted
+                || std::mem::take(&mut scenario_templates);
             let send_templates =
-                iteration <= 3 || last_template_send.elapsed() >= TEMPLATE_REFRESH_INTERVAL;
+                force_templates || last_template_send.elapsed() >= TEMPLATE_REFRESH_INTERVAL;
             if send_templates && iteration > 3 {
-                if args.verbose {
+                if args.verbose && config.is_none() {
                     println!(
                         "Template refresh: {} seconds since last send",
                         last_template_send.elapsed().as_secs()
@@ -161,26 +459,35 @@ fn main() -> Result<()> {
             // Generate packets
             let mut packets = Vec::new();
 
-            // Send cached templates if needed
-            if send_templates && let Some(ref cache) = template_cache {
-                // Add cached V9 templates
-                for template_packet in cache.v9_templates() {
-                    packets.push(template_packet.clone());
-                }
-                // Add cached IPFIX templates
-                for template_packet in cache.ipfix_templates() {
-                    packets.push(template_packet.clone());
+            // Send cached V9/IPFIX templates: all of them when forced, otherwise
+            // those exporters whose own refresh timer has expired
+            if !options.suppress_templates
+                && let Some(ref mut cache) = template_cache
+            {
+                let template_packets = cache.due_templates(
+                    now,
+                    TEMPLATE_REFRESH_INTERVAL,
+                    force_templates,
+                    options.uptime_elapsed,
+                );
+                if args.verbose && !force_templates && !template_packets.is_empty() {
+                    println!(
+                        "Template refresh: {} exporter(s) due",
+                        template_packets.len()
+                    );
                 }
+                packets.extend(template_packets);
             }
 
             // Generate data packets
-            if let Some(ref cfg) = config {
+            if config.is_some() {
                 let mut data_packets = generate_packets_from_config(
-                    cfg,
+                    &active_flows,
                     &mut v5_sequence_numbers,
                     &mut v9_sequence_numbers,
                     &mut ipfix_sequence_numbers,
                     false, // Never generate templates here - use cache instead
+                    &options,
                     args.verbose,
                 )?;
                 packets.append(&mut data_packets);
@@ -190,45 +497,97 @@ fn main() -> Result<()> {
                 let v9_seq = *v9_sequence_numbers.get(&1).unwrap_or(&0);
                 let ipfix_seq = *ipfix_sequence_numbers.get(&2).unwrap_or(&0);
                 let (sample_packets, next_v9_seq, next_ipfix_seq) =
-                    generator::generate_all_samples_with_seq(v9_seq, ipfix_seq, send_templates)?;
+                    generator::generate_all_samples_with_seq(
+                        v9_seq,
+                        ipfix_seq,
+                        send_templates,
+                        &options,
+                    )?;
                 v9_sequence_numbers.insert(1, next_v9_seq);
                 ipfix_sequence_numbers.insert(2, next_ipfix_seq);
                 packets.extend(sample_packets);
             }
 
+            finish_packets(&mut packets, &options, Duration::ZERO);
+            if args.reverse_order {
+                transmitter::reverse_data_packets(&mut packets);
+            }
+
             if args.verbose {
                 println!("Generated {} packet(s)", packets.len());
             }
 
-            // Output packets
-            if let Some(ref mut writer) = pcap_writer {
-                writer.write_packets(&packets)?;
-            } else {
+            // Output packets (UDP unless writing to a file, both with --tee)
+            if args.sends_to_collector() {
                 if args.verbose {
                     println!("Transmitting packets to {}", destination);
                 }
-                transmitter::send_udp(&packets, destination, args.source_port, args.verbose)?;
+                match (&mut dtls_sender, &mut tcp_sender, &mut raw_sender) {
+                    (Some(sender), _, _) => sender.send(&packets, args.verbose)?,
+                    (None, Some(sender), _) => sender.send(&packets, args.verbose)?,
+                    (None, None, Some(sender)) => sender.send(&packets, args.verbose)?,
+                    (None, None, None) => transmitter::send_udp(
+                        &packets,
+                        destination,
+                        args.source_port,
+                        &exporter_ports,
+                        args.verbose,
+                    )?,
+                }
+            }
+            if let Some(ref mut writer) = output_writer {
+                writer.write_packets(&packets)?;
             }
 
-            iteration += 1;
-
-            // Sleep for the specified interval, checking for shutdown periodically
-            let sleep_start = std::time::Instant::now();
-            let sleep_duration = Duration::from_secs(interval_secs);
+            run_stats.record(&packets);
+            if let Some(interval) = summary_interval
+                && let Some(line) = run_stats.report_due(std::time::Instant::now(), interval)
+            {
+                eprintln!("{}", line);
+            }
 
-            while sleep_start.elapsed() < sleep_duration {
-                if shutdown.load(Ordering::Relaxed) {
-                    break;
+            // Lose sequence numbers after every Nth export (testing only)
+            if let Some(gap) = args.sequence_gap
+                && iteration % args.sequence_gap_every == 0
+            {
+                if args.verbose {
+                    println!("Skipping {} sequence number(s)", gap);
                 }
-                thread::sleep(Duration::from_millis(100));
+                generator::skip_sequence_numbers(&mut v5_sequence_numbers, gap);
+                generator::skip_sequence_numbers(&mut v9_sequence_numbers, gap);
+                generator::skip_sequence_numbers(&mut ipfix_sequence_numbers, gap);
             }
+
+            iteration += 1;
+
+            // Sleep until the next flow is due, checking for shutdown periodically
+            let sleep_start = std::time::Instant::now();
+            let sleep_duration = schedule
+                .next_wakeup(elapsed, |index| scenario.is_running(index))
+                .map_or(default_interval, |due| {
+                    due.saturating_sub(sleep_start.duration_since(run_start))
+                });
+            let sleep_duration = scenario.next_step().map_or(sleep_duration, |at| {
+                sleep_duration.min(at.saturating_sub(run_start.elapsed()))
+            });
+
+            sleep_unless_shutdown(sleep_duration, &shutdown);
         }
 
-        // Close pcap writer if it exists
-        if let Some(writer) = pcap_writer {
+        // Close output writer if it exists
+        if let Some(writer) = output_writer {
+            if args.max_write_mbps.is_some() && !args.quiet {
+                println!(
+                    "Write limit: {} packet(s) dropped from the output file",
+                    writer.dropped_packets()
+                );
+            }
             writer.close()?;
         }
 
+        // Stop the health endpoint thread
+        drop(health_server);
+
         if args.verbose {
             println!("Shutdown complete.");
         }
@@ -237,19 +596,331 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn run_once(args: &Cli) -> Result<()> {
-    // Generate or load packets
-    let packets = if let Some(ref config_path) = args.config {
-        // Load and parse YAML configuration
+/// Sleep for `duration`, waking early once shutdown has been requested
+fn sleep_unless_shutdown(duration: Duration, shutdown: &AtomicBool) {
+    let sleep_start = std::time::Instant::now();
+    while sleep_start.elapsed() < duration {
+        if shutdown.load(Ordering::Relaxed) {
+            break;
+        }
+        thread::sleep(
+            duration
+                .saturating_sub(sleep_start.elapsed())
+                .min(Duration::from_millis(100)),
+        );
+    }
+}
+
+/// Wait for a reader when --output is a FIFO
+///
+/// Returns a write handle to hold until the output writer has opened the
+/// FIFO, so the reader never sees end of stream in between.
+fn wait_for_fifo_reader(args: &Cli, shutdown: &AtomicBool) -> Result<Option<std::fs::File>> {
+    let Some(ref path) = args.output else {
+        return Ok(None);
+    };
+    if !transmitter::is_fifo(path) {
+        return Ok(None);
+    }
+
+    if !args.quiet {
+        eprintln!("Waiting for a reader to open FIFO {:?}", path);
+    }
+    transmitter::wait_for_fifo_reader(path, shutdown)
+}
+
+/// Open the DTLS session with --transport dtls when packets go to the network
+fn connect_dtls(args: &Cli, destination: SocketAddr) -> Result<Option<transmitter::DtlsSender>> {
+    if args.transport != cli::Transport::Dtls || !args.sends_to_collector() {
+        return Ok(None);
+    }
+    transmitter::DtlsSender::connect(
+        destination,
+        args.source_port,
+        &transmitter::DtlsOptions::from(args),
+        args.verbose,
+    )
+    .map(Some)
+}
+
+/// Whether packets go to the network over --transport tcp
+fn sends_over_tcp(args: &Cli) -> bool {
+    args.transport == cli::Transport::Tcp && args.sends_to_collector()
+}
+
+/// Open the TCP connection with --transport tcp when packets go to the network
+fn connect_tcp(args: &Cli, destination: SocketAddr) -> Result<Option<transmitter::TcpSender>> {
+    if !sends_over_tcp(args) {
+        return Ok(None);
+    }
+    transmitter::TcpSender::connect(destination, args.verbose).map(Some)
+}
+
+/// Reject a --transport tcp run with anything but IPFIX, before connecting
+///
+/// Only IPFIX messages carry their length to frame them on a stream, so the
+/// default samples, which include V5 and V9, cannot be sent either.
+fn check_tcp_flows(args: &Cli, config: Option<&config::Config>) -> Result<()> {
+    if !sends_over_tcp(args) {
+        return Ok(());
+    }
+    let Some(config) = config else {
+        return Err(error::NetflowError::Configuration(
+            "--transport tcp requires a configuration of IPFIX flows; the default samples include V5 and V9"
+                .to_string(),
+        ));
+    };
+    match config
+        .flows
+        .iter()
+        .position(|flow| !matches!(flow, FlowConfig::IPFix(_)))
+    {
+        Some(index) => Err(error::NetflowError::Configuration(format!(
+            "Flow {} is not IPFIX; --transport tcp carries IPFIX only",
+            index
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn open_raw_sender(args: &Cli, destination: SocketAddr) -> Result<Option<transmitter::RawSender>> {
+    let Some(source_ip) = args.spoof_source else {
+        return Ok(None);
+    };
+    if !args.sends_to_collector() {
+        return Ok(None);
+    }
+    transmitter::RawSender::open(
+        source_ip,
+        destination,
+        args.source_port,
+        transmitter::ExporterPorts::new(&args.exporter_port),
+        args.verbose,
+    )
+    .map(Some)
+}
+
+/// Block until the collector is reachable when --wait-for-collector is set
+///
+/// Only applies when packets are actually sent over UDP.
+fn wait_for_collector(args: &Cli, destination: SocketAddr) -> Result<()> {
+    let Some(timeout_secs) = args.wait_for_collector else {
+        return Ok(());
+    };
+    if !args.sends_to_collector() {
+        return Ok(());
+    }
+
+    if args.verbose {
+        println!(
+            "Waiting up to {}s for collector at {}",
+            timeout_secs, destination
+        );
+    }
+    transmitter::wait_for_collector(destination, Duration::from_secs(timeout_secs), args.verbose)
+}
+
+/// Print the fully resolved configuration (--print-config)
+fn print_config(args: &Cli) -> Result<()> {
+    let mut config = load_config(args)?.unwrap_or_else(generator::sample_config);
+    let destination = parse_destination(args)?;
+    config.destination = config::schema::Destination {
+        ip: destination.ip().to_string(),
+        port: destination.port(),
+    };
+
+    print!("{}", serde_yaml::to_string(&config)?);
+    Ok(())
+}
+
+/// Time generation serially and in parallel and print the table (--bench-compare)
+fn bench_compare(args: &Cli) -> Result<()> {
+    let config = load_config(args)?.unwrap_or_else(generator::sample_config);
+    let options = generator::GeneratorOptions::from(args);
+
+    let results = bench::compare(&bench::thread_counts(args.threads), || {
+        let packets = generate_packets_from_config(
+            &config.flows,
+            &mut HashMap::new(),
+            &mut HashMap::new(),
+            &mut HashMap::new(),
+            true,
+            &options,
+            false,
+        )?;
+        Ok(packets.len())
+    })?;
+
+    print!("{}", bench::format_table(&results));
+
+    // The connected fast path of send_udp against plain send_to
+    let packets = generate_packets_from_config(
+        &config.flows,
+        &mut HashMap::new(),
+        &mut HashMap::new(),
+        &mut HashMap::new(),
+        true,
+        &options,
+        false,
+    )?;
+    if let Some(packet) = packets.first() {
+        println!();
+        print!(
+            "{}",
+            bench::format_send_paths(&bench::compare_send_paths(packet)?)
+        );
+    }
+    Ok(())
+}
+
+/// Load and validate the configuration from --config or --config-dir
+///
+/// Returns `None` when neither is given and default samples should be used.
+fn load_config(args: &Cli) -> Result<Option<config::Config>> {
+    let schema = match args.template_schema {
+        Some(ref path) => {
+            if args.verbose {
+                println!("Loading template schema from {:?}", path);
+            }
+            Some(config::parse_template_schema(path)?)
+        }
+        None => None,
+    };
+    let schema = schema.as_ref();
+
+    let mut config = if let Some(ref config_path) = args.config {
         if args.verbose {
             println!("Loading configuration from {:?}", config_path);
         }
+        parse_yaml_file(config_path, schema)?
+    } else if let Some(ref config_dir) = args.config_dir {
+        if args.verbose {
+            println!("Loading configuration directory {:?}", config_dir);
+        }
+        parse_yaml_dir(config_dir, schema)?
+    } else if let Some(ref template_path) = args.template {
+        if args.verbose {
+            println!(
+                "Loading template from {:?} with {} inline record(s)",
+                template_path,
+                args.record.len()
+            );
+        }
+        parse_template_with_records(
+            template_path,
+            &args.record,
+            args.records_ndjson.as_deref(),
+            schema,
+        )?
+    } else if !args.flows.is_empty() {
+        if args.verbose {
+            println!(
+                "Building configuration from {} --flow spec(s)",
+                args.flows.len()
+            );
+        }
+        config::parse_flow_specs(&args.flows)?
+    } else {
+        if args.verbose {
+            println!("No configuration provided, using default samples");
+        }
+        return Ok(None);
+    };
 
-        let config = parse_yaml_file(config_path)?;
-        validate_config(&config)?;
+    validate_config(&config)?;
+    // Lint before expansion, which copies records on purpose
+    if args.strict {
+        config::validate_strict(&config)?;
+    }
+    // Unique flows replace the records a flow table would generate
+    if args.unique_flows.is_some()
+        && let Some(index) = config
+            .flows
+            .iter()
+            .position(|flow| flow.flow_table().is_some())
+    {
+        return Err(error::NetflowError::Configuration(format!(
+            "Flow {} has a flow_table, which cannot be combined with --unique-flows",
+            index
+        )));
+    }
+    if let Some(ref asn_db) = args.asn_db {
+        let database = config::AsnDatabase::open(asn_db)?;
+        config::fill_as_numbers(&mut config, |address| database.lookup(address));
+    }
+    config::fill_hop_ttls(&mut config);
+    let instance = match args.instance_id {
+        Some(ref instance_id) => Some(instance_id.clone()),
+        None if args.exporter_id_from_host => Some(config::hostname()?),
+        None => None,
+    };
+    if let Some(instance) = instance {
+        let id = config::derive_exporter_id(&instance);
+        if args.verbose {
+            println!("Exporter ID {} derived from instance {:?}", id, instance);
+        }
+        config::fill_exporter_ids(&mut config, id);
+    }
+    config::expand_observation_domains(&mut config);
+    // The unique flows budget is split over the expanded flows
+    if let Some(total) = args.unique_flows.and_then(|n| usize::try_from(n).ok()) {
+        generator::unique_flows::key_ranges(&config.flows, total)?;
+    }
+    // Rotating exporters start in their first observation domain
+    for flow in &mut config.flows {
+        generator::rotate_observation_domain(flow, Duration::ZERO);
+    }
+    if args.verbose {
+        println!("Configuration loaded: {} flow(s)", config.flows.len());
+    }
+
+    Ok(Some(config))
+}
+
+fn run_once(args: &Cli) -> Result<()> {
+    let options = generator::GeneratorOptions::from(args);
 
+    // Generate or load packets
+    let mut packets = if let Some(ref path) = args.replay {
+        let payloads = transmitter::read_replay_file(path)?;
         if args.verbose {
-            println!("Configuration loaded: {} flow(s)", config.flows.len());
+            println!("Read {} payload(s) from {:?}", payloads.len(), path);
+        }
+        if sends_over_tcp(args)
+            && let Some(index) = payloads
+                .iter()
+                .position(|payload| payload.get(..2) != Some(&[0x00, 0x0a][..]))
+        {
+            return Err(error::NetflowError::Configuration(format!(
+                "Replayed payload {} is not IPFIX; --transport tcp carries IPFIX only",
+                index
+            )));
+        }
+        payloads
+    } else if let Some(mut config) = load_config(args)? {
+        check_tcp_flows(args, Some(&config))?;
+        // Merged directories may share exporters across files, so check their
+        // templates for collisions as continuous mode does via the cache
+        if args.config_dir.is_some() {
+            template_cache::TemplateCache::from_config(
+                &config.flows,
+                args.allow_template_conflicts,
+                &options,
+                args.quiet,
+                false,
+            )?;
+        }
+
+        // A single export uses the first phase of any TCP flag sequence,
+        // the initial contents of any flow table and one address pool draw
+        let states = generator::FlowState::for_flows(&config.flows, &options)?;
+        for (flow, mut state) in config.flows.iter_mut().zip(states) {
+            generator::apply_tcp_flag_phase(flow, 0);
+            state.apply(flow);
+        }
+
+        if args.stream {
+            return stream_once(args, config, &options);
         }
 
         // Generate packets from config (single-shot mode doesn't need sequence tracking across runs)
@@ -257,40 +928,82 @@ fn run_once(args: &Cli) -> Result<()> {
         let mut v9_sequence_numbers = HashMap::new();
         let mut ipfix_sequence_numbers = HashMap::new();
         generate_packets_from_config(
-            &config,
+            &config.flows,
             &mut v5_sequence_numbers,
             &mut v9_sequence_numbers,
             &mut ipfix_sequence_numbers,
             true, // Always send templates in single-shot mode
+            &options,
             args.verbose,
         )?
     } else {
-        // Use default samples
-        if args.verbose {
-            println!("No configuration provided, using default samples");
-        }
-
-        generator::generate_all_samples()?
+        check_tcp_flows(args, None)?;
+        generator::generate_all_samples(&options)?
     };
 
+    finish_packets(&mut packets, &options, Duration::ZERO);
+    if args.reverse_order {
+        transmitter::reverse_data_packets(&mut packets);
+    }
+
     if args.verbose {
         println!("Generated {} packet(s)", packets.len());
     }
 
+    // Assertion mode checks the generated output without sending it
+    if args.assert_packets.is_some() || args.assert_bytes.is_some() {
+        let mut totals = stats::RunStats::new(std::time::Instant::now());
+        totals.record(&packets);
+        totals.check_totals(args.assert_packets, args.assert_bytes)?;
+        if !args.quiet {
+            println!("Output assertion passed");
+        }
+        return Ok(());
+    }
+
     // Get destination (needed for both UDP transmission and pcap file generation)
     let destination = parse_destination(args)?;
 
+    wait_for_collector(args, destination)?;
+
     // Output packets
-    if let Some(ref output_path) = args.output {
-        // Write to pcap file (always first write in single-shot mode)
-        transmitter::write_to_file(&packets, output_path, destination, args.verbose, true)?;
-    } else {
+    if args.sends_to_collector() {
         // Send via UDP
         if args.verbose {
             println!("Transmitting packets to {}", destination);
         }
 
-        transmitter::send_udp(&packets, destination, args.source_port, args.verbose)?;
+        match (
+            connect_dtls(args, destination)?,
+            connect_tcp(args, destination)?,
+            open_raw_sender(args, destination)?,
+        ) {
+            (Some(mut sender), _, _) => sender.send(&packets, args.verbose)?,
+            (None, Some(mut sender), _) => sender.send(&packets, args.verbose)?,
+            (None, None, Some(mut sender)) => sender.send(&packets, args.verbose)?,
+            (None, None, None) => transmitter::send_udp(
+                &packets,
+                destination,
+                args.source_port,
+                &transmitter::ExporterPorts::new(&args.exporter_port),
+                args.verbose,
+            )?,
+        }
+    }
+    if let Some(ref output_path) = args.output {
+        // Single-shot mode has no Ctrl+C handler, so the wait needs no shutdown flag
+        let fifo_reader = wait_for_fifo_reader(args, &AtomicBool::new(false))?;
+
+        // Write to output file in the selected format
+        transmitter::write_output_file(
+            &packets,
+            output_path,
+            args.output_format,
+            destination,
+            &transmitter::PcapOptions::from(args),
+            args.verbose,
+        )?;
+        drop(fifo_reader);
     }
 
     if args.verbose {
@@ -300,124 +1013,326 @@ fn run_once(args: &Cli) -> Result<()> {
     Ok(())
 }
 
+/// Batches of flow packets buffered between the generator and the sender (--stream)
+const STREAM_QUEUE_DEPTH: usize = 4;
+
+/// Single-shot export that sends or writes each flow's packets as they are built
+///
+/// A generator thread builds a few flows at a time while this thread sends
+/// or writes the packets of earlier ones, so only those flows' packets are
+/// held rather than the whole export (--stream). The packets are the same
+/// as those of a normal single export.
+fn stream_once(
+    args: &Cli,
+    config: config::Config,
+    options: &generator::GeneratorOptions,
+) -> Result<()> {
+    let assert_only = args.assert_packets.is_some() || args.assert_bytes.is_some();
+    let destination = parse_destination(args)?;
+    let send = !assert_only && args.sends_to_collector();
+
+    let (mut dtls, mut tcp, mut raw) = if send {
+        wait_for_collector(args, destination)?;
+        (
+            connect_dtls(args, destination)?,
+            connect_tcp(args, destination)?,
+            open_raw_sender(args, destination)?,
+        )
+    } else {
+        (None, None, None)
+    };
+    let exporter_ports = transmitter::ExporterPorts::new(&args.exporter_port);
+
+    // Single-shot mode has no Ctrl+C handler, so the wait needs no shutdown flag
+    let (fifo_reader, mut writer) = match args.output {
+        Some(ref output_path) if !assert_only => (
+            wait_for_fifo_reader(args, &AtomicBool::new(false))?,
+            Some(transmitter::OutputWriter::new(
+                output_path,
+                args.output_format,
+                destination,
+                &transmitter::PcapOptions::from(args),
+                args.verbose,
+            )?),
+        ),
+        _ => (None, None),
+    };
+
+    let mut totals = stats::RunStats::new(std::time::Instant::now());
+    let mut spacing_offset = Duration::ZERO;
+    let (sender, receiver) = std::sync::mpsc::sync_channel(STREAM_QUEUE_DEPTH);
+
+    thread::scope(|scope| {
+        let producer = scope.spawn(move || {
+            stream_packets_from_config(&config.flows, options, args.verbose, |packets| {
+                sender.send(packets).is_ok()
+            })
+        });
+
+        let consumed = receiver.iter().try_for_each(|mut packets: Vec<Vec<u8>>| {
+            spacing_offset = finish_packets(&mut packets, options, spacing_offset);
+            totals.record(&packets);
+
+            if send {
+                match (dtls.as_mut(), tcp.as_mut(), raw.as_mut()) {
+                    (Some(sender), _, _) => sender.send(&packets, args.verbose)?,
+                    (None, Some(sender), _) => sender.send(&packets, args.verbose)?,
+                    (None, None, Some(sender)) => sender.send(&packets, args.verbose)?,
+                    (None, None, None) => transmitter::send_udp(
+                        &packets,
+                        destination,
+                        args.source_port,
+                        &exporter_ports,
+                        args.verbose,
+                    )?,
+                }
+            }
+            if let Some(ref mut writer) = writer {
+                writer.write_packets(&packets)?;
+            }
+            Ok(())
+        });
+
+        // A failed send stops the generator at its next batch
+        drop(receiver);
+        let produced = producer.join().unwrap_or_else(|_| {
+            Err(error::NetflowError::Generation(
+                "Packet generator thread panicked".to_string(),
+            ))
+        });
+        consumed.and(produced)
+    })?;
+
+    if let Some(writer) = writer {
+        writer.close()?;
+    }
+    drop(fifo_reader);
+
+    if assert_only {
+        totals.check_totals(args.assert_packets, args.assert_bytes)?;
+        if !args.quiet {
+            println!("Output assertion passed");
+        }
+    } else if args.verbose {
+        println!("Done!");
+    }
+
+    Ok(())
+}
+
+/// Header adjustments made to generated packets before they are sent or written
+///
+/// Packet spacing continues from `spacing_offset`, and the offset for the
+/// packets that follow is returned, so batches are spaced as one (--stream).
+fn finish_packets(
+    packets: &mut [Vec<u8>],
+    options: &generator::GeneratorOptions,
+    spacing_offset: Duration,
+) -> Duration {
+    let next_offset =
+        generator::stagger_packet_timestamps_from(packets, options.packet_spacing, spacing_offset);
+    if let Some(count) = options.override_count {
+        generator::override_header_count(packets, count);
+    }
+    if let Some(bytes) = options.truncate_bytes {
+        generator::truncate_packets(packets, bytes);
+    }
+    next_offset
+}
+
 fn generate_packets_from_config(
-    config: &config::Config,
+    flows: &[FlowConfig],
     v5_sequence_numbers: &mut HashMap<(u8, u8), u32>,
     v9_sequence_numbers: &mut HashMap<u32, u32>,
     ipfix_sequence_numbers: &mut HashMap<u32, u32>,
     send_templates: bool,
+    options: &generator::GeneratorOptions,
     verbose: bool,
 ) -> Result<Vec<Vec<u8>>> {
-    use rayon::prelude::*;
-
-    // Per-exporter parallelization: Group flows by exporter ID and process each group in parallel
-    // Flows from the same exporter are processed sequentially to maintain sequence number ordering
-    // Flows from different exporters can be processed in parallel for better performance
+    // Every flow is built in parallel before any packet is returned
+    let mut flow_batches: FlowPackets = Vec::with_capacity(flows.len());
+    generate_flow_packets(
+        flows,
+        SequenceNumbers {
+            v5: v5_sequence_numbers,
+            v9: v9_sequence_numbers,
+            ipfix: ipfix_sequence_numbers,
+        },
+        send_templates,
+        options,
+        verbose,
+        flows.len(),
+        |packets| {
+            flow_batches.push(packets);
+            true
+        },
+    )?;
+
+    let all_packets = if options.interleave {
+        generator::interleave_flow_batches(flow_batches)
+    } else {
+        flow_batches.into_iter().flatten().collect()
+    };
 
-    if config.flows.is_empty() {
-        return Ok(Vec::new());
+    if verbose {
+        println!("Generated {} packet(s) total", all_packets.len());
     }
 
-    // Group flows by exporter ID
-    let grouped_flows = group_flows_by_exporter(&config.flows);
+    Ok(all_packets)
+}
+
+/// Generate the packets of a single export flow by flow, handing each to `emit`
+///
+/// The streaming form of `generate_packets_from_config` (--stream): flows
+/// are built as many at a time as there are threads, templates included,
+/// and their packets are the same as those of a normal single export.
+/// Generation stops early once `emit` returns false.
+fn stream_packets_from_config(
+    flows: &[FlowConfig],
+    options: &generator::GeneratorOptions,
+    verbose: bool,
+    emit: impl FnMut(Vec<Vec<u8>>) -> bool,
+) -> Result<()> {
+    generate_flow_packets(
+        flows,
+        SequenceNumbers {
+            v5: &mut HashMap::new(),
+            v9: &mut HashMap::new(),
+            ipfix: &mut HashMap::new(),
+        },
+        true,
+        options,
+        verbose,
+        rayon::current_num_threads(),
+        emit,
+    )
+}
+
+/// Build the packets of each flow in configuration order, handing them to `emit`
+///
+/// Each exporter numbers its flows on from the previous one, so sequence
+/// numbers are assigned before a window of `window` flows is built in
+/// parallel. Flows of different exporters are built side by side, and
+/// flows of the same exporter never wait on each other. Generation stops
+/// early once `emit` returns false.
+fn generate_flow_packets(
+    flows: &[FlowConfig],
+    mut sequence_numbers: SequenceNumbers,
+    send_templates: bool,
+    options: &generator::GeneratorOptions,
+    verbose: bool,
+    window: usize,
+    mut emit: impl FnMut(Vec<Vec<u8>>) -> bool,
+) -> Result<()> {
+    use rayon::prelude::*;
+
+    let exporter_ids: Vec<ExporterId> = flows
+        .iter()
+        .enumerate()
+        .map(|(index, flow)| extract_exporter_id(flow, index))
+        .collect();
+
+    // Templates of every flow of each exporter, for data-only flows (--shared-templates)
+    let mut shared_templates = HashMap::new();
+    for exporter_id in &exporter_ids {
+        if let Entry::Vacant(entry) = shared_templates.entry(*exporter_id) {
+            entry.insert(if options.shared_templates {
+                exporter_templates(
+                    flows
+                        .iter()
+                        .zip(&exporter_ids)
+                        .filter(|(_, id)| *id == exporter_id)
+                        .map(|(flow, _)| flow),
+                )?
+            } else {
+                (Vec::new(), Vec::new())
+            });
+        }
+    }
 
     if verbose {
         println!(
-            "Processing {} exporter group(s) in parallel",
-            grouped_flows.len()
+            "Processing {} flow(s) from {} exporter(s) in parallel",
+            flows.len(),
+            shared_templates.len()
         );
     }
 
-    // Process groups in parallel
-    let results: Vec<(ExporterId, Vec<Vec<u8>>, u32)> = grouped_flows
-        .par_iter()
-        .map(|(exporter_id, flows)| {
-            // Get initial sequence for this exporter
-            let initial_seq = match exporter_id {
-                ExporterId::V5 {
-                    engine_type,
-                    engine_id,
-                } => *v5_sequence_numbers
-                    .get(&(*engine_type, *engine_id))
-                    .unwrap_or(&0),
-                ExporterId::V7(_) => 0, // V7 sequences not tracked across iterations
-                ExporterId::V9(source_id) => *v9_sequence_numbers.get(source_id).unwrap_or(&0),
-                ExporterId::IPFix(obs_domain_id) => {
-                    *ipfix_sequence_numbers.get(obs_domain_id).unwrap_or(&0)
-                }
-            };
-
-            if verbose {
-                match exporter_id {
-                    ExporterId::V5 {
-                        engine_type,
-                        engine_id,
-                    } => {
-                        println!(
-                            "Processing V5 exporter (engine_type={}, engine_id={}) with {} flow(s)",
-                            engine_type,
-                            engine_id,
-                            flows.len()
-                        );
-                    }
-                    ExporterId::V7(index) => {
-                        println!("Processing V7 flow #{}", index);
-                    }
-                    ExporterId::V9(source_id) => {
-                        println!(
-                            "Processing V9 exporter (source_id={}) with {} flow(s)",
-                            source_id,
-                            flows.len()
-                        );
-                    }
-                    ExporterId::IPFix(obs_domain_id) => {
-                        println!(
-                            "Processing IPFIX exporter (observation_domain_id={}) with {} flow(s)",
-                            obs_domain_id,
-                            flows.len()
-                        );
-                    }
-                }
-            }
-
-            let (packets, next_seq) =
-                process_exporter_group(flows, initial_seq, send_templates, verbose)?;
-
-            Ok((*exporter_id, packets, next_seq))
-        })
-        .collect::<Result<Vec<_>>>()?;
-
-    // Merge results and update sequence numbers
-    let mut all_packets = Vec::new();
+    for (flows, exporter_ids) in flows
+        .chunks(window.max(1))
+        .zip(exporter_ids.chunks(window.max(1)))
+    {
+        // V5/V7 flows reach --min-packet-size with extra records, which must be
+        // in place before sequence numbers are assigned, and take --export-lag
+        // on their record times
+        let adjusted: Vec<FlowConfig>;
+        let flows = if options.min_packet_size.is_some() || options.export_lag.is_some() {
+            adjusted = flows
+                .iter()
+                .cloned()
+                .map(|mut flow| {
+                    adjust_flow(&mut flow, options)?;
+                    Ok(flow)
+                })
+                .collect::<Result<_>>()?;
+            &adjusted
+        } else {
+            flows
+        };
 
-    for (exporter_id, packets, next_seq) in results {
-        all_packets.extend(packets);
+        let mut sequence_assignments = Vec::with_capacity(flows.len());
+        for (flow, exporter_id) in flows.iter().zip(exporter_ids) {
+            let Some(sequence) = sequence_numbers.next_for(*exporter_id) else {
+                sequence_assignments.push(0);
+                continue;
+            };
+            sequence_assignments.push(*sequence);
+            *sequence = sequence
+                .checked_add(flow_record_count(flow))
+                .ok_or_else(|| {
+                    error::NetflowError::Generation("Sequence number overflow".to_string())
+                })?;
+        }
 
-        // Update sequence tracking for V5/V9/IPFIX
-        match exporter_id {
-            ExporterId::V5 {
-                engine_type,
-                engine_id,
-            } => {
-                v5_sequence_numbers.insert((engine_type, engine_id), next_seq);
-            }
-            ExporterId::V9(source_id) => {
-                v9_sequence_numbers.insert(source_id, next_seq);
-            }
-            ExporterId::IPFix(obs_domain_id) => {
-                ipfix_sequence_numbers.insert(obs_domain_id, next_seq);
-            }
-            ExporterId::V7(_) => {
-                // No tracking for V7
+        let flow_packets: FlowPackets = flows
+            .par_iter()
+            .zip(exporter_ids)
+            .zip(sequence_assignments)
+            .map(|((flow, exporter_id), sequence)| {
+                let (shared_v9, shared_ipfix) = shared_templates
+                    .get(exporter_id)
+                    .map(|(v9, ipfix)| (v9.as_slice(), ipfix.as_slice()))
+                    .unwrap_or_default();
+                build_flow_packets(
+                    flow,
+                    sequence,
+                    send_templates,
+                    (shared_v9, shared_ipfix),
+                    options,
+                    verbose,
+                )
+            })
+            .collect::<Result<_>>()?;
+
+        for packets in flow_packets {
+            if !emit(packets) {
+                return Ok(());
             }
         }
     }
 
-    if verbose {
-        println!("Generated {} packet(s) total", all_packets.len());
-    }
+    Ok(())
+}
 
-    Ok(all_packets)
+/// Apply --min-packet-size record padding and --export-lag to a V5/V7 flow
+fn adjust_flow(flow: &mut FlowConfig, options: &generator::GeneratorOptions) -> Result<()> {
+    if let Some(size) = options.min_packet_size {
+        generator::pad_records(flow, size)?;
+    }
+    if let Some(lag) = options.export_lag {
+        generator::lag_record_times(flow, lag);
+    }
+    Ok(())
 }
 
 fn parse_destination(args: &Cli) -> Result<SocketAddr> {
@@ -437,6 +1352,56 @@ fn parse_destination(args: &Cli) -> Result<SocketAddr> {
     }
 }
 
+/// Check the projected packet rate against --max-pps
+fn check_packet_rate(
+    args: &Cli,
+    config: Option<&config::Config>,
+    interval_secs: u64,
+) -> Result<()> {
+    let unique_flows = args.unique_flows.and_then(|n| usize::try_from(n).ok());
+    let projected_pps =
+        generator::projected_packets_per_sec(config, interval_secs, unique_flows, args.ramp);
+
+    if args.verbose {
+        if projected_pps == u64::MAX {
+            println!("Projected rate: unbounded (flows exported with no interval)");
+        } else {
+            println!(
+                "Projected rate{}: {} packet(s)/sec",
+                if args.ramp.is_some() {
+                    " at --ramp CAP"
+                } else {
+                    ""
+                },
+                projected_pps
+            );
+        }
+    }
+
+    if projected_pps > args.max_pps {
+        if args.force {
+            if !args.quiet {
+                eprintln!(
+                    "Warning: projected packet rate exceeds --max-pps {}, continuing because --force was given",
+                    args.max_pps
+                );
+            }
+        } else {
+            return Err(error::NetflowError::Configuration(format!(
+                "Projected packet rate exceeds --max-pps {} ({} packet(s)/sec); use --force to override",
+                args.max_pps,
+                if projected_pps == u64::MAX {
+                    "unbounded".to_string()
+                } else {
+                    projected_pps.to_string()
+                }
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 /// Extract exporter ID from a flow config
 fn extract_exporter_id(flow: &FlowConfig, index: usize) -> ExporterId {
     match flow {
@@ -476,144 +1441,251 @@ fn extract_exporter_id(flow: &FlowConfig, index: usize) -> ExporterId {
     }
 }
 
-/// Group flows by exporter ID for parallel processing
-fn group_flows_by_exporter(flows: &[FlowConfig]) -> HashMap<ExporterId, Vec<FlowConfig>> {
-    let mut groups: HashMap<ExporterId, Vec<FlowConfig>> = HashMap::new();
-
-    for (index, flow) in flows.iter().enumerate() {
-        let exporter_id = extract_exporter_id(flow, index);
-        groups.entry(exporter_id).or_default().push(flow.clone());
-    }
-
-    groups
-}
-
-/// Process all flows for a single exporter group in parallel
-/// Pre-calculates sequence numbers, then generates packets concurrently
-fn process_exporter_group(
-    flows: &[FlowConfig],
-    initial_sequence: u32,
-    send_templates: bool,
-    verbose: bool,
-) -> Result<(Vec<Vec<u8>>, u32)> {
-    use rayon::prelude::*;
-
-    // Phase 1: Pre-calculate sequence number ranges (sequential, lightweight)
-    // This assigns each flow a starting sequence number
-    let mut sequence_assignments = Vec::with_capacity(flows.len());
-    let mut current_seq = initial_sequence;
-
+/// Every V9 and IPFIX template defined by a group of flows
+///
+/// Data-only flows with --shared-templates are encoded against these.
+fn exporter_templates<'a>(
+    flows: impl IntoIterator<Item = &'a FlowConfig>,
+) -> Result<(Vec<generator::V9Template>, Vec<generator::IPFixTemplate>)> {
+    let mut v9_templates = Vec::new();
+    let mut ipfix_templates = Vec::new();
     for flow in flows {
-        let start_seq = current_seq;
-
-        // Calculate how many records this flow will generate
-        let record_count = match flow {
-            FlowConfig::V5(config) => u32::try_from(config.flowsets.len()).unwrap_or(0),
-            FlowConfig::V7(_) => 0, // V7 doesn't use sequence numbers
+        match flow {
             FlowConfig::V9(config) => {
-                // Count data records across all data flowsets
-                config
-                    .flowsets
-                    .iter()
-                    .map(|fs| {
-                        if let config::schema::V9FlowSet::Data { records, .. } = fs {
-                            u32::try_from(records.len()).unwrap_or(0)
-                        } else {
-                            0
-                        }
-                    })
-                    .sum()
+                for flowset in &config.flowsets {
+                    v9_templates.extend(generator::V9Template::from_flowset(flowset)?);
+                }
             }
             FlowConfig::IPFix(config) => {
-                // Count data records across all data flowsets
-                config
-                    .flowsets
-                    .iter()
-                    .map(|fs| {
-                        if let config::schema::IPFixFlowSet::Data { records, .. } = fs {
-                            u32::try_from(records.len()).unwrap_or(0)
-                        } else {
-                            0
-                        }
-                    })
-                    .sum()
+                for flowset in &config.flowsets {
+                    ipfix_templates
+                        .extend(generator::IPFixTemplate::from_flowset(config, flowset)?);
+                }
             }
-        };
-
-        sequence_assignments.push(start_seq);
-        current_seq = current_seq.checked_add(record_count).ok_or_else(|| {
-            error::NetflowError::Generation("Sequence number overflow".to_string())
-        })?;
+            FlowConfig::V5(_) | FlowConfig::V7(_) => {}
+        }
     }
+    Ok((v9_templates, ipfix_templates))
+}
 
-    let final_sequence = current_seq;
-
-    // Phase 2: Generate packets in parallel with pre-assigned sequence numbers
-    let results: Vec<(usize, Vec<Vec<u8>>)> = flows
-        .par_iter()
-        .enumerate()
-        .map(|(index, flow)| {
-            let assigned_seq = sequence_assignments[index];
-
-            let packets = match flow {
-                FlowConfig::V5(v5_config) => {
-                    if verbose {
-                        println!("  Generating NetFlow V5 packet...");
-                    }
-                    vec![generator::build_v5_packet(
-                        v5_config.clone(),
-                        Some(assigned_seq),
-                    )?]
-                }
-                FlowConfig::V7(v7_config) => {
-                    if verbose {
-                        println!("  Generating NetFlow V7 packet...");
-                    }
-                    vec![generator::build_v7_packet(v7_config.clone())?]
-                }
-                FlowConfig::V9(v9_config) => {
-                    if verbose {
-                        let template_msg = if send_templates {
-                            " (with templates)"
-                        } else {
-                            ""
-                        };
-                        println!("  Generating NetFlow V9 packet(s){}...", template_msg);
+/// Sequence numbers a flow consumes: its data records (V7 has none)
+fn flow_record_count(flow: &FlowConfig) -> u32 {
+    match flow {
+        FlowConfig::V5(config) => u32::try_from(config.flowsets.len())
+            .unwrap_or(0)
+            .saturating_mul(u32::try_from(config.packet_count()).unwrap_or(1)),
+        FlowConfig::V7(_) => 0, // V7 doesn't use sequence numbers
+        FlowConfig::V9(config) => {
+            // Count data records across all data flowsets
+            config
+                .flowsets
+                .iter()
+                .map(|fs| {
+                    if let config::schema::V9FlowSet::Data { records, .. } = fs {
+                        u32::try_from(records.len()).unwrap_or(0)
+                    } else {
+                        0
                     }
-                    let (batch, _) = generator::build_v9_packets(
-                        v9_config.clone(),
-                        Some(assigned_seq),
-                        send_templates,
-                    )?;
-                    batch
-                }
-                FlowConfig::IPFix(ipfix_config) => {
-                    if verbose {
-                        let template_msg = if send_templates {
-                            " (with templates)"
-                        } else {
-                            ""
-                        };
-                        println!("  Generating IPFIX packet(s){}...", template_msg);
+                })
+                .sum()
+        }
+        FlowConfig::IPFix(config) => {
+            // Count data records across all data flowsets
+            config
+                .flowsets
+                .iter()
+                .map(|fs| {
+                    if let config::schema::IPFixFlowSet::Data { records, .. } = fs {
+                        u32::try_from(records.len()).unwrap_or(0)
+                    } else {
+                        0
                     }
-                    let (batch, _) = generator::build_ipfix_packets(
-                        ipfix_config.clone(),
-                        Some(assigned_seq),
-                        send_templates,
-                    )?;
-                    batch
-                }
-            };
+                })
+                .sum()
+        }
+    }
+}
 
-            Ok((index, packets))
-        })
-        .collect::<Result<Vec<_>>>()?;
+/// Build the packets of one flow, starting at `assigned_seq`
+///
+/// The shared templates are those of the flow's exporter under
+/// --shared-templates, and empty otherwise.
+fn build_flow_packets(
+    flow: &FlowConfig,
+    assigned_seq: u32,
+    send_templates: bool,
+    (shared_v9, shared_ipfix): (&[generator::V9Template], &[generator::IPFixTemplate]),
+    options: &generator::GeneratorOptions,
+    verbose: bool,
+) -> Result<Vec<Vec<u8>>> {
+    let packets = match flow {
+        FlowConfig::V5(v5_config) => {
+            if verbose {
+                println!("  Generating NetFlow V5 packet...");
+            }
+            let (batch, _) = generator::build_v5_packets(v5_config.clone(), Some(assigned_seq))?;
+            batch
+        }
+        FlowConfig::V7(v7_config) => {
+            if verbose {
+                println!("  Generating NetFlow V7 packet...");
+            }
+            vec![generator::build_v7_packet(v7_config.clone())?]
+        }
+        FlowConfig::V9(v9_config) => {
+            if verbose {
+                let template_msg = if send_templates {
+                    " (with templates)"
+                } else {
+                    ""
+                };
+                println!("  Generating NetFlow V9 packet(s){}...", template_msg);
+            }
+            let (batch, _) = generator::build_v9_packets_with_shared(
+                v9_config.clone(),
+                Some(assigned_seq),
+                send_templates,
+                shared_v9,
+                options,
+            )?;
+            batch
+        }
+        FlowConfig::IPFix(ipfix_config) => {
+            if verbose {
+                let template_msg = if send_templates {
+                    " (with templates)"
+                } else {
+                    ""
+                };
+                println!("  Generating IPFIX packet(s){}...", template_msg);
+            }
+            let (batch, _) = generator::build_ipfix_packets_with_shared(
+                ipfix_config.clone(),
+                Some(assigned_seq),
+                send_templates,
+                shared_ipfix,
+                options,
+            )?;
+            batch
+        }
+    };
+    Ok(packets)
+}
 
-    // Phase 3: Flatten results in original order
-    let mut all_packets = Vec::new();
-    for (_, packets) in results {
-        all_packets.extend(packets);
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_matches_single_export() {
+        let yaml = r#"
+flows:
+  - version: v9
+    header:
+      unix_secs: 1735141200
+      sys_up_time: 360000
+      source_id: 7
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IPV4_SRC_ADDR
+            field_length: 4
+          - field_type: IN_BYTES
+            field_length: 4
+      - type: data
+        template_id: 256
+        records:
+"#;
+        let records = "          - src_addr: 10.0.0.1\n            in_bytes: 1\n".repeat(300);
+        let rest = r#"
+  - version: v9
+    header:
+      unix_secs: 1735141200
+      sys_up_time: 360000
+      source_id: 7
+    pack_data_flowsets: 1400
+    flowsets:
+      - type: template
+        template_id: 257
+        fields:
+          - field_type: IPV4_DST_ADDR
+            field_length: 4
+      - type: data
+        template_id: 257
+        records:
+          - dst_addr: 10.1.0.1
+      - type: data
+        template_id: 257
+        records:
+          - dst_addr: 10.1.0.2
+  - version: ipfix
+    header:
+      export_time: 1735141200
+      observation_domain_id: 3
+    flowsets:
+      - type: template
+        template_id: 300
+        fields:
+          - field_type: sourceIPv4Address
+            field_length: 4
+      - type: data
+        template_id: 300
+        records:
+          - source_ipv4_address: 192.168.0.1
+"#;
+        let config = config::parse_yaml_str(&format!("{}{}{}", yaml, records, rest)).unwrap();
+        let options = generator::GeneratorOptions {
+            packet_spacing: Duration::from_millis(250),
+            ..generator::GeneratorOptions::default()
+        };
 
-    Ok((all_packets, final_sequence))
+        let mut once = generate_packets_from_config(
+            &config.flows,
+            &mut HashMap::new(),
+            &mut HashMap::new(),
+            &mut HashMap::new(),
+            true,
+            &options,
+            false,
+        )
+        .unwrap();
+        finish_packets(&mut once, &options, Duration::ZERO);
+        // 300 V9 records stay in one data packet, as the flowset is configured
+        assert_eq!(once.len(), 6);
+        assert_eq!(once[1].len(), 2424);
+
+        let mut streamed = Vec::new();
+        let mut spacing_offset = Duration::ZERO;
+        stream_packets_from_config(&config.flows, &options, false, |mut packets| {
+            spacing_offset = finish_packets(&mut packets, &options, spacing_offset);
+            streamed.extend(packets);
+            true
+        })
+        .unwrap();
+        assert_eq!(streamed, once);
+
+        // However many flows are built at a time
+        let mut windowed = Vec::new();
+        generate_flow_packets(
+            &config.flows,
+            SequenceNumbers {
+                v5: &mut HashMap::new(),
+                v9: &mut HashMap::new(),
+                ipfix: &mut HashMap::new(),
+            },
+            true,
+            &options,
+            false,
+            1,
+            |packets| {
+                windowed.push(packets);
+                true
+            },
+        )
+        .unwrap();
+        let mut windowed: Vec<Vec<u8>> = windowed.into_iter().flatten().collect();
+        finish_packets(&mut windowed, &options, Duration::ZERO);
+        assert_eq!(windowed, once);
+    }
 }
@@ -0,0 +1,146 @@
+use crate::error::{NetflowError, Result};
+use std::time::{Duration, Instant};
+
+/// Running totals for periodic `--summary-interval` progress lines
+///
+/// Rates are computed over the time since the previous report, so a line
+/// reflects the current throughput rather than the whole-run average.
+#[derive(Debug)]
+pub struct RunStats {
+    start: Instant,
+    packets: u64,
+    bytes: u64,
+    last_report: Instant,
+    last_packets: u64,
+    last_bytes: u64,
+}
+
+impl RunStats {
+    pub fn new(start: Instant) -> Self {
+        Self {
+            start,
+            packets: 0,
+            bytes: 0,
+            last_report: start,
+            last_packets: 0,
+            last_bytes: 0,
+        }
+    }
+
+    /// Account for a batch of packets that was sent or written
+    pub fn record(&mut self, packets: &[Vec<u8>]) {
+        let count = u64::try_from(packets.len()).unwrap_or(u64::MAX);
+        let bytes: u64 = packets
+            .iter()
+            .map(|packet| u64::try_from(packet.len()).unwrap_or(u64::MAX))
+            .fold(0, u64::saturating_add);
+        self.packets = self.packets.saturating_add(count);
+        self.bytes = self.bytes.saturating_add(bytes);
+    }
+
+    /// Fail unless the recorded totals match the expected ones
+    ///
+    /// Used by `--assert-packets`/`--assert-bytes`; every mismatch is
+    /// listed in the error.
+    pub fn check_totals(&self, packets: Option<u64>, bytes: Option<u64>) -> Result<()> {
+        let mut mismatches = Vec::new();
+        if let Some(expected) = packets
+            && expected != self.packets
+        {
+            mismatches.push(format!(
+                "generated {} packet(s), expected {}",
+                self.packets, expected
+            ));
+        }
+        if let Some(expected) = bytes
+            && expected != self.bytes
+        {
+            mismatches.push(format!(
+                "generated {} bytes, expected {}",
+                self.bytes, expected
+            ));
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(NetflowError::Validation(format!(
+                "Output assertion failed: {}",
+                mismatches.join("; ")
+            )))
+        }
+    }
+
+    /// Progress line if `interval` has passed since the last report
+    pub fn report_due(&mut self, now: Instant, interval: Duration) -> Option<String> {
+        let since_last = now.saturating_duration_since(self.last_report);
+        if since_last < interval {
+            return None;
+        }
+
+        let secs = since_last.as_secs_f64();
+        let packets = self.packets.saturating_sub(self.last_packets);
+        let bytes = self.bytes.saturating_sub(self.last_bytes);
+        let line = format!(
+            "[{}s] {} packet(s) sent ({} bytes), {:.1} packets/sec, {:.1} bytes/sec",
+            now.saturating_duration_since(self.start).as_secs(),
+            self.packets,
+            self.bytes,
+            packets as f64 / secs,
+            bytes as f64 / secs
+        );
+
+        self.last_report = now;
+        self.last_packets = self.packets;
+        self.last_bytes = self.bytes;
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_due() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(10);
+        let mut stats = RunStats::new(start);
+
+        stats.record(&[vec![0; 100], vec![0; 50]]);
+        assert!(
+            stats
+                .report_due(start + Duration::from_secs(9), interval)
+                .is_none()
+        );
+
+        // Rates cover only the packets since the previous report
+        let line = stats
+            .report_due(start + Duration::from_secs(10), interval)
+            .unwrap();
+        assert_eq!(
+            line,
+            "[10s] 2 packet(s) sent (150 bytes), 0.2 packets/sec, 15.0 bytes/sec"
+        );
+        stats.record(&[vec![0; 100]]);
+        let line = stats
+            .report_due(start + Duration::from_secs(30), interval)
+            .unwrap();
+        assert!(line.starts_with("[30s] 3 packet(s) sent (250 bytes), 0.1 packets/sec"));
+    }
+
+    #[test]
+    fn test_check_totals() {
+        let mut stats = RunStats::new(Instant::now());
+        stats.record(&[vec![0; 100], vec![0; 50]]);
+
+        assert!(stats.check_totals(Some(2), Some(150)).is_ok());
+        assert!(stats.check_totals(None, None).is_ok());
+        let err = stats.check_totals(Some(3), Some(150)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Validation error: Output assertion failed: generated 2 packet(s), expected 3"
+        );
+        assert!(stats.check_totals(Some(2), Some(151)).is_err());
+    }
+}
@@ -1,16 +1,39 @@
 use crate::config::schema::{FlowConfig, IPFixConfig, V9Config};
 use crate::error::{NetflowError, Result};
-use crate::generator;
+use crate::generator::{self, GeneratorOptions};
 use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// Cache for storing generated template packets
 /// Ensures templates are generated once and reused across iterations
 #[derive(Debug)]
 pub struct TemplateCache {
     /// V9 template packets keyed by source_id
-    v9_templates: HashMap<u32, Vec<u8>>,
+    v9_templates: HashMap<u32, CachedTemplates>,
     /// IPFIX template packets keyed by observation_domain_id
-    ipfix_templates: HashMap<u32, Vec<u8>>,
+    ipfix_templates: HashMap<u32, CachedTemplates>,
+    /// Next periodic template refresh per exporter
+    refresh_due: HashMap<ExporterKey, Instant>,
+}
+
+/// A cached template packet and the configured header times it is sent with
+///
+/// The header is re-stamped on every send, so templates carry the same
+/// uptime and export time as the data packets sent alongside them.
+#[derive(Debug)]
+struct CachedTemplates {
+    packet: Vec<u8>,
+    /// Configured V9 sys_up_time, before --advance-uptime (unused for IPFIX)
+    sys_up_time: u32,
+    /// Configured V9 unix_secs or IPFIX export_time, or the current time when unset
+    export_time: Option<u32>,
+}
+
+/// Exporter whose templates are cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum ExporterKey {
+    V9(u32),
+    IPFix(u32),
 }
 
 impl TemplateCache {
@@ -19,22 +42,35 @@ impl TemplateCache {
         Self {
             v9_templates: HashMap::new(),
             ipfix_templates: HashMap::new(),
+            refresh_due: HashMap::new(),
         }
     }
 
     /// Build template cache from configuration
     /// This validates that there are no template_id collisions and generates all template packets
-    pub fn from_config(flows: &[FlowConfig], verbose: bool) -> Result<Self> {
+    ///
+    /// When `allow_conflicts` is set, a template_id redefined with different fields
+    /// produces a warning (unless `quiet`) and the later definition wins instead of an error.
+    /// Templates are emitted in template_id order, options templates first with
+    /// `options.options_first`.
+    pub fn from_config(
+        flows: &[FlowConfig],
+        allow_conflicts: bool,
+        options: &GeneratorOptions,
+        quiet: bool,
+        verbose: bool,
+    ) -> Result<Self> {
         let mut cache = Self::new();
 
         // Group flows by exporter to validate and cache templates
         let mut v9_by_source: HashMap<u32, Vec<&V9Config>> = HashMap::new();
         let mut ipfix_by_domain: HashMap<u32, Vec<&IPFixConfig>> = HashMap::new();
 
-        // Group flows by their exporter IDs
+        // Group flows by their exporter IDs; combined-message flows send
+        // their templates inside their own packets instead
         for flow in flows {
             match flow {
-                FlowConfig::V9(config) => {
+                FlowConfig::V9(config) if !config.combined_message => {
                     let source_id = config
                         .header
                         .as_ref()
@@ -42,7 +78,7 @@ impl TemplateCache {
                         .unwrap_or(1);
                     v9_by_source.entry(source_id).or_default().push(config);
                 }
-                FlowConfig::IPFix(config) => {
+                FlowConfig::IPFix(config) if !config.combined_message => {
                     let obs_domain_id = config
                         .header
                         .as_ref()
@@ -59,14 +95,47 @@ impl TemplateCache {
 
         // Build and validate V9 templates
         for (source_id, configs) in v9_by_source {
-            let template_packet = build_v9_template_cache(source_id, &configs, verbose)?;
-            cache.v9_templates.insert(source_id, template_packet);
+            let packet = build_v9_template_cache(
+                source_id,
+                &configs,
+                allow_conflicts,
+                options,
+                quiet,
+                verbose,
+            )?;
+            let header = configs.iter().find_map(|config| config.header.as_ref());
+            cache.v9_templates.insert(
+                source_id,
+                CachedTemplates {
+                    packet,
+                    sys_up_time: header.and_then(|h| h.sys_up_time).unwrap_or(360000),
+                    export_time: header.and_then(|h| h.unix_secs),
+                },
+            );
         }
 
         // Build and validate IPFIX templates
         for (obs_domain_id, configs) in ipfix_by_domain {
-            let template_packet = build_ipfix_template_cache(obs_domain_id, &configs, verbose)?;
-            cache.ipfix_templates.insert(obs_domain_id, template_packet);
+            let packet = build_ipfix_template_cache(
+                obs_domain_id,
+                &configs,
+                allow_conflicts,
+                options,
+                quiet,
+                verbose,
+            )?;
+            let export_time = configs
+                .iter()
+                .find_map(|config| config.header.as_ref())
+                .and_then(|h| h.export_time);
+            cache.ipfix_templates.insert(
+                obs_domain_id,
+                CachedTemplates {
+                    packet,
+                    sys_up_time: 0,
+                    export_time,
+                },
+            );
         }
 
         if verbose && (!cache.v9_templates.is_empty() || !cache.ipfix_templates.is_empty()) {
@@ -85,14 +154,90 @@ impl TemplateCache {
         Ok(cache)
     }
 
-    /// Get all V9 template packets (for sending to network)
-    pub fn v9_templates(&self) -> impl Iterator<Item = &Vec<u8>> {
-        self.v9_templates.values()
+    /// Template packets to send this iteration
+    ///
+    /// With `force` (startup, newly started flows) every exporter's templates
+    /// are sent. Otherwise each exporter refreshes on its own `refresh` timer;
+    /// the first refreshes are staggered across the interval so many exporters
+    /// (e.g. simulated observation domains) don't all refresh at once. Forced
+    /// sends don't shift an exporter's refresh schedule.
+    ///
+    /// Headers are stamped with the current export time and, for V9, the
+    /// configured sys_up_time plus `uptime_elapsed` (--advance-uptime).
+    pub fn due_templates(
+        &mut self,
+        now: Instant,
+        refresh: Duration,
+        force: bool,
+        uptime_elapsed: Option<Duration>,
+    ) -> Vec<Vec<u8>> {
+        let mut exporters: Vec<ExporterKey> = self
+            .v9_templates
+            .keys()
+            .map(|id| ExporterKey::V9(*id))
+            .chain(
+                self.ipfix_templates
+                    .keys()
+                    .map(|id| ExporterKey::IPFix(*id)),
+            )
+            .collect();
+        exporters.sort();
+
+        let exporter_count = u32::try_from(exporters.len()).unwrap_or(u32::MAX);
+        let unix_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| u32::try_from(now.as_secs()).unwrap_or(u32::MAX));
+        let uptime_millis = uptime_elapsed.map_or(0, generator::v9::elapsed_millis);
+        let mut packets = Vec::new();
+        for (index, exporter) in exporters.into_iter().enumerate() {
+            let stagger = u32::try_from(index)
+                .ok()
+                .and_then(|index| refresh.checked_mul(index))
+                .and_then(|offset| offset.checked_div(exporter_count))
+                .unwrap_or_default();
+            let due = self.refresh_due.entry(exporter).or_insert_with(|| {
+                refresh
+                    .checked_add(stagger)
+                    .and_then(|delay| now.checked_add(delay))
+                    .unwrap_or(now)
+            });
+
+            let refresh_now = now >= *due;
+            if refresh_now {
+                *due = now.checked_add(refresh).unwrap_or(now);
+            }
+            if force || refresh_now {
+                // V9: sys_up_time at bytes 4..8, unix_secs at 8..12.
+                // IPFIX: export time at bytes 4..8.
+                let stamped = match exporter {
+                    ExporterKey::V9(id) => self.v9_templates.get(&id).map(|cached| {
+                        let sys_up_time = cached.sys_up_time.wrapping_add(uptime_millis);
+                        let mut packet = cached.packet.clone();
+                        stamp(&mut packet, 4, sys_up_time);
+                        stamp(&mut packet, 8, cached.export_time.unwrap_or(unix_secs));
+                        packet
+                    }),
+                    ExporterKey::IPFix(id) => self.ipfix_templates.get(&id).map(|cached| {
+                        let mut packet = cached.packet.clone();
+                        stamp(&mut packet, 4, cached.export_time.unwrap_or(unix_secs));
+                        packet
+                    }),
+                };
+                packets.extend(stamped);
+            }
+        }
+
+        packets
     }
+}
 
-    /// Get all IPFIX template packets (for sending to network)
-    pub fn ipfix_templates(&self) -> impl Iterator<Item = &Vec<u8>> {
-        self.ipfix_templates.values()
+/// Overwrite the big-endian u32 header field at `offset`
+fn stamp(packet: &mut [u8], offset: usize, value: u32) {
+    if let Some(field) = offset
+        .checked_add(4)
+        .and_then(|end| packet.get_mut(offset..end))
+    {
+        field.copy_from_slice(&value.to_be_bytes());
     }
 }
 
@@ -101,34 +246,42 @@ impl TemplateCache {
 fn build_v9_template_cache(
     source_id: u32,
     configs: &[&V9Config],
+    allow_conflicts: bool,
+    options: &GeneratorOptions,
+    quiet: bool,
     verbose: bool,
 ) -> Result<Vec<u8>> {
     use std::collections::HashSet;
 
     // Collect all templates and validate no collisions
-    let mut template_map: HashMap<u16, Vec<crate::config::schema::V9TemplateField>> =
-        HashMap::new();
+    let mut template_map: HashMap<u16, generator::v9::V9Template> = HashMap::new();
     let mut seen_template_ids = HashSet::new();
 
     for config in configs {
         for flowset in &config.flowsets {
-            if let crate::config::schema::V9FlowSet::Template {
-                template_id,
-                fields,
-            } = flowset
-            {
-                if !seen_template_ids.insert(*template_id) {
+            if let Some(template) = generator::v9::V9Template::from_flowset(flowset)? {
+                let template_id = template.template_id;
+                if !seen_template_ids.insert(template_id) {
                     // Check if the fields are identical
-                    if let Some(existing_fields) = template_map.get(template_id)
-                        && existing_fields != fields
+                    if let Some(existing) = template_map.get(&template_id)
+                        && *existing != template
                     {
-                        return Err(NetflowError::Configuration(format!(
-                            "Template ID {} is used with different field definitions in source_id {}",
-                            template_id, source_id
-                        )));
+                        if !allow_conflicts {
+                            return Err(NetflowError::Configuration(format!(
+                                "Template ID {} is used with different field definitions in source_id {}",
+                                template_id, source_id
+                            )));
+                        }
+                        if !quiet {
+                            eprintln!(
+                                "Warning: Template ID {} is redefined with different fields in source_id {}, using the later definition",
+                                template_id, source_id
+                            );
+                        }
+                        template_map.insert(template_id, template);
                     }
                 } else {
-                    template_map.insert(*template_id, fields.clone());
+                    template_map.insert(template_id, template);
                 }
             }
         }
@@ -151,8 +304,8 @@ fn build_v9_template_cache(
 
     // Build the template packet using the generator's function
     // We'll call the existing build_template_packet function
-    let templates: Vec<(u16, Vec<crate::config::schema::V9TemplateField>)> =
-        template_map.into_iter().collect();
+    let mut templates: Vec<generator::v9::V9Template> = template_map.into_values().collect();
+    templates.sort_by_key(|template| template.template_id);
 
     // Get current time for header
     let now = std::time::SystemTime::now()
@@ -161,13 +314,19 @@ fn build_v9_template_cache(
     let unix_secs = u32::try_from(now.as_secs()).unwrap_or(u32::MAX);
     let sys_up_time = 360000; // Default value
 
-    generator::v9::build_template_packet_for_cache(
+    let mut packet = generator::v9::build_template_packet_for_cache(
         sys_up_time,
         unix_secs,
         0, // sequence number (templates don't affect sequence)
         source_id,
         &templates,
-    )
+        options.options_first,
+        options.group_templates,
+    )?;
+    if let (Some(size), Some(template)) = (options.min_packet_size, templates.first()) {
+        generator::v9::pad_to_min_size(&mut packet, size, template)?;
+    }
+    Ok(packet)
 }
 
 /// Build an IPFIX template packet from multiple configs with the same observation_domain_id
@@ -175,34 +334,43 @@ fn build_v9_template_cache(
 fn build_ipfix_template_cache(
     observation_domain_id: u32,
     configs: &[&IPFixConfig],
+    allow_conflicts: bool,
+    options: &GeneratorOptions,
+    quiet: bool,
     verbose: bool,
 ) -> Result<Vec<u8>> {
     use std::collections::HashSet;
 
-    // Collect all templates and validate no collisions
-    let mut template_map: HashMap<u16, Vec<crate::config::schema::IPFixTemplateField>> =
-        HashMap::new();
+    // Collect all templates (including options templates) and validate no collisions
+    let mut template_map: HashMap<u16, generator::ipfix::IPFixTemplate> = HashMap::new();
     let mut seen_template_ids = HashSet::new();
 
     for config in configs {
         for flowset in &config.flowsets {
-            if let crate::config::schema::IPFixFlowSet::Template {
-                template_id,
-                fields,
-            } = flowset
+            if let Some(template) = generator::ipfix::IPFixTemplate::from_flowset(config, flowset)?
             {
-                if !seen_template_ids.insert(*template_id) {
+                let template_id = template.template_id;
+                if !seen_template_ids.insert(template_id) {
                     // Check if the fields are identical
-                    if let Some(existing_fields) = template_map.get(template_id)
-                        && existing_fields != fields
+                    if let Some(existing) = template_map.get(&template_id)
+                        && *existing != template
                     {
-                        return Err(NetflowError::Configuration(format!(
-                            "Template ID {} is used with different field definitions in observation_domain_id {}",
-                            template_id, observation_domain_id
-                        )));
+                        if !allow_conflicts {
+                            return Err(NetflowError::Configuration(format!(
+                                "Template ID {} is used with different field definitions in observation_domain_id {}",
+                                template_id, observation_domain_id
+                            )));
+                        }
+                        if !quiet {
+                            eprintln!(
+                                "Warning: Template ID {} is redefined with different fields in observation_domain_id {}, using the later definition",
+                                template_id, observation_domain_id
+                            );
+                        }
+                        template_map.insert(template_id, template);
                     }
                 } else {
-                    template_map.insert(*template_id, fields.clone());
+                    template_map.insert(template_id, template);
                 }
             }
         }
@@ -224,8 +392,8 @@ fn build_ipfix_template_cache(
     }
 
     // Build the template packet using the generator's function
-    let templates: Vec<(u16, Vec<crate::config::schema::IPFixTemplateField>)> =
-        template_map.into_iter().collect();
+    let mut templates: Vec<generator::ipfix::IPFixTemplate> = template_map.into_values().collect();
+    templates.sort_by_key(|template| template.template_id);
 
     // Get current time for header
     let now = std::time::SystemTime::now()
@@ -233,10 +401,136 @@ fn build_ipfix_template_cache(
         .map_err(|e| NetflowError::Generation(format!("Failed to get system time: {}", e)))?;
     let export_time = u32::try_from(now.as_secs()).unwrap_or(u32::MAX);
 
-    generator::ipfix::build_template_packet_for_cache(
+    let mut packet = generator::ipfix::build_template_packet_for_cache(
         export_time,
         0, // sequence number (templates don't affect sequence)
         observation_domain_id,
         &templates,
-    )
+        !options.no_padding,
+        options.options_first,
+        options.group_templates,
+    )?;
+    if let (Some(size), Some(template)) = (options.min_packet_size, templates.first()) {
+        generator::ipfix::pad_message(&mut packet, usize::from(size), template)?;
+    }
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_yaml_str;
+
+    #[test]
+    fn test_template_conflict_strict_and_allowed() {
+        let yaml = r#"
+flows:
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IN_BYTES
+            field_length: 4
+  - version: v9
+    flowsets:
+      - type: template
+        template_id: 256
+        fields:
+          - field_type: IN_PKTS
+            field_length: 4
+"#;
+        let config = parse_yaml_str(yaml).unwrap();
+
+        assert!(
+            TemplateCache::from_config(
+                &config.flows,
+                false,
+                &GeneratorOptions::default(),
+                false,
+                false
+            )
+            .is_err()
+        );
+
+        let mut cache = TemplateCache::from_config(
+            &config.flows,
+            true,
+            &GeneratorOptions::default(),
+            true,
+            false,
+        )
+        .unwrap();
+        let packets = cache.due_templates(Instant::now(), Duration::from_secs(30), true, None);
+        let packet = &packets[0];
+        // Header (20) + flowset header (4) + template header (4), then the later field (IN_PKTS = 2)
+        assert_eq!(packet.len(), 32);
+        assert_eq!(&packet[28..30], &2u16.to_be_bytes());
+
+        // Each send carries the advanced uptime and the current export time
+        let packets = cache.due_templates(
+            Instant::now(),
+            Duration::from_secs(30),
+            true,
+            Some(Duration::from_secs(2)),
+        );
+        let sys_up_time = u32::from_be_bytes(packets[0][4..8].try_into().unwrap());
+        assert_eq!(sys_up_time, 362000);
+        let unix_secs = u32::from_be_bytes(packets[0][8..12].try_into().unwrap());
+        assert!(unix_secs > 1_700_000_000);
+    }
+
+    #[test]
+    fn test_due_templates_per_exporter_timing() {
+        let mut config = parse_yaml_str(
+            r#"
+flows:
+  - version: ipfix
+    observation_domains: 2
+    flowsets:
+      - type: template
+        template_id: 300
+        fields:
+          - field_type: octetDeltaCount
+            field_length: 4
+"#,
+        )
+        .unwrap();
+        crate::config::expand_observation_domains(&mut config);
+        let mut cache = TemplateCache::from_config(
+            &config.flows,
+            false,
+            &GeneratorOptions::default(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        let refresh = Duration::from_secs(30);
+        let start = Instant::now();
+        assert_eq!(cache.due_templates(start, refresh, true, None).len(), 2);
+
+        // Domain 1 refreshes after 30s, domain 2 staggered by half the interval
+        let domain_of = |packet: &Vec<u8>| u32::from_be_bytes(packet[12..16].try_into().unwrap());
+        let at = |secs| start + Duration::from_secs(secs);
+        assert!(cache.due_templates(at(29), refresh, false, None).is_empty());
+        let due: Vec<u32> = cache
+            .due_templates(at(30), refresh, false, None)
+            .iter()
+            .map(domain_of)
+            .collect();
+        assert_eq!(due, [1]);
+        let due: Vec<u32> = cache
+            .due_templates(at(45), refresh, false, None)
+            .iter()
+            .map(domain_of)
+            .collect();
+        assert_eq!(due, [2]);
+        let due: Vec<u32> = cache
+            .due_templates(at(60), refresh, false, None)
+            .iter()
+            .map(domain_of)
+            .collect();
+        assert_eq!(due, [1]);
+    }
 }
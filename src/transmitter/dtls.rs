@@ -0,0 +1,285 @@
+//! Export over DTLS (RFC 7011 section 10.4.2) for collectors requiring encrypted transport
+//!
+//! Built with the `dtls` Cargo feature, which links the system OpenSSL.
+
+use crate::cli::Cli;
+use std::path::PathBuf;
+
+/// How the DTLS session authenticates the collector
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(feature = "dtls"), allow(dead_code))]
+pub struct DtlsOptions {
+    /// PEM CA certificate(s) used to verify the collector's certificate
+    pub ca_file: Option<PathBuf>,
+    /// Name the collector's certificate must match (default: its IP address)
+    pub server_name: Option<String>,
+    /// Skip certificate verification entirely (testing only)
+    pub insecure: bool,
+}
+
+impl From<&Cli> for DtlsOptions {
+    fn from(args: &Cli) -> Self {
+        Self {
+            ca_file: args.dtls_ca.clone(),
+            server_name: args.dtls_server_name.clone(),
+            insecure: args.dtls_insecure,
+        }
+    }
+}
+
+#[cfg(feature = "dtls")]
+pub use session::DtlsSender;
+#[cfg(not(feature = "dtls"))]
+pub use stub::DtlsSender;
+
+#[cfg(feature = "dtls")]
+mod session {
+    use super::DtlsOptions;
+    use crate::error::{NetflowError, Result};
+    use openssl::ssl::{SslConnector, SslMethod, SslStream, SslVerifyMode};
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, UdpSocket};
+    use std::time::Duration;
+
+    /// Give up on a handshake the collector never answers
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Path MTU assumed for handshake fragmentation
+    const DTLS_MTU: u32 = 1500;
+
+    /// Connected UDP socket presented to OpenSSL as a datagram stream
+    ///
+    /// Each write is sent as one datagram, so each DTLS record (and each
+    /// NetFlow packet) travels in its own UDP datagram.
+    #[derive(Debug)]
+    struct UdpChannel(UdpSocket);
+
+    impl Read for UdpChannel {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.recv(buf)
+        }
+    }
+
+    impl Write for UdpChannel {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.send(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// DTLS session to a collector, kept open for the whole run
+    pub struct DtlsSender {
+        stream: SslStream<UdpChannel>,
+        destination: SocketAddr,
+    }
+
+    impl DtlsSender {
+        /// Bind `source_port`, connect to the collector and complete the handshake
+        pub fn connect(
+            destination: SocketAddr,
+            source_port: u16,
+            options: &DtlsOptions,
+            verbose: bool,
+        ) -> Result<Self> {
+            let socket = UdpSocket::bind(("0.0.0.0", source_port))
+                .map_err(|e| NetflowError::Network(format!("Failed to bind UDP socket: {}", e)))?;
+            socket
+                .connect(destination)
+                .and_then(|()| socket.set_read_timeout(Some(HANDSHAKE_TIMEOUT)))
+                .map_err(|e| {
+                    NetflowError::Network(format!("Failed to connect to {}: {}", destination, e))
+                })?;
+
+            let tls_error = |e: openssl::error::ErrorStack| {
+                NetflowError::Network(format!("DTLS setup failed: {}", e))
+            };
+            let mut builder = SslConnector::builder(SslMethod::dtls()).map_err(tls_error)?;
+            if let Some(ref ca_file) = options.ca_file {
+                builder.set_ca_file(ca_file).map_err(tls_error)?;
+            }
+            if options.insecure {
+                builder.set_verify(SslVerifyMode::NONE);
+            }
+
+            let mut config = builder.build().configure().map_err(tls_error)?;
+            config.set_mtu(DTLS_MTU).map_err(tls_error)?;
+            // Without a server name the certificate must be issued for the collector's IP
+            let server_name = match options.server_name {
+                Some(ref name) => name.as_str(),
+                None => {
+                    config.set_use_server_name_indication(false);
+                    config.set_verify_hostname(false);
+                    if !options.insecure {
+                        config
+                            .param_mut()
+                            .set_ip(destination.ip())
+                            .map_err(tls_error)?;
+                    }
+                    ""
+                }
+            };
+
+            if verbose {
+                println!("Starting DTLS handshake with {}", destination);
+            }
+            let stream = config
+                .connect(server_name, UdpChannel(socket))
+                .map_err(|e| {
+                    NetflowError::Network(format!(
+                        "DTLS handshake with {} failed: {}",
+                        destination, e
+                    ))
+                })?;
+            if verbose {
+                println!("DTLS session established ({})", stream.ssl().version_str());
+            }
+
+            Ok(Self {
+                stream,
+                destination,
+            })
+        }
+
+        /// Send each packet as one DTLS record, in order
+        pub fn send(&mut self, packets: &[Vec<u8>], verbose: bool) -> Result<()> {
+            for (i, packet) in packets.iter().enumerate() {
+                self.stream.ssl_write(packet).map_err(|e| {
+                    NetflowError::Network(format!("Failed to send packet over DTLS: {}", e))
+                })?;
+
+                if verbose {
+                    let packet_num = i.checked_add(1).unwrap_or(i);
+                    println!(
+                        "Sent packet {} ({} bytes) over DTLS to {}",
+                        packet_num,
+                        packet.len(),
+                        self.destination
+                    );
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Placeholder when built without the `dtls` feature; connecting always fails
+#[cfg(not(feature = "dtls"))]
+mod stub {
+    use super::DtlsOptions;
+    use crate::error::{NetflowError, Result};
+    use std::net::SocketAddr;
+
+    #[allow(dead_code)]
+    pub struct DtlsSender(std::convert::Infallible);
+
+    impl DtlsSender {
+        pub fn connect(
+            _destination: SocketAddr,
+            _source_port: u16,
+            _options: &DtlsOptions,
+            _verbose: bool,
+        ) -> Result<Self> {
+            Err(NetflowError::Configuration(
+                "--transport dtls requires building with the `dtls` feature \
+                 (cargo build --features dtls)"
+                    .to_string(),
+            ))
+        }
+
+        pub fn send(&mut self, _packets: &[Vec<u8>], _verbose: bool) -> Result<()> {
+            match self.0 {}
+        }
+    }
+}
+
+#[cfg(all(test, feature = "dtls"))]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::ssl::{SslAcceptor, SslMethod};
+    use openssl::x509::X509;
+    use openssl::x509::extension::SubjectAlternativeName;
+    use std::io::{Read, Write};
+    use std::net::UdpSocket;
+
+    /// Server side of the channel; learns its peer from the first datagram
+    #[derive(Debug)]
+    struct ServerChannel(UdpSocket);
+
+    impl Read for ServerChannel {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let (len, peer) = self.0.recv_from(buf)?;
+            self.0.connect(peer)?;
+            Ok(len)
+        }
+    }
+
+    impl Write for ServerChannel {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.send(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dtls_send() {
+        // Self-signed certificate for 127.0.0.1
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let san = SubjectAlternativeName::new()
+            .ip("127.0.0.1")
+            .build(&cert.x509v3_context(None, None))
+            .unwrap();
+        cert.append_extension(san).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = cert.build();
+
+        let ca_path =
+            std::env::temp_dir().join(format!("netflow_dtls_ca_{}.pem", std::process::id()));
+        std::fs::write(&ca_path, cert.to_pem().unwrap()).unwrap();
+
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let destination = server.local_addr().unwrap();
+        let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::dtls()).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        let acceptor = acceptor.build();
+        let server = std::thread::spawn(move || {
+            let mut stream = acceptor.accept(ServerChannel(server)).unwrap();
+            let mut buf = [0u8; 2048];
+            let first = stream.read(&mut buf).unwrap();
+            let second = stream.read(&mut buf).unwrap();
+            (first, buf[..second].to_vec())
+        });
+
+        let options = DtlsOptions {
+            ca_file: Some(ca_path.clone()),
+            ..Default::default()
+        };
+        let mut sender = DtlsSender::connect(destination, 0, &options, false).unwrap();
+        sender
+            .send(&[vec![0x00, 0x0a, 0x00, 0x10], vec![0xab; 1400]], false)
+            .unwrap();
+
+        // Each packet arrives as its own record
+        let (first, second) = server.join().unwrap();
+        std::fs::remove_file(&ca_path).unwrap();
+        assert_eq!(first, 4);
+        assert_eq!(second, vec![0xab; 1400]);
+    }
+}
@@ -0,0 +1,104 @@
+//! Streaming --output into a named pipe (FIFO)
+//!
+//! Opening a FIFO for writing blocks until another process opens it for
+//! reading, which would also block Ctrl+C handling. Instead the FIFO is
+//! polled with non-blocking opens until a reader appears.
+
+use crate::error::Result;
+use std::fs::File;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Delay between attempts to open a FIFO that has no reader yet
+#[cfg(unix)]
+const READER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether `path` is an existing named pipe
+#[cfg(unix)]
+pub fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+pub fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+/// Wait until a reader has opened the FIFO at `path`
+///
+/// Returns a write handle on the FIFO, to be held until the output writer
+/// has opened the path itself; closing every write handle would signal end
+/// of stream to the reader. Returns `None` if `path` is not a FIFO or
+/// `shutdown` was set while waiting.
+#[cfg(unix)]
+pub fn wait_for_fifo_reader(path: &Path, shutdown: &AtomicBool) -> Result<Option<File>> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if !is_fifo(path) {
+        return Ok(None);
+    }
+
+    while !shutdown.load(Ordering::Relaxed) {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+        {
+            Ok(file) => return Ok(Some(file)),
+            // ENXIO: no process has the FIFO open for reading yet
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) => {
+                std::thread::sleep(READER_POLL_INTERVAL);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(not(unix))]
+pub fn wait_for_fifo_reader(_path: &Path, _shutdown: &AtomicBool) -> Result<Option<File>> {
+    Ok(None)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_wait_for_fifo_reader() {
+        let path = std::env::temp_dir().join(format!("netflow_fifo_{}", std::process::id()));
+        let status = std::process::Command::new("mkfifo")
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert!(is_fifo(&path));
+
+        // Without a reader the wait only ends on shutdown
+        let shutdown = AtomicBool::new(true);
+        assert!(wait_for_fifo_reader(&path, &shutdown).unwrap().is_none());
+
+        let reader_path = path.clone();
+        let reader = std::thread::spawn(move || {
+            let mut data = Vec::new();
+            File::open(reader_path)
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            data
+        });
+        shutdown.store(false, Ordering::Relaxed);
+        let guard = wait_for_fifo_reader(&path, &shutdown).unwrap().unwrap();
+
+        // Output written through a separately opened handle reaches the reader
+        std::fs::write(&path, b"pcap").unwrap();
+        drop(guard);
+        assert_eq!(reader.join().unwrap(), b"pcap");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
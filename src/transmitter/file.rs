@@ -0,0 +1,186 @@
+use crate::cli::OutputFormat;
+use crate::error::Result;
+use crate::transmitter::pcapng::PersistentPcapNgWriter;
+use crate::transmitter::udp::{PcapOptions, PersistentPcapWriter, write_to_file};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Persistent writer for bare NetFlow payloads (hex or raw format)
+///
+/// Unlike the pcap writer, payloads are written without any
+/// Ethernet/IP/UDP wrapping.
+pub struct PersistentPayloadWriter {
+    writer: BufWriter<File>,
+    format: OutputFormat,
+    verbose: bool,
+}
+
+impl PersistentPayloadWriter {
+    /// Create a new persistent payload writer
+    pub fn new(path: &Path, format: OutputFormat, verbose: bool) -> Result<Self> {
+        let file = File::create(path)?;
+
+        if verbose {
+            println!("Created {:?} output file at {:?}", format, path);
+        }
+
+        Ok(Self {
+            writer: BufWriter::new(file),
+            format,
+            verbose,
+        })
+    }
+
+    /// Write packets to the output file
+    pub fn write_packets(&mut self, packets: &[Vec<u8>]) -> Result<()> {
+        if self.verbose {
+            println!("Writing {} payload(s) to output file", packets.len());
+        }
+
+        write_payloads(&mut self.writer, packets, self.format)?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Flush and close the output file
+    pub fn close(mut self) -> Result<()> {
+        self.writer.flush()?;
+
+        if self.verbose {
+            println!("Output file closed successfully");
+        }
+
+        Ok(())
+    }
+}
+
+/// Persistent output writer for continuous mode, selected by output format
+pub enum OutputWriter {
+    Pcap(PersistentPcapWriter),
+    PcapNg(PersistentPcapNgWriter),
+    Payload(PersistentPayloadWriter),
+}
+
+impl OutputWriter {
+    /// Create a writer for the given output format
+    pub fn new(
+        path: &Path,
+        format: OutputFormat,
+        destination: SocketAddr,
+        pcap_options: &PcapOptions,
+        verbose: bool,
+    ) -> Result<Self> {
+        match format {
+            OutputFormat::Pcap => Ok(Self::Pcap(PersistentPcapWriter::new(
+                path,
+                destination,
+                pcap_options,
+                verbose,
+            )?)),
+            OutputFormat::Pcapng => Ok(Self::PcapNg(PersistentPcapNgWriter::new(
+                path,
+                destination,
+                pcap_options,
+                verbose,
+            )?)),
+            OutputFormat::Hex | OutputFormat::Raw => Ok(Self::Payload(
+                PersistentPayloadWriter::new(path, format, verbose)?,
+            )),
+        }
+    }
+
+    /// Write packets to the output file
+    pub fn write_packets(&mut self, packets: &[Vec<u8>]) -> Result<()> {
+        match self {
+            Self::Pcap(writer) => writer.write_packets(packets),
+            Self::PcapNg(writer) => writer.write_packets(packets),
+            Self::Payload(writer) => writer.write_packets(packets),
+        }
+    }
+
+    /// Packets left out of the file by --max-write-mbps
+    pub fn dropped_packets(&self) -> u64 {
+        match self {
+            Self::Pcap(writer) => writer.dropped_packets(),
+            Self::PcapNg(writer) => writer.dropped_packets(),
+            Self::Payload(_) => 0,
+        }
+    }
+
+    /// Close the output file
+    pub fn close(self) -> Result<()> {
+        match self {
+            Self::Pcap(writer) => writer.close(),
+            Self::PcapNg(writer) => writer.close(),
+            Self::Payload(writer) => writer.close(),
+        }
+    }
+}
+
+/// Write packets to a file in the requested output format (single-shot mode)
+pub fn write_output_file(
+    packets: &[Vec<u8>],
+    path: &Path,
+    format: OutputFormat,
+    destination: SocketAddr,
+    pcap_options: &PcapOptions,
+    verbose: bool,
+) -> Result<()> {
+    match format {
+        OutputFormat::Pcap => {
+            write_to_file(packets, path, destination, pcap_options, verbose, true)
+        }
+        OutputFormat::Pcapng => {
+            let mut writer = PersistentPcapNgWriter::new(path, destination, pcap_options, verbose)?;
+            writer.write_packets(packets)?;
+            writer.close()
+        }
+        OutputFormat::Hex | OutputFormat::Raw => {
+            let mut writer = PersistentPayloadWriter::new(path, format, verbose)?;
+            writer.write_packets(packets)?;
+            writer.close()
+        }
+    }
+}
+
+/// Encode payloads as hex lines or raw bytes
+fn write_payloads<W: Write>(
+    writer: &mut W,
+    packets: &[Vec<u8>],
+    format: OutputFormat,
+) -> Result<()> {
+    for packet in packets {
+        match format {
+            OutputFormat::Hex => {
+                for byte in packet {
+                    write!(writer, "{:02x}", byte)?;
+                }
+                writeln!(writer)?;
+            }
+            _ => writer.write_all(packet)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_payloads_hex_and_raw() {
+        let packets = vec![vec![0x00, 0x05, 0xab], vec![0x0a, 0xff]];
+
+        let mut hex = Vec::new();
+        write_payloads(&mut hex, &packets, OutputFormat::Hex).unwrap();
+        assert_eq!(String::from_utf8(hex).unwrap(), "0005ab\n0aff\n");
+
+        let mut raw = Vec::new();
+        write_payloads(&mut raw, &packets, OutputFormat::Raw).unwrap();
+        assert_eq!(raw, vec![0x00, 0x05, 0xab, 0x0a, 0xff]);
+    }
+}
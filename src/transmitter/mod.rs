@@ -1,3 +1,21 @@
+pub mod dtls;
+pub mod fifo;
+pub mod file;
+pub mod order;
+pub mod pcapng;
+pub mod ports;
+pub mod raw;
+pub mod replay;
+pub mod tcp;
+pub mod throttle;
 pub mod udp;
 
+pub use dtls::{DtlsOptions, DtlsSender};
+pub use fifo::{is_fifo, wait_for_fifo_reader};
+pub use file::*;
+pub use order::reverse_data_packets;
+pub use ports::{ExporterPort, ExporterPorts};
+pub use raw::RawSender;
+pub use replay::read_replay_file;
+pub use tcp::TcpSender;
 pub use udp::*;
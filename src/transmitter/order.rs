@@ -0,0 +1,53 @@
+//! Deliberate reordering of a batch before it is sent (`--reverse-order`)
+
+/// Send a batch's data packets in reverse order, keeping templates in front
+///
+/// Sequence numbers are assigned in ascending order before this runs, so a
+/// collector receives them descending within the batch. Packets that open
+/// with a V9 or IPFIX template set stay first, in their original order, so
+/// the data that follows can still be decoded.
+pub fn reverse_data_packets(packets: &mut Vec<Vec<u8>>) {
+    let (mut ordered, mut data): (Vec<_>, Vec<_>) = packets
+        .drain(..)
+        .partition(|packet| starts_with_template(packet));
+    data.reverse();
+    ordered.append(&mut data);
+    *packets = ordered;
+}
+
+/// Whether the first flowset/set of a V9 or IPFIX packet is a (options) template
+fn starts_with_template(packet: &[u8]) -> bool {
+    let read_u16 = |offset: usize| {
+        packet
+            .get(offset..offset.saturating_add(2))
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    };
+    match read_u16(0) {
+        Some(9) => matches!(read_u16(20), Some(0 | 1)),
+        Some(10) => matches!(read_u16(16), Some(2 | 3)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_data_packets() {
+        let ipfix = |set_id: u16, seq: u8| {
+            let mut packet = vec![0, 10, 0, 20, 0, 0, 0, 0, 0, 0, 0, seq, 0, 0, 0, 1];
+            packet.extend_from_slice(&set_id.to_be_bytes());
+            packet.extend_from_slice(&[0, 4]);
+            packet
+        };
+        let v5 = |seq: u8| vec![0, 5, 0, 1, seq];
+
+        let mut packets = vec![ipfix(2, 0), ipfix(256, 0), ipfix(256, 1), v5(0), v5(1)];
+        reverse_data_packets(&mut packets);
+        assert_eq!(
+            packets,
+            vec![ipfix(2, 0), v5(1), v5(0), ipfix(256, 1), ipfix(256, 0)]
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::error::{NetflowError, Result};
+use crate::transmitter::throttle::WriteLimiter;
+use crate::transmitter::udp::{PcapOptions, build_udp_packet, packet_timestamp};
+use pcap_file::pcapng::PcapNgWriter;
+use pcap_file::pcapng::blocks::enhanced_packet::{EnhancedPacketBlock, EnhancedPacketOption};
+use pcap_file::pcapng::blocks::interface_description::{
+    InterfaceDescriptionBlock, InterfaceDescriptionOption,
+};
+use std::borrow::Cow;
+use std::fs::File;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Persistent pcapng writer that annotates each packet with its exporter
+///
+/// The synthetic IP/UDP headers carry the same source for every exporter,
+/// so the exporter (source_id / observation_domain_id) is recorded as a
+/// packet comment instead.
+pub struct PersistentPcapNgWriter {
+    writer: PcapNgWriter<File>,
+    destination: SocketAddr,
+    options: PcapOptions,
+    next_ip_id: u16,
+    limiter: Option<WriteLimiter>,
+    verbose: bool,
+}
+
+impl PersistentPcapNgWriter {
+    /// Create a new pcapng file with a single interface for the destination
+    pub fn new(
+        path: &Path,
+        destination: SocketAddr,
+        options: &PcapOptions,
+        verbose: bool,
+    ) -> Result<Self> {
+        let file = File::create(path)?;
+        let mut writer =
+            PcapNgWriter::new(file).map_err(|e| NetflowError::Io(std::io::Error::other(e)))?;
+
+        let interface = InterfaceDescriptionBlock {
+            linktype: options.datalink.pcap_datalink(),
+            snaplen: 0,
+            options: vec![
+                InterfaceDescriptionOption::IfName(Cow::Borrowed("netflow_generator")),
+                InterfaceDescriptionOption::Comment(Cow::Owned(format!(
+                    "NetFlow export to {}",
+                    destination
+                ))),
+            ],
+        };
+        writer
+            .write_pcapng_block(interface)
+            .map_err(|e| NetflowError::Io(std::io::Error::other(e)))?;
+
+        if verbose {
+            println!("Created pcapng file at {:?}", path);
+        }
+
+        Ok(Self {
+            writer,
+            destination,
+            options: options.clone(),
+            next_ip_id: options.ip_id_start,
+            limiter: options
+                .write_limit
+                .map(|limit| WriteLimiter::new(limit, std::time::Instant::now())),
+            verbose,
+        })
+    }
+
+    /// Write packets to the pcapng file, each with an exporter comment
+    ///
+    /// Packets over the write limit are dropped or delayed first.
+    pub fn write_packets(&mut self, packets: &[Vec<u8>]) -> Result<()> {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let packets = match self.limiter {
+            Some(ref mut limiter) => limiter.admit(packets, self.options.ttls().len()),
+            None => Cow::Borrowed(packets),
+        };
+
+        if self.verbose {
+            println!("Writing {} packet(s) to pcapng file", packets.len());
+        }
+
+        let src_ip = self.options.source_ip();
+        let batch_start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        for (i, netflow_payload) in packets.iter().enumerate() {
+            let timestamp = packet_timestamp(batch_start, i, &self.options)?;
+            let ip_id = self.next_ip_id;
+            self.next_ip_id = self.next_ip_id.wrapping_add(1);
+
+            // With --ttl-sweep the same packet is recorded once per TTL
+            for &ttl in self.options.ttls() {
+                let packet_data = build_udp_packet(
+                    src_ip,
+                    self.options.source_port(netflow_payload),
+                    self.destination,
+                    self.options.datalink,
+                    ttl,
+                    ip_id,
+                    netflow_payload,
+                )?;
+
+                let options = exporter_comment(netflow_payload)
+                    .map(|comment| vec![EnhancedPacketOption::Comment(Cow::Owned(comment))])
+                    .unwrap_or_default();
+
+                let block = EnhancedPacketBlock {
+                    interface_id: 0,
+                    timestamp,
+                    original_len: u32::try_from(packet_data.len()).map_err(|_| {
+                        NetflowError::InvalidPacket("Packet size exceeds u32::MAX".to_string())
+                    })?,
+                    data: Cow::Borrowed(&packet_data),
+                    options,
+                };
+
+                self.writer
+                    .write_pcapng_block(block)
+                    .map_err(|e| NetflowError::Io(std::io::Error::other(e)))?;
+
+                if self.verbose {
+                    let packet_num = i.checked_add(1).unwrap_or(i);
+                    println!(
+                        "Wrote packet {} ({} bytes, TTL {})",
+                        packet_num,
+                        packet_data.len(),
+                        ttl
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Packets left out of the file by the write limit
+    pub fn dropped_packets(&self) -> u64 {
+        self.limiter.as_ref().map_or(0, WriteLimiter::dropped)
+    }
+
+    /// Close the pcapng writer (drops the writer which flushes automatically)
+    pub fn close(self) -> Result<()> {
+        drop(self.writer);
+
+        if self.verbose {
+            println!("Pcapng file closed successfully");
+        }
+
+        Ok(())
+    }
+}
+
+/// Describe the exporter of a NetFlow payload, read from its header
+pub fn exporter_comment(payload: &[u8]) -> Option<String> {
+    let version = u16::from_be_bytes([*payload.first()?, *payload.get(1)?]);
+    let be_u32 = |offset: usize| -> Option<u32> {
+        let end = offset.checked_add(4)?;
+        Some(u32::from_be_bytes(
+            payload.get(offset..end)?.try_into().ok()?,
+        ))
+    };
+
+    match version {
+        5 => Some(format!(
+            "NetFlow v5 engine_type={} engine_id={}",
+            payload.get(20)?,
+            payload.get(21)?
+        )),
+        7 => Some("NetFlow v7".to_string()),
+        9 => Some(format!("NetFlow v9 source_id={}", be_u32(16)?)),
+        10 => Some(format!("IPFIX observation_domain_id={}", be_u32(12)?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exporter_comment() {
+        let mut v9 = vec![0u8; 20];
+        v9[1] = 9;
+        v9[16..20].copy_from_slice(&7u32.to_be_bytes());
+        assert_eq!(
+            exporter_comment(&v9).as_deref(),
+            Some("NetFlow v9 source_id=7")
+        );
+
+        let mut ipfix = vec![0u8; 16];
+        ipfix[1] = 10;
+        ipfix[12..16].copy_from_slice(&3u32.to_be_bytes());
+        assert_eq!(
+            exporter_comment(&ipfix).as_deref(),
+            Some("IPFIX observation_domain_id=3")
+        );
+
+        // Truncated headers are not annotated
+        assert_eq!(exporter_comment(&v9[..10]), None);
+    }
+}
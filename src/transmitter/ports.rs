@@ -0,0 +1,105 @@
+//! Per-exporter UDP source ports (`--exporter-port`)
+//!
+//! Collectors often key exporter state on the source IP:port, so each
+//! simulated exporter can be given its own stable source port.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Source port mapped to an exporter ID, parsed from `ID=PORT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExporterPort {
+    pub exporter_id: u32,
+    pub port: u16,
+}
+
+impl FromStr for ExporterPort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((id, port)) = s.split_once('=') else {
+            return Err(format!("expected ID=PORT, got '{}'", s));
+        };
+        Ok(Self {
+            exporter_id: id
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid exporter ID '{}'", id))?,
+            port: port
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid port '{}'", port))?,
+        })
+    }
+}
+
+/// Source ports for exporters listed with `--exporter-port`
+#[derive(Debug, Clone, Default)]
+pub struct ExporterPorts(HashMap<u32, u16>);
+
+impl ExporterPorts {
+    pub fn new(ports: &[ExporterPort]) -> Self {
+        Self(
+            ports
+                .iter()
+                .map(|entry| (entry.exporter_id, entry.port))
+                .collect(),
+        )
+    }
+
+    /// Source port for a NetFlow payload, or `default` if its exporter is unmapped
+    pub fn port_for(&self, payload: &[u8], default: u16) -> u16 {
+        exporter_id(payload)
+            .and_then(|id| self.0.get(&id).copied())
+            .unwrap_or(default)
+    }
+}
+
+/// Exporter ID from a NetFlow payload header
+///
+/// The V5 engine_id, V9 source_id or IPFIX observation_domain_id. V7 has
+/// no exporter ID.
+pub fn exporter_id(payload: &[u8]) -> Option<u32> {
+    let version = u16::from_be_bytes([*payload.first()?, *payload.get(1)?]);
+    let be_u32 = |offset: usize| -> Option<u32> {
+        let end = offset.checked_add(4)?;
+        Some(u32::from_be_bytes(
+            payload.get(offset..end)?.try_into().ok()?,
+        ))
+    };
+
+    match version {
+        5 => payload.get(21).copied().map(u32::from),
+        9 => be_u32(16),
+        10 => be_u32(12),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exporter_ports() {
+        let ports = ExporterPorts::new(&["1=3001".parse().unwrap(), "2 = 3002".parse().unwrap()]);
+
+        let mut v9 = vec![0u8; 20];
+        v9[1] = 9;
+        v9[16..20].copy_from_slice(&2u32.to_be_bytes());
+        assert_eq!(ports.port_for(&v9, 2056), 3002);
+
+        let mut ipfix = vec![0u8; 16];
+        ipfix[1] = 10;
+        ipfix[12..16].copy_from_slice(&7u32.to_be_bytes());
+        assert_eq!(ports.port_for(&ipfix, 2056), 2056);
+
+        let mut v5 = vec![0u8; 24];
+        v5[1] = 5;
+        v5[21] = 1;
+        assert_eq!(ports.port_for(&v5, 2056), 3001);
+
+        assert!("1:3001".parse::<ExporterPort>().is_err());
+        assert!("1=70000".parse::<ExporterPort>().is_err());
+    }
+}
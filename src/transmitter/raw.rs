@@ -0,0 +1,153 @@
+//! Sending from a spoofed exporter address over a raw socket (`--spoof-source`)
+//!
+//! Each NetFlow payload is wrapped in a full IPv4/UDP packet, built as for
+//! pcap output but with the spoofed source address, and handed to the kernel
+//! with the IP header included. Raw sockets need root or CAP_NET_RAW; without
+//! them opening the sender fails rather than sending from the real address.
+
+use crate::transmitter::ports::ExporterPorts;
+
+#[cfg(unix)]
+pub use socket::RawSender;
+#[cfg(not(unix))]
+pub use stub::RawSender;
+
+#[cfg(unix)]
+mod socket {
+    use super::ExporterPorts;
+    use crate::cli::Datalink;
+    use crate::error::{NetflowError, Result};
+    use crate::transmitter::udp::{DEFAULT_TTL, build_udp_packet};
+    use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    /// IPPROTO_RAW: the sender supplies the whole IPv4 header
+    const IPPROTO_RAW: i32 = 255;
+
+    /// Raw socket sending every packet from one spoofed source address
+    pub struct RawSender {
+        socket: Socket,
+        source_ip: Ipv4Addr,
+        source_port: u16,
+        exporter_ports: ExporterPorts,
+        destination: SocketAddr,
+        /// IP identification of the next packet
+        next_ip_id: u16,
+    }
+
+    impl RawSender {
+        /// Open a raw socket for packets from `source_ip` to `destination`
+        ///
+        /// Packets use `source_port`, or their exporter's port from
+        /// `exporter_ports`, as with UDP sending.
+        pub fn open(
+            source_ip: Ipv4Addr,
+            destination: SocketAddr,
+            source_port: u16,
+            exporter_ports: ExporterPorts,
+            verbose: bool,
+        ) -> Result<Self> {
+            if !destination.is_ipv4() {
+                return Err(NetflowError::InvalidDestination(
+                    "--spoof-source requires an IPv4 destination".to_string(),
+                ));
+            }
+
+            let socket = Socket::new(
+                Domain::IPV4,
+                Type::RAW,
+                Some(Protocol::from(IPPROTO_RAW)),
+            )
+            .map_err(|e| {
+                NetflowError::Network(if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    format!(
+                        "--spoof-source needs a raw socket, which requires root or CAP_NET_RAW: {}",
+                        e
+                    )
+                } else {
+                    format!("Failed to open raw socket: {}", e)
+                })
+            })?;
+            socket.set_header_included_v4(true).map_err(|e| {
+                NetflowError::Network(format!("Failed to enable IP_HDRINCL: {}", e))
+            })?;
+
+            if verbose {
+                println!(
+                    "Opened raw socket, sending from spoofed source {}",
+                    source_ip
+                );
+            }
+
+            Ok(Self {
+                socket,
+                source_ip,
+                source_port,
+                exporter_ports,
+                destination,
+                next_ip_id: 1,
+            })
+        }
+
+        /// Send each packet in its own spoofed IPv4/UDP datagram
+        pub fn send(&mut self, packets: &[Vec<u8>], verbose: bool) -> Result<()> {
+            let destination = SockAddr::from(self.destination);
+            for packet in packets {
+                let port = self.exporter_ports.port_for(packet, self.source_port);
+                let datagram = build_udp_packet(
+                    self.source_ip,
+                    port,
+                    self.destination,
+                    Datalink::Raw,
+                    DEFAULT_TTL,
+                    self.next_ip_id,
+                    packet,
+                )?;
+                self.next_ip_id = self.next_ip_id.wrapping_add(1);
+
+                self.socket
+                    .send_to(&datagram, &destination)
+                    .map_err(|e| NetflowError::Network(format!("Failed to send packet: {}", e)))?;
+
+                if verbose {
+                    println!(
+                        "Sent packet ({} bytes) from {}:{} to {}",
+                        packet.len(),
+                        self.source_ip,
+                        port,
+                        self.destination
+                    );
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod stub {
+    use super::ExporterPorts;
+    use crate::error::{NetflowError, Result};
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    #[allow(dead_code)]
+    pub struct RawSender(std::convert::Infallible);
+
+    impl RawSender {
+        pub fn open(
+            _source_ip: Ipv4Addr,
+            _destination: SocketAddr,
+            _source_port: u16,
+            _exporter_ports: ExporterPorts,
+            _verbose: bool,
+        ) -> Result<Self> {
+            Err(NetflowError::Configuration(
+                "--spoof-source is only supported on Unix".to_string(),
+            ))
+        }
+
+        pub fn send(&mut self, _packets: &[Vec<u8>], _verbose: bool) -> Result<()> {
+            match self.0 {}
+        }
+    }
+}
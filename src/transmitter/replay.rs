@@ -0,0 +1,169 @@
+//! Replaying NetFlow payloads captured in a pcap file (`--replay`)
+//!
+//! The pcap global header's magic number gives the byte order of the file,
+//! so captures written on big-endian and little-endian hosts both replay
+//! correctly. Only IPv4/UDP packets are extracted; anything else is skipped.
+
+use crate::error::{NetflowError, Result};
+use pcap_file::DataLink;
+use pcap_file::pcap::PcapReader;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// IPv4 EtherType
+const ETHERTYPE_IPV4: u16 = 0x0800;
+/// 802.1Q VLAN tag EtherType
+const ETHERTYPE_VLAN: u16 = 0x8100;
+/// UDP protocol number in the IPv4 header
+const IPPROTO_UDP: u8 = 17;
+/// UDP header length
+const UDP_HEADER_LEN: usize = 8;
+
+/// Read the UDP payloads of every IPv4/UDP packet in a pcap file
+pub fn read_replay_file(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let file = File::open(path)?;
+    read_udp_payloads(BufReader::new(file)).map_err(|e| match e {
+        NetflowError::InvalidPacket(msg) => {
+            NetflowError::InvalidPacket(format!("Failed to replay {:?}: {}", path, msg))
+        }
+        other => other,
+    })
+}
+
+/// Read UDP payloads from a pcap stream of either byte order
+fn read_udp_payloads<R: Read>(reader: R) -> Result<Vec<Vec<u8>>> {
+    let mut reader =
+        PcapReader::new(reader).map_err(|e| NetflowError::InvalidPacket(e.to_string()))?;
+    let datalink = reader.header().datalink;
+
+    let mut payloads = Vec::new();
+    while let Some(packet) = reader.next_packet() {
+        let packet = packet.map_err(|e| NetflowError::InvalidPacket(e.to_string()))?;
+        if let Some(payload) = udp_payload(datalink, &packet.data)? {
+            payloads.push(payload.to_vec());
+        }
+    }
+
+    Ok(payloads)
+}
+
+/// UDP payload of a captured frame, or `None` if it is not IPv4/UDP
+fn udp_payload(datalink: DataLink, frame: &[u8]) -> Result<Option<&[u8]>> {
+    let be_u16 = |offset: usize| -> Option<u16> {
+        Some(u16::from_be_bytes([
+            *frame.get(offset)?,
+            *frame.get(offset.checked_add(1)?)?,
+        ]))
+    };
+
+    // Offset of the IPv4 header, after the link-layer header
+    let ip_start = match datalink {
+        DataLink::ETHERNET => match be_u16(12) {
+            Some(ETHERTYPE_IPV4) => 14,
+            Some(ETHERTYPE_VLAN) if be_u16(16) == Some(ETHERTYPE_IPV4) => 18,
+            _ => return Ok(None),
+        },
+        DataLink::LINUX_SLL => match be_u16(14) {
+            Some(ETHERTYPE_IPV4) => 16,
+            _ => return Ok(None),
+        },
+        DataLink::RAW | DataLink::IPV4 => 0,
+        other => {
+            return Err(NetflowError::InvalidPacket(format!(
+                "unsupported link type {:?}",
+                other
+            )));
+        }
+    };
+
+    let Some(ip) = frame.get(ip_start..) else {
+        return Ok(None);
+    };
+    match (ip.first(), ip.get(9)) {
+        (Some(version_ihl), Some(&IPPROTO_UDP)) if version_ihl >> 4 == 4 => {
+            let udp_start = usize::from(version_ihl & 0x0f).saturating_mul(4);
+            let udp_len = ip
+                .get(udp_start.saturating_add(4)..udp_start.saturating_add(6))
+                .and_then(|len| len.try_into().ok())
+                .map_or(0, |len| usize::from(u16::from_be_bytes(len)));
+            let payload_start = udp_start.saturating_add(UDP_HEADER_LEN);
+            // Trust the UDP length over the frame length, which may include padding
+            let payload_end = udp_start.saturating_add(udp_len).min(ip.len());
+            Ok(ip.get(payload_start..payload_end))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Datalink;
+    use crate::transmitter::udp::build_udp_packet;
+
+    /// Hand-built pcap file with one record, in the given byte order
+    fn sample_pcap(big_endian: bool, frame: &[u8]) -> Vec<u8> {
+        let u16_bytes = |v: u16| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let u32_bytes = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let len = u32::try_from(frame.len()).unwrap();
+
+        let mut file = Vec::new();
+        file.extend_from_slice(&u32_bytes(0xa1b2_c3d4)); // magic
+        file.extend_from_slice(&u16_bytes(2)); // version major
+        file.extend_from_slice(&u16_bytes(4)); // version minor
+        file.extend_from_slice(&u32_bytes(0)); // thiszone
+        file.extend_from_slice(&u32_bytes(0)); // sigfigs
+        file.extend_from_slice(&u32_bytes(65535)); // snaplen
+        file.extend_from_slice(&u32_bytes(1)); // DLT_EN10MB
+        file.extend_from_slice(&u32_bytes(1_700_000_000)); // ts_sec
+        file.extend_from_slice(&u32_bytes(0)); // ts_usec
+        file.extend_from_slice(&u32_bytes(len)); // incl_len
+        file.extend_from_slice(&u32_bytes(len)); // orig_len
+        file.extend_from_slice(frame);
+        file
+    }
+
+    #[test]
+    fn test_replay_both_endiannesses() {
+        let payload = vec![0x00, 0x0a, 0x00, 0x10, 0xde, 0xad, 0xbe, 0xef];
+        let frame = build_udp_packet(
+            "10.0.0.1".parse().unwrap(),
+            2056,
+            "127.0.0.1:2055".parse().unwrap(),
+            Datalink::Ethernet,
+            64,
+            1,
+            &payload,
+        )
+        .unwrap();
+
+        let big = sample_pcap(true, &frame);
+        let little = sample_pcap(false, &frame);
+        assert_eq!(&big[..4], &[0xa1, 0xb2, 0xc3, 0xd4]);
+        assert_eq!(&little[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+
+        for file in [big, little] {
+            let payloads = read_udp_payloads(file.as_slice()).unwrap();
+            assert_eq!(payloads, vec![payload.clone()]);
+        }
+
+        // Ethernet padding after a short UDP payload is not replayed
+        let mut padded = frame.clone();
+        padded.extend_from_slice(&[0; 4]);
+        let payloads = read_udp_payloads(sample_pcap(true, &padded).as_slice()).unwrap();
+        assert_eq!(payloads, vec![payload]);
+    }
+}
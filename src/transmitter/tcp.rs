@@ -0,0 +1,205 @@
+//! Export over TCP (RFC 7011 section 10.4) with connection-health monitoring
+//!
+//! TCP has no application-level acknowledgement, so a successful write only
+//! means the bytes were queued. A collector that rejects the data resets or
+//! closes the connection instead. Before and after each batch the socket is
+//! peeked once without blocking for that reset or close, which is reported
+//! as an error naming the last packet sent.
+
+use crate::error::{NetflowError, Result};
+use std::io::{ErrorKind, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+/// Give up on a collector that never accepts the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// IPFIX message header version; V5, V7 and V9 have no length field to frame them on a stream
+const IPFIX_VERSION: u16 = 10;
+
+/// TCP connection to a collector, kept open for the whole run
+pub struct TcpSender {
+    stream: TcpStream,
+    destination: SocketAddr,
+    /// Packets written over the connection so far
+    sent: u64,
+    /// Description of the most recently written packet
+    last_packet: Option<String>,
+}
+
+impl TcpSender {
+    /// Connect to the collector from an ephemeral port
+    pub fn connect(destination: SocketAddr, verbose: bool) -> Result<Self> {
+        let stream = TcpStream::connect_timeout(&destination, CONNECT_TIMEOUT).map_err(|e| {
+            NetflowError::Network(format!(
+                "Failed to connect to {} over TCP: {}",
+                destination, e
+            ))
+        })?;
+        // Each message goes out as soon as it is written, as a UDP export would
+        stream
+            .set_nodelay(true)
+            .map_err(|e| NetflowError::Network(format!("Failed to configure TCP socket: {}", e)))?;
+        if verbose {
+            println!("TCP connection established with {}", destination);
+        }
+
+        Ok(Self {
+            stream,
+            destination,
+            sent: 0,
+            last_packet: None,
+        })
+    }
+
+    /// Write each IPFIX message to the stream, in order
+    ///
+    /// Fails if a packet is not IPFIX, or if the collector has reset or
+    /// closed the connection.
+    pub fn send(&mut self, packets: &[Vec<u8>], verbose: bool) -> Result<()> {
+        if packets
+            .iter()
+            .any(|packet| packet.get(..2) != Some(&IPFIX_VERSION.to_be_bytes()[..]))
+        {
+            return Err(NetflowError::Configuration(
+                "--transport tcp carries IPFIX only; V5, V7 and V9 packets cannot be framed on a stream"
+                    .to_string(),
+            ));
+        }
+        self.check_health()?;
+        for packet in packets {
+            self.stream
+                .write_all(packet)
+                .map_err(|e| self.connection_error(&e))?;
+            self.sent = self.sent.saturating_add(1);
+            self.last_packet = Some(describe_packet(packet, self.sent));
+
+            if verbose {
+                println!(
+                    "Sent packet {} ({} bytes) over TCP to {}",
+                    self.sent,
+                    packet.len(),
+                    self.destination
+                );
+            }
+        }
+        self.check_health()
+    }
+
+    /// Error out if the collector has reset or closed the connection
+    ///
+    /// Peeks without blocking: pending data from the collector is left
+    /// unread, and an empty receive queue means the connection is healthy.
+    fn check_health(&self) -> Result<()> {
+        match self.peek() {
+            Ok(0) => Err(self.rejection("closed the connection")),
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(self.connection_error(&e)),
+        }
+    }
+
+    /// Peek at one byte with MSG_DONTWAIT, leaving the socket blocking
+    #[cfg(unix)]
+    fn peek(&self) -> std::io::Result<usize> {
+        let mut byte = [std::mem::MaybeUninit::<u8>::uninit()];
+        socket2::SockRef::from(&self.stream)
+            .recv_with_flags(&mut byte, libc::MSG_PEEK | libc::MSG_DONTWAIT)
+    }
+
+    /// Peek at one byte, switching the socket to non-blocking around the call
+    #[cfg(not(unix))]
+    fn peek(&self) -> std::io::Result<usize> {
+        self.stream.set_nonblocking(true)?;
+        let mut byte = [0u8; 1];
+        let peeked = self.stream.peek(&mut byte);
+        self.stream.set_nonblocking(false)?;
+        peeked
+    }
+
+    fn connection_error(&self, error: &std::io::Error) -> NetflowError {
+        match error.kind() {
+            ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => {
+                self.rejection("reset the connection")
+            }
+            ErrorKind::BrokenPipe => self.rejection("closed the connection"),
+            _ => NetflowError::Network(format!(
+                "Failed to send packet over TCP to {}: {}",
+                self.destination, error
+            )),
+        }
+    }
+
+    /// Error for a collector that dropped the connection, naming the last packet sent
+    fn rejection(&self, what: &str) -> NetflowError {
+        let after = match self.last_packet {
+            Some(ref packet) => format!("after {}", packet),
+            None => "before any packet was sent".to_string(),
+        };
+        NetflowError::Network(format!(
+            "Collector at {} {} {}",
+            self.destination, what, after
+        ))
+    }
+}
+
+/// `packet N (observation domain D, sequence S, B bytes)` for an IPFIX message
+fn describe_packet(packet: &[u8], number: u64) -> String {
+    let word = |offset: usize| {
+        packet
+            .get(offset..offset.saturating_add(4))
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u32::from_be_bytes)
+    };
+    match (word(12), word(8)) {
+        (Some(domain), Some(sequence)) => format!(
+            "packet {} (observation domain {}, sequence {}, {} bytes)",
+            number,
+            domain,
+            sequence,
+            packet.len()
+        ),
+        _ => format!("packet {} ({} bytes)", number, packet.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_tcp_close_names_last_packet() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let destination = listener.local_addr().unwrap();
+        // The collector reads one 16-byte message header, then hangs up
+        let collector = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0u8; 16];
+            stream.read_exact(&mut header).unwrap();
+            header
+        });
+
+        let mut message = vec![0x00, 0x0a, 0x00, 0x10, 0, 0, 0, 0];
+        message.extend_from_slice(&41u32.to_be_bytes());
+        message.extend_from_slice(&7u32.to_be_bytes());
+        let mut sender = TcpSender::connect(destination, false).unwrap();
+        sender.send(std::slice::from_ref(&message), false).unwrap();
+        assert_eq!(collector.join().unwrap().to_vec(), message);
+
+        // Non-IPFIX packets cannot be framed
+        assert!(sender.send(&[vec![0x00, 0x09, 0x00, 0x01]], false).is_err());
+
+        // The close surfaces on a later send, within a few attempts
+        let error = (0..50)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                sender.send(std::slice::from_ref(&message), false).err()
+            })
+            .expect("the closed connection was never detected");
+        let error = error.to_string();
+        assert!(error.contains(&destination.to_string()), "{}", error);
+        assert!(error.contains("observation domain 7"), "{}", error);
+    }
+}
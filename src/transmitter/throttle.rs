@@ -0,0 +1,144 @@
+//! Write throughput limit for continuous pcap output (`--max-write-mbps`)
+//!
+//! A token bucket refilled at the configured rate, holding at most one
+//! second's worth of bytes, decides whether each packet may be written now.
+
+use crate::cli::{Cli, WriteLimitPolicy};
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
+
+const MICROS_PER_SEC: u128 = 1_000_000;
+
+/// Maximum write rate and what to do with packets above it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteLimit {
+    pub bytes_per_sec: u64,
+    pub policy: WriteLimitPolicy,
+}
+
+impl WriteLimit {
+    pub fn from_args(args: &Cli) -> Option<Self> {
+        args.max_write_mbps.map(|mbps| Self {
+            bytes_per_sec: mbps.saturating_mul(1_000_000) / 8,
+            policy: args.write_limit_policy,
+        })
+    }
+}
+
+/// Token bucket enforcing a `WriteLimit`
+#[derive(Debug)]
+pub struct WriteLimiter {
+    limit: WriteLimit,
+    available: u64,
+    last_refill: Instant,
+    dropped: u64,
+}
+
+impl WriteLimiter {
+    pub fn new(limit: WriteLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            available: limit.bytes_per_sec,
+            last_refill: now,
+            dropped: 0,
+        }
+    }
+
+    /// Packets dropped so far under the `drop` policy
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Packets of a batch that may be written, each costing `copies` times its length
+    ///
+    /// Over the limit, the `drop` policy discards packets and the `block`
+    /// policy sleeps until the bucket has refilled.
+    pub fn admit<'a>(&mut self, packets: &'a [Vec<u8>], copies: usize) -> Cow<'a, [Vec<u8>]> {
+        let mut kept: Option<Vec<Vec<u8>>> = None;
+
+        for (i, packet) in packets.iter().enumerate() {
+            let cost = u64::try_from(packet.len().saturating_mul(copies)).unwrap_or(u64::MAX);
+            let admitted = loop {
+                match self.acquire(cost, Instant::now()) {
+                    None => break true,
+                    Some(wait) => match self.limit.policy {
+                        WriteLimitPolicy::Drop => break false,
+                        WriteLimitPolicy::Block => std::thread::sleep(wait),
+                    },
+                }
+            };
+
+            if !admitted {
+                // Copy the packets admitted so far once the batch first loses one
+                self.dropped = self.dropped.saturating_add(1);
+                kept.get_or_insert_with(|| packets.get(..i).unwrap_or_default().to_vec());
+            } else if let Some(ref mut kept) = kept {
+                kept.push(packet.clone());
+            }
+        }
+
+        kept.map_or(Cow::Borrowed(packets), Cow::Owned)
+    }
+
+    /// Take `bytes` from the bucket, or return how long until they are available
+    ///
+    /// Costs above the bucket size are capped at it, so oversized packets
+    /// are written at most once per second rather than never.
+    fn acquire(&mut self, bytes: u64, now: Instant) -> Option<Duration> {
+        let rate = self.limit.bytes_per_sec.max(1);
+        let elapsed = now.saturating_duration_since(self.last_refill).as_micros();
+        let refill = u64::try_from(elapsed.saturating_mul(u128::from(rate)) / MICROS_PER_SEC)
+            .unwrap_or(u64::MAX);
+        if refill > 0 {
+            self.available = self.available.saturating_add(refill).min(rate);
+            self.last_refill = now;
+        }
+
+        let bytes = bytes.min(rate);
+        if self.available >= bytes {
+            self.available = self.available.saturating_sub(bytes);
+            return None;
+        }
+
+        let missing = u128::from(bytes.saturating_sub(self.available));
+        let micros = missing
+            .saturating_mul(MICROS_PER_SEC)
+            .div_ceil(u128::from(rate));
+        Some(Duration::from_micros(
+            u64::try_from(micros).unwrap_or(u64::MAX),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_limiter() {
+        let start = Instant::now();
+        let limit = WriteLimit {
+            bytes_per_sec: 1000,
+            policy: WriteLimitPolicy::Drop,
+        };
+        let mut limiter = WriteLimiter::new(limit, start);
+
+        // One second's worth is available up front, then the bucket refills at the rate
+        assert_eq!(limiter.acquire(600, start), None);
+        assert_eq!(
+            limiter.acquire(600, start),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(
+            limiter.acquire(600, start + Duration::from_millis(200)),
+            None
+        );
+
+        // Dropped packets are left out of the batch and counted
+        let mut limiter = WriteLimiter::new(limit, Instant::now());
+        let packets = vec![vec![1; 400], vec![2; 400], vec![3; 400], vec![4; 100]];
+        let kept = limiter.admit(&packets, 1);
+        assert_eq!(kept.iter().map(|p| p[0]).collect::<Vec<_>>(), vec![1, 2, 4]);
+        assert_eq!(limiter.dropped(), 1);
+    }
+}
@@ -1,33 +1,46 @@
+use crate::cli::{Cli, Datalink};
 use crate::error::{NetflowError, Result};
+use crate::transmitter::ports::ExporterPorts;
+use crate::transmitter::throttle::{WriteLimit, WriteLimiter};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::fs::File;
 use std::net::{SocketAddr, UdpSocket};
 use std::path::Path;
+use std::time::Duration;
 
 /// Send packets via UDP
+///
+/// Packets go out from `source_port`, or from the port mapped to their
+/// exporter in `exporter_ports`, with one socket bound per port.
 pub fn send_udp(
     packets: &[Vec<u8>],
     destination: SocketAddr,
     source_port: u16,
+    exporter_ports: &ExporterPorts,
     verbose: bool,
 ) -> Result<()> {
-    // Create UDP socket with fixed source port to match real router behavior
-    // Real NetFlow exporters use a consistent source port rather than ephemeral ports.
-    // This ensures proper parser scoping in collectors that key on source address
-    // (IP:port) + observation_domain_id/source_id per RFC 7011/3954.
-    let bind_addr = format!("0.0.0.0:{}", source_port);
-    let socket = UdpSocket::bind(&bind_addr)
-        .map_err(|e| NetflowError::Network(format!("Failed to bind UDP socket: {}", e)))?;
-
     if verbose {
-        println!("Bound UDP socket to {}", socket.local_addr().unwrap());
         println!("Sending {} packet(s) to {}", packets.len(), destination);
     }
 
+    // Bound sockets by source port, and whether each could be connected
+    let mut sockets: HashMap<u16, (UdpSocket, bool)> = HashMap::new();
+
     // Send each packet
     for (i, packet) in packets.iter().enumerate() {
-        socket
-            .send_to(packet, destination)
-            .map_err(|e| NetflowError::Network(format!("Failed to send packet: {}", e)))?;
+        let port = exporter_ports.port_for(packet, source_port);
+        let (socket, connected) = match sockets.entry(port) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(bind_socket(port, destination, verbose)?),
+        };
+
+        if *connected {
+            send_connected(socket, packet, verbose)
+        } else {
+            socket.send_to(packet, destination).map(drop)
+        }
+        .map_err(|e| NetflowError::Network(format!("Failed to send packet: {}", e)))?;
 
         if verbose {
             let packet_num = i.checked_add(1).unwrap_or(i);
@@ -47,21 +60,227 @@ pub fn send_udp(
     Ok(())
 }
 
+/// Bind a UDP socket to `source_port` and connect it to `destination` if possible
+fn bind_socket(
+    source_port: u16,
+    destination: SocketAddr,
+    verbose: bool,
+) -> Result<(UdpSocket, bool)> {
+    // Create UDP socket with fixed source port to match real router behavior
+    // Real NetFlow exporters use a consistent source port rather than ephemeral ports.
+    // This ensures proper parser scoping in collectors that key on source address
+    // (IP:port) + observation_domain_id/source_id per RFC 7011/3954.
+    let bind_addr = format!("0.0.0.0:{}", source_port);
+    let socket = UdpSocket::bind(&bind_addr)
+        .map_err(|e| NetflowError::Network(format!("Failed to bind UDP socket: {}", e)))?;
+
+    // Connecting a single-destination socket avoids a route lookup on every
+    // send; fall back to send_to if the connect itself is refused.
+    let connected = socket.connect(destination).is_ok();
+
+    if verbose {
+        println!("Bound UDP socket to {}", socket.local_addr().unwrap());
+    }
+
+    Ok((socket, connected))
+}
+
+/// Sends of one packet on a connected socket before giving it up as refused
+const REFUSED_SEND_ATTEMPTS: u32 = 3;
+
+/// Send `packet` on a connected socket, as fire-and-forget as `send_to`
+///
+/// A connected socket reports the ICMP port-unreachable for an earlier
+/// datagram as a refused send, without sending. No collector listening is
+/// not an error for an exporter, so the packet is sent again; each refusal
+/// clears the pending error. A packet refused every time is dropped.
+fn send_connected(socket: &UdpSocket, packet: &[u8], verbose: bool) -> std::io::Result<()> {
+    for _ in 0..REFUSED_SEND_ATTEMPTS {
+        match socket.send(packet) {
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if verbose {
+        println!("Packet dropped: the collector refused it (no listener?)");
+    }
+    Ok(())
+}
+
+/// Initial delay between collector probes, doubled after each failed attempt
+const PROBE_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+/// Upper bound on the delay between collector probes
+const PROBE_MAX_BACKOFF: Duration = Duration::from_secs(2);
+/// How long to wait for an ICMP rejection after each probe
+const PROBE_REPLY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Wait until the collector at `destination` appears reachable
+///
+/// Sends an empty UDP datagram on a connected socket and watches for the
+/// resulting ICMP port-unreachable, which surfaces as a refused receive.
+/// Retries with exponential backoff until no rejection is seen or `timeout`
+/// elapses. This is best-effort: a collector that silently drops packets
+/// is indistinguishable from one that is listening.
+pub fn wait_for_collector(destination: SocketAddr, timeout: Duration, verbose: bool) -> Result<()> {
+    let bind_addr = if destination.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let start = std::time::Instant::now();
+    let mut backoff = PROBE_INITIAL_BACKOFF;
+    let mut attempt: u32 = 0;
+
+    loop {
+        attempt = attempt.saturating_add(1);
+
+        match probe_collector(bind_addr, destination) {
+            Ok(()) => {
+                if verbose {
+                    println!(
+                        "Collector at {} is reachable (attempt {})",
+                        destination, attempt
+                    );
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                if verbose {
+                    println!(
+                        "Collector at {} not ready (attempt {}): {}",
+                        destination, attempt, e
+                    );
+                }
+            }
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Err(NetflowError::Network(format!(
+                "Collector at {} not reachable after {} attempt(s) in {:.1}s",
+                destination,
+                attempt,
+                elapsed.as_secs_f64()
+            )));
+        }
+
+        std::thread::sleep(backoff.min(timeout.saturating_sub(elapsed)));
+        backoff = backoff.saturating_mul(2).min(PROBE_MAX_BACKOFF);
+    }
+}
+
+/// Send a single probe datagram, failing if the destination rejects it
+fn probe_collector(bind_addr: &str, destination: SocketAddr) -> std::io::Result<()> {
+    let socket = UdpSocket::bind(bind_addr)?;
+    socket.connect(destination)?;
+    socket.set_read_timeout(Some(PROBE_REPLY_TIMEOUT))?;
+    socket.send(&[])?;
+
+    let mut buf = [0u8; 1];
+    match socket.recv(&mut buf) {
+        // A reply or silence both count as reachable
+        Ok(_) => Ok(()),
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Options controlling how packets are encapsulated in pcap output
+#[derive(Debug, Clone, Default)]
+pub struct PcapOptions {
+    /// Link-layer header type
+    pub datalink: Datalink,
+    /// Time between successive packets' record timestamps within a batch
+    pub packet_spacing: Duration,
+    /// IP TTLs to write each packet with, one record per TTL (empty: default TTL only)
+    pub ttl_sweep: Vec<u8>,
+    /// IP identification of the first packet, incremented for each following packet
+    pub ip_id_start: u16,
+    /// UDP source ports of exporters given their own (others use PCAP_SOURCE_PORT)
+    pub exporter_ports: ExporterPorts,
+    /// Write throughput limit for continuous output
+    pub write_limit: Option<WriteLimit>,
+    /// Exporter IP packets are recorded from (--spoof-source), else DEFAULT_SOURCE_IP
+    pub source_ip: Option<std::net::Ipv4Addr>,
+}
+
+impl From<&Cli> for PcapOptions {
+    fn from(args: &Cli) -> Self {
+        Self {
+            datalink: args.datalink,
+            packet_spacing: Duration::from_micros(args.packet_spacing_us),
+            ttl_sweep: args.ttl_sweep.clone(),
+            ip_id_start: args.pcap_ip_id,
+            exporter_ports: ExporterPorts::new(&args.exporter_port),
+            write_limit: WriteLimit::from_args(args),
+            source_ip: args.spoof_source,
+        }
+    }
+}
+
+impl PcapOptions {
+    /// Source IP recorded for every packet
+    pub(crate) fn source_ip(&self) -> std::net::Ipv4Addr {
+        self.source_ip.unwrap_or(DEFAULT_SOURCE_IP)
+    }
+
+    /// UDP source port recorded for a NetFlow payload
+    pub(crate) fn source_port(&self, payload: &[u8]) -> u16 {
+        self.exporter_ports.port_for(payload, PCAP_SOURCE_PORT)
+    }
+
+    /// TTLs each NetFlow packet is written with, in record order
+    pub(crate) fn ttls(&self) -> &[u8] {
+        if self.ttl_sweep.is_empty() {
+            &[DEFAULT_TTL]
+        } else {
+            &self.ttl_sweep
+        }
+    }
+}
+
+impl Datalink {
+    /// Pcap link type for the global header
+    pub(crate) fn pcap_datalink(self) -> pcap_file::DataLink {
+        match self {
+            Datalink::Ethernet => pcap_file::DataLink::ETHERNET,
+            Datalink::Raw => pcap_file::DataLink::RAW,
+            Datalink::LinuxSll => pcap_file::DataLink::LINUX_SLL,
+        }
+    }
+}
+
 /// Persistent pcap writer for continuous mode
 pub struct PersistentPcapWriter {
     writer: pcap_file::pcap::PcapWriter<File>,
     destination: SocketAddr,
+    options: PcapOptions,
+    next_ip_id: u16,
+    limiter: Option<WriteLimiter>,
     verbose: bool,
 }
 
 impl PersistentPcapWriter {
     /// Create a new persistent pcap writer
-    pub fn new(path: &Path, destination: SocketAddr, verbose: bool) -> Result<Self> {
+    pub fn new(
+        path: &Path,
+        destination: SocketAddr,
+        options: &PcapOptions,
+        verbose: bool,
+    ) -> Result<Self> {
         use pcap_file::pcap::{PcapHeader, PcapWriter};
 
         let file = File::create(path)?;
         let pcap_header = PcapHeader {
-            datalink: pcap_file::DataLink::ETHERNET,
+            datalink: options.datalink.pcap_datalink(),
             ..Default::default()
         };
         let writer = PcapWriter::with_header(file, pcap_header)
@@ -74,17 +293,34 @@ impl PersistentPcapWriter {
         Ok(Self {
             writer,
             destination,
+            options: options.clone(),
+            next_ip_id: options.ip_id_start,
+            limiter: options
+                .write_limit
+                .map(|limit| WriteLimiter::new(limit, std::time::Instant::now())),
             verbose,
         })
     }
 
-    /// Write packets to the pcap file
+    /// Write packets to the pcap file, subject to the write limit
     pub fn write_packets(&mut self, packets: &[Vec<u8>]) -> Result<()> {
+        let packets = match self.limiter {
+            Some(ref mut limiter) => limiter.admit(packets, self.options.ttls().len()),
+            None => std::borrow::Cow::Borrowed(packets),
+        };
+
         if self.verbose {
             println!("Writing {} packet(s) to pcap file", packets.len());
         }
 
-        write_packets_to_pcap(&mut self.writer, packets, self.destination, self.verbose)?;
+        write_packets_to_pcap(
+            &mut self.writer,
+            &packets,
+            self.destination,
+            &self.options,
+            &mut self.next_ip_id,
+            self.verbose,
+        )?;
 
         if self.verbose {
             println!("Successfully wrote packets to pcap file");
@@ -93,6 +329,11 @@ impl PersistentPcapWriter {
         Ok(())
     }
 
+    /// Packets left out of the file by the write limit
+    pub fn dropped_packets(&self) -> u64 {
+        self.limiter.as_ref().map_or(0, WriteLimiter::dropped)
+    }
+
     /// Close the pcap writer (drops the writer which flushes automatically)
     pub fn close(self) -> Result<()> {
         if self.verbose {
@@ -115,6 +356,7 @@ pub fn write_to_file(
     packets: &[Vec<u8>],
     path: &std::path::Path,
     destination: SocketAddr,
+    options: &PcapOptions,
     verbose: bool,
     first_write: bool,
 ) -> Result<()> {
@@ -137,18 +379,34 @@ pub fn write_to_file(
 
         let file = File::create(path)?;
         let pcap_header = PcapHeader {
-            datalink: pcap_file::DataLink::ETHERNET,
+            datalink: options.datalink.pcap_datalink(),
             ..Default::default()
         };
         let mut pcap_writer = PcapWriter::with_header(file, pcap_header)
             .map_err(|e| NetflowError::Io(std::io::Error::other(e)))?;
 
-        write_packets_to_pcap(&mut pcap_writer, packets, destination, verbose)?;
+        let mut next_ip_id = options.ip_id_start;
+        write_packets_to_pcap(
+            &mut pcap_writer,
+            packets,
+            destination,
+            options,
+            &mut next_ip_id,
+            verbose,
+        )?;
     } else {
         // Append to existing file without header
         let mut file = OpenOptions::new().append(true).open(path)?;
 
-        append_packets_to_pcap(&mut file, packets, destination, verbose)?;
+        let mut next_ip_id = options.ip_id_start;
+        append_packets_to_pcap(
+            &mut file,
+            packets,
+            destination,
+            options,
+            &mut next_ip_id,
+            verbose,
+        )?;
     }
 
     if verbose {
@@ -163,36 +421,56 @@ fn write_packets_to_pcap<W: std::io::Write>(
     pcap_writer: &mut pcap_file::pcap::PcapWriter<W>,
     packets: &[Vec<u8>],
     destination: SocketAddr,
+    options: &PcapOptions,
+    next_ip_id: &mut u16,
     verbose: bool,
 ) -> Result<()> {
     use pcap_file::pcap::PcapPacket;
     use std::time::{SystemTime, UNIX_EPOCH};
 
-    let src_ip = std::net::Ipv4Addr::new(10, 0, 0, 1);
-    let src_port: u16 = 12345;
+    let src_ip = options.source_ip();
+    let batch_start = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
 
     for (i, netflow_payload) in packets.iter().enumerate() {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default();
-
-        let packet_data = build_udp_packet(src_ip, src_port, destination, netflow_payload)?;
-
-        let pcap_packet = PcapPacket {
-            timestamp,
-            orig_len: u32::try_from(packet_data.len()).map_err(|_| {
-                NetflowError::InvalidPacket("Packet size exceeds u32::MAX".to_string())
-            })?,
-            data: std::borrow::Cow::Borrowed(&packet_data),
-        };
-
-        pcap_writer
-            .write_packet(&pcap_packet)
-            .map_err(|e| NetflowError::Io(std::io::Error::other(e)))?;
-
-        if verbose {
-            let packet_num = i.checked_add(1).unwrap_or(i);
-            println!("Wrote packet {} ({} bytes)", packet_num, packet_data.len());
+        let timestamp = packet_timestamp(batch_start, i, options)?;
+        let ip_id = *next_ip_id;
+        *next_ip_id = next_ip_id.wrapping_add(1);
+
+        // With --ttl-sweep the same packet is recorded once per TTL
+        for &ttl in options.ttls() {
+            let packet_data = build_udp_packet(
+                src_ip,
+                options.source_port(netflow_payload),
+                destination,
+                options.datalink,
+                ttl,
+                ip_id,
+                netflow_payload,
+            )?;
+
+            let pcap_packet = PcapPacket {
+                timestamp,
+                orig_len: u32::try_from(packet_data.len()).map_err(|_| {
+                    NetflowError::InvalidPacket("Packet size exceeds u32::MAX".to_string())
+                })?,
+                data: std::borrow::Cow::Borrowed(&packet_data),
+            };
+
+            pcap_writer
+                .write_packet(&pcap_packet)
+                .map_err(|e| NetflowError::Io(std::io::Error::other(e)))?;
+
+            if verbose {
+                let packet_num = i.checked_add(1).unwrap_or(i);
+                println!(
+                    "Wrote packet {} ({} bytes, TTL {})",
+                    packet_num,
+                    packet_data.len(),
+                    ttl
+                );
+            }
         }
     }
 
@@ -204,59 +482,132 @@ fn append_packets_to_pcap<W: std::io::Write>(
     writer: &mut W,
     packets: &[Vec<u8>],
     destination: SocketAddr,
+    options: &PcapOptions,
+    next_ip_id: &mut u16,
     verbose: bool,
 ) -> Result<()> {
     use std::time::{SystemTime, UNIX_EPOCH};
 
-    let src_ip = std::net::Ipv4Addr::new(10, 0, 0, 1);
-    let src_port: u16 = 12345;
+    let src_ip = options.source_ip();
+    let batch_start = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
 
     for (i, netflow_payload) in packets.iter().enumerate() {
-        // Get current timestamp as Duration since EPOCH
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default();
+        // Timestamp as Duration since EPOCH, staggered within the batch
+        let timestamp = packet_timestamp(batch_start, i, options)?;
+        let ip_id = *next_ip_id;
+        *next_ip_id = next_ip_id.wrapping_add(1);
+
+        // With --ttl-sweep the same packet is recorded once per TTL
+        for &ttl in options.ttls() {
+            // Build the complete packet: Ethernet + IP + UDP + NetFlow payload
+            let packet_data = build_udp_packet(
+                src_ip,
+                options.source_port(netflow_payload),
+                destination,
+                options.datalink,
+                ttl,
+                ip_id,
+                netflow_payload,
+            )?;
+
+            // Manually write pcap packet record format
+            // See: https://wiki.wireshark.org/Development/LibpcapFileFormat
+
+            let packet_len = u32::try_from(packet_data.len()).map_err(|_| {
+                NetflowError::InvalidPacket("Packet size exceeds u32::MAX".to_string())
+            })?;
 
-        // Build the complete packet: Ethernet + IP + UDP + NetFlow payload
-        let packet_data = build_udp_packet(src_ip, src_port, destination, netflow_payload)?;
+            // Timestamp seconds (4 bytes, little-endian for standard pcap)
+            let ts_sec = u32::try_from(timestamp.as_secs()).unwrap_or(u32::MAX);
+            writer.write_all(&ts_sec.to_le_bytes())?;
 
-        // Manually write pcap packet record format
-        // See: https://wiki.wireshark.org/Development/LibpcapFileFormat
+            // Timestamp microseconds (4 bytes, little-endian)
+            let ts_usec = timestamp.subsec_micros();
+            writer.write_all(&ts_usec.to_le_bytes())?;
 
-        let packet_len = u32::try_from(packet_data.len())
-            .map_err(|_| NetflowError::InvalidPacket("Packet size exceeds u32::MAX".to_string()))?;
+            // Captured packet length (4 bytes, little-endian)
+            writer.write_all(&packet_len.to_le_bytes())?;
 
-        // Timestamp seconds (4 bytes, little-endian for standard pcap)
-        let ts_sec = u32::try_from(timestamp.as_secs()).unwrap_or(u32::MAX);
-        writer.write_all(&ts_sec.to_le_bytes())?;
+            // Original packet length (4 bytes, little-endian)
+            writer.write_all(&packet_len.to_le_bytes())?;
 
-        // Timestamp microseconds (4 bytes, little-endian)
-        let ts_usec = timestamp.subsec_micros();
-        writer.write_all(&ts_usec.to_le_bytes())?;
+            // Packet data
+            writer.write_all(&packet_data)?;
 
-        // Captured packet length (4 bytes, little-endian)
-        writer.write_all(&packet_len.to_le_bytes())?;
+            if verbose {
+                let packet_num = i.checked_add(1).unwrap_or(i);
+                println!(
+                    "Wrote packet {} ({} bytes, TTL {})",
+                    packet_num,
+                    packet_data.len(),
+                    ttl
+                );
+            }
+        }
+    }
 
-        // Original packet length (4 bytes, little-endian)
-        writer.write_all(&packet_len.to_le_bytes())?;
+    Ok(())
+}
 
-        // Packet data
-        writer.write_all(&packet_data)?;
+/// Pcap record timestamp for the packet at `index` in a batch
+///
+/// Without packet spacing every record uses the current time, as before.
+pub(crate) fn packet_timestamp(
+    batch_start: Duration,
+    index: usize,
+    options: &PcapOptions,
+) -> Result<Duration> {
+    use std::time::{SystemTime, UNIX_EPOCH};
 
-        if verbose {
-            let packet_num = i.checked_add(1).unwrap_or(i);
-            println!("Wrote packet {} ({} bytes)", packet_num, packet_data.len());
-        }
+    if options.packet_spacing.is_zero() {
+        return Ok(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default());
     }
 
-    Ok(())
+    let index = u32::try_from(index)
+        .map_err(|_| NetflowError::InvalidPacket("Too many packets in batch".to_string()))?;
+    options
+        .packet_spacing
+        .checked_mul(index)
+        .and_then(|offset| batch_start.checked_add(offset))
+        .ok_or_else(|| NetflowError::InvalidPacket("Packet timestamp overflow".to_string()))
 }
 
-/// Build a complete UDP packet with Ethernet, IP, and UDP headers
-fn build_udp_packet(
+/// UDP source port in pcap output for exporters without an --exporter-port
+pub(crate) const PCAP_SOURCE_PORT: u16 = 12345;
+
+/// Source IP of the synthetic exporter in capture files without --spoof-source
+pub(crate) const DEFAULT_SOURCE_IP: std::net::Ipv4Addr = std::net::Ipv4Addr::new(10, 0, 0, 1);
+
+/// IP TTL of packets in capture files unless --ttl-sweep is given
+pub(crate) const DEFAULT_TTL: u8 = 64;
+
+/// Source MAC for an exporter IP
+///
+/// The default exporter keeps the fixed 00:00:00:00:00:01; any other source
+/// IP gets a locally administered 02:00:<a>:<b>:<c>:<d> so distinct exporters
+/// are distinguishable (and stable) at L2.
+fn source_mac(src_ip: std::net::Ipv4Addr) -> [u8; 6] {
+    if src_ip == DEFAULT_SOURCE_IP {
+        return [0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+    }
+    let [a, b, c, d] = src_ip.octets();
+    [0x02, 0x00, a, b, c, d]
+}
+
+/// Build a complete UDP packet with link-layer, IP, and UDP headers
+///
+/// The IPv4 header checksum is computed for the given `ttl` and `ip_id`.
+pub(crate) fn build_udp_packet(
     src_ip: std::net::Ipv4Addr,
     src_port: u16,
     dest: SocketAddr,
+    datalink: Datalink,
+    ttl: u8,
+    ip_id: u16,
     payload: &[u8],
 ) -> Result<Vec<u8>> {
     let mut packet = Vec::new();
@@ -271,13 +622,31 @@ fn build_udp_packet(
         }
     };
 
-    // Ethernet header (14 bytes)
-    // Destination MAC: 00:00:00:00:00:02
-    packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x02]);
-    // Source MAC: 00:00:00:00:00:01
-    packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x01]);
-    // EtherType: 0x0800 (IPv4)
-    packet.extend_from_slice(&[0x08, 0x00]);
+    match datalink {
+        Datalink::Ethernet => {
+            // Ethernet header (14 bytes)
+            // Destination MAC: 00:00:00:00:00:02
+            packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x02]);
+            // Source MAC: derived from the source IP
+            packet.extend_from_slice(&source_mac(src_ip));
+            // EtherType: 0x0800 (IPv4)
+            packet.extend_from_slice(&[0x08, 0x00]);
+        }
+        Datalink::LinuxSll => {
+            // Linux cooked capture header (16 bytes)
+            packet.extend_from_slice(&[0x00, 0x04]); // Packet type: sent by us
+            packet.extend_from_slice(&[0x00, 0x01]); // ARPHRD_ETHER
+            packet.extend_from_slice(&[0x00, 0x06]); // Link-layer address length
+            // Source MAC derived from the source IP, padded to 8 bytes
+            packet.extend_from_slice(&source_mac(src_ip));
+            packet.extend_from_slice(&[0x00, 0x00]);
+            packet.extend_from_slice(&[0x08, 0x00]); // Protocol: IPv4
+        }
+        Datalink::Raw => {
+            // No link-layer header, packet starts at IPv4
+        }
+    }
+    let ip_start = packet.len();
 
     // IPv4 header (20 bytes minimum)
     let ip_total_length = 20_usize
@@ -291,19 +660,26 @@ fn build_udp_packet(
     packet.push(0x45); // Version (4) + IHL (5)
     packet.push(0x00); // DSCP + ECN
     packet.extend_from_slice(&ip_total_length_u16.to_be_bytes()); // Total length
-    packet.extend_from_slice(&[0x00, 0x00]); // Identification
+    packet.extend_from_slice(&ip_id.to_be_bytes()); // Identification
     packet.extend_from_slice(&[0x40, 0x00]); // Flags (DF) + Fragment offset
-    packet.push(64); // TTL
+    packet.push(ttl); // TTL
     packet.push(17); // Protocol (UDP)
     packet.extend_from_slice(&[0x00, 0x00]); // Checksum (placeholder)
     packet.extend_from_slice(&src_ip.octets()); // Source IP
     packet.extend_from_slice(&dest_ip.octets()); // Destination IP
 
-    // Calculate IP checksum
-    let ip_checksum = calculate_checksum(&packet[14..34])?;
-    let checksum_bytes = ip_checksum.to_be_bytes();
-    packet[24] = checksum_bytes[0];
-    packet[25] = checksum_bytes[1];
+    // Calculate IP checksum over the 20-byte IPv4 header
+    let ip_end = ip_start
+        .checked_add(20)
+        .ok_or_else(|| NetflowError::InvalidPacket("IP header offset overflow".to_string()))?;
+    let checksum_pos = ip_start
+        .checked_add(10)
+        .ok_or_else(|| NetflowError::InvalidPacket("IP header offset overflow".to_string()))?;
+    let checksum_end = checksum_pos
+        .checked_add(2)
+        .ok_or_else(|| NetflowError::InvalidPacket("IP header offset overflow".to_string()))?;
+    let ip_checksum = calculate_checksum(&packet[ip_start..ip_end])?;
+    packet[checksum_pos..checksum_end].copy_from_slice(&ip_checksum.to_be_bytes());
 
     // UDP header (8 bytes)
     let udp_length = 8_usize
@@ -375,6 +751,7 @@ mod tests {
             std::slice::from_ref(&test_packet),
             receiver_addr,
             2056,
+            &ExporterPorts::default(),
             false,
         ) {
             Ok(_) => {
@@ -390,4 +767,96 @@ mod tests {
             Err(e) => panic!("Unexpected error: {:?}", e),
         }
     }
+
+    #[test]
+    fn test_send_udp_without_collector() {
+        // Nothing listens on the port, so loopback answers with port unreachable
+        let closed = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let destination = closed.local_addr().unwrap();
+        drop(closed);
+
+        let packets = vec![vec![0x00, 0x05, 0x00, 0x01]; 4];
+        for _ in 0..3 {
+            assert!(send_udp(&packets, destination, 0, &ExporterPorts::default(), false).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_wait_for_collector() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let listening = receiver.local_addr().unwrap();
+        assert!(wait_for_collector(listening, Duration::from_secs(1), false).is_ok());
+
+        // A closed port is refused on loopback, so the probe times out
+        drop(receiver);
+        assert!(wait_for_collector(listening, Duration::from_millis(300), false).is_err());
+    }
+
+    #[test]
+    fn test_build_udp_packet_datalinks() {
+        let dest: SocketAddr = "192.168.1.100:2055".parse().unwrap();
+        let src_ip = DEFAULT_SOURCE_IP;
+        let payload = [0x00, 0x09, 0x00, 0x01];
+
+        for (datalink, ip_start) in [
+            (Datalink::Ethernet, 14),
+            (Datalink::LinuxSll, 16),
+            (Datalink::Raw, 0),
+        ] {
+            let packet =
+                build_udp_packet(src_ip, 12345, dest, datalink, DEFAULT_TTL, 0, &payload).unwrap();
+            assert_eq!(packet.len(), ip_start + 20 + 8 + payload.len());
+            assert_eq!(packet[ip_start], 0x45);
+            // A correct header checksums to zero
+            assert_eq!(
+                calculate_checksum(&packet[ip_start..ip_start + 20]).unwrap(),
+                0
+            );
+        }
+
+        // The checksum is recomputed for a swept TTL and IP identification
+        let packet =
+            build_udp_packet(src_ip, 12345, dest, Datalink::Raw, 61, 0xbeef, &payload).unwrap();
+        assert_eq!(packet[8], 61);
+        assert_eq!(&packet[4..6], &[0xbe, 0xef]);
+        assert_eq!(calculate_checksum(&packet[..20]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_source_mac_derivation() {
+        let dest: SocketAddr = "192.168.1.100:2055".parse().unwrap();
+        let mac_of = |src_ip| {
+            build_udp_packet(src_ip, 12345, dest, Datalink::Ethernet, DEFAULT_TTL, 0, &[]).unwrap()
+                [6..12]
+                .to_vec()
+        };
+
+        assert_eq!(mac_of(DEFAULT_SOURCE_IP), [0, 0, 0, 0, 0, 1]);
+        assert_eq!(
+            mac_of(std::net::Ipv4Addr::new(192, 0, 2, 7)),
+            [0x02, 0x00, 192, 0, 2, 7]
+        );
+
+        // Pcap output records packets from the --spoof-source address
+        let options = PcapOptions {
+            source_ip: Some(std::net::Ipv4Addr::new(192, 0, 2, 7)),
+            ..PcapOptions::default()
+        };
+        let mut writer = pcap_file::pcap::PcapWriter::new(Vec::new()).unwrap();
+        let mut ip_id = 0;
+        write_packets_to_pcap(
+            &mut writer,
+            &[vec![0x00, 0x09]],
+            dest,
+            &options,
+            &mut ip_id,
+            false,
+        )
+        .unwrap();
+        let capture = writer.into_writer();
+        // 24-byte file header, then a 16-byte record header
+        let frame = &capture[40..];
+        assert_eq!(&frame[6..12], &[0x02, 0x00, 192, 0, 2, 7]);
+        assert_eq!(&frame[26..30], &[192, 0, 2, 7]);
+    }
 }